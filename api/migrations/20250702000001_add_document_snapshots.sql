@@ -0,0 +1,20 @@
+-- Named and automatic snapshots for document version history
+CREATE TABLE IF NOT EXISTS document_snapshots (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    document_id UUID NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    name VARCHAR(255),
+    kind VARCHAR(20) NOT NULL DEFAULT 'manual' CHECK (kind IN ('manual', 'auto')),
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_document_snapshots_document_id ON document_snapshots(document_id, created_at DESC);
+
+-- Seed history for documents that were saved before every update was recorded,
+-- so replaying history reproduces their current state
+INSERT INTO document_update_history (document_id, update_data, created_at)
+SELECT du.document_id, du.update_data, du.created_at
+FROM document_updates du
+WHERE NOT EXISTS (
+    SELECT 1 FROM document_update_history h WHERE h.document_id = du.document_id
+);
@@ -0,0 +1,3 @@
+-- Mark rows produced by merging older updates during history compaction
+ALTER TABLE document_update_history
+    ADD COLUMN IF NOT EXISTS is_checkpoint BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,8 @@
+-- Realtime payloads too large for a NOTIFY, relayed between API replicas by id
+CREATE TABLE IF NOT EXISTS cluster_messages (
+    id BIGSERIAL PRIMARY KEY,
+    payload BYTEA NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_cluster_messages_created_at ON cluster_messages(created_at);
@@ -0,0 +1,10 @@
+-- Latest automatic (batched) git sync outcome per user
+CREATE TABLE IF NOT EXISTS git_batch_sync_status (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    status TEXT NOT NULL CHECK (status IN ('queued', 'started', 'committed', 'pushed', 'conflicted', 'retrying', 'failed')),
+    documents TEXT[] NOT NULL DEFAULT '{}',
+    retry_count INTEGER NOT NULL DEFAULT 0,
+    commit_hash TEXT,
+    message TEXT,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,17 @@
+-- Allow several git configurations per user: one for the whole document tree
+-- (folder_id IS NULL) and one per mapped folder
+ALTER TABLE git_configs DROP CONSTRAINT git_configs_user_id_key;
+
+ALTER TABLE git_configs
+    ADD COLUMN folder_id UUID REFERENCES documents(id) ON DELETE CASCADE,
+    -- Quiet period before automatic changes are committed; NULL uses the default
+    ADD COLUMN sync_interval_secs INTEGER CHECK (sync_interval_secs > 0);
+
+CREATE UNIQUE INDEX idx_git_configs_user_root ON git_configs(user_id) WHERE folder_id IS NULL;
+CREATE UNIQUE INDEX idx_git_configs_user_folder ON git_configs(user_id, folder_id) WHERE folder_id IS NOT NULL;
+
+-- Folder repository an operation ran against; NULL for the user's root repository
+ALTER TABLE git_sync_logs
+    ADD COLUMN git_config_id UUID REFERENCES git_configs(id) ON DELETE CASCADE;
+
+CREATE INDEX idx_git_sync_logs_git_config_id ON git_sync_logs(git_config_id);
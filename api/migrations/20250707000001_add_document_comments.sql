@@ -0,0 +1,35 @@
+-- Comment threads anchored to a range of document text. Anchors are encoded
+-- Yrs sticky indexes so they follow the text through concurrent edits
+CREATE TABLE IF NOT EXISTS document_comment_threads (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    document_id UUID NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    anchor_start BYTEA NOT NULL,
+    anchor_end BYTEA NOT NULL,
+    -- Text the thread was created on, kept for when the anchored range is deleted
+    quote TEXT NOT NULL DEFAULT '',
+    status VARCHAR(20) NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'resolved')),
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    resolved_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    resolved_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_document_comment_threads_document_id ON document_comment_threads(document_id, created_at);
+
+CREATE TRIGGER update_document_comment_threads_updated_at
+    BEFORE UPDATE ON document_comment_threads
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at();
+
+-- Comments of a thread; guests commenting through a share link have no author_id
+CREATE TABLE IF NOT EXISTS document_comments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    thread_id UUID NOT NULL REFERENCES document_comment_threads(id) ON DELETE CASCADE,
+    author_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    author_name VARCHAR(255) NOT NULL,
+    body TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_document_comments_thread_id ON document_comments(thread_id, created_at);
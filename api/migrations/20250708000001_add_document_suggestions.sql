@@ -0,0 +1,26 @@
+-- Proposed edits from reviewers, anchored like comment threads. A suggestion replaces the
+-- anchored range with `replacement`; an empty range is an insertion, an empty
+-- replacement a deletion
+CREATE TABLE IF NOT EXISTS document_suggestions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    document_id UUID NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    anchor_start BYTEA NOT NULL,
+    anchor_end BYTEA NOT NULL,
+    -- Text of the range when the suggestion was made; accepting requires it to be unchanged
+    original_text TEXT NOT NULL,
+    replacement TEXT NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'accepted', 'rejected')),
+    author_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    author_name VARCHAR(255) NOT NULL,
+    decided_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    decided_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_document_suggestions_document_id ON document_suggestions(document_id, created_at);
+
+CREATE TRIGGER update_document_suggestions_updated_at
+    BEFORE UPDATE ON document_suggestions
+    FOR EACH ROW
+    EXECUTE FUNCTION update_updated_at();
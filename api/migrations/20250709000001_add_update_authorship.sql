@@ -0,0 +1,18 @@
+-- Attribute each recorded update to the user and Yjs client that produced it
+ALTER TABLE document_update_history
+    ADD COLUMN IF NOT EXISTS author_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    ADD COLUMN IF NOT EXISTS client_id BIGINT;
+
+-- Clock ranges of text inserted by each Yjs client, used to attribute the current text.
+-- Kept separately from the update history so compaction does not lose authorship
+CREATE TABLE IF NOT EXISTS document_authorship (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    document_id UUID NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    client_id BIGINT NOT NULL,
+    clock_start BIGINT NOT NULL,
+    clock_end BIGINT NOT NULL,
+    author_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_document_authorship_document_id ON document_authorship(document_id, client_id, clock_start);
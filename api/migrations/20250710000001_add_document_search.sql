@@ -0,0 +1,12 @@
+-- Plain text of each document's content for full-text search, refreshed whenever the
+-- CRDT state is synced to the documents table. Titles are searched from documents.
+-- The 'simple' configuration does no stemming, so notes in any language are indexed alike.
+CREATE TABLE IF NOT EXISTS document_search_index (
+    document_id UUID PRIMARY KEY REFERENCES documents(id) ON DELETE CASCADE,
+    content TEXT NOT NULL,
+    content_vector TSVECTOR GENERATED ALWAYS AS (to_tsvector('simple', content)) STORED,
+    indexed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_document_search_index_vector ON document_search_index USING GIN (content_vector);
+CREATE INDEX idx_documents_title_search ON documents USING GIN (to_tsvector('simple', title));
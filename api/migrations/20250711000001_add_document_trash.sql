@@ -0,0 +1,10 @@
+-- Soft deletion: trashed documents and folders keep their row, tree position, links,
+-- tags and attachments until restored or purged. Everything trashed together points
+-- to the item the user deleted, so it is restored or purged as one entry.
+ALTER TABLE documents
+    ADD COLUMN deleted_at TIMESTAMPTZ,
+    ADD COLUMN deleted_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    ADD COLUMN trash_root_id UUID REFERENCES documents(id) ON DELETE CASCADE;
+
+CREATE INDEX idx_documents_trash_root ON documents(trash_root_id) WHERE trash_root_id IS NOT NULL;
+CREATE INDEX idx_documents_deleted_at ON documents(owner_id, deleted_at) WHERE deleted_at IS NOT NULL;
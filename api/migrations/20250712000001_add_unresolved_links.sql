@@ -0,0 +1,20 @@
+-- Keep links whose target does not exist yet. The target text as written in the link
+-- is stored so a document created or renamed later with that title can be bound to it,
+-- and links lose their target instead of disappearing when it is deleted.
+ALTER TABLE document_links
+    ALTER COLUMN target_document_id DROP NOT NULL,
+    ADD COLUMN target_text TEXT;
+
+ALTER TABLE document_links DROP CONSTRAINT document_links_target_document_id_fkey;
+ALTER TABLE document_links
+    ADD CONSTRAINT document_links_target_document_id_fkey
+    FOREIGN KEY (target_document_id) REFERENCES documents(id) ON DELETE SET NULL;
+
+-- Existing links were all resolved; their current target title is the best known text
+UPDATE document_links dl
+SET target_text = d.title
+FROM documents d
+WHERE d.id = dl.target_document_id;
+
+CREATE INDEX idx_document_links_unresolved ON document_links(LOWER(target_text))
+    WHERE target_document_id IS NULL;
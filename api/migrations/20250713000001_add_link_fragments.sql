@@ -0,0 +1,8 @@
+-- Heading and block references: [[Doc#Heading]] and [[Doc#^block-id]]. The fragment is
+-- stored as written after '#', with whether it was found in the target's content.
+ALTER TABLE document_links
+    ADD COLUMN target_fragment TEXT,
+    ADD COLUMN fragment_resolved BOOLEAN;
+
+CREATE INDEX idx_document_links_target_fragment ON document_links(target_document_id, target_fragment)
+    WHERE target_fragment IS NOT NULL;
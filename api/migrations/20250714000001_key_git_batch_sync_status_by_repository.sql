@@ -0,0 +1,9 @@
+-- Track the latest batch sync outcome of every repository of a user: the root
+-- repository (git_config_id IS NULL) and each mapped folder repository
+ALTER TABLE git_batch_sync_status DROP CONSTRAINT git_batch_sync_status_pkey;
+
+ALTER TABLE git_batch_sync_status
+    ADD COLUMN git_config_id UUID REFERENCES git_configs(id) ON DELETE CASCADE;
+
+CREATE UNIQUE INDEX idx_git_batch_sync_status_user_root ON git_batch_sync_status(user_id) WHERE git_config_id IS NULL;
+CREATE UNIQUE INDEX idx_git_batch_sync_status_repository ON git_batch_sync_status(git_config_id) WHERE git_config_id IS NOT NULL;
@@ -0,0 +1,15 @@
+-- Login sessions. The access and refresh tokens issued by one login, and every
+-- token refreshed from them, carry the session id, so logging out can end that
+-- session's realtime connections and keep them from joining again.
+CREATE TABLE IF NOT EXISTS auth_sessions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    revoked_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_auth_sessions_user_id ON auth_sessions(user_id);
+
+-- NULL for refresh tokens issued before sessions were tracked
+ALTER TABLE refresh_tokens
+    ADD COLUMN session_id UUID REFERENCES auth_sessions(id) ON DELETE CASCADE;
@@ -0,0 +1,4 @@
+-- Remember where the rows merged into a checkpoint started, so replaying to a time
+-- inside a compacted span can be refused instead of silently returning older content
+ALTER TABLE document_update_history
+    ADD COLUMN IF NOT EXISTS compacted_from TIMESTAMPTZ;
@@ -16,6 +16,17 @@ pub struct Config {
     pub git_auto_sync: bool,
     pub git_sync_interval: u64,
     pub signup_enabled: bool,
+    pub history_retention_days: i64,
+    pub history_compaction_interval: u64,
+    pub document_cache_idle_timeout: u64,
+    pub document_cache_max_documents: usize,
+    pub document_cache_max_memory_mb: usize,
+    pub document_cache_eviction_interval: u64,
+    pub cluster_sync_enabled: bool,
+    pub file_watcher_enabled: bool,
+    pub file_watcher_debounce_ms: u64,
+    pub trash_retention_days: i64,
+    pub trash_purge_interval: u64,
 }
 
 impl Config {
@@ -59,6 +70,50 @@ impl Config {
                 .unwrap_or_else(|_| "true".to_string())
                 .parse()
                 .unwrap_or(true),
+            history_retention_days: std::env::var("HISTORY_RETENTION_DAYS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30),
+            history_compaction_interval: std::env::var("HISTORY_COMPACTION_INTERVAL")
+                .unwrap_or_else(|_| "3600".to_string())
+                .parse()
+                .unwrap_or(3600),
+            document_cache_idle_timeout: std::env::var("DOCUMENT_CACHE_IDLE_TIMEOUT")
+                .unwrap_or_else(|_| "600".to_string())
+                .parse()
+                .unwrap_or(600),
+            document_cache_max_documents: std::env::var("DOCUMENT_CACHE_MAX_DOCUMENTS")
+                .unwrap_or_else(|_| "1000".to_string())
+                .parse()
+                .unwrap_or(1000),
+            document_cache_max_memory_mb: std::env::var("DOCUMENT_CACHE_MAX_MEMORY_MB")
+                .unwrap_or_else(|_| "256".to_string())
+                .parse()
+                .unwrap_or(256),
+            document_cache_eviction_interval: std::env::var("DOCUMENT_CACHE_EVICTION_INTERVAL")
+                .unwrap_or_else(|_| "60".to_string())
+                .parse()
+                .unwrap_or(60),
+            cluster_sync_enabled: std::env::var("CLUSTER_SYNC_ENABLED")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .unwrap_or(false),
+            file_watcher_enabled: std::env::var("FILE_WATCHER_ENABLED")
+                .unwrap_or_else(|_| "false".to_string())
+                .parse()
+                .unwrap_or(false),
+            file_watcher_debounce_ms: std::env::var("FILE_WATCHER_DEBOUNCE_MS")
+                .unwrap_or_else(|_| "500".to_string())
+                .parse()
+                .unwrap_or(500),
+            trash_retention_days: std::env::var("TRASH_RETENTION_DAYS")
+                .unwrap_or_else(|_| "30".to_string())
+                .parse()
+                .unwrap_or(30),
+            trash_purge_interval: std::env::var("TRASH_PURGE_INTERVAL")
+                .unwrap_or_else(|_| "3600".to_string())
+                .parse()
+                .unwrap_or(3600),
         })
     }
 }
\ No newline at end of file
@@ -0,0 +1,140 @@
+use std::ops::Range;
+use serde::Serialize;
+use yrs::updates::decoder::Decode;
+use yrs::updates::encoder::Encode;
+use yrs::{Assoc, StickyIndex};
+
+use super::CrdtDocument;
+use crate::error::Result;
+
+/// Range of the content text pinned with sticky indexes, so it keeps covering the same
+/// text through concurrent edits. An empty range marks an insertion point.
+#[derive(Debug, Clone)]
+pub struct TextAnchor {
+    pub start: StickyIndex,
+    pub end: StickyIndex,
+}
+
+/// Current position of an anchor in the content, in characters
+#[derive(Debug, Clone, Serialize)]
+pub struct TextRange {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+impl TextAnchor {
+    /// Anchor a character range of the current content, None if it is out of bounds
+    pub fn from_chars(doc: &CrdtDocument, content: &str, start: usize, end: usize) -> Option<Self> {
+        if start > end {
+            return None;
+        }
+        let start = char_to_utf16(content, start)?;
+        let end = char_to_utf16(content, end)?;
+
+        if start == end {
+            // Stick to the preceding text so the point stays after it
+            let point = doc.sticky_index(start, Assoc::Before)?;
+            return Some(Self { start: point.clone(), end: point });
+        }
+        Some(Self {
+            start: doc.sticky_index(start, Assoc::After)?,
+            end: doc.sticky_index(end, Assoc::Before)?,
+        })
+    }
+
+    /// Anchor from base64 Yjs relative positions, as produced by `Y.encodeRelativePosition`
+    pub fn from_base64(start: &str, end: &str) -> Result<Self> {
+        let decode = |encoded: &str| -> Result<StickyIndex> {
+            let bytes = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, encoded)?;
+            Ok(StickyIndex::decode_v1(&bytes)?)
+        };
+        Ok(Self { start: decode(start)?, end: decode(end)? })
+    }
+
+    /// Anchor from its stored encoding
+    pub fn decode(start: &[u8], end: &[u8]) -> Option<Self> {
+        Some(Self {
+            start: StickyIndex::decode_v1(start).ok()?,
+            end: StickyIndex::decode_v1(end).ok()?,
+        })
+    }
+
+    pub fn encode(&self) -> (Vec<u8>, Vec<u8>) {
+        (self.start.encode_v1(), self.end.encode_v1())
+    }
+
+    /// Current UTF-16 range in the content, None if the anchor no longer resolves
+    pub fn offsets(&self, doc: &CrdtDocument) -> Option<Range<u32>> {
+        let start = doc.resolve_sticky_index(&self.start)?;
+        let end = doc.resolve_sticky_index(&self.end)?;
+        (start <= end).then_some(start..end)
+    }
+
+    /// Current range in characters of the given content
+    pub fn text_range(&self, doc: &CrdtDocument, content: &str) -> Option<TextRange> {
+        let offsets = self.offsets(doc)?;
+        let start = utf16_to_byte(content, offsets.start)?;
+        let end = utf16_to_byte(content, offsets.end)?;
+        let text = content.get(start..end)?;
+        let start = content[..start].chars().count();
+        Some(TextRange {
+            start,
+            end: start + text.chars().count(),
+            text: text.to_string(),
+        })
+    }
+}
+
+/// UTF-16 offset of a character index, allowing the index just past the end
+fn char_to_utf16(content: &str, index: usize) -> Option<u32> {
+    let mut chars = content.chars();
+    let mut offset = 0;
+    for _ in 0..index {
+        offset += chars.next()?.len_utf16();
+    }
+    Some(offset as u32)
+}
+
+/// Byte offset of a UTF-16 offset, None if it is past the end or splits a character
+fn utf16_to_byte(content: &str, offset: u32) -> Option<usize> {
+    let mut utf16 = 0;
+    for (byte, c) in content.char_indices() {
+        if utf16 >= offset as usize {
+            return (utf16 == offset as usize).then_some(byte);
+        }
+        utf16 += c.len_utf16();
+    }
+    (utf16 == offset as usize).then_some(content.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_anchor_ranges_count_characters() {
+        let mut doc = CrdtDocument::new_with_content(Uuid::new_v4());
+        doc.set_content("Über den Fluss").unwrap();
+        let content = doc.get_content().unwrap();
+
+        let anchor = TextAnchor::from_chars(&doc, &content, 5, 8).unwrap();
+        let range = anchor.text_range(&doc, &content).unwrap();
+        assert_eq!((range.start, range.end, range.text.as_str()), (5, 8, "den"));
+
+        let point = TextAnchor::from_chars(&doc, &content, 14, 14).unwrap();
+        assert_eq!(point.offsets(&doc), Some(14..14));
+        assert!(TextAnchor::from_chars(&doc, &content, 5, 15).is_none());
+
+        // Text before the anchor outside the Basic Multilingual Plane still shifts it by characters
+        doc.replace_range(0, 0, "🙂 ").unwrap();
+        let content = doc.get_content().unwrap();
+        let range = anchor.text_range(&doc, &content).unwrap();
+        assert_eq!((range.start, range.end, range.text.as_str()), (7, 10, "den"));
+
+        // Replacing the whole text deletes the anchored range
+        doc.set_content("Quer").unwrap();
+        assert!(anchor.offsets(&doc).is_some_and(|range| range.is_empty()));
+    }
+}
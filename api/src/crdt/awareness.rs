@@ -7,6 +7,8 @@ use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 use serde_json::Value;
 use dashmap::DashMap;
+use yrs::block::ClientID;
+use yrs::sync::awareness::{AwarenessUpdate, AwarenessUpdateEntry};
 
 use crate::error::Result;
 
@@ -36,10 +38,20 @@ pub struct SelectionRange {
     pub head: CursorPosition,
 }
 
+/// Last known y-protocols awareness state of a Yjs client
+#[derive(Debug, Clone)]
+pub struct AwarenessClientState {
+    pub clock: u32,
+    pub json: Arc<str>,
+    /// Socket or y-websocket connection the client sends its awareness through
+    pub owner: String,
+}
+
 /// Awareness state for a document
 pub struct DocumentAwareness {
     document_id: Uuid,
     states: Arc<RwLock<HashMap<String, UserPresence>>>,
+    clients: Arc<RwLock<HashMap<ClientID, AwarenessClientState>>>,
     /// Timeout in seconds for removing inactive users
     timeout_seconds: i64,
 }
@@ -49,6 +61,7 @@ impl DocumentAwareness {
         Self {
             document_id,
             states: Arc::new(RwLock::new(HashMap::new())),
+            clients: Arc::new(RwLock::new(HashMap::new())),
             timeout_seconds: 30, // Default 30 seconds timeout
         }
     }
@@ -131,6 +144,102 @@ impl DocumentAwareness {
         inactive_users
     }
 
+    /// Apply a y-protocols awareness update sent through `owner`.
+    ///
+    /// Returns the entries that were accepted, following the Yjs rules: newer clocks win and a
+    /// `null` state removes the client. Entries for clients owned by another connection are
+    /// dropped so a socket cannot overwrite or remove someone else's cursor.
+    pub fn apply_awareness_update(&self, owner: &str, update: &AwarenessUpdate) -> Option<AwarenessUpdate> {
+        let mut clients = self.clients.write();
+        let mut accepted = HashMap::new();
+
+        for (client_id, entry) in &update.clients {
+            let removed = entry.json.as_ref() == "null";
+            if let Some(current) = clients.get(client_id) {
+                if current.owner != owner {
+                    continue;
+                }
+                if current.clock > entry.clock || (current.clock == entry.clock && !removed) {
+                    continue;
+                }
+            } else if removed {
+                continue;
+            }
+
+            if removed {
+                clients.remove(client_id);
+            } else {
+                clients.insert(*client_id, AwarenessClientState {
+                    clock: entry.clock,
+                    json: entry.json.clone(),
+                    owner: owner.to_string(),
+                });
+            }
+            accepted.insert(*client_id, entry.clone());
+        }
+        drop(clients);
+
+        if accepted.is_empty() {
+            return None;
+        }
+
+        // Awareness traffic keeps the owner's presence alive
+        if let Some(presence) = self.states.write().get_mut(owner) {
+            presence.last_seen = Utc::now();
+        }
+
+        Some(AwarenessUpdate { clients: accepted })
+    }
+
+    /// Whether any of the given Yjs clients belongs to `owner`
+    pub fn owns_any_client(&self, owner: &str, client_ids: &[ClientID]) -> bool {
+        let clients = self.clients.read();
+        client_ids
+            .iter()
+            .any(|id| clients.get(id).is_some_and(|state| state.owner == owner))
+    }
+
+    /// Remove the Yjs clients of a connection.
+    ///
+    /// Returns the removal update to broadcast so other peers drop their cursors.
+    pub fn remove_owner(&self, owner: &str) -> Option<AwarenessUpdate> {
+        let mut clients = self.clients.write();
+        let removed: HashMap<ClientID, AwarenessUpdateEntry> = clients
+            .iter()
+            .filter(|(_, state)| state.owner == owner)
+            .map(|(id, state)| (*id, AwarenessUpdateEntry {
+                clock: state.clock + 1,
+                json: Arc::from("null"),
+            }))
+            .collect();
+
+        if removed.is_empty() {
+            return None;
+        }
+        for client_id in removed.keys() {
+            clients.remove(client_id);
+        }
+
+        Some(AwarenessUpdate { clients: removed })
+    }
+
+    /// Full awareness update with every known client, sent to newly joined peers
+    pub fn full_update(&self) -> Option<AwarenessUpdate> {
+        let clients = self.clients.read();
+        if clients.is_empty() {
+            return None;
+        }
+
+        Some(AwarenessUpdate {
+            clients: clients
+                .iter()
+                .map(|(id, state)| (*id, AwarenessUpdateEntry {
+                    clock: state.clock,
+                    json: state.json.clone(),
+                }))
+                .collect(),
+        })
+    }
 
     /// Get awareness state as JSON for broadcasting
     pub fn to_json(&self) -> Value {
@@ -178,6 +287,14 @@ impl AwarenessManager {
         self.documents.remove(document_id).map(|(_, awareness)| awareness)
     }
 
+    /// Find the document whose awareness already knows one of the connection's clients
+    pub fn find_client_document(&self, owner: &str, client_ids: &[ClientID]) -> Option<Uuid> {
+        self.documents
+            .iter()
+            .find(|entry| entry.value().owns_any_client(owner, client_ids))
+            .map(|entry| *entry.key())
+    }
+
     /// Cleanup all inactive users across all documents
     pub fn cleanup_all_inactive_users(&self) -> HashMap<Uuid, Vec<String>> {
         let mut result = HashMap::new();
@@ -228,6 +345,29 @@ mod tests {
         assert_eq!(awareness.get_active_users().len(), 0);
     }
 
+    fn awareness_update(client_id: ClientID, clock: u32, json: &str) -> AwarenessUpdate {
+        let mut clients = HashMap::new();
+        clients.insert(client_id, AwarenessUpdateEntry { clock, json: Arc::from(json) });
+        AwarenessUpdate { clients }
+    }
+
+    #[test]
+    fn test_awareness_client_states() {
+        let awareness = DocumentAwareness::new(Uuid::new_v4());
+
+        assert!(awareness.apply_awareness_update("socket1", &awareness_update(7, 1, r#"{"user":{"name":"a"}}"#)).is_some());
+        // Stale clocks and other owners are ignored
+        assert!(awareness.apply_awareness_update("socket1", &awareness_update(7, 0, "{}")).is_none());
+        assert!(awareness.apply_awareness_update("socket2", &awareness_update(7, 5, "null")).is_none());
+        assert!(awareness.owns_any_client("socket1", &[7]));
+
+        let removal = awareness.remove_owner("socket1").unwrap();
+        let entry = &removal.clients[&7];
+        assert_eq!(entry.clock, 2);
+        assert_eq!(entry.json.as_ref(), "null");
+        assert!(awareness.full_update().is_none());
+    }
+
     #[test]
     fn test_awareness_manager() {
         let manager = AwarenessManager::new();
@@ -3,28 +3,35 @@ use std::sync::Arc;
 use dashmap::DashMap;
 use parking_lot::RwLock;
 use uuid::Uuid;
-use yrs::{Doc, Options, Transact, Update, StateVector, Text, GetString, ReadTxn};
+use yrs::{Any, Assoc, Doc, IndexedSequence, OffsetKind, Options, Out, Snapshot, StickyIndex, Transact, Update, StateVector, Text, GetString, ReadTxn};
+use yrs::types::text::YChange;
 use yrs::updates::encoder::Encode;
 use yrs::updates::decoder::Decode;
 use chrono::{DateTime, Utc};
+use std::time::Instant;
 
+use super::text_diff;
 use crate::error::Result;
 
 /// CRDT document manager that handles Y.Doc instances
 pub struct DocumentManager {
     /// Cache of loaded documents
     documents: Arc<DashMap<Uuid, Arc<RwLock<CrdtDocument>>>>,
+    /// Last time each cached document was accessed
+    last_access: Arc<DashMap<Uuid, Instant>>,
 }
 
 impl DocumentManager {
     pub fn new() -> Self {
         Self {
             documents: Arc::new(DashMap::new()),
+            last_access: Arc::new(DashMap::new()),
         }
     }
 
     /// Get or create a document
     pub fn get_or_create(&self, document_id: Uuid) -> Arc<RwLock<CrdtDocument>> {
+        self.touch(document_id);
         self.documents
             .entry(document_id)
             .or_insert_with(|| Arc::new(RwLock::new(CrdtDocument::new_with_content(document_id))))
@@ -33,19 +40,77 @@ impl DocumentManager {
 
     /// Remove a document from cache
     pub fn remove(&self, document_id: &Uuid) -> Option<Arc<RwLock<CrdtDocument>>> {
+        self.last_access.remove(document_id);
         self.documents.remove(document_id).map(|(_, doc)| doc)
     }
 
+    /// Remove a document only if no one else holds a reference to it and it has not
+    /// been modified since `modified_at`
+    pub fn remove_if_unchanged(&self, document_id: &Uuid, modified_at: DateTime<Utc>) -> bool {
+        let removed = self.documents
+            .remove_if(document_id, |_, doc| {
+                Arc::strong_count(doc) == 1 && doc.read().last_modified() == modified_at
+            })
+            .is_some();
+        if removed {
+            self.last_access.remove(document_id);
+        }
+        removed
+    }
+
     /// Get document if exists in cache
     pub fn get(&self, document_id: &Uuid) -> Option<Arc<RwLock<CrdtDocument>>> {
-        self.documents.get(document_id).map(|entry| entry.value().clone())
+        let doc = self.documents.get(document_id).map(|entry| entry.value().clone());
+        if doc.is_some() {
+            self.touch(*document_id);
+        }
+        doc
     }
 
+
+    /// Get document without counting it as an access
+    pub fn peek(&self, document_id: &Uuid) -> Option<Arc<RwLock<CrdtDocument>>> {
+        self.documents.get(document_id).map(|entry| entry.value().clone())
+    }
     
     /// Get all document IDs currently in cache
     pub fn get_all_document_ids(&self) -> Vec<Uuid> {
         self.documents.iter().map(|entry| *entry.key()).collect()
     }
+
+    /// Cached document IDs with their last access time, least recently used first
+    pub fn documents_by_last_access(&self) -> Vec<(Uuid, Instant)> {
+        let mut entries: Vec<(Uuid, Instant)> = self.documents
+            .iter()
+            .map(|entry| {
+                let accessed = self.last_access
+                    .get(entry.key())
+                    .map(|at| *at)
+                    .unwrap_or_else(Instant::now);
+                (*entry.key(), accessed)
+            })
+            .collect();
+        entries.sort_by_key(|(_, accessed)| *accessed);
+        entries
+    }
+
+    fn touch(&self, document_id: Uuid) {
+        self.last_access.insert(document_id, Instant::now());
+    }
+}
+
+impl Default for DocumentManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Piece of the content text inserted by one Yjs client, starting at `clock`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextChunk {
+    pub client_id: u64,
+    pub clock: u32,
+    pub text: String,
 }
 
 /// CRDT document wrapper
@@ -58,7 +123,10 @@ pub struct CrdtDocument {
 impl CrdtDocument {
     pub fn new(id: Uuid) -> Self {
         let mut options = Options::default();
-        options.client_id = rand::random();
+        // Updates encode client ids in 32 bits, so larger ids would not round-trip
+        options.client_id = rand::random::<u32>() as u64;
+        // Index text in UTF-16 code units like Yjs clients, which sticky indexes rely on
+        options.offset_kind = OffsetKind::Utf16;
         
         Self {
             id,
@@ -82,6 +150,19 @@ impl CrdtDocument {
         Ok(doc)
     }
 
+    /// Rebuild a document by replaying a sequence of updates
+    pub fn from_updates<I, U>(id: Uuid, updates: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = U>,
+        U: AsRef<[u8]>,
+    {
+        let mut doc = Self::new_with_content(id);
+        for update in updates {
+            doc.apply_update(update.as_ref())?;
+        }
+        Ok(doc)
+    }
+
     /// Get document ID
     pub fn id(&self) -> Uuid {
         self.id
@@ -99,24 +180,55 @@ impl CrdtDocument {
         Ok(text.get_string(&self.doc.transact()))
     }
 
-    /// Set document content from markdown string
+    /// Set document content from markdown string.
+    ///
+    /// Only the differing text is deleted and inserted, so concurrent edits, cursors and
+    /// anchors elsewhere in the document are left intact.
     pub fn set_content(&mut self, content: &str) -> Result<()> {
         let text = self.get_text();
         let mut txn = self.doc.transact_mut();
-        
-        // Clear existing content
-        let len = text.len(&txn);
-        if len > 0 {
-            text.remove_range(&mut txn, 0, len);
+        let current = text.get_string(&txn);
+
+        // UTF-16 offset of each character of the current content, plus its end
+        let mut offsets = Vec::with_capacity(current.len() + 1);
+        let mut offset = 0;
+        for c in current.chars() {
+            offsets.push(offset);
+            offset += c.len_utf16() as u32;
+        }
+        offsets.push(offset);
+
+        // Apply from the end so positions of earlier edits stay valid
+        for edit in text_diff::text_edits(&current, content).into_iter().rev() {
+            let start = offsets[edit.position];
+            if edit.delete > 0 {
+                text.remove_range(&mut txn, start, offsets[edit.position + edit.delete] - start);
+            }
+            if !edit.insert.is_empty() {
+                text.insert(&mut txn, start, &edit.insert);
+            }
         }
-        
-        // Insert new content
-        text.insert(&mut txn, 0, content);
         self.last_modified = Utc::now();
         
         Ok(())
     }
 
+    /// Replace a UTF-16 range of the content text
+    pub fn replace_range(&mut self, start: u32, end: u32, content: &str) -> Result<()> {
+        let text = self.get_text();
+        let mut txn = self.doc.transact_mut();
+
+        if end > start {
+            text.remove_range(&mut txn, start, end - start);
+        }
+        if !content.is_empty() {
+            text.insert(&mut txn, start, content);
+        }
+        self.last_modified = Utc::now();
+
+        Ok(())
+    }
+
     /// Apply an update to the document
     pub fn apply_update(&mut self, update: &[u8]) -> Result<()> {
         self.doc.transact_mut().apply_update(Update::decode_v1(update)?)?;
@@ -144,11 +256,63 @@ impl CrdtDocument {
         txn.state_vector().encode_v1()
     }
 
+    /// Sticky index at a UTF-16 offset of the content text, None past its end
+    pub fn sticky_index(&self, index: u32, assoc: Assoc) -> Option<StickyIndex> {
+        let text = self.get_text();
+        let mut txn = self.doc.transact_mut();
+        text.sticky_index(&mut txn, index, assoc)
+    }
+
+    /// Current UTF-16 offset of a sticky index, None if it no longer resolves
+    pub fn resolve_sticky_index(&self, index: &StickyIndex) -> Option<u32> {
+        let txn = self.doc.transact();
+        index.get_offset(&txn).map(|offset| offset.index)
+    }
+
+    /// Content text split into the pieces inserted by each client, in document order
+    pub fn text_chunks(&self) -> Vec<TextChunk> {
+        let text = self.get_text();
+        let mut txn = self.doc.transact_mut();
+        let current = txn.snapshot();
+
+        // Against an empty snapshot every visible item counts as added, tagged with its id
+        text.diff_range(&mut txn, Some(&current), Some(&Snapshot::default()), YChange::identity)
+            .into_iter()
+            .filter_map(|diff| match (diff.insert, diff.ychange) {
+                (Out::Any(Any::String(text)), Some(change)) => Some(TextChunk {
+                    client_id: change.id.client,
+                    clock: change.id.clock,
+                    text: text.to_string(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
     /// Get last modified time
     pub fn last_modified(&self) -> DateTime<Utc> {
         self.last_modified
     }
 
+    /// Approximate memory footprint, based on the encoded document state
+    pub fn estimated_size(&self) -> usize {
+        let txn = self.doc.transact();
+        txn.encode_state_as_update_v1(&StateVector::default()).len()
+    }
+
+}
+
+/// Whether an encoded update neither inserts nor deletes anything
+pub fn is_empty_update(update: &[u8]) -> Result<bool> {
+    let update = Update::decode_v1(update)?;
+    Ok(update.state_vector().is_empty() && update.delete_set().is_empty())
+}
+
+/// State vector of a client once it includes an update of its own
+pub fn merge_state_vector(state_vector: &[u8], update: &[u8]) -> Result<Vec<u8>> {
+    let mut state_vector = StateVector::decode_v1(state_vector)?;
+    state_vector.merge(Update::decode_v1(update)?.state_vector());
+    Ok(state_vector.encode_v1())
 }
 
 #[cfg(test)]
@@ -175,6 +339,109 @@ mod tests {
         assert_eq!(doc.get_content().unwrap(), "New content");
     }
 
+    #[test]
+    fn test_replay_updates() {
+        let doc_id = Uuid::new_v4();
+        let mut doc = CrdtDocument::new(doc_id);
+        let mut updates = Vec::new();
+
+        for content in ["first", "second", "third"] {
+            let before = doc.get_state_vector();
+            doc.set_content(content).unwrap();
+            updates.push(doc.get_update_since(&before).unwrap());
+        }
+
+        let partial = CrdtDocument::from_updates(doc_id, &updates[..2]).unwrap();
+        assert_eq!(partial.get_content().unwrap(), "second");
+
+        let full = CrdtDocument::from_updates(doc_id, &updates).unwrap();
+        assert_eq!(full.get_content().unwrap(), "third");
+    }
+
+    #[test]
+    fn test_sticky_index_follows_edits() {
+        let mut doc = CrdtDocument::new_with_content(Uuid::new_v4());
+        doc.set_content("hello world").unwrap();
+        let start = doc.sticky_index(6, Assoc::After).unwrap();
+        let end = doc.sticky_index(11, Assoc::Before).unwrap();
+
+        let text = doc.get_text();
+        text.insert(&mut doc.doc.transact_mut(), 0, "say: ");
+        assert_eq!(doc.resolve_sticky_index(&start), Some(11));
+        assert_eq!(doc.resolve_sticky_index(&end), Some(16));
+    }
+
+    #[test]
+    fn test_text_chunks_follow_clients() {
+        let mut doc = CrdtDocument::new_with_content(Uuid::new_v4());
+        doc.set_content("hello world").unwrap();
+
+        let mut other = CrdtDocument::new_with_content(doc.id());
+        other.apply_update(&doc.get_state_as_update().unwrap()).unwrap();
+        let state_before = other.get_state_vector();
+        other.replace_range(5, 5, " there").unwrap();
+        doc.apply_update(&other.get_update_since(&state_before).unwrap()).unwrap();
+
+        let chunks: Vec<(u64, u32, String)> = doc.text_chunks()
+            .into_iter()
+            .map(|chunk| (chunk.client_id, chunk.clock, chunk.text))
+            .collect();
+        let (own, theirs) = (doc.doc.client_id(), other.doc.client_id());
+        assert_eq!(chunks, vec![
+            (own, 0, "hello".to_string()),
+            (theirs, 0, " there".to_string()),
+            (own, 5, " world".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_sync_round_trip_skips_own_update() {
+        let mut server = CrdtDocument::new_with_content(Uuid::new_v4());
+        server.set_content("shared").unwrap();
+        let mut client = CrdtDocument::new_with_content(server.id());
+        client.apply_update(&server.get_state_as_update().unwrap()).unwrap();
+
+        let client_before = client.get_state_vector();
+        client.replace_range(0, 0, "mine ").unwrap();
+        let update = client.get_update_since(&client_before).unwrap();
+        server.replace_range(11, 11, " theirs").unwrap();
+
+        assert!(!is_empty_update(&update).unwrap());
+        assert!(is_empty_update(&server.get_update_since(&server.get_state_vector()).unwrap()).unwrap());
+
+        server.apply_update(&update).unwrap();
+        let state_vector = merge_state_vector(&client_before, &update).unwrap();
+        let diff = server.get_update_since(&state_vector).unwrap();
+        assert_eq!(Update::decode_v1(&diff).unwrap().state_vector().get(&client.doc.client_id()), 0);
+
+        client.apply_update(&diff).unwrap();
+        assert_eq!(client.get_content().unwrap(), "mine shared theirs");
+        assert_eq!(server.get_content().unwrap(), "mine shared theirs");
+    }
+
+    #[test]
+    fn test_set_content_keeps_concurrent_edits() {
+        let mut doc = CrdtDocument::new_with_content(Uuid::new_v4());
+        doc.set_content("# Steps\nstop\nstart\n").unwrap();
+        let mut other = CrdtDocument::new_with_content(doc.id());
+        other.apply_update(&doc.get_state_as_update().unwrap()).unwrap();
+        let anchor = doc.sticky_index(13, Assoc::After).unwrap();
+
+        // A full-content replacement and a live edit made at the same time both survive
+        let (doc_before, other_before) = (doc.get_state_vector(), other.get_state_vector());
+        doc.set_content("# Steps\nstop the service\nstart\n").unwrap();
+        other.replace_range(19, 19, "verify\n").unwrap();
+        let doc_update = doc.get_update_since(&doc_before).unwrap();
+        doc.apply_update(&other.get_update_since(&other_before).unwrap()).unwrap();
+        other.apply_update(&doc_update).unwrap();
+
+        let expected = "# Steps\nstop the service\nstart\nverify\n";
+        assert_eq!(doc.get_content().unwrap(), expected);
+        assert_eq!(other.get_content().unwrap(), expected);
+        // The anchor on "start" moved with its text instead of collapsing
+        assert_eq!(doc.resolve_sticky_index(&anchor), Some(25));
+    }
+
     #[test]
     fn test_document_manager() {
         let manager = DocumentManager::new();
@@ -199,4 +466,43 @@ mod tests {
         manager.remove(&doc_id);
         assert!(manager.get(&doc_id).is_none());
     }
+    #[test]
+    fn test_remove_if_unchanged() {
+        let manager = DocumentManager::new();
+        let doc_id = Uuid::new_v4();
+
+        // Not evicted while someone else holds the document
+        let held = manager.get_or_create(doc_id);
+        let modified_at = held.read().last_modified();
+        assert!(!manager.remove_if_unchanged(&doc_id, modified_at));
+        drop(held);
+
+        // Not evicted after a modification made since the flush
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        manager.peek(&doc_id).unwrap().write().replace_range(0, 0, "late edit").unwrap();
+        assert!(!manager.remove_if_unchanged(&doc_id, modified_at));
+        assert!(manager.peek(&doc_id).is_some());
+
+        let modified_at = manager.peek(&doc_id).unwrap().read().last_modified();
+        assert!(manager.remove_if_unchanged(&doc_id, modified_at));
+        assert!(manager.peek(&doc_id).is_none());
+        assert!(manager.documents_by_last_access().is_empty());
+    }
+
+    #[test]
+    fn test_documents_by_last_access() {
+        let manager = DocumentManager::new();
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        for id in [a, b, c] {
+            manager.get_or_create(id);
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        // Reading counts as an access, peeking does not
+        manager.get(&a);
+        manager.peek(&b);
+
+        let order: Vec<Uuid> = manager.documents_by_last_access().into_iter().map(|(id, _)| id).collect();
+        assert_eq!(order, vec![b, c, a]);
+    }
 }
\ No newline at end of file
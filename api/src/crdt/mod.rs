@@ -1,9 +1,12 @@
 pub mod document;
 pub mod awareness;
 pub mod persistence;
+pub mod anchor;
+pub mod text_diff;
 
-pub use document::{CrdtDocument, DocumentManager};
+pub use document::{CrdtDocument, DocumentManager, TextChunk, is_empty_update, merge_state_vector};
 pub use awareness::{
     AwarenessManager, UserPresence, CursorPosition, SelectionRange
 };
-pub use persistence::{DocumentPersistence, serialization};
\ No newline at end of file
+pub use persistence::{DocumentPersistence, serialization};
+pub use anchor::{TextAnchor, TextRange};
\ No newline at end of file
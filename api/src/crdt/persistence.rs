@@ -2,8 +2,14 @@
 use uuid::Uuid;
 use sqlx::{PgPool, Postgres, Transaction};
 use chrono::{DateTime, Utc};
-use crate::error::Result;
+use yrs::Update;
+use yrs::updates::decoder::Decode;
+use crate::error::{Error, Result};
 use crate::crdt::document::CrdtDocument;
+use crate::entities::snapshot::AuthorshipSpan;
+
+/// Seconds of history before the last save replayed when loading a document
+const LOAD_REPLAY_MARGIN_SECS: i64 = 60;
 
 /// Persistence layer for CRDT documents
 pub struct DocumentPersistence {
@@ -20,12 +26,15 @@ impl DocumentPersistence {
         let state = document.get_state_as_update()?;
         let state_vector = document.get_state_vector();
         
-        // First try to update
-        let result = sqlx::query!(
+        // Upsert in one statement, replicas may save the same document concurrently
+        sqlx::query!(
             r#"
-            UPDATE document_updates 
-            SET update_data = $2, state_vector = $3, created_at = $4
-            WHERE document_id = $1
+            INSERT INTO document_updates (document_id, update_data, state_vector, created_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (document_id) DO UPDATE
+            SET update_data = EXCLUDED.update_data,
+                state_vector = EXCLUDED.state_vector,
+                created_at = EXCLUDED.created_at
             "#,
             document.id(),
             &state,
@@ -34,31 +43,19 @@ impl DocumentPersistence {
         )
         .execute(&self.pool)
         .await?;
-        
-        // If no rows were updated, insert
-        if result.rows_affected() == 0 {
-            sqlx::query!(
-                r#"
-                INSERT INTO document_updates (document_id, update_data, state_vector, created_at)
-                VALUES ($1, $2, $3, $4)
-                "#,
-                document.id(),
-                &state,
-                &state_vector,
-                Utc::now()
-            )
-            .execute(&self.pool)
-            .await?;
-        }
 
         Ok(())
     }
 
-    /// Load document state from database
+    /// Load document state from database.
+    ///
+    /// The saved state can lag behind updates recorded by other replicas or right before
+    /// a crash, so history since shortly before the save is replayed on top. Applying an
+    /// update twice is a no-op for the CRDT.
     pub async fn load_document(&self, document_id: Uuid) -> Result<Option<CrdtDocument>> {
         let result = sqlx::query!(
             r#"
-            SELECT update_data 
+            SELECT update_data, created_at
             FROM document_updates 
             WHERE document_id = $1
             "#,
@@ -69,29 +66,75 @@ impl DocumentPersistence {
 
         match result {
             Some(row) => {
-                let doc = CrdtDocument::from_state(document_id, &row.update_data)?;
+                let mut doc = CrdtDocument::from_state(document_id, &row.update_data)?;
+                let since = row.created_at - chrono::Duration::seconds(LOAD_REPLAY_MARGIN_SECS);
+                for update in self.get_updates_since(document_id, since).await? {
+                    doc.apply_update(&update)?;
+                }
                 Ok(Some(doc))
             }
             None => Ok(None),
         }
     }
 
-    /// Save incremental update
+    /// Save incremental update, attributing the text it inserts to its author.
+    ///
+    /// Updates to documents in the trash are rejected, so edits racing a trash cannot
+    /// change what a restore brings back.
     pub async fn save_update(
         &self,
         document_id: Uuid,
         update: &[u8],
+        author: Option<Uuid>,
         tx: &mut Transaction<'_, Postgres>,
     ) -> Result<()> {
-        sqlx::query!(
+        let inserted = inserted_clock_ranges(update)?;
+        let now = Utc::now();
+        // Editors send updates of their own client only; merged updates have no single one
+        let client_id = match inserted.as_slice() {
+            [(client_id, _)] => Some(*client_id as i64),
+            _ => None,
+        };
+
+        let saved = sqlx::query(
             r#"
-            INSERT INTO document_update_history (document_id, update_data, created_at)
-            VALUES ($1, $2, $3)
+            INSERT INTO document_update_history (document_id, update_data, created_at, author_id, client_id)
+            SELECT $1, $2, $3, $4, $5
+            WHERE NOT EXISTS (SELECT 1 FROM documents WHERE id = $1 AND deleted_at IS NOT NULL)
+            "#,
+        )
+        .bind(document_id)
+        .bind(update)
+        .bind(now)
+        .bind(author)
+        .bind(client_id)
+        .execute(&mut **tx)
+        .await?;
+        if saved.rows_affected() == 0 {
+            return Err(Error::Conflict("Document is in the trash".to_string()));
+        }
+
+        if inserted.is_empty() {
+            return Ok(());
+        }
+
+        let client_ids: Vec<i64> = inserted.iter().map(|(client_id, _)| *client_id as i64).collect();
+        let clock_starts: Vec<i64> = inserted.iter().map(|(_, clocks)| clocks.start as i64).collect();
+        let clock_ends: Vec<i64> = inserted.iter().map(|(_, clocks)| clocks.end as i64).collect();
+
+        sqlx::query(
+            r#"
+            INSERT INTO document_authorship (document_id, client_id, clock_start, clock_end, author_id, created_at)
+            SELECT $1, client_id, clock_start, clock_end, $5, $6
+            FROM UNNEST($2::BIGINT[], $3::BIGINT[], $4::BIGINT[]) AS t(client_id, clock_start, clock_end)
             "#,
-            document_id,
-            update,
-            Utc::now()
         )
+        .bind(document_id)
+        .bind(&client_ids)
+        .bind(&clock_starts)
+        .bind(&clock_ends)
+        .bind(author)
+        .bind(now)
         .execute(&mut **tx)
         .await?;
 
@@ -99,15 +142,33 @@ impl DocumentPersistence {
     }
     
     /// Save incremental update with automatic transaction
-    pub async fn save_update_auto(&self, document_id: Uuid, update: &[u8]) -> Result<()> {
+    pub async fn save_update_auto(&self, document_id: Uuid, update: &[u8], author: Option<Uuid>) -> Result<()> {
         let mut tx = self.pool.begin().await?;
         
-        self.save_update(document_id, update, &mut tx).await?;
+        self.save_update(document_id, update, author, &mut tx).await?;
         
         tx.commit().await?;
         Ok(())
     }
 
+    /// Recorded insertions of a document, oldest first, with their authors' names
+    pub async fn get_authorship(&self, document_id: Uuid) -> Result<Vec<AuthorshipSpan>> {
+        let spans = sqlx::query_as::<_, AuthorshipSpan>(
+            r#"
+            SELECT a.client_id, a.clock_start, a.clock_end, a.author_id, u.name AS author_name, a.created_at
+            FROM document_authorship a
+            LEFT JOIN users u ON u.id = a.author_id
+            WHERE a.document_id = $1
+            ORDER BY a.created_at ASC
+            "#,
+        )
+        .bind(document_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(spans)
+    }
+
     /// Get updates since a given timestamp
     pub async fn get_updates_since(
         &self,
@@ -130,7 +191,188 @@ impl DocumentPersistence {
         Ok(rows.into_iter().map(|row| row.update_data).collect())
     }
 
+    /// Get all updates recorded up to and including a given timestamp.
+    ///
+    /// Fails when the timestamp falls inside a span merged by history compaction, since
+    /// the state at that point can no longer be reconstructed.
+    pub async fn get_updates_until(
+        &self,
+        document_id: Uuid,
+        until: DateTime<Utc>,
+    ) -> Result<Vec<Vec<u8>>> {
+        let compacted = sqlx::query_as::<_, (DateTime<Utc>, DateTime<Utc>)>(
+            r#"
+            SELECT compacted_from, created_at
+            FROM document_update_history
+            WHERE document_id = $1 AND is_checkpoint AND compacted_from <= $2 AND created_at > $2
+            LIMIT 1
+            "#,
+        )
+        .bind(document_id)
+        .bind(until)
+        .fetch_optional(&self.pool)
+        .await?;
+        if let Some((from, to)) = compacted {
+            return Err(Error::BadRequest(format!(
+                "History between {} and {} was compacted; pick a time outside that range",
+                from.to_rfc3339(),
+                to.to_rfc3339()
+            )));
+        }
+
+        let updates = sqlx::query_scalar::<_, Vec<u8>>(
+            r#"
+            SELECT update_data
+            FROM document_update_history
+            WHERE document_id = $1 AND created_at <= $2
+            ORDER BY created_at ASC
+            "#,
+        )
+        .bind(document_id)
+        .bind(until)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(updates)
+    }
+
+
+
+    /// Get documents that have more than one history row at or before the cutoff
+    pub async fn get_documents_with_history_before(&self, cutoff: DateTime<Utc>) -> Result<Vec<Uuid>> {
+        let ids = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            SELECT document_id
+            FROM document_update_history
+            WHERE created_at <= $1
+            GROUP BY document_id
+            HAVING COUNT(*) > 1 AND BOOL_OR(NOT is_checkpoint)
+            "#,
+        )
+        .bind(cutoff)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(ids)
+    }
+
+    /// Merge history rows at or before the cutoff into daily checkpoint rows.
+    ///
+    /// Each group of two or more rows is replaced by a single merged update stamped with
+    /// the group's latest timestamp, remembering where the group started. Groups never
+    /// span a snapshot in `boundaries`, so replaying up to those snapshots yields the same
+    /// document as before. Authorship ranges at or before the cutoff are merged the same
+    /// way when they continue each other on the same day.
+    /// Returns the number of history rows removed.
+    pub async fn compact_history(
+        &self,
+        document_id: Uuid,
+        cutoff: DateTime<Utc>,
+        boundaries: &[DateTime<Utc>],
+    ) -> Result<usize> {
+        let mut tx = self.pool.begin().await?;
+
+        let rows = sqlx::query_as::<_, (Uuid, Vec<u8>, DateTime<Utc>, Option<DateTime<Utc>>)>(
+            r#"
+            SELECT id, update_data, created_at, compacted_from
+            FROM document_update_history
+            WHERE document_id = $1 AND created_at <= $2
+            ORDER BY created_at ASC
+            FOR UPDATE
+            "#,
+        )
+        .bind(document_id)
+        .bind(cutoff)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let timestamps: Vec<DateTime<Utc>> = rows.iter().map(|(_, _, created_at, _)| *created_at).collect();
+        let mut removed = 0;
+
+        for segment in checkpoint_segments(&timestamps, boundaries) {
+            let group = &rows[segment];
+            if group.len() < 2 {
+                continue;
+            }
+
+            let merged = yrs::merge_updates_v1(group.iter().map(|(_, update, _, _)| update))?;
+            let ids: Vec<Uuid> = group.iter().map(|(id, _, _, _)| *id).collect();
+            let checkpoint_at = group[group.len() - 1].2;
+            // An earlier checkpoint at the head of the group already covers rows before it
+            let compacted_from = group[0].3.unwrap_or(group[0].2);
+
+            sqlx::query("DELETE FROM document_update_history WHERE id = ANY($1)")
+                .bind(&ids)
+                .execute(&mut *tx)
+                .await?;
+
+            sqlx::query(
+                r#"
+                INSERT INTO document_update_history (document_id, update_data, created_at, is_checkpoint, compacted_from)
+                VALUES ($1, $2, $3, TRUE, $4)
+                "#,
+            )
+            .bind(document_id)
+            .bind(&merged)
+            .bind(checkpoint_at)
+            .bind(compacted_from)
+            .execute(&mut *tx)
+            .await?;
+
+            removed += ids.len() - 1;
+        }
+
+        self.compact_authorship(document_id, cutoff, &mut tx).await?;
+
+        tx.commit().await?;
+        Ok(removed)
+    }
+
+    /// Merge authorship ranges at or before the cutoff that continue each other
+    async fn compact_authorship(
+        &self,
+        document_id: Uuid,
+        cutoff: DateTime<Utc>,
+        tx: &mut Transaction<'_, Postgres>,
+    ) -> Result<()> {
+        let rows = sqlx::query_as::<_, AuthorshipRow>(
+            r#"
+            SELECT id, client_id, clock_start, clock_end, author_id, created_at
+            FROM document_authorship
+            WHERE document_id = $1 AND created_at <= $2
+            ORDER BY client_id, clock_start
+            FOR UPDATE
+            "#,
+        )
+        .bind(document_id)
+        .bind(cutoff)
+        .fetch_all(&mut **tx)
+        .await?;
+
+        for run in authorship_runs(&rows) {
+            let group = &rows[run];
+            if group.len() < 2 {
+                continue;
+            }
+
+            let ids: Vec<Uuid> = group[1..].iter().map(|(id, ..)| *id).collect();
+            let (_, _, _, clock_end, _, _) = group[group.len() - 1];
+            let latest = group.iter().map(|(.., created_at)| *created_at).max();
+
+            sqlx::query("DELETE FROM document_authorship WHERE id = ANY($1)")
+                .bind(&ids)
+                .execute(&mut **tx)
+                .await?;
+            sqlx::query("UPDATE document_authorship SET clock_end = $2, created_at = $3 WHERE id = $1")
+                .bind(group[0].0)
+                .bind(clock_end)
+                .bind(latest)
+                .execute(&mut **tx)
+                .await?;
+        }
 
+        Ok(())
+    }
 
     /// Sync CRDT document content back to the main documents table
     pub async fn sync_to_documents_table(&self, document: &CrdtDocument) -> Result<()> {
@@ -151,11 +393,98 @@ impl DocumentPersistence {
         .execute(&self.pool)
         .await?;
 
+        self.index_content(document.id(), &document.get_content()?).await
+    }
+
+    /// Refresh the full-text search index with a document's current content
+    pub async fn index_content(&self, document_id: Uuid, content: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO document_search_index (document_id, content)
+            SELECT id, $2 FROM documents WHERE id = $1
+            ON CONFLICT (document_id) DO UPDATE
+            SET content = EXCLUDED.content, indexed_at = NOW()
+            WHERE document_search_index.content IS DISTINCT FROM EXCLUDED.content
+            "#,
+        )
+        .bind(document_id)
+        .bind(content)
+        .execute(&self.pool)
+        .await?;
+
         Ok(())
     }
 
 }
 
+/// Split ordered timestamps into checkpoint groups.
+///
+/// A new group starts on each UTC day and after every boundary, since a snapshot at `b`
+/// covers exactly the rows with `created_at <= b`.
+fn checkpoint_segments(timestamps: &[DateTime<Utc>], boundaries: &[DateTime<Utc>]) -> Vec<std::ops::Range<usize>> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+
+    for i in 1..timestamps.len() {
+        let (prev, next) = (timestamps[i - 1], timestamps[i]);
+        let crosses_boundary = boundaries.iter().any(|b| prev <= *b && *b < next);
+        if prev.date_naive() != next.date_naive() || crosses_boundary {
+            segments.push(start..i);
+            start = i;
+        }
+    }
+
+    if start < timestamps.len() {
+        segments.push(start..timestamps.len());
+    }
+
+    segments
+}
+
+/// An authorship row: id, client, clock start and end, author and time
+type AuthorshipRow = (Uuid, i64, i64, i64, Option<Uuid>, DateTime<Utc>);
+
+/// Split authorship rows ordered by client and clock into runs to merge.
+///
+/// A run continues while the next range starts where the previous one ended and has
+/// the same client, author and UTC day.
+fn authorship_runs(rows: &[AuthorshipRow]) -> Vec<std::ops::Range<usize>> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+
+    for i in 1..rows.len() {
+        let (_, prev_client, _, prev_end, prev_author, prev_at) = rows[i - 1];
+        let (_, client, clock_start, _, author, at) = rows[i];
+        let continues = client == prev_client
+            && clock_start == prev_end
+            && author == prev_author
+            && at.date_naive() == prev_at.date_naive();
+        if !continues {
+            runs.push(start..i);
+            start = i;
+        }
+    }
+
+    if start < rows.len() {
+        runs.push(start..rows.len());
+    }
+
+    runs
+}
+
+/// Clock ranges of the content each client inserted in an update
+fn inserted_clock_ranges(update: &[u8]) -> Result<Vec<(u64, std::ops::Range<u32>)>> {
+    let update = Update::decode_v1(update)?;
+    let lower = update.state_vector_lower();
+
+    Ok(update
+        .state_vector()
+        .iter()
+        .map(|(client_id, end)| (*client_id, lower.get(client_id)..*end))
+        .filter(|(_, clocks)| !clocks.is_empty())
+        .collect())
+}
+
 /// Helper functions for serialization
 pub mod serialization {
     use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
@@ -170,7 +499,11 @@ pub mod serialization {
 
 #[cfg(test)]
 mod tests {
-    use super::serialization;
+    use super::{authorship_runs, checkpoint_segments, inserted_clock_ranges, serialization};
+    use crate::crdt::CrdtDocument;
+    use crate::test_support::{db_test, TestDb};
+    use chrono::{TimeZone, Utc};
+    use uuid::Uuid;
 
     #[test]
     fn test_serialization() {
@@ -181,4 +514,140 @@ mod tests {
         let decoded = BASE64.decode(&encoded).unwrap();
         assert_eq!(data, decoded);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_checkpoint_segments() {
+        let at = |d: u32, h: u32| Utc.with_ymd_and_hms(2025, 7, d, h, 0, 0).unwrap();
+        let timestamps = vec![at(1, 9), at(1, 10), at(1, 12), at(2, 8), at(2, 9)];
+
+        // Split by day only
+        assert_eq!(checkpoint_segments(&timestamps, &[]), vec![0..3, 3..5]);
+
+        // A snapshot taken at 10:00 on day 1 keeps its rows separate
+        assert_eq!(checkpoint_segments(&timestamps, &[at(1, 10)]), vec![0..2, 2..3, 3..5]);
+
+        assert!(checkpoint_segments(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn test_authorship_runs() {
+        let alice = Some(Uuid::new_v4());
+        let at = |d: u32, h: u32| Utc.with_ymd_and_hms(2025, 7, d, h, 0, 0).unwrap();
+        let row = |client: i64, start: i64, end: i64, author: Option<Uuid>, at| (Uuid::new_v4(), client, start, end, author, at);
+        let rows = vec![
+            row(1, 0, 3, alice, at(1, 9)),
+            row(1, 3, 5, alice, at(1, 10)),
+            // Gap in the clocks
+            row(1, 6, 8, alice, at(1, 11)),
+            // Different author, then a different day
+            row(1, 8, 9, None, at(1, 12)),
+            row(1, 9, 10, None, at(2, 8)),
+            // Different client
+            row(2, 10, 12, None, at(2, 9)),
+        ];
+
+        assert_eq!(authorship_runs(&rows), vec![0..2, 2..3, 3..4, 4..5, 5..6]);
+        assert!(authorship_runs(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_inserted_clock_ranges() {
+        let mut doc = CrdtDocument::new_with_content(Uuid::new_v4());
+        doc.set_content("hello").unwrap();
+        let state_before = doc.get_state_vector();
+        doc.replace_range(5, 5, " world").unwrap();
+        let update = doc.get_update_since(&state_before).unwrap();
+
+        let ranges = inserted_clock_ranges(&update).unwrap();
+        assert_eq!(ranges.iter().map(|(_, clocks)| clocks.clone()).collect::<Vec<_>>(), vec![5..11]);
+
+        // Deleting text inserts nothing
+        let state_before = doc.get_state_vector();
+        doc.replace_range(0, 5, "").unwrap();
+        assert!(inserted_clock_ranges(&doc.get_update_since(&state_before).unwrap()).unwrap().is_empty());
+    }
+
+    db_test! {
+        async fn test_save_update_rejects_trashed_documents() {
+            let db = TestDb::connect().await;
+            let persistence = super::DocumentPersistence::new((*db.pool).clone());
+            let owner_id = db.create_user().await;
+            let document_id = db.create_document("Trashed", owner_id).await;
+
+            let mut tx = db.pool.begin().await.unwrap();
+            sqlx::query("UPDATE documents SET deleted_at = NOW() WHERE id = $1")
+                .bind(document_id)
+                .execute(&mut *tx)
+                .await
+                .unwrap();
+
+            let mut doc = CrdtDocument::new_with_content(document_id);
+            let state_before = doc.get_state_vector();
+            doc.set_content("late edit").unwrap();
+            let update = doc.get_update_since(&state_before).unwrap();
+
+            assert!(persistence.save_update(document_id, &update, None, &mut tx).await.is_err());
+
+            sqlx::query("UPDATE documents SET deleted_at = NULL WHERE id = $1")
+                .bind(document_id)
+                .execute(&mut *tx)
+                .await
+                .unwrap();
+            assert!(persistence.save_update(document_id, &update, None, &mut tx).await.is_ok());
+
+            tx.rollback().await.unwrap();
+        }
+    }
+
+    db_test! {
+        async fn test_compacted_history_merges_authorship_and_refuses_replay_inside() {
+            let db = TestDb::connect().await;
+            let pool = db.pool.as_ref();
+            let persistence = super::DocumentPersistence::new(pool.clone());
+            let at = |h: u32| Utc.with_ymd_and_hms(2020, 1, 1, h, 0, 0).unwrap();
+            let owner_id = db.create_user().await;
+            let document_id = db.create_document("Compacted", owner_id).await;
+
+            let mut doc = CrdtDocument::new_with_content(document_id);
+            for (hour, content) in [(9, "one"), (10, "two"), (12, "three")] {
+                let state_before = doc.get_state_vector();
+                doc.set_content(content).unwrap();
+                sqlx::query("INSERT INTO document_update_history (document_id, update_data, created_at) VALUES ($1, $2, $3)")
+                    .bind(document_id)
+                    .bind(doc.get_update_since(&state_before).unwrap())
+                    .bind(at(hour))
+                    .execute(pool)
+                    .await
+                    .unwrap();
+            }
+
+            for (hour, clocks) in [(9, 0..3), (10, 3..6)] {
+                sqlx::query(
+                    "INSERT INTO document_authorship (document_id, client_id, clock_start, clock_end, author_id, created_at) VALUES ($1, 1, $2, $3, $4, $5)"
+                )
+                .bind(document_id)
+                .bind(clocks.start as i64)
+                .bind(clocks.end as i64)
+                .bind(owner_id)
+                .bind(at(hour))
+                .execute(pool)
+                .await
+                .unwrap();
+            }
+
+            assert_eq!(persistence.compact_history(document_id, at(23), &[]).await.unwrap(), 2);
+            let authorship = persistence.get_authorship(document_id).await.unwrap();
+            let inside = persistence.get_updates_until(document_id, at(11)).await;
+            let before = persistence.get_updates_until(document_id, at(8)).await.unwrap();
+            let at_checkpoint = persistence.get_updates_until(document_id, at(12)).await.unwrap();
+
+            assert_eq!(
+                authorship.iter().map(|span| (span.clock_start, span.clock_end, span.created_at)).collect::<Vec<_>>(),
+                vec![(0, 6, at(10))]
+            );
+            assert!(inside.is_err());
+            assert!(before.is_empty());
+            assert_eq!(CrdtDocument::from_updates(document_id, &at_checkpoint).unwrap().get_content().unwrap(), "three");
+        }
+    }
+}
@@ -0,0 +1,260 @@
+/// Most line edits searched for before replacing the changed region wholesale
+const MAX_LINE_EDITS: usize = 1000;
+/// Most character edits searched for within a block of changed lines
+const MAX_CHAR_EDITS: usize = 500;
+
+/// Replacement of `delete` characters at `position` of the old text by `insert`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub position: usize,
+    pub delete: usize,
+    pub insert: String,
+}
+
+/// Edits turning `old` into `new`, in ascending order of position.
+///
+/// Lines are compared first, then characters within changed lines, so an edit touches
+/// only the text that actually differs. Positions are character offsets into `old`.
+pub fn text_edits(old: &str, new: &str) -> Vec<TextEdit> {
+    let old: Vec<char> = old.chars().collect();
+    let new: Vec<char> = new.chars().collect();
+
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+
+    let mut edits = Vec::new();
+    if old_mid.is_empty() && new_mid.is_empty() {
+        return edits;
+    }
+
+    let old_lines = split_lines(old_mid);
+    let new_lines = split_lines(new_mid);
+    let Some(line_ops) = diff(&old_lines, &new_lines, MAX_LINE_EDITS) else {
+        edits.push(replace(prefix, old_mid, new_mid));
+        return edits;
+    };
+
+    // Walk the line script, diffing each block of changed lines by characters
+    let (mut old_line, mut new_line) = (0, 0);
+    let (mut old_pos, mut new_pos) = (prefix, 0);
+    let mut ops = line_ops.into_iter().peekable();
+    while let Some(op) = ops.next() {
+        let (mut deleted, mut inserted) = (0, 0);
+        match op {
+            DiffOp::Equal(n) => {
+                for line in &old_lines[old_line..old_line + n] {
+                    old_pos += line.len();
+                }
+                for line in &new_lines[new_line..new_line + n] {
+                    new_pos += line.len();
+                }
+                old_line += n;
+                new_line += n;
+                continue;
+            }
+            DiffOp::Delete(n) => deleted += n,
+            DiffOp::Insert(n) => inserted += n,
+        }
+        while let Some(DiffOp::Delete(n) | DiffOp::Insert(n)) = ops.peek().copied() {
+            match ops.next() {
+                Some(DiffOp::Delete(_)) => deleted += n,
+                _ => inserted += n,
+            }
+        }
+
+        let old_len: usize = old_lines[old_line..old_line + deleted].iter().map(|line| line.len()).sum();
+        let new_len: usize = new_lines[new_line..new_line + inserted].iter().map(|line| line.len()).sum();
+        let old_block = &old_mid[old_pos - prefix..old_pos - prefix + old_len];
+        let new_block = &new_mid[new_pos..new_pos + new_len];
+
+        match diff(old_block, new_block, MAX_CHAR_EDITS) {
+            Some(char_ops) => edits.extend(char_edits(old_pos, &char_ops, new_block)),
+            None => edits.push(replace(old_pos, old_block, new_block)),
+        }
+
+        old_line += deleted;
+        new_line += inserted;
+        old_pos += old_len;
+        new_pos += new_len;
+    }
+
+    edits
+}
+
+fn replace(position: usize, old: &[char], new: &[char]) -> TextEdit {
+    TextEdit {
+        position,
+        delete: old.len(),
+        insert: new.iter().collect(),
+    }
+}
+
+/// Lines of the text, each keeping its trailing newline
+fn split_lines(text: &[char]) -> Vec<&[char]> {
+    text.split_inclusive(|c| *c == '\n').collect()
+}
+
+/// Turn a character script into edits, merging adjacent deletions and insertions
+fn char_edits(start: usize, ops: &[DiffOp], new: &[char]) -> Vec<TextEdit> {
+    let mut edits: Vec<TextEdit> = Vec::new();
+    let (mut old_pos, mut new_pos) = (start, 0);
+    let mut pending: Option<TextEdit> = None;
+
+    for op in ops {
+        match *op {
+            DiffOp::Equal(n) => {
+                edits.extend(pending.take());
+                old_pos += n;
+                new_pos += n;
+            }
+            DiffOp::Delete(n) => {
+                pending.get_or_insert_with(|| TextEdit { position: old_pos, delete: 0, insert: String::new() }).delete += n;
+                old_pos += n;
+            }
+            DiffOp::Insert(n) => {
+                let edit = pending.get_or_insert_with(|| TextEdit { position: old_pos, delete: 0, insert: String::new() });
+                edit.insert.extend(&new[new_pos..new_pos + n]);
+                new_pos += n;
+            }
+        }
+    }
+    edits.extend(pending);
+
+    edits
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiffOp {
+    Equal(usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Shortest edit script between two sequences (Myers), None if it needs more than
+/// `max_edits` insertions and deletions
+fn diff<T: PartialEq>(a: &[T], b: &[T], max_edits: usize) -> Option<Vec<DiffOp>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max_d = (a.len() + b.len()).min(max_edits) as isize;
+    let offset = max_d + 1;
+    let mut v = vec![0isize; 2 * max_d as usize + 3];
+    // Furthest reaching x per diagonal before each round, for backtracking
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    for d in 0..=max_d {
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let i = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[i - 1] < v[i + 1]) { v[i + 1] } else { v[i - 1] + 1 };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[i] = x;
+            if x >= n && y >= m {
+                return Some(backtrack(&trace, n, m));
+            }
+        }
+    }
+
+    None
+}
+
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize) -> Vec<DiffOp> {
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        // Rounds store diagonals -d..=d starting at index 0
+        let at = |k: isize| v[(k + d) as usize];
+        let k = x - y;
+        let (prev_x, prev_y) = if d == 0 {
+            (0, 0)
+        } else {
+            let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) { k + 1 } else { k - 1 };
+            (at(prev_k), at(prev_k) - prev_k)
+        };
+
+        while x > prev_x && y > prev_y {
+            push_op(&mut ops, DiffOp::Equal(1));
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            push_op(&mut ops, if x == prev_x { DiffOp::Insert(1) } else { DiffOp::Delete(1) });
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+
+    ops.reverse();
+    ops
+}
+
+/// Append an operation, extending the last one if it is of the same kind
+fn push_op(ops: &mut Vec<DiffOp>, op: DiffOp) {
+    match (ops.last_mut(), op) {
+        (Some(DiffOp::Equal(n)), DiffOp::Equal(m))
+        | (Some(DiffOp::Delete(n)), DiffOp::Delete(m))
+        | (Some(DiffOp::Insert(n)), DiffOp::Insert(m)) => *n += m,
+        _ => ops.push(op),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(old: &str, edits: &[TextEdit]) -> String {
+        let mut chars: Vec<char> = old.chars().collect();
+        for edit in edits.iter().rev() {
+            chars.splice(edit.position..edit.position + edit.delete, edit.insert.chars());
+        }
+        chars.into_iter().collect()
+    }
+
+    #[test]
+    fn test_text_edits_are_minimal() {
+        let old = "# Runbook\n\nRestart the service.\nCheck the logs.\n";
+        let new = "# Runbook\n\nRestart the web service.\nCheck the logs.\nDone.\n";
+        let edits = text_edits(old, new);
+
+        assert_eq!(edits.len(), 2);
+        assert_eq!(edits[0], TextEdit { position: 23, delete: 0, insert: "web ".to_string() });
+        assert_eq!(edits[1].delete, 0);
+        assert_eq!(edits[1].insert.chars().count(), 6);
+        assert_eq!(apply(old, &edits), new);
+    }
+
+    #[test]
+    fn test_text_edits_round_trip() {
+        let cases = [
+            ("", ""),
+            ("", "new"),
+            ("old", ""),
+            ("same", "same"),
+            ("Über\nden Fluss\n", "Unter\nden 🙂 Fluss\nweg"),
+            ("a\nb\nc\nd\n", "d\nc\nb\na\n"),
+            ("line\nline\nline\n", "line\nother\nline\nline\n"),
+        ];
+        for (old, new) in cases {
+            assert_eq!(apply(old, &text_edits(old, new)), new, "{:?} -> {:?}", old, new);
+        }
+    }
+
+    #[test]
+    fn test_diff_gives_up_past_limit() {
+        let a: Vec<u32> = (0..50).collect();
+        let b: Vec<u32> = (50..100).collect();
+        assert!(diff(&a, &b, 10).is_none());
+        assert_eq!(diff(&a, &b, 100), Some(vec![DiffOp::Delete(50), DiffOp::Insert(50)]));
+    }
+}
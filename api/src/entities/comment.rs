@@ -0,0 +1,92 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use sqlx::FromRow;
+
+use crate::crdt::TextRange;
+
+pub const THREAD_STATUS_OPEN: &str = "open";
+pub const THREAD_STATUS_RESOLVED: &str = "resolved";
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CommentThread {
+    pub id: Uuid,
+    pub document_id: Uuid,
+    /// Encoded sticky index of the first character of the range
+    #[serde(skip)]
+    pub anchor_start: Vec<u8>,
+    /// Encoded sticky index just past the last character of the range
+    #[serde(skip)]
+    pub anchor_end: Vec<u8>,
+    pub quote: String,
+    pub status: String,
+    pub created_by: Option<Uuid>,
+    pub resolved_by: Option<Uuid>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Comment {
+    pub id: Uuid,
+    pub thread_id: Uuid,
+    pub author_id: Option<Uuid>,
+    pub author_name: String,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Who wrote a comment; guests only have a display name
+#[derive(Debug, Clone)]
+pub struct CommentAuthor {
+    pub user_id: Option<Uuid>,
+    pub name: String,
+}
+
+/// Range of document text a new thread or suggestion is anchored to
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum AnchorRequest {
+    /// Base64 Yjs relative positions, as produced by `Y.encodeRelativePosition`
+    Relative { start: String, end: String },
+    /// Character offsets into the current document content
+    Offsets { start_index: usize, end_index: usize },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateThreadRequest {
+    pub anchor: AnchorRequest,
+    pub body: String,
+    /// Display name for guests commenting through a share link
+    pub author_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReplyRequest {
+    pub body: String,
+    pub author_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommentListQuery {
+    pub include_resolved: Option<bool>,
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommentThreadResponse {
+    #[serde(flatten)]
+    pub thread: CommentThread,
+    /// Base64 Yjs relative positions of the anchor
+    pub anchor_start: String,
+    pub anchor_end: String,
+    /// None when the anchored text has been deleted
+    pub range: Option<TextRange>,
+    pub comments: Vec<Comment>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CommentThreadListResponse {
+    pub threads: Vec<CommentThreadResponse>,
+}
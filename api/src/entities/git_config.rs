@@ -14,6 +14,10 @@ pub struct GitConfig {
     pub auth_type: String, // 'ssh' or 'token'
     pub auth_data: serde_json::Value, // Encrypted SSH private key or token
     pub auto_sync: bool,
+    /// Folder synced to this repository; None for the user's whole document tree
+    pub folder_id: Option<Uuid>,
+    /// Seconds without changes before an automatic sync; None uses the default
+    pub sync_interval_secs: Option<i32>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -48,6 +52,8 @@ pub struct CreateGitConfigRequest {
     pub auth_type: String,
     pub auth_data: serde_json::Value,
     pub auto_sync: Option<bool>,
+    pub folder_id: Option<Uuid>,
+    pub sync_interval_secs: Option<i32>,
 }
 
 impl CreateGitConfigRequest {
@@ -77,6 +83,7 @@ pub struct UpdateGitConfigRequest {
     pub auth_type: Option<String>,
     pub auth_data: Option<serde_json::Value>,
     pub auto_sync: Option<bool>,
+    pub sync_interval_secs: Option<i32>,
 }
 
 impl UpdateGitConfigRequest {
@@ -108,6 +115,8 @@ pub struct GitConfigResponse {
     pub branch_name: String,
     pub auth_type: String,
     pub auto_sync: bool,
+    pub folder_id: Option<Uuid>,
+    pub sync_interval_secs: Option<i32>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -120,6 +129,8 @@ impl From<GitConfig> for GitConfigResponse {
             branch_name: config.branch_name,
             auth_type: config.auth_type,
             auto_sync: config.auto_sync,
+            folder_id: config.folder_id,
+            sync_interval_secs: config.sync_interval_secs,
             created_at: config.created_at,
             updated_at: config.updated_at,
         }
@@ -130,6 +141,8 @@ impl From<GitConfig> for GitConfigResponse {
 pub struct GitSyncLog {
     pub id: Uuid,
     pub user_id: Uuid,
+    /// Folder repository the operation ran against; None for the root repository
+    pub git_config_id: Option<Uuid>,
     pub operation: String, // 'push', 'pull', 'commit'
     pub status: String,    // 'success', 'error'
     pub message: Option<String>,
@@ -169,6 +182,54 @@ pub struct GitStatus {
     pub untracked_files: u32,
     pub last_sync: Option<DateTime<Utc>>,
     pub sync_enabled: bool,
+    pub last_batch_sync: Option<GitBatchSyncStatus>,
+    /// Folder repositories of the user, only reported with the root repository's status
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub repositories: Vec<GitRepositorySyncStatus>,
+}
+
+/// Sync schedule, latest batch sync and recent logs of a folder repository
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitRepositorySyncStatus {
+    pub id: Uuid,
+    pub folder_id: Option<Uuid>,
+    pub repository_url: String,
+    pub auto_sync: bool,
+    pub sync_interval_secs: Option<i32>,
+    pub last_sync: Option<DateTime<Utc>>,
+    pub last_batch_sync: Option<GitBatchSyncStatus>,
+    pub logs: Vec<GitSyncLogResponse>,
+}
+
+/// Stage of an automatic (batched) git sync
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "text")]
+#[sqlx(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum GitBatchSyncStage {
+    Queued,
+    Started,
+    Committed,
+    Pushed,
+    /// Unresolved merge conflicts block the sync until the user resolves them
+    Conflicted,
+    /// Failed, another attempt is scheduled
+    Retrying,
+    /// Failed after the last retry
+    Failed,
+}
+
+/// Latest batch sync state of a repository, pushed to the user's sockets as `git:sync` events
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct GitBatchSyncStatus {
+    /// Folder repository the batch belongs to; None for the root repository
+    pub git_config_id: Option<Uuid>,
+    pub status: GitBatchSyncStage,
+    pub documents: Vec<String>,
+    pub retry_count: i32,
+    pub commit_hash: Option<String>,
+    pub message: Option<String>,
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -6,3 +6,8 @@ pub mod share;
 pub mod git_config;
 pub mod tag;
 
+pub mod snapshot;
+pub mod comment;
+pub mod suggestion;
+pub mod search;
+pub mod trash;
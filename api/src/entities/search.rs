@@ -0,0 +1,49 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use sqlx::FromRow;
+
+#[derive(Debug, Deserialize)]
+pub struct ContentSearchQuery {
+    /// Words to match; `"quoted phrases"`, `prefix*`, `-excluded` and `OR` are supported
+    pub q: String,
+    /// Only documents carrying this tag
+    pub tag: Option<String>,
+    /// Only documents of this type (`document` or `scrap`)
+    #[serde(rename = "type")]
+    pub document_type: Option<String>,
+    /// Only documents inside this folder, at any depth
+    pub folder_id: Option<Uuid>,
+    pub updated_after: Option<DateTime<Utc>>,
+    pub updated_before: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// Filters applied to a content search, besides the query itself
+#[derive(Debug, Clone, Default)]
+pub struct ContentSearchFilters {
+    pub tag: Option<String>,
+    pub document_type: Option<String>,
+    pub folder_id: Option<Uuid>,
+    pub updated_after: Option<DateTime<Utc>>,
+    pub updated_before: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ContentSearchHit {
+    pub id: Uuid,
+    pub title: String,
+    pub document_type: String,
+    pub file_path: Option<String>,
+    /// HTML-escaped excerpt of the content with matches wrapped in `<mark>`
+    pub snippet: String,
+    pub rank: f32,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContentSearchResponse {
+    pub results: Vec<ContentSearchHit>,
+    pub total: i64,
+}
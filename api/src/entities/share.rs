@@ -34,6 +34,16 @@ impl Permission {
     pub fn has_permission(&self, required: Permission) -> bool {
         self.level() >= required.level()
     }
+
+    /// Names of the permissions that include this one, for `permission = ANY(...)` filters:
+    /// stored permissions are text, which does not sort by level
+    pub fn granted_by(&self) -> Vec<String> {
+        [Permission::View, Permission::Comment, Permission::Edit, Permission::Admin, Permission::Owner]
+            .into_iter()
+            .filter(|permission| permission.has_permission(*self))
+            .map(|permission| permission.to_string())
+            .collect()
+    }
 }
 
 impl fmt::Display for Permission {
@@ -76,6 +86,11 @@ pub struct ShareDocumentRequest {
     pub expires_at: Option<DateTime<Utc>>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct UpdatePermissionRequest {
+    pub permission: Permission,
+}
+
 #[derive(Debug, Serialize)]
 pub struct ShareResponse {
     pub token: String,
@@ -91,4 +106,15 @@ pub struct SharedDocument {
     #[serde(rename = "type")]
     pub doc_type: String,
     pub permission: Permission,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_granted_by_follows_levels() {
+        assert_eq!(Permission::View.granted_by(), vec!["view", "comment", "edit", "admin", "owner"]);
+        assert_eq!(Permission::Edit.granted_by(), vec!["edit", "admin", "owner"]);
+    }
+}
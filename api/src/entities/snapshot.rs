@@ -0,0 +1,90 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use sqlx::FromRow;
+
+pub const SNAPSHOT_KIND_MANUAL: &str = "manual";
+pub const SNAPSHOT_KIND_AUTO: &str = "auto";
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DocumentSnapshot {
+    pub id: Uuid,
+    pub document_id: Uuid,
+    pub name: Option<String>,
+    pub kind: String,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSnapshotRequest {
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryContentQuery {
+    pub at: Option<DateTime<Utc>>,
+    pub snapshot_id: Option<Uuid>,
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RestoreRequest {
+    pub at: Option<DateTime<Utc>>,
+    pub snapshot_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SnapshotListResponse {
+    pub snapshots: Vec<DocumentSnapshot>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistoryContentResponse {
+    pub document_id: Uuid,
+    pub at: DateTime<Utc>,
+    pub content: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestoreResponse {
+    pub document_id: Uuid,
+    pub restored_from: DateTime<Utc>,
+    pub backup_snapshot_id: Uuid,
+    pub content: String,
+}
+
+/// Clock range of text a Yjs client inserted in one recorded update
+#[derive(Debug, Clone, FromRow)]
+pub struct AuthorshipSpan {
+    pub client_id: i64,
+    pub clock_start: i64,
+    pub clock_end: i64,
+    pub author_id: Option<Uuid>,
+    pub author_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Range of the current content, in characters, last written by one author
+#[derive(Debug, Clone, Serialize)]
+pub struct BlameRange {
+    pub start: usize,
+    pub end: usize,
+    pub client_id: u64,
+    /// None for text written before authorship was recorded or by unknown clients
+    pub author_id: Option<Uuid>,
+    pub author_name: Option<String>,
+    pub changed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BlameQuery {
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BlameResponse {
+    pub document_id: Uuid,
+    pub content: String,
+    pub ranges: Vec<BlameRange>,
+}
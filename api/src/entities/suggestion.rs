@@ -0,0 +1,65 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use sqlx::FromRow;
+
+use crate::crdt::TextRange;
+use crate::entities::comment::AnchorRequest;
+
+pub const SUGGESTION_STATUS_PENDING: &str = "pending";
+pub const SUGGESTION_STATUS_ACCEPTED: &str = "accepted";
+pub const SUGGESTION_STATUS_REJECTED: &str = "rejected";
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Suggestion {
+    pub id: Uuid,
+    pub document_id: Uuid,
+    #[serde(skip)]
+    pub anchor_start: Vec<u8>,
+    #[serde(skip)]
+    pub anchor_end: Vec<u8>,
+    pub original_text: String,
+    pub replacement: String,
+    pub status: String,
+    pub author_id: Option<Uuid>,
+    pub author_name: String,
+    pub decided_by: Option<Uuid>,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateSuggestionRequest {
+    /// Range to replace; an empty range inserts at that point
+    pub anchor: AnchorRequest,
+    /// Text to put in place of the range; empty to delete it
+    pub replacement: String,
+    /// Display name for guests suggesting through a share link
+    pub author_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SuggestionListQuery {
+    /// Only list suggestions with this status; all when omitted
+    pub status: Option<String>,
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SuggestionResponse {
+    #[serde(flatten)]
+    pub suggestion: Suggestion,
+    /// Base64 Yjs relative positions of the anchor
+    pub anchor_start: String,
+    pub anchor_end: String,
+    /// Current range in the document, None if the anchor no longer resolves
+    pub range: Option<TextRange>,
+    /// The anchored text changed since the suggestion was made, so it can no longer be applied
+    pub outdated: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SuggestionListResponse {
+    pub suggestions: Vec<SuggestionResponse>,
+}
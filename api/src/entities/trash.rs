@@ -0,0 +1,34 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use sqlx::FromRow;
+
+/// Document or folder a user deleted, with everything that was trashed along with it
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct TrashEntry {
+    pub id: Uuid,
+    pub title: String,
+    pub document_type: String,
+    /// Folder the entry is restored to, if it is not in the trash itself
+    pub parent_id: Option<Uuid>,
+    pub file_path: Option<String>,
+    pub deleted_at: DateTime<Utc>,
+    pub deleted_by: Option<Uuid>,
+    /// Number of documents and folders in the entry, itself included
+    pub item_count: i64,
+    /// When the entry is purged automatically, None when trash never expires
+    #[sqlx(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrashListResponse {
+    pub entries: Vec<TrashEntry>,
+    /// Days entries stay in the trash, None when they are kept until purged
+    pub retention_days: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EmptyTrashResponse {
+    pub purged: usize,
+}
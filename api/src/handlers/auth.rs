@@ -14,6 +14,7 @@ use crate::{
     utils::jwt::JwtService,
     middleware::auth::{auth_middleware, AuthUser},
     db::models::User,
+    socketio::Revocation,
 };
 
 #[derive(Debug, Deserialize)]
@@ -154,7 +155,22 @@ async fn logout(
     
     // Logout user
     let refresh_token = req.and_then(|r| Some(r.refresh_token));
-    auth_service.logout(auth_user.user_id, refresh_token.as_deref()).await?;
+    let sessions = auth_service.logout(auth_user.user_id, refresh_token.as_deref(), auth_user.session_id).await?;
+    
+    // Realtime connections of the ended sessions are closed as well, all of the user's
+    // when the sessions are not known
+    if sessions.is_empty() {
+        state.session_revoker.revoke(Revocation::UserSessions {
+            user_id: auth_user.user_id,
+            session_id: None,
+        });
+    }
+    for session_id in sessions {
+        state.session_revoker.revoke(Revocation::UserSessions {
+            user_id: auth_user.user_id,
+            session_id: Some(session_id),
+        });
+    }
     
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,115 @@
+use axum::{
+    extract::{State, Path, Query},
+    Json,
+    Extension,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::{
+    error::{Error, Result},
+    state::AppState,
+    middleware::{optional_auth::OptionalAuthUser, permission::check_document_permission},
+    entities::share::Permission,
+    entities::comment::{
+        CommentListQuery, CommentThreadListResponse, CommentThreadResponse, CreateThreadRequest, ReplyRequest,
+    },
+};
+
+async fn ensure_permission(
+    state: &Arc<AppState>,
+    document_id: Uuid,
+    auth_user: &OptionalAuthUser,
+    share_token: Option<String>,
+    required: Permission,
+) -> Result<()> {
+    let check = check_document_permission(state, document_id, auth_user.user_id, share_token, required).await?;
+    if !check.has_access {
+        return Err(Error::Forbidden);
+    }
+    Ok(())
+}
+
+/// List comment threads of a document
+pub async fn list_threads(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+    Path(document_id): Path<Uuid>,
+    Query(query): Query<CommentListQuery>,
+) -> Result<Json<CommentThreadListResponse>> {
+    ensure_permission(&state, document_id, &auth_user, query.token.clone(), Permission::View).await?;
+
+    let threads = state.comment_service
+        .list_threads(document_id, query.include_resolved.unwrap_or(false))
+        .await?;
+
+    Ok(Json(CommentThreadListResponse { threads }))
+}
+
+/// Start a comment thread on a range of the document
+pub async fn create_thread(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+    Path(document_id): Path<Uuid>,
+    Query(params): Query<HashMap<String, String>>,
+    Json(req): Json<CreateThreadRequest>,
+) -> Result<Json<CommentThreadResponse>> {
+    ensure_permission(&state, document_id, &auth_user, params.get("token").cloned(), Permission::Comment).await?;
+
+    let author = state.comment_service.author(auth_user.user_id, req.author_name.as_deref()).await;
+    let thread = state.comment_service
+        .create_thread(document_id, &req.anchor, &req.body, &author)
+        .await?;
+
+    Ok(Json(thread))
+}
+
+/// Reply to a comment thread
+pub async fn reply_to_thread(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+    Path((document_id, thread_id)): Path<(Uuid, Uuid)>,
+    Query(params): Query<HashMap<String, String>>,
+    Json(req): Json<ReplyRequest>,
+) -> Result<Json<CommentThreadResponse>> {
+    ensure_permission(&state, document_id, &auth_user, params.get("token").cloned(), Permission::Comment).await?;
+
+    let author = state.comment_service.author(auth_user.user_id, req.author_name.as_deref()).await;
+    let thread = state.comment_service
+        .reply(document_id, thread_id, &req.body, &author)
+        .await?;
+
+    Ok(Json(thread))
+}
+
+/// Mark a comment thread as resolved
+pub async fn resolve_thread(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+    Path((document_id, thread_id)): Path<(Uuid, Uuid)>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<CommentThreadResponse>> {
+    ensure_permission(&state, document_id, &auth_user, params.get("token").cloned(), Permission::Comment).await?;
+
+    let thread = state.comment_service
+        .set_resolved(document_id, thread_id, true, auth_user.user_id)
+        .await?;
+
+    Ok(Json(thread))
+}
+
+/// Reopen a resolved comment thread
+pub async fn reopen_thread(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+    Path((document_id, thread_id)): Path<(Uuid, Uuid)>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<CommentThreadResponse>> {
+    ensure_permission(&state, document_id, &auth_user, params.get("token").cloned(), Permission::Comment).await?;
+
+    let thread = state.comment_service
+        .set_resolved(document_id, thread_id, false, auth_user.user_id)
+        .await?;
+
+    Ok(Json(thread))
+}
@@ -46,6 +46,7 @@ pub struct BacklinkInfo {
     pub file_path: Option<String>,
     pub link_type: String,
     pub link_text: Option<String>,
+    pub section: Option<String>,
     pub link_count: i64,
 }
 
@@ -63,10 +64,37 @@ pub struct OutgoingLink {
     pub file_path: Option<String>,
     pub link_type: String,
     pub link_text: Option<String>,
+    pub section: Option<String>,
+    pub section_exists: Option<bool>,
     pub position_start: Option<i32>,
     pub position_end: Option<i32>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct BrokenLinksQuery {
+    /// Only links in documents inside this folder, at any depth
+    pub folder_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BrokenLinksResponse {
+    pub broken_links: Vec<BrokenLinkInfo>,
+    pub total_count: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BrokenLinkInfo {
+    pub source_document_id: String,
+    pub source_title: String,
+    pub source_file_path: Option<String>,
+    pub target_text: String,
+    pub target_fragment: Option<String>,
+    pub reason: String,
+    pub link_type: String,
+    pub link_text: Option<String>,
+    pub position: Option<i32>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct LinkStatsResponse {
     pub backlink_count: usize,
@@ -100,6 +128,7 @@ pub async fn get_backlinks(
                 file_path: link.file_path,
                 link_type: link.link_type,
                 link_text: link.link_text,
+                section: link.section,
                 link_count: link.link_count,
             })
             .collect(),
@@ -134,6 +163,8 @@ pub async fn get_outgoing_links(
                 file_path: link.file_path,
                 link_type: link.link_type,
                 link_text: link.link_text,
+                section: link.section,
+                section_exists: link.section_exists,
                 position_start: link.position_start,
                 position_end: link.position_end,
             })
@@ -189,4 +220,35 @@ pub async fn get_link_stats(
     };
     
     Ok(Json(response))
-}
\ No newline at end of file
+}
+/// List links in the user's documents whose target does not exist
+#[axum::debug_handler]
+pub async fn get_broken_links(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<BrokenLinksQuery>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+) -> Result<Json<BrokenLinksResponse>> {
+    let user_id = auth_user.user_id.ok_or(crate::error::Error::Unauthorized)?;
+    
+    let broken_links = state.document_links_service.find_broken_links(user_id, query.folder_id).await?;
+    
+    let response = BrokenLinksResponse {
+        total_count: broken_links.len(),
+        broken_links: broken_links
+            .into_iter()
+            .map(|link| BrokenLinkInfo {
+                source_document_id: link.source_document_id.to_string(),
+                source_title: link.source_title,
+                source_file_path: link.source_file_path,
+                target_text: link.target_text,
+                target_fragment: link.target_fragment,
+                reason: link.reason,
+                link_type: link.link_type,
+                link_text: link.link_text,
+                position: link.position,
+            })
+            .collect(),
+    };
+    
+    Ok(Json(response))
+}
@@ -2,10 +2,10 @@ use axum::{
     extract::{State, Extension, Path, Query},
     Json,
     Router,
-    routing::{get, post},
+    routing::{delete, get, post},
     middleware::from_fn_with_state,
     response::{IntoResponse, Response},
-    http::{header, StatusCode},
+    http::{header, HeaderMap, HeaderName, StatusCode},
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
@@ -23,6 +23,9 @@ use crate::{
     db::models::Document,
     crdt::serialization,
     entities::share::Permission,
+    services::document_links::RenameLinksReport,
+    services::link_parser::LinkFragment,
+    services::transclusion::{EmbedInfo, DEFAULT_EMBED_DEPTH},
 };
 
 #[derive(Debug, Deserialize)]
@@ -79,6 +82,9 @@ pub struct DocumentResponse {
     pub published_at: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub owner_username: Option<String>, // Actually owner name
+    /// Documents whose links were rewritten, when the update renamed the document
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub renamed_links: Option<RenameLinksReport>,
 }
 
 impl From<Document> for DocumentResponse {
@@ -97,6 +103,7 @@ impl From<Document> for DocumentResponse {
             visibility: Some(doc.visibility),
             published_at: doc.published_at.map(|dt| dt.to_rfc3339()),
             owner_username: None, // This will be populated by the handler when needed
+            renamed_links: None,
         }
     }
 }
@@ -106,6 +113,15 @@ pub struct DocumentContentResponse {
     pub content: String,
 }
 
+#[derive(Debug, Serialize)]
+pub struct RenderedDocumentResponse {
+    pub id: String,
+    pub title: String,
+    /// Markdown with embeds the viewer may read expanded in place
+    pub content: String,
+    pub embeds: Vec<EmbedInfo>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct DocumentStateResponse {
     pub state: String, // Base64 encoded CRDT state
@@ -121,20 +137,55 @@ pub struct DocumentUpdatesResponse {
     pub updates: Vec<String>, // Base64 encoded updates
 }
 
+/// Header carrying a base64 state vector next to a binary sync body
+const STATE_VECTOR_HEADER: &str = "x-yjs-state-vector";
+
+#[derive(Debug, Deserialize)]
+pub struct DocumentSyncRequest {
+    /// Base64 Yjs update with the client's local changes
+    pub update: Option<String>,
+    /// Base64 state vector of the client; the full state is returned when omitted
+    pub state_vector: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DocumentSyncResponse {
+    pub update: String, // Base64 encoded changes the client is missing
+    pub state_vector: String, // Base64 encoded server state vector
+}
+
 pub fn routes(state: Arc<AppState>) -> Router {
     Router::new()
         // All routes use optional auth
         .route("/", get(list_documents).post(create_document))
         .route("/:id", get(get_document_with_share).put(update_document_with_share).delete(delete_document))
         .route("/:id/content", get(get_document_content_with_share))
+        .route("/:id/rendered", get(get_rendered_document_with_share))
         .route("/:id/state", get(get_document_state_with_share))
         .route("/:id/updates", post(get_document_updates_with_share))
+        .route("/:id/sync", post(sync_document_with_share))
         .route("/:id/download", get(download_document_with_share))
         .route("/:id/file-path", get(get_document_file_path))
         .route("/:id/backlinks", get(crate::handlers::document_links::get_backlinks))
         .route("/:id/links", get(crate::handlers::document_links::get_outgoing_links))
         .route("/:id/link-stats", get(crate::handlers::document_links::get_link_stats))
+        .route("/:id/snapshots", get(crate::handlers::history::list_snapshots).post(crate::handlers::history::create_snapshot))
+        .route("/:id/history/content", get(crate::handlers::history::get_history_content))
+        .route("/:id/blame", get(crate::handlers::history::get_blame))
+        .route("/:id/restore", post(crate::handlers::history::restore_document))
+        .route("/:id/comments", get(crate::handlers::comments::list_threads).post(crate::handlers::comments::create_thread))
+        .route("/:id/comments/:thread_id/replies", post(crate::handlers::comments::reply_to_thread))
+        .route("/:id/comments/:thread_id/resolve", post(crate::handlers::comments::resolve_thread))
+        .route("/:id/comments/:thread_id/reopen", post(crate::handlers::comments::reopen_thread))
+        .route("/:id/suggestions", get(crate::handlers::suggestions::list_suggestions).post(crate::handlers::suggestions::create_suggestion))
+        .route("/:id/suggestions/:suggestion_id/accept", post(crate::handlers::suggestions::accept_suggestion))
+        .route("/:id/suggestions/:suggestion_id/reject", post(crate::handlers::suggestions::reject_suggestion))
         .route("/search", get(crate::handlers::document_links::search_documents))
+        .route("/search/content", get(crate::handlers::search::search_content))
+        .route("/links/broken", get(crate::handlers::document_links::get_broken_links))
+        .route("/trash", get(crate::handlers::trash::list_trash).delete(crate::handlers::trash::empty_trash))
+        .route("/trash/:id", delete(crate::handlers::trash::purge_from_trash))
+        .route("/trash/:id/restore", post(crate::handlers::trash::restore_from_trash))
         .layer(from_fn_with_state(state.clone(), optional_auth_middleware))
         .with_state(state)
 }
@@ -171,7 +222,7 @@ async fn create_document(
     
     // Initialize CRDT with content if provided
     if let Some(ref content) = req.content {
-        state.crdt_service.set_document_content(document.id, content).await?;
+        state.crdt_service.set_document_content(document.id, content, Some(user_id)).await?;
         
         // Re-save document to file with content
         state.document_service.save_to_file_with_content(&document, content).await?;
@@ -321,7 +372,7 @@ async fn update_document_internal(
     req: UpdateDocumentRequest,
     log_suffix: &str,
 ) -> Result<Json<DocumentResponse>> {
-    let document = state.document_service.update_document(
+    let (document, renamed_links) = state.document_service.update_document(
         id,
         user_id,
         req.title.as_deref(),
@@ -332,7 +383,7 @@ async fn update_document_internal(
     // Update CRDT content if provided
     if let Some(ref content) = req.content {
         tracing::info!("Updating document {} with content{}: {} chars", document.id, log_suffix, content.len());
-        state.crdt_service.set_document_content(document.id, content).await?;
+        state.crdt_service.set_document_content(document.id, content, Some(user_id)).await?;
         
         // Save updated content to file
         tracing::info!("Saving document {} to file{}", document.id, log_suffix);
@@ -346,7 +397,9 @@ async fn update_document_internal(
         tracing::info!("No content provided for document {} update{}", document.id, log_suffix);
     }
     
-    Ok(Json(document.into()))
+    let mut response: DocumentResponse = document.into();
+    response.renamed_links = renamed_links;
+    Ok(Json(response))
 }
 
 async fn update_document_with_share(
@@ -439,9 +492,61 @@ async fn get_document_content_with_share(
     // Get content from CRDT
     let content = state.crdt_service.get_document_content(id).await?;
     
+    // A heading or ^block-id limits the content to that section, as used by section embeds
+    let content = match params.get("section") {
+        Some(section) => LinkFragment::parse(section.strip_prefix('#').unwrap_or(section))
+            .and_then(|fragment| fragment.section(&content))
+            .ok_or_else(|| Error::NotFound(format!("Section not found: {}", section)))?,
+        None => content,
+    };
+    
     Ok(Json(DocumentContentResponse { content }))
 }
 
+async fn get_rendered_document_with_share(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<RenderedDocumentResponse>> {
+    let share_token = params.get("token").cloned();
+    let user_id = auth_user.user_id;
+    
+    // Check permissions with optional auth and share token
+    let check = check_document_permission(
+        &state,
+        id,
+        user_id,
+        share_token,
+        Permission::View
+    ).await?;
+    
+    if !check.has_access {
+        return Err(crate::error::Error::Forbidden);
+    }
+    
+    let document = state.document_repository
+        .get_by_id(id)
+        .await?
+        .ok_or_else(|| Error::NotFound("Document not found".to_string()))?;
+    
+    let max_depth = match params.get("max_depth") {
+        Some(depth) => depth.parse::<usize>()
+            .map_err(|_| Error::BadRequest(format!("Invalid max_depth: {}", depth)))?,
+        None => DEFAULT_EMBED_DEPTH,
+    };
+    
+    // Embedded documents are checked against the viewer, not the share token
+    let rendered = state.transclusion_service.render(id, document.owner_id, user_id, max_depth).await?;
+    
+    Ok(Json(RenderedDocumentResponse {
+        id: document.id.to_string(),
+        title: document.title,
+        content: rendered.content,
+        embeds: rendered.embeds,
+    }))
+}
+
 async fn get_document_state(
     State(state): State<Arc<AppState>>,
     Extension(auth_user): Extension<OptionalAuthUser>,
@@ -557,6 +662,92 @@ async fn get_document_updates_with_share(
         updates: updates_base64,
     }))
 }
+
+/// Apply a client's Yjs update and return what the client is missing in one round trip.
+///
+/// JSON bodies carry base64 fields; any other body is the raw update, with the state
+/// vector in the `X-Yjs-State-Vector` header, and gets a raw update back.
+async fn sync_document_with_share(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+    Path(id): Path<Uuid>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response> {
+    let decode = |value: &[u8]| base64::Engine::decode(&base64::engine::general_purpose::STANDARD, value);
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+
+    let (update, state_vector) = if is_json {
+        let req: DocumentSyncRequest = serde_json::from_slice(&body)?;
+        (
+            req.update.map(|update| decode(update.as_bytes())).transpose()?,
+            req.state_vector.map(|sv| decode(sv.as_bytes())).transpose()?,
+        )
+    } else {
+        let state_vector = headers.get(STATE_VECTOR_HEADER).map(|sv| decode(sv.as_bytes())).transpose()?;
+        ((!body.is_empty()).then(|| body.to_vec()), state_vector)
+    };
+
+    // Only updates that change something need edit access; an empty one just pulls
+    let update = match update {
+        Some(update) if !crate::crdt::is_empty_update(&update)? => Some(update),
+        _ => None,
+    };
+    let required = if update.is_some() { Permission::Edit } else { Permission::View };
+    let check = check_document_permission(&state, id, auth_user.user_id, params.get("token").cloned(), required).await?;
+    if !check.has_access {
+        return Err(Error::Forbidden);
+    }
+
+    if let Some(update) = &update {
+        state.crdt_service.apply_client_update(id, update, auth_user.user_id).await?;
+        state.broadcaster.broadcast_document_update(id, update, None)?;
+
+        // Persist edited content to file (also refreshes links and tags)
+        let content = state.crdt_service.get_document_content(id).await?;
+        if let Some(document) = state.document_repository.get_by_id(id).await? {
+            if let Err(e) = state.document_service.save_to_file_with_content(&document, &content).await {
+                tracing::warn!("Failed to save document {} to file after sync: {}", id, e);
+            }
+        }
+    }
+
+    // The client already has its own update, so it is not sent back
+    let state_vector = match (state_vector, &update) {
+        (Some(state_vector), Some(update)) => Some(crate::crdt::merge_state_vector(&state_vector, update)?),
+        (state_vector, _) => state_vector,
+    };
+
+    let doc = state.crdt_service.load_or_create_document(id).await?;
+    let (diff, server_state_vector) = {
+        let doc = doc.read();
+        let diff = match &state_vector {
+            Some(state_vector) => doc.get_update_since(state_vector)?,
+            None => doc.get_state_as_update()?,
+        };
+        (diff, doc.get_state_vector())
+    };
+
+    if is_json {
+        return Ok(Json(DocumentSyncResponse {
+            update: serialization::update_to_base64(&diff),
+            state_vector: serialization::update_to_base64(&server_state_vector),
+        }).into_response());
+    }
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+            (HeaderName::from_static(STATE_VECTOR_HEADER), serialization::update_to_base64(&server_state_vector)),
+        ],
+        diff,
+    ).into_response())
+}
+
 async fn download_document_with_share(
     State(state): State<Arc<AppState>>,
     Extension(auth_user): Extension<OptionalAuthUser>,
@@ -585,8 +776,11 @@ async fn download_document_with_share(
         .await?
         .ok_or_else(|| crate::error::Error::NotFound("Document not found".to_string()))?;
     
-    // Get document content
-    let content = state.crdt_service.get_document_content(id).await?;
+    // Get document content, with the embeds the user may read expanded
+    let content = state.transclusion_service
+        .render(id, document.owner_id, user_id, DEFAULT_EMBED_DEPTH)
+        .await?
+        .content;
     
     // Create ZIP in memory
     let mut zip_buffer = Cursor::new(Vec::new());
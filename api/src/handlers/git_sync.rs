@@ -4,11 +4,12 @@ use axum::{
     http::StatusCode,
     response::Json,
     Extension,
-    routing::{get, post, delete},
+    routing::{get, post, put, delete},
     Router,
     middleware::from_fn_with_state,
 };
 use serde::Deserialize;
+use uuid::Uuid;
 
 use crate::{
     entities::{
@@ -19,7 +20,7 @@ use crate::{
     },
     repository::GitConfigRepository,
     services::{
-        git_sync::{GitSyncService, GitCommit},
+        git_sync::{GitSyncService, GitCommit, GitRepositoryScope},
         git_diff::{GitDiffService, DiffResult},
         git_conflict::{GitConflictService, ConflictInfo, MergeResolution},
     },
@@ -52,34 +53,11 @@ pub async fn create_or_update_config(
     // Create a mutable copy of the request to encrypt auth data
     let mut encrypted_request = request;
     
-    // Validate auth_type
-    if encrypted_request.auth_type != "ssh" && encrypted_request.auth_type != "token" {
-        return Err(Error::BadRequest("auth_type must be 'ssh' or 'token'".to_string()));
-    }
-
-    // Validate auth_data structure based on auth_type
-    match encrypted_request.auth_type.as_str() {
-        "ssh" => {
-            if let Some(private_key_value) = encrypted_request.auth_data.get("private_key") {
-                if let Some(private_key) = private_key_value.as_str() {
-                    // Validate that it looks like an SSH private key
-                    if !private_key.contains("BEGIN") || !private_key.contains("PRIVATE KEY") {
-                        return Err(Error::BadRequest("Invalid SSH private key format".to_string()));
-                    }
-                } else {
-                    return Err(Error::BadRequest("SSH auth requires 'private_key' to be a string".to_string()));
-                }
-            } else {
-                return Err(Error::BadRequest("SSH auth requires 'private_key' in auth_data".to_string()));
-            }
-        },
-        "token" => {
-            if !encrypted_request.auth_data.get("token").and_then(|v| v.as_str()).is_some() {
-                return Err(Error::BadRequest("Token auth requires 'token' in auth_data".to_string()));
-            }
-        },
-        _ => unreachable!(),
+    if encrypted_request.folder_id.is_some() {
+        return Err(Error::BadRequest("Folder repositories are configured through /git/repositories".to_string()));
     }
+    validate_auth(&encrypted_request.auth_type, &encrypted_request.auth_data)?;
+    validate_sync_interval(encrypted_request.sync_interval_secs)?;
 
     // Encrypt sensitive auth data before storing
     encrypted_request.encrypt_auth_data(&encryption_service)?;
@@ -93,6 +71,7 @@ pub async fn create_or_update_config(
             auth_type: Some(encrypted_request.auth_type),
             auth_data: Some(encrypted_request.auth_data),
             auto_sync: encrypted_request.auto_sync,
+            sync_interval_secs: encrypted_request.sync_interval_secs,
         };
         
         // Note: auth_data is already encrypted in encrypted_request
@@ -106,9 +85,10 @@ pub async fn create_or_update_config(
             &state.config.jwt_secret
         )?;
         
-        let status = git_sync_service.get_status(auth_user.user_id).await?;
+        let scope = git_sync_service.root_scope(auth_user.user_id).await?;
+        let status = git_sync_service.get_status(&scope).await?;
         if !status.repository_initialized {
-            git_sync_service.init_repository(auth_user.user_id).await?;
+            git_sync_service.init_repository(&scope).await?;
         }
         
         Ok(Json(updated_config.into()))
@@ -122,12 +102,54 @@ pub async fn create_or_update_config(
             state.config.upload_dir.clone().into(),
             &state.config.jwt_secret
         )?;
-        git_sync_service.init_repository(auth_user.user_id).await?;
+        let scope = git_sync_service.root_scope(auth_user.user_id).await?;
+        git_sync_service.init_repository(&scope).await?;
         
         Ok(Json(new_config.into()))
     }
 }
 
+/// Check that auth data carries the credential its auth type needs
+fn validate_auth(auth_type: &str, auth_data: &serde_json::Value) -> crate::error::Result<()> {
+    // Validate auth_type
+    if auth_type != "ssh" && auth_type != "token" {
+        return Err(Error::BadRequest("auth_type must be 'ssh' or 'token'".to_string()));
+    }
+
+    // Validate auth_data structure based on auth_type
+    match auth_type {
+        "ssh" => {
+            if let Some(private_key_value) = auth_data.get("private_key") {
+                if let Some(private_key) = private_key_value.as_str() {
+                    // Validate that it looks like an SSH private key
+                    if !private_key.contains("BEGIN") || !private_key.contains("PRIVATE KEY") {
+                        return Err(Error::BadRequest("Invalid SSH private key format".to_string()));
+                    }
+                } else {
+                    return Err(Error::BadRequest("SSH auth requires 'private_key' to be a string".to_string()));
+                }
+            } else {
+                return Err(Error::BadRequest("SSH auth requires 'private_key' in auth_data".to_string()));
+            }
+        },
+        "token" => {
+            if !auth_data.get("token").and_then(|v| v.as_str()).is_some() {
+                return Err(Error::BadRequest("Token auth requires 'token' in auth_data".to_string()));
+            }
+        },
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
+fn validate_sync_interval(sync_interval_secs: Option<i32>) -> crate::error::Result<()> {
+    if sync_interval_secs.is_some_and(|secs| secs <= 0) {
+        return Err(Error::BadRequest("sync_interval_secs must be positive".to_string()));
+    }
+    Ok(())
+}
+
 // GET /api/git/config - Get git configuration
 pub async fn get_config(
     State(state): State<Arc<AppState>>,
@@ -158,8 +180,9 @@ pub async fn manual_sync(
     let git_config_repo = Arc::new(GitConfigRepository::new(state.db_pool.clone()));
     let git_sync_service = GitSyncService::new(git_config_repo, state.config.upload_dir.clone().into(), &state.config.jwt_secret)?;
     
+    let scope = git_sync_service.root_scope(auth_user.user_id).await?;
     let sync_result = git_sync_service.sync(
-        auth_user.user_id,
+        &scope,
         None,
         false,
     ).await?;
@@ -175,7 +198,8 @@ pub async fn get_status(
     let git_config_repo = Arc::new(GitConfigRepository::new(state.db_pool.clone()));
     let git_sync_service = GitSyncService::new(git_config_repo, state.config.upload_dir.clone().into(), &state.config.jwt_secret)?;
     
-    let status = git_sync_service.get_status(auth_user.user_id).await?;
+    let scope = git_sync_service.root_scope(auth_user.user_id).await?;
+    let status = git_sync_service.get_status(&scope).await?;
     Ok(Json(status))
 }
 
@@ -187,7 +211,8 @@ pub async fn init_repository(
     let git_config_repo = Arc::new(GitConfigRepository::new(state.db_pool.clone()));
     let git_sync_service = GitSyncService::new(git_config_repo, state.config.upload_dir.clone().into(), &state.config.jwt_secret)?;
     
-    git_sync_service.init_repository(auth_user.user_id).await?;
+    let scope = git_sync_service.root_scope(auth_user.user_id).await?;
+    git_sync_service.init_repository(&scope).await?;
     
     Ok(Json(serde_json::json!({
         "success": true,
@@ -203,7 +228,8 @@ pub async fn deinit_repository(
     let git_config_repo = Arc::new(GitConfigRepository::new(state.db_pool.clone()));
     let git_sync_service = GitSyncService::new(git_config_repo, state.config.upload_dir.clone().into(), &state.config.jwt_secret)?;
     
-    git_sync_service.deinit_repository(auth_user.user_id).await?;
+    let scope = git_sync_service.root_scope(auth_user.user_id).await?;
+    git_sync_service.deinit_repository(&scope).await?;
     
     Ok(Json(serde_json::json!({
         "success": true,
@@ -219,7 +245,7 @@ pub async fn get_sync_logs(
     let git_config_repo = Arc::new(GitConfigRepository::new(state.db_pool.clone()));
     
     let limit = 50;
-    let logs = git_config_repo.get_sync_logs(auth_user.user_id, limit).await?;
+    let logs = git_config_repo.get_sync_logs(auth_user.user_id, None, limit).await?;
     
     let response: Vec<GitSyncLogResponse> = logs.into_iter().map(|log| log.into()).collect();
     Ok(Json(response))
@@ -237,7 +263,8 @@ pub async fn get_commit_history(
         &state.config.jwt_secret
     )?;
     
-    let commits = git_sync_service.get_commit_history(auth_user.user_id, Some(50)).await?;
+    let scope = git_sync_service.root_scope(auth_user.user_id).await?;
+    let commits = git_sync_service.get_commit_history(&scope, Some(50)).await?;
     Ok(Json(commits))
 }
 
@@ -421,7 +448,8 @@ pub async fn get_conflicts(
         &state.config.jwt_secret
     )?;
     
-    let conflicts = git_sync_service.get_conflicts(auth_user.user_id).await?;
+    let scope = git_sync_service.root_scope(auth_user.user_id).await?;
+    let conflicts = git_sync_service.get_conflicts(&scope).await?;
     Ok(Json(conflicts))
 }
 
@@ -472,18 +500,32 @@ pub async fn pull_from_remote(
         &state.config.jwt_secret
     )?;
     
-    match git_sync_service.pull_from_remote(auth_user.user_id).await {
+    let scope = git_sync_service.root_scope(auth_user.user_id).await?;
+    pull_repository(&state, &git_sync_service, &scope).await
+}
+
+async fn pull_repository(
+    state: &AppState,
+    git_sync_service: &GitSyncService,
+    scope: &GitRepositoryScope,
+) -> crate::error::Result<Json<serde_json::Value>> {
+    // Only what the pull changed is imported back into documents
+    let head_before = state.git_import_service.head_commit(scope)?;
+    
+    match git_sync_service.pull_from_remote(scope).await {
         Ok(_) => {
+            let imported = state.git_import_service.import_changes(scope, head_before).await?;
             Ok(Json(serde_json::json!({
                 "success": true,
                 "message": "Pull completed successfully",
-                "has_conflicts": false
+                "has_conflicts": false,
+                "imported": imported
             })))
         },
         Err(e) => {
             if e.to_string().contains("conflicts detected") {
                 // Get conflict details
-                let conflicts = git_sync_service.get_conflicts(auth_user.user_id).await?;
+                let conflicts = git_sync_service.get_conflicts(scope).await?;
                 Ok(Json(serde_json::json!({
                     "success": false,
                     "message": "Pull completed with conflicts",
@@ -509,7 +551,8 @@ pub async fn create_gitignore(
         &state.config.jwt_secret
     )?;
     
-    git_sync_service.create_default_gitignore(auth_user.user_id).await?;
+    let scope = git_sync_service.root_scope(auth_user.user_id).await?;
+    git_sync_service.create_default_gitignore(&scope).await?;
     
     Ok(Json(serde_json::json!({
         "success": true,
@@ -534,7 +577,8 @@ pub async fn add_gitignore_patterns(
         &state.config.jwt_secret
     )?;
     
-    git_sync_service.add_to_gitignore(auth_user.user_id, payload.patterns).await?;
+    let scope = git_sync_service.root_scope(auth_user.user_id).await?;
+    git_sync_service.add_to_gitignore(&scope, payload.patterns).await?;
     
     Ok(Json(serde_json::json!({
         "success": true,
@@ -553,7 +597,8 @@ pub async fn get_gitignore_patterns(
         &state.config.jwt_secret
     )?;
     
-    let patterns = git_sync_service.get_gitignore_patterns(auth_user.user_id).await?;
+    let scope = git_sync_service.root_scope(auth_user.user_id).await?;
+    let patterns = git_sync_service.get_gitignore_patterns(&scope).await?;
     
     Ok(Json(serde_json::json!({
         "patterns": patterns
@@ -577,7 +622,8 @@ pub async fn check_path_ignored(
         &state.config.jwt_secret
     )?;
     
-    let is_ignored = git_sync_service.is_path_ignored(auth_user.user_id, &payload.path).await?;
+    let scope = git_sync_service.root_scope(auth_user.user_id).await?;
+    let is_ignored = git_sync_service.is_path_ignored(&scope, &payload.path).await?;
     
     Ok(Json(serde_json::json!({
         "path": payload.path,
@@ -585,6 +631,165 @@ pub async fn check_path_ignored(
     })))
 }
 
+fn git_sync_service(state: &AppState) -> crate::error::Result<GitSyncService> {
+    GitSyncService::new(
+        Arc::new(GitConfigRepository::new(state.db_pool.clone())),
+        state.config.upload_dir.clone().into(),
+        &state.config.jwt_secret,
+    )
+}
+
+// GET /api/git/repositories - List the root and folder repository configurations
+pub async fn list_repositories(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<AuthUser>,
+) -> crate::error::Result<Json<Vec<GitConfigResponse>>> {
+    let git_config_repo = GitConfigRepository::new(state.db_pool.clone());
+    
+    let configs = git_config_repo.list_by_user(auth_user.user_id).await?;
+    Ok(Json(configs.into_iter().map(|c| c.into()).collect()))
+}
+
+// POST /api/git/repositories - Sync a folder to its own repository
+pub async fn create_repository(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<AuthUser>,
+    Json(mut request): Json<CreateGitConfigRequest>,
+) -> crate::error::Result<(StatusCode, Json<GitConfigResponse>)> {
+    let git_config_repo = Arc::new(GitConfigRepository::new(state.db_pool.clone()));
+    let encryption_service = EncryptionService::new(&state.config.jwt_secret)?;
+    
+    let folder_id = request.folder_id
+        .ok_or_else(|| Error::BadRequest("folder_id is required".to_string()))?;
+    if git_config_repo.get_folder_titles(auth_user.user_id, folder_id).await?.is_none() {
+        return Err(Error::NotFound("Folder not found".to_string()));
+    }
+    if git_config_repo.get_by_folder(auth_user.user_id, folder_id).await?.is_some() {
+        return Err(Error::Conflict("Folder is already synced to a repository".to_string()));
+    }
+    validate_auth(&request.auth_type, &request.auth_data)?;
+    validate_sync_interval(request.sync_interval_secs)?;
+    
+    request.encrypt_auth_data(&encryption_service)?;
+    let config = git_config_repo.create(auth_user.user_id, request).await?;
+    
+    let git_sync_service = git_sync_service(&state)?;
+    let scope = git_sync_service.folder_scope(auth_user.user_id, config.id).await?;
+    git_sync_service.init_repository(&scope).await?;
+    
+    Ok((StatusCode::CREATED, Json(config.into())))
+}
+
+// PUT /api/git/repositories/:id - Update a folder repository configuration
+pub async fn update_repository(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(id): Path<Uuid>,
+    Json(mut request): Json<UpdateGitConfigRequest>,
+) -> crate::error::Result<Json<GitConfigResponse>> {
+    let git_config_repo = GitConfigRepository::new(state.db_pool.clone());
+    let encryption_service = EncryptionService::new(&state.config.jwt_secret)?;
+    
+    let existing = git_config_repo.get_by_id(auth_user.user_id, id).await?
+        .ok_or_else(|| Error::NotFound("Git repository not found".to_string()))?;
+    
+    // Credentials are only replaced together with their auth type
+    match (&request.auth_type, &request.auth_data) {
+        (Some(auth_type), Some(auth_data)) => validate_auth(auth_type, auth_data)?,
+        (None, None) => {}
+        _ => return Err(Error::BadRequest("auth_type and auth_data must be updated together".to_string())),
+    }
+    validate_sync_interval(request.sync_interval_secs)?;
+    
+    request.encrypt_auth_data(&encryption_service)?;
+    let config = git_config_repo.update_by_id(auth_user.user_id, existing.id, request).await?;
+    Ok(Json(config.into()))
+}
+
+// DELETE /api/git/repositories/:id - Stop syncing a folder and remove its repository
+pub async fn delete_repository(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(id): Path<Uuid>,
+) -> crate::error::Result<StatusCode> {
+    let git_sync_service = git_sync_service(&state)?;
+    
+    let scope = git_sync_service.folder_scope(auth_user.user_id, id).await?;
+    if scope.open().is_ok() {
+        git_sync_service.deinit_repository(&scope).await?;
+    } else {
+        GitConfigRepository::new(state.db_pool.clone()).delete_by_id(auth_user.user_id, id).await?;
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// POST /api/git/repositories/:id/sync - Manual sync of a folder repository
+pub async fn sync_repository(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(id): Path<Uuid>,
+) -> crate::error::Result<Json<GitSyncResponse>> {
+    let git_sync_service = git_sync_service(&state)?;
+    
+    let scope = git_sync_service.folder_scope(auth_user.user_id, id).await?;
+    let sync_result = git_sync_service.sync(&scope, None, false).await?;
+    Ok(Json(sync_result))
+}
+
+// GET /api/git/repositories/:id/status - Get the status of a folder repository
+pub async fn get_repository_status(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(id): Path<Uuid>,
+) -> crate::error::Result<Json<GitStatus>> {
+    let git_sync_service = git_sync_service(&state)?;
+    
+    let scope = git_sync_service.folder_scope(auth_user.user_id, id).await?;
+    let status = git_sync_service.get_status(&scope).await?;
+    Ok(Json(status))
+}
+
+// GET /api/git/repositories/:id/logs - Get sync logs of a folder repository
+pub async fn get_repository_logs(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(id): Path<Uuid>,
+) -> crate::error::Result<Json<Vec<GitSyncLogResponse>>> {
+    let git_config_repo = GitConfigRepository::new(state.db_pool.clone());
+    
+    let config = git_config_repo.get_by_id(auth_user.user_id, id).await?
+        .ok_or_else(|| Error::NotFound("Git repository not found".to_string()))?;
+    let logs = git_config_repo.get_sync_logs(auth_user.user_id, Some(config.id), 50).await?;
+    
+    let response: Vec<GitSyncLogResponse> = logs.into_iter().map(|log| log.into()).collect();
+    Ok(Json(response))
+}
+
+// GET /api/git/repositories/:id/commits - Get commit history of a folder repository
+pub async fn get_repository_commits(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(id): Path<Uuid>,
+) -> crate::error::Result<Json<Vec<GitCommit>>> {
+    let git_sync_service = git_sync_service(&state)?;
+    
+    let scope = git_sync_service.folder_scope(auth_user.user_id, id).await?;
+    let commits = git_sync_service.get_commit_history(&scope, Some(50)).await?;
+    Ok(Json(commits))
+}
+
+// POST /api/git/repositories/:id/pull - Pull a folder repository from its remote
+pub async fn pull_repository_from_remote(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(id): Path<Uuid>,
+) -> crate::error::Result<Json<serde_json::Value>> {
+    let git_sync_service = git_sync_service(&state)?;
+    
+    let scope = git_sync_service.folder_scope(auth_user.user_id, id).await?;
+    pull_repository(&state, &git_sync_service, &scope).await
+}
+
 // Route definitions
 pub fn routes(state: Arc<AppState>) -> Router {
     Router::new()
@@ -611,6 +816,13 @@ pub fn routes(state: Arc<AppState>) -> Router {
         .route("/gitignore/patterns", post(add_gitignore_patterns))
         .route("/gitignore/patterns", get(get_gitignore_patterns))
         .route("/gitignore/check", post(check_path_ignored))
+        .route("/repositories", get(list_repositories).post(create_repository))
+        .route("/repositories/:id", put(update_repository).delete(delete_repository))
+        .route("/repositories/:id/sync", post(sync_repository))
+        .route("/repositories/:id/status", get(get_repository_status))
+        .route("/repositories/:id/logs", get(get_repository_logs))
+        .route("/repositories/:id/commits", get(get_repository_commits))
+        .route("/repositories/:id/pull", post(pull_repository_from_remote))
         .layer(from_fn_with_state(state.clone(), auth_middleware))
         .with_state(state)
 }
\ No newline at end of file
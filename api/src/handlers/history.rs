@@ -0,0 +1,176 @@
+use axum::{
+    extract::{State, Path, Query},
+    Json,
+    Extension,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::{
+    error::{Error, Result},
+    state::AppState,
+    middleware::{optional_auth::OptionalAuthUser, permission::check_document_permission},
+    entities::share::Permission,
+    entities::snapshot::{
+        BlameQuery, BlameResponse, CreateSnapshotRequest, DocumentSnapshot, HistoryContentQuery,
+        HistoryContentResponse, RestoreRequest, RestoreResponse, SnapshotListResponse,
+    },
+};
+
+/// List snapshots of a document
+pub async fn list_snapshots(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+    Path(document_id): Path<Uuid>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<SnapshotListResponse>> {
+    let share_token = params.get("token").cloned();
+    let check = check_document_permission(
+        &state,
+        document_id,
+        auth_user.user_id,
+        share_token,
+        Permission::View
+    ).await?;
+
+    if !check.has_access {
+        return Err(Error::Forbidden);
+    }
+
+    let snapshots = state.history_service.list_snapshots(document_id).await?;
+
+    Ok(Json(SnapshotListResponse { snapshots }))
+}
+
+/// Create a named snapshot of the current document state
+pub async fn create_snapshot(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+    Path(document_id): Path<Uuid>,
+    Query(params): Query<HashMap<String, String>>,
+    Json(req): Json<CreateSnapshotRequest>,
+) -> Result<Json<DocumentSnapshot>> {
+    let share_token = params.get("token").cloned();
+    let check = check_document_permission(
+        &state,
+        document_id,
+        auth_user.user_id,
+        share_token,
+        Permission::Edit
+    ).await?;
+
+    if !check.has_access {
+        return Err(Error::Forbidden);
+    }
+
+    let snapshot = state.history_service
+        .create_snapshot(document_id, req.name.as_deref(), auth_user.user_id)
+        .await?;
+
+    Ok(Json(snapshot))
+}
+
+/// Render document content as of a snapshot or timestamp
+pub async fn get_history_content(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+    Path(document_id): Path<Uuid>,
+    Query(query): Query<HistoryContentQuery>,
+) -> Result<Json<HistoryContentResponse>> {
+    let check = check_document_permission(
+        &state,
+        document_id,
+        auth_user.user_id,
+        query.token.clone(),
+        Permission::View
+    ).await?;
+
+    if !check.has_access {
+        return Err(Error::Forbidden);
+    }
+
+    let at = state.history_service
+        .resolve_point(document_id, query.snapshot_id, query.at)
+        .await?;
+    let content = state.history_service.get_content_at(document_id, at).await?;
+
+    Ok(Json(HistoryContentResponse {
+        document_id,
+        at,
+        content,
+    }))
+}
+
+/// Show who last wrote each range of the current content
+pub async fn get_blame(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+    Path(document_id): Path<Uuid>,
+    Query(query): Query<BlameQuery>,
+) -> Result<Json<BlameResponse>> {
+    let check = check_document_permission(
+        &state,
+        document_id,
+        auth_user.user_id,
+        query.token.clone(),
+        Permission::View
+    ).await?;
+
+    if !check.has_access {
+        return Err(Error::Forbidden);
+    }
+
+    let (content, ranges) = state.history_service.blame(document_id).await?;
+
+    Ok(Json(BlameResponse {
+        document_id,
+        content,
+        ranges,
+    }))
+}
+
+/// Restore a document to a snapshot or timestamp
+pub async fn restore_document(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+    Path(document_id): Path<Uuid>,
+    Query(params): Query<HashMap<String, String>>,
+    Json(req): Json<RestoreRequest>,
+) -> Result<Json<RestoreResponse>> {
+    let share_token = params.get("token").cloned();
+    let check = check_document_permission(
+        &state,
+        document_id,
+        auth_user.user_id,
+        share_token,
+        Permission::Edit
+    ).await?;
+
+    if !check.has_access {
+        return Err(Error::Forbidden);
+    }
+
+    let document = state.document_repository
+        .get_by_id(document_id)
+        .await?
+        .ok_or_else(|| Error::NotFound("Document not found".to_string()))?;
+
+    let at = state.history_service
+        .resolve_point(document_id, req.snapshot_id, req.at)
+        .await?;
+    let outcome = state.history_service
+        .restore(document_id, at, auth_user.user_id)
+        .await?;
+
+    // Persist restored content to file (also refreshes links and tags)
+    if let Err(e) = state.document_service.save_to_file_with_content(&document, &outcome.content).await {
+        tracing::warn!("Failed to save restored document {} to file: {}", document_id, e);
+    }
+
+    Ok(Json(RestoreResponse {
+        document_id,
+        restored_from: outcome.restored_from,
+        backup_snapshot_id: outcome.backup_snapshot.id,
+        content: outcome.content,
+    }))
+}
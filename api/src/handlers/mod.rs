@@ -13,6 +13,11 @@ pub mod git_sync;
 pub mod document_links;
 pub mod public_documents;
 pub mod tags;
+pub mod history;
+pub mod comments;
+pub mod suggestions;
+pub mod search;
+pub mod trash;
 
 pub fn routes(state: Arc<AppState>) -> Router {
     // Merge document routes with public document management routes
@@ -29,6 +34,7 @@ pub fn routes(state: Arc<AppState>) -> Router {
         .nest("/git", git_sync::routes(state.clone()))
         .nest("/socketio", socketio::routes(state.clone()))
         .nest("/tags", tags::routes(state.clone()))
+        .nest("/yjs", crate::websocket::routes(state.clone()))
         .merge(public_documents::routes(state.clone()))
         .merge(public_documents::my_documents_routes(state))
 }
\ No newline at end of file
@@ -12,6 +12,7 @@ use crate::{
     error::{Error, Result},
     state::AppState,
     middleware::optional_auth::{OptionalAuthUser, optional_auth_middleware},
+    services::transclusion::DEFAULT_EMBED_DEPTH,
 };
 
 #[derive(Debug, Deserialize)]
@@ -187,8 +188,16 @@ async fn get_public_document(
             "posts": posts_json
         }).to_string()
     } else {
-        // For documents, get content from CRDT service
-        state.crdt_service.get_document_content(doc_info.id).await?
+        // For documents, get content from CRDT service with published embeds expanded
+        let owner_id = state.document_repository
+            .get_by_id(doc_info.id)
+            .await?
+            .ok_or_else(|| Error::NotFound("Public document not found".to_string()))?
+            .owner_id;
+        state.transclusion_service
+            .render(doc_info.id, owner_id, None, DEFAULT_EMBED_DEPTH)
+            .await?
+            .content
     };
     
     let response = PublicDocumentResponse {
@@ -0,0 +1,23 @@
+use axum::{
+    extract::{State, Query},
+    Json,
+    Extension,
+};
+use std::sync::Arc;
+use crate::{
+    error::{Error, Result},
+    state::AppState,
+    middleware::optional_auth::OptionalAuthUser,
+    entities::search::{ContentSearchQuery, ContentSearchResponse},
+};
+
+/// Full-text search over the content of the documents the user can view
+pub async fn search_content(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+    Query(query): Query<ContentSearchQuery>,
+) -> Result<Json<ContentSearchResponse>> {
+    let user_id = auth_user.user_id.ok_or(Error::Unauthorized)?;
+    let response = state.search_service.search(user_id, &query).await?;
+    Ok(Json(response))
+}
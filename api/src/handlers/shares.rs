@@ -2,7 +2,7 @@ use axum::{
     extract::{Extension, Path, State},
     http::StatusCode,
     Router,
-    routing::{get, post, delete},
+    routing::{get, post, put, delete},
     Json,
     middleware::from_fn_with_state,
 };
@@ -13,7 +13,8 @@ use crate::{
     state::AppState,
     error::Error,
     middleware::auth::{AuthUser, auth_middleware},
-    entities::share::ShareDocumentRequest,
+    entities::share::{ShareDocumentRequest, UpdatePermissionRequest},
+    socketio::Revocation,
 };
 
 pub fn routes(state: Arc<AppState>) -> Router {
@@ -24,6 +25,7 @@ pub fn routes(state: Arc<AppState>) -> Router {
         .nest("/", Router::new()
             .route("/documents/:id/share", post(create_share_link))
             .route("/documents/:id/shares", get(list_document_shares))
+            .route("/documents/:id/permissions/:user_id", put(update_user_permission).delete(remove_user_permission))
             .route("/:token", delete(delete_share))
             .layer(from_fn_with_state(state.clone(), auth_middleware))
         )
@@ -63,7 +65,53 @@ async fn delete_share(
     Extension(auth_user): Extension<AuthUser>,
     Path(token): Path<String>,
 ) -> Result<StatusCode, Error> {
-    state.share_service.delete_share(&token, auth_user.user_id).await?;
+    let share_link = state.share_service.delete_share(&token, auth_user.user_id).await?;
+
+    // Sockets that joined through the link lose access right away
+    state.session_revoker.revoke(Revocation::ShareLink {
+        document_id: share_link.document_id,
+        token: share_link.token,
+    });
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn update_user_permission(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<AuthUser>,
+    Path((document_id, user_id)): Path<(Uuid, Uuid)>,
+    Json(request): Json<UpdatePermissionRequest>,
+) -> Result<Json<serde_json::Value>, Error> {
+    let permission = state.share_service.set_user_permission(
+        document_id,
+        auth_user.user_id,
+        user_id,
+        request.permission,
+    ).await?;
+
+    // Live sessions of the user are downgraded if the permission was lowered
+    state.session_revoker.revoke(Revocation::UserPermission {
+        document_id,
+        user_id,
+        permission: Some(permission.permission),
+    });
+
+    Ok(Json(json!({
+        "data": permission
+    })))
+}
+
+async fn remove_user_permission(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<AuthUser>,
+    Path((document_id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, Error> {
+    state.share_service.remove_user_permission(document_id, auth_user.user_id, user_id).await?;
+
+    state.session_revoker.revoke(Revocation::UserPermission {
+        document_id,
+        user_id,
+        permission: None,
+    });
     Ok(StatusCode::NO_CONTENT)
 }
 
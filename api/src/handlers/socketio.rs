@@ -102,4 +102,3 @@ pub async fn get_document_stats(
         last_modified,
     }))
 }
-
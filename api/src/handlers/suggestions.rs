@@ -0,0 +1,108 @@
+use axum::{
+    extract::{State, Path, Query},
+    Json,
+    Extension,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::{
+    error::{Error, Result},
+    state::AppState,
+    middleware::{optional_auth::OptionalAuthUser, permission::check_document_permission},
+    entities::share::Permission,
+    entities::suggestion::{
+        CreateSuggestionRequest, SuggestionListQuery, SuggestionListResponse, SuggestionResponse,
+    },
+};
+
+async fn ensure_permission(
+    state: &Arc<AppState>,
+    document_id: Uuid,
+    auth_user: &OptionalAuthUser,
+    share_token: Option<String>,
+    required: Permission,
+) -> Result<()> {
+    let check = check_document_permission(state, document_id, auth_user.user_id, share_token, required).await?;
+    if !check.has_access {
+        return Err(Error::Forbidden);
+    }
+    Ok(())
+}
+
+/// List suggested edits of a document
+pub async fn list_suggestions(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+    Path(document_id): Path<Uuid>,
+    Query(query): Query<SuggestionListQuery>,
+) -> Result<Json<SuggestionListResponse>> {
+    ensure_permission(&state, document_id, &auth_user, query.token.clone(), Permission::View).await?;
+
+    let suggestions = state.suggestion_service
+        .list_suggestions(document_id, query.status.as_deref())
+        .await?;
+
+    Ok(Json(SuggestionListResponse { suggestions }))
+}
+
+/// Propose replacing a range of the document without editing it
+pub async fn create_suggestion(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+    Path(document_id): Path<Uuid>,
+    Query(params): Query<HashMap<String, String>>,
+    Json(req): Json<CreateSuggestionRequest>,
+) -> Result<Json<SuggestionResponse>> {
+    ensure_permission(&state, document_id, &auth_user, params.get("token").cloned(), Permission::Comment).await?;
+
+    let author = state.comment_service.author(auth_user.user_id, req.author_name.as_deref()).await;
+    let suggestion = state.suggestion_service
+        .create_suggestion(document_id, &req.anchor, &req.replacement, &author)
+        .await?;
+
+    Ok(Json(suggestion))
+}
+
+/// Apply a suggestion to the document
+pub async fn accept_suggestion(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+    Path((document_id, suggestion_id)): Path<(Uuid, Uuid)>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<SuggestionResponse>> {
+    ensure_permission(&state, document_id, &auth_user, params.get("token").cloned(), Permission::Edit).await?;
+
+    let document = state.document_repository
+        .get_by_id(document_id)
+        .await?
+        .ok_or_else(|| Error::NotFound("Document not found".to_string()))?;
+
+    let suggestion = state.suggestion_service
+        .accept_suggestion(document_id, suggestion_id, auth_user.user_id)
+        .await?;
+
+    // Persist edited content to file (also refreshes links and tags)
+    let content = state.crdt_service.get_document_content(document_id).await?;
+    if let Err(e) = state.document_service.save_to_file_with_content(&document, &content).await {
+        tracing::warn!("Failed to save document {} to file after accepting suggestion: {}", document_id, e);
+    }
+
+    Ok(Json(suggestion))
+}
+
+/// Decline a suggestion, leaving the document unchanged
+pub async fn reject_suggestion(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+    Path((document_id, suggestion_id)): Path<(Uuid, Uuid)>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<SuggestionResponse>> {
+    ensure_permission(&state, document_id, &auth_user, params.get("token").cloned(), Permission::Edit).await?;
+
+    let suggestion = state.suggestion_service
+        .reject_suggestion(document_id, suggestion_id, auth_user.user_id)
+        .await?;
+
+    Ok(Json(suggestion))
+}
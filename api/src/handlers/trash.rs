@@ -0,0 +1,58 @@
+use axum::{
+    extract::{State, Path},
+    Json,
+    Extension,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+use crate::{
+    error::{Error, Result},
+    state::AppState,
+    middleware::optional_auth::OptionalAuthUser,
+    entities::trash::{EmptyTrashResponse, TrashListResponse},
+    handlers::documents::DocumentResponse,
+};
+
+/// List the documents and folders in the user's trash
+pub async fn list_trash(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+) -> Result<Json<TrashListResponse>> {
+    let user_id = auth_user.user_id.ok_or(Error::Unauthorized)?;
+    let entries = state.document_service.list_trash(user_id).await?;
+    let retention_days = Some(state.config.trash_retention_days).filter(|days| *days > 0);
+
+    Ok(Json(TrashListResponse { entries, retention_days }))
+}
+
+/// Restore a trash entry to where it was deleted from
+pub async fn restore_from_trash(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<DocumentResponse>> {
+    let user_id = auth_user.user_id.ok_or(Error::Unauthorized)?;
+    let document = state.document_service.restore_document(id, user_id).await?;
+    Ok(Json(document.into()))
+}
+
+/// Permanently delete a trash entry
+pub async fn purge_from_trash(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<EmptyTrashResponse>> {
+    let user_id = auth_user.user_id.ok_or(Error::Unauthorized)?;
+    let purged = state.document_service.purge_document(id, user_id).await?;
+    Ok(Json(EmptyTrashResponse { purged }))
+}
+
+/// Permanently delete everything in the user's trash
+pub async fn empty_trash(
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+) -> Result<Json<EmptyTrashResponse>> {
+    let user_id = auth_user.user_id.ok_or(Error::Unauthorized)?;
+    let purged = state.document_service.empty_trash(user_id).await?;
+    Ok(Json(EmptyTrashResponse { purged }))
+}
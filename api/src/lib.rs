@@ -10,5 +10,8 @@ pub mod socketio;
 pub mod state;
 pub mod utils;
 pub mod crdt;
+pub mod websocket;
+#[cfg(test)]
+mod test_support;
 
 pub use error::{Error, Result};
\ No newline at end of file
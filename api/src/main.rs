@@ -21,7 +21,10 @@ mod repository;
 mod services;
 mod socketio;
 mod state;
+#[cfg(test)]
+mod test_support;
 mod utils;
+mod websocket;
 
 use crate::state::AppState;
 
@@ -73,6 +76,43 @@ async fn main() -> Result<()> {
         info!("Git batch sync service started");
     }
     
+    // Start document cache eviction
+    app_state.document_cache_service.start().await;
+    
+    // Start history compaction if retention is configured
+    if let Some(ref compaction) = app_state.history_compaction_service {
+        compaction.start().await;
+        info!("History compaction service started");
+    }
+    
+    // Start purging expired trash if retention is configured
+    if let Some(ref trash_expiry) = app_state.trash_expiry_service {
+        trash_expiry.start().await;
+        info!("Trash expiry service started");
+    }
+    
+    // Start cross-replica fan-out if enabled
+    if let Some(ref cluster_sync) = app_state.cluster_sync_service {
+        cluster_sync.start().await;
+        info!("Cluster sync service started (node {})", cluster_sync.node_id());
+    }
+    
+    // Index the content of documents saved before search indexing existed
+    let search_service = app_state.search_service.clone();
+    tokio::spawn(async move {
+        match search_service.index_missing().await {
+            Ok(0) => {}
+            Ok(indexed) => info!("Indexed {} documents for search", indexed),
+            Err(e) => warn!("Failed to index documents for search: {}", e),
+        }
+    });
+    
+    // Start picking up external edits to the stored files if enabled
+    if let Some(ref file_watcher) = app_state.file_watcher_service {
+        file_watcher.start().await;
+        info!("File watcher service started");
+    }
+    
     // Start server
     let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
     info!("Starting server on {}", addr);
@@ -115,6 +155,28 @@ async fn shutdown_signal(app_state: Arc<AppState>) {
         batch_sync.stop().await;
         info!("Git batch sync service stopped");
     }
+    
+    if let Some(ref compaction) = app_state.history_compaction_service {
+        compaction.stop().await;
+        info!("History compaction service stopped");
+    }
+    
+    if let Some(ref trash_expiry) = app_state.trash_expiry_service {
+        trash_expiry.stop().await;
+        info!("Trash expiry service stopped");
+    }
+    
+    if let Some(ref cluster_sync) = app_state.cluster_sync_service {
+        cluster_sync.stop().await;
+        info!("Cluster sync service stopped");
+    }
+    
+    if let Some(ref file_watcher) = app_state.file_watcher_service {
+        file_watcher.stop().await;
+        info!("File watcher service stopped");
+    }
+    
+    app_state.document_cache_service.stop().await;
 
     warn!("Shutdown signal received, starting graceful shutdown...");
     
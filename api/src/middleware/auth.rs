@@ -14,6 +14,8 @@ use crate::{error::Error, state::AppState};
 #[derive(Clone)]
 pub struct AuthUser {
     pub user_id: Uuid,
+    /// Login session of the access token
+    pub session_id: Option<Uuid>,
 }
 
 pub async fn auth_middleware(
@@ -31,6 +33,7 @@ pub async fn auth_middleware(
     // Create auth user
     let auth_user = AuthUser {
         user_id: claims.sub,
+        session_id: claims.sid,
     };
     
     // Insert auth user into request extensions
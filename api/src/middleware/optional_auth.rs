@@ -14,6 +14,8 @@ use uuid::Uuid;
 #[derive(Debug, Clone)]
 pub struct OptionalAuthUser {
     pub user_id: Option<Uuid>,
+    /// Login session of the access token
+    pub session_id: Option<Uuid>,
 }
 
 pub async fn optional_auth_middleware(
@@ -23,6 +25,7 @@ pub async fn optional_auth_middleware(
     next: Next,
 ) -> Result<Response, Error> {
     let mut user_id: Option<Uuid> = None;
+    let mut session_id: Option<Uuid> = None;
     
     if let Some(auth_header) = auth {
         let token = auth_header.token();
@@ -38,12 +41,13 @@ pub async fn optional_auth_middleware(
         if let Ok(claims) = jwt_service.verify_token(token) {
             // Set user_id if token is valid
             user_id = Some(claims.sub);
+            session_id = claims.sid;
         }
         // If token is invalid, we don't error out, just continue without auth
     }
     
     // Insert OptionalAuthUser with the user_id (which may be None)
-    request.extensions_mut().insert(OptionalAuthUser { user_id });
+    request.extensions_mut().insert(OptionalAuthUser { user_id, session_id });
     
     let response = next.run(request).await;
     Ok(response)
@@ -12,7 +12,7 @@ use crate::{
     entities::share::Permission,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PermissionCheck {
     pub has_access: bool,
     pub is_share_link: bool,
@@ -28,9 +28,9 @@ pub async fn check_resource_permission(
     required_permission: Permission,
     expected_type: Option<&str>, // None for any type, Some("scrap") for scraps only, etc.
 ) -> Result<PermissionCheck> {
-    // First check if resource exists
+    // First check if resource exists; trashed documents are only reachable through the trash
     let doc = state.document_repository
-        .get_by_id(resource_id)
+        .get_active_by_id(resource_id)
         .await?
         .ok_or_else(|| {
             let resource_name = expected_type.unwrap_or("Resource");
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+use uuid::Uuid;
+use sqlx::PgPool;
+use crate::entities::comment::{Comment, CommentAuthor, CommentThread, THREAD_STATUS_RESOLVED};
+use crate::error::Result;
+
+const THREAD_COLUMNS: &str = "id, document_id, anchor_start, anchor_end, quote, status, created_by, resolved_by, resolved_at, created_at, updated_at";
+
+pub struct CommentRepository {
+    pool: Arc<PgPool>,
+}
+
+impl CommentRepository {
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self { pool }
+    }
+
+    /// Create a thread together with its first comment
+    pub async fn create_thread(
+        &self,
+        document_id: Uuid,
+        anchor_start: &[u8],
+        anchor_end: &[u8],
+        quote: &str,
+        author: &CommentAuthor,
+        body: &str,
+    ) -> Result<(CommentThread, Comment)> {
+        let mut tx = self.pool.begin().await?;
+
+        let thread = sqlx::query_as::<_, CommentThread>(&format!(
+            r#"
+            INSERT INTO document_comment_threads (document_id, anchor_start, anchor_end, quote, created_by)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING {}
+            "#,
+            THREAD_COLUMNS
+        ))
+        .bind(document_id)
+        .bind(anchor_start)
+        .bind(anchor_end)
+        .bind(quote)
+        .bind(author.user_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let comment = sqlx::query_as::<_, Comment>(
+            r#"
+            INSERT INTO document_comments (thread_id, author_id, author_name, body)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, thread_id, author_id, author_name, body, created_at
+            "#,
+        )
+        .bind(thread.id)
+        .bind(author.user_id)
+        .bind(&author.name)
+        .bind(body)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok((thread, comment))
+    }
+
+    pub async fn add_comment(
+        &self,
+        thread_id: Uuid,
+        author: &CommentAuthor,
+        body: &str,
+    ) -> Result<Comment> {
+        let comment = sqlx::query_as::<_, Comment>(
+            r#"
+            INSERT INTO document_comments (thread_id, author_id, author_name, body)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, thread_id, author_id, author_name, body, created_at
+            "#,
+        )
+        .bind(thread_id)
+        .bind(author.user_id)
+        .bind(&author.name)
+        .bind(body)
+        .fetch_one(self.pool.as_ref())
+        .await?;
+
+        Ok(comment)
+    }
+
+    pub async fn get_thread(&self, document_id: Uuid, thread_id: Uuid) -> Result<Option<CommentThread>> {
+        let thread = sqlx::query_as::<_, CommentThread>(&format!(
+            "SELECT {} FROM document_comment_threads WHERE id = $1 AND document_id = $2",
+            THREAD_COLUMNS
+        ))
+        .bind(thread_id)
+        .bind(document_id)
+        .fetch_optional(self.pool.as_ref())
+        .await?;
+
+        Ok(thread)
+    }
+
+    /// Threads of a document, oldest first
+    pub async fn list_threads(&self, document_id: Uuid, include_resolved: bool) -> Result<Vec<CommentThread>> {
+        let threads = sqlx::query_as::<_, CommentThread>(&format!(
+            r#"
+            SELECT {}
+            FROM document_comment_threads
+            WHERE document_id = $1 AND ($2 OR status <> 'resolved')
+            ORDER BY created_at
+            "#,
+            THREAD_COLUMNS
+        ))
+        .bind(document_id)
+        .bind(include_resolved)
+        .fetch_all(self.pool.as_ref())
+        .await?;
+
+        Ok(threads)
+    }
+
+    /// Comments of the given threads, oldest first
+    pub async fn list_comments(&self, thread_ids: &[Uuid]) -> Result<Vec<Comment>> {
+        let comments = sqlx::query_as::<_, Comment>(
+            r#"
+            SELECT id, thread_id, author_id, author_name, body, created_at
+            FROM document_comments
+            WHERE thread_id = ANY($1)
+            ORDER BY created_at
+            "#,
+        )
+        .bind(thread_ids)
+        .fetch_all(self.pool.as_ref())
+        .await?;
+
+        Ok(comments)
+    }
+
+    /// Resolve or reopen a thread
+    pub async fn set_status(&self, thread_id: Uuid, status: &str, user_id: Option<Uuid>) -> Result<CommentThread> {
+        let resolved = status == THREAD_STATUS_RESOLVED;
+        let thread = sqlx::query_as::<_, CommentThread>(&format!(
+            r#"
+            UPDATE document_comment_threads
+            SET status = $2,
+                resolved_by = CASE WHEN $3 THEN $4 ELSE NULL END,
+                resolved_at = CASE WHEN $3 THEN NOW() ELSE NULL END
+            WHERE id = $1
+            RETURNING {}
+            "#,
+            THREAD_COLUMNS
+        ))
+        .bind(thread_id)
+        .bind(status)
+        .bind(resolved)
+        .bind(user_id)
+        .fetch_one(self.pool.as_ref())
+        .await?;
+
+        Ok(thread)
+    }
+}
@@ -2,9 +2,14 @@
 use std::sync::Arc;
 use sqlx::PgPool;
 use uuid::Uuid;
+use chrono::{DateTime, Utc};
 use crate::db::models::Document;
+use crate::entities::trash::TrashEntry;
 use crate::error::{Error, Result};
 
+const DOCUMENT_COLUMNS: &str = "id, owner_id, title, type, parent_id, file_path, crdt_state, version, \
+    COALESCE(visibility, 'private') AS visibility, published_at, created_at, updated_at, last_edited_by, last_edited_at";
+
 #[derive(Clone)]
 pub struct DocumentRepository {
     pool: Arc<PgPool>,
@@ -104,7 +109,7 @@ impl DocumentRepository {
                 COALESCE(visibility, 'private') as "visibility!", published_at,
                 created_at as "created_at!", updated_at as "updated_at!", last_edited_by, last_edited_at
             FROM documents
-            WHERE owner_id = $1
+            WHERE owner_id = $1 AND deleted_at IS NULL
             ORDER BY updated_at DESC
             "#,
             owner_id
@@ -240,7 +245,7 @@ impl DocumentRepository {
                     COALESCE(visibility, 'private') as visibility, published_at,
                     created_at, updated_at, last_edited_by, last_edited_at
                 FROM documents
-                WHERE parent_id = $1
+                WHERE parent_id = $1 AND deleted_at IS NULL
                 
                 UNION ALL
                 
@@ -250,6 +255,7 @@ impl DocumentRepository {
                     d.created_at, d.updated_at, d.last_edited_by, d.last_edited_at
                 FROM documents d
                 INNER JOIN descendant_tree dt ON d.parent_id = dt.id
+                WHERE d.deleted_at IS NULL
             )
             SELECT id, owner_id, title, type as "type", parent_id, 
                 file_path, crdt_state, version,
@@ -277,4 +283,223 @@ impl DocumentRepository {
         
         Ok(result.flatten().map(|v| v == "public").unwrap_or(false))
     }
-}
\ No newline at end of file
+    
+    /// Document by id unless it is in the trash
+    pub async fn get_active_by_id(&self, id: Uuid) -> Result<Option<Document>> {
+        let document = sqlx::query_as::<_, Document>(&format!(
+            "SELECT {} FROM documents WHERE id = $1 AND deleted_at IS NULL",
+            DOCUMENT_COLUMNS
+        ))
+        .bind(id)
+        .fetch_optional(self.pool.as_ref())
+        .await?;
+        
+        Ok(document)
+    }
+    
+    pub async fn is_trashed(&self, id: Uuid) -> Result<bool> {
+        let trashed = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM documents WHERE id = $1 AND deleted_at IS NOT NULL)"
+        )
+        .bind(id)
+        .fetch_one(self.pool.as_ref())
+        .await?;
+        
+        Ok(trashed)
+    }
+    
+    /// Move a document and everything below it that is not already trashed to the trash,
+    /// returning the trashed rows
+    pub async fn trash(&self, id: Uuid, owner_id: Uuid, deleted_by: Uuid) -> Result<Vec<Document>> {
+        let documents = sqlx::query_as::<_, Document>(&format!(
+            r#"
+            WITH RECURSIVE subtree AS (
+                SELECT id FROM documents WHERE id = $1 AND owner_id = $2 AND deleted_at IS NULL
+                UNION ALL
+                SELECT d.id FROM documents d
+                INNER JOIN subtree s ON d.parent_id = s.id
+                WHERE d.deleted_at IS NULL
+            )
+            UPDATE documents
+            SET deleted_at = NOW(), deleted_by = $3, trash_root_id = $1
+            WHERE id IN (SELECT id FROM subtree)
+            RETURNING {}
+            "#,
+            DOCUMENT_COLUMNS
+        ))
+        .bind(id)
+        .bind(owner_id)
+        .bind(deleted_by)
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        
+        if documents.is_empty() {
+            return Err(Error::NotFound("Document not found".to_string()));
+        }
+        
+        Ok(documents)
+    }
+    
+    /// Take a trash entry out of the trash, returning the restored rows.
+    ///
+    /// The entry goes back under its original parent, or to the top level when that
+    /// parent is in the trash itself.
+    pub async fn restore(&self, id: Uuid, owner_id: Uuid) -> Result<Vec<Document>> {
+        let mut tx = self.pool.begin().await?;
+        
+        sqlx::query(
+            r#"
+            UPDATE documents SET parent_id = NULL
+            WHERE id = $1 AND owner_id = $2 AND trash_root_id = $1
+              AND EXISTS (SELECT 1 FROM documents p WHERE p.id = documents.parent_id AND p.deleted_at IS NOT NULL)
+            "#,
+        )
+        .bind(id)
+        .bind(owner_id)
+        .execute(&mut *tx)
+        .await?;
+        
+        let documents = sqlx::query_as::<_, Document>(&format!(
+            r#"
+            UPDATE documents
+            SET deleted_at = NULL, deleted_by = NULL, trash_root_id = NULL
+            WHERE trash_root_id = $1 AND owner_id = $2
+              AND EXISTS (SELECT 1 FROM documents r WHERE r.id = $1 AND r.trash_root_id = $1)
+            RETURNING {}
+            "#,
+            DOCUMENT_COLUMNS
+        ))
+        .bind(id)
+        .bind(owner_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        
+        if documents.is_empty() {
+            return Err(Error::NotFound("Trash entry not found".to_string()));
+        }
+        
+        tx.commit().await?;
+        Ok(documents)
+    }
+    
+    /// Permanently delete a trash entry, returning the ids of the deleted rows.
+    ///
+    /// Items below it that were trashed on their own keep their trash entry and move
+    /// to the top level.
+    pub async fn purge(&self, id: Uuid, owner_id: Uuid) -> Result<Vec<Uuid>> {
+        let mut tx = self.pool.begin().await?;
+        
+        sqlx::query(
+            r#"
+            UPDATE documents SET parent_id = NULL
+            WHERE id = trash_root_id AND trash_root_id <> $1
+              AND parent_id IN (SELECT id FROM documents WHERE trash_root_id = $1 AND owner_id = $2)
+            "#,
+        )
+        .bind(id)
+        .bind(owner_id)
+        .execute(&mut *tx)
+        .await?;
+        
+        let ids = sqlx::query_scalar::<_, Uuid>(
+            "SELECT id FROM documents WHERE trash_root_id = $1 AND owner_id = $2"
+        )
+        .bind(id)
+        .bind(owner_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        
+        let result = sqlx::query(
+            "DELETE FROM documents WHERE id = $1 AND owner_id = $2 AND trash_root_id = $1"
+        )
+        .bind(id)
+        .bind(owner_id)
+        .execute(&mut *tx)
+        .await?;
+        
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound("Trash entry not found".to_string()));
+        }
+        
+        tx.commit().await?;
+        Ok(ids)
+    }
+    
+    /// Trash entries of a user, most recently deleted first
+    pub async fn list_trash(&self, owner_id: Uuid) -> Result<Vec<TrashEntry>> {
+        let entries = sqlx::query_as::<_, TrashEntry>(
+            r#"
+            SELECT r.id, r.title, r.type AS document_type, r.parent_id, r.file_path,
+                   r.deleted_at, r.deleted_by,
+                   (SELECT COUNT(*) FROM documents d WHERE d.trash_root_id = r.id) AS item_count
+            FROM documents r
+            WHERE r.owner_id = $1 AND r.trash_root_id = r.id
+            ORDER BY r.deleted_at DESC
+            "#,
+        )
+        .bind(owner_id)
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        
+        Ok(entries)
+    }
+    
+    /// Trash entries of all users deleted before the cutoff, as (id, owner) pairs
+    pub async fn list_trash_before(&self, cutoff: DateTime<Utc>) -> Result<Vec<(Uuid, Uuid)>> {
+        let entries = sqlx::query_as::<_, (Uuid, Uuid)>(
+            "SELECT id, owner_id FROM documents WHERE trash_root_id = id AND deleted_at < $1"
+        )
+        .bind(cutoff)
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{db_test, TestDb};
+
+    /// Parent of a document, with its trash entry
+    async fn position(repository: &DocumentRepository, id: Uuid) -> Option<(Option<Uuid>, Option<Uuid>)> {
+        sqlx::query_as("SELECT parent_id, trash_root_id FROM documents WHERE id = $1")
+            .bind(id)
+            .fetch_optional(repository.pool.as_ref())
+            .await
+            .unwrap()
+    }
+
+    db_test! {
+        async fn test_trash_restore_and_purge_nested_entries() {
+            let db = TestDb::connect().await;
+            let repository = DocumentRepository::new(db.pool.clone());
+            let owner_id = db.create_user().await;
+            let folder = repository.create(owner_id, "Projects", None, "folder", None).await.unwrap().id;
+            let subfolder = repository.create(owner_id, "Launch", None, "folder", Some(folder)).await.unwrap().id;
+            let note = repository.create(owner_id, "Checklist", None, "document", Some(subfolder)).await.unwrap().id;
+
+            // Trashing a folder takes its contents along, restoring puts it back in place
+            let trashed = repository.trash(subfolder, owner_id, owner_id).await.unwrap();
+            assert_eq!(trashed.len(), 2);
+            assert_eq!(position(&repository, note).await, Some((Some(subfolder), Some(subfolder))));
+            repository.restore(subfolder, owner_id).await.unwrap();
+            assert_eq!(position(&repository, subfolder).await, Some((Some(folder), None)));
+            assert_eq!(position(&repository, note).await, Some((Some(subfolder), None)));
+
+            // A note trashed on its own stays an entry when the folder above it is purged
+            repository.trash(note, owner_id, owner_id).await.unwrap();
+            repository.trash(folder, owner_id, owner_id).await.unwrap();
+            let mut purged = repository.purge(folder, owner_id).await.unwrap();
+            purged.sort();
+            let mut expected = vec![folder, subfolder];
+            expected.sort();
+            assert_eq!(purged, expected);
+            assert_eq!(position(&repository, subfolder).await, None);
+            assert_eq!(position(&repository, note).await, Some((None, Some(note))));
+            let entries = repository.list_trash(owner_id).await.unwrap();
+            assert_eq!(entries.iter().map(|entry| entry.id).collect::<Vec<_>>(), vec![note]);
+        }
+    }
+}
@@ -3,7 +3,7 @@ use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::{
-    entities::git_config::{GitConfig, GitSyncLog, CreateGitConfigRequest, UpdateGitConfigRequest},
+    entities::git_config::{GitConfig, GitSyncLog, GitBatchSyncStage, GitBatchSyncStatus, CreateGitConfigRequest, UpdateGitConfigRequest},
     error::{Error, Result},
 };
 
@@ -20,16 +20,18 @@ impl GitConfigRepository {
         let config = sqlx::query_as!(
             GitConfig,
             r#"
-            INSERT INTO git_configs (user_id, repository_url, branch_name, auth_type, auth_data, auto_sync)
-            VALUES ($1, $2, $3, $4, $5, $6)
-            RETURNING id, user_id, repository_url, branch_name, auth_type, auth_data, auto_sync, created_at, updated_at
+            INSERT INTO git_configs (user_id, repository_url, branch_name, auth_type, auth_data, auto_sync, folder_id, sync_interval_secs)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id, user_id, repository_url, branch_name, auth_type, auth_data, auto_sync, folder_id, sync_interval_secs, created_at, updated_at
             "#,
             user_id,
             request.repository_url,
             request.branch_name.unwrap_or_else(|| "main".to_string()),
             request.auth_type,
             request.auth_data,
-            request.auto_sync.unwrap_or(true)
+            request.auto_sync.unwrap_or(true),
+            request.folder_id,
+            request.sync_interval_secs
         )
         .fetch_one(self.pool.as_ref())
         .await?;
@@ -37,10 +39,11 @@ impl GitConfigRepository {
         Ok(config)
     }
 
+    /// Configuration of the repository holding the user's whole document tree
     pub async fn get_by_user_id(&self, user_id: Uuid) -> Result<Option<GitConfig>> {
         let config = sqlx::query_as!(
             GitConfig,
-            "SELECT id, user_id, repository_url, branch_name, auth_type, auth_data, auto_sync, created_at, updated_at FROM git_configs WHERE user_id = $1",
+            "SELECT id, user_id, repository_url, branch_name, auth_type, auth_data, auto_sync, folder_id, sync_interval_secs, created_at, updated_at FROM git_configs WHERE user_id = $1 AND folder_id IS NULL",
             user_id
         )
         .fetch_optional(self.pool.as_ref())
@@ -49,119 +52,154 @@ impl GitConfigRepository {
         Ok(config)
     }
 
-    pub async fn update(&self, user_id: Uuid, request: UpdateGitConfigRequest) -> Result<GitConfig> {
-        // Build dynamic update query
-        let mut query_parts = vec![];
-        let mut param_count = 1;
-        let mut params: Vec<String> = vec![];
-
-        if let Some(url) = &request.repository_url {
-            query_parts.push(format!("repository_url = ${}", param_count));
-            params.push(url.clone());
-            param_count += 1;
-        }
-
-        if let Some(branch) = &request.branch_name {
-            query_parts.push(format!("branch_name = ${}", param_count));
-            params.push(branch.clone());
-            param_count += 1;
-        }
+    pub async fn get_by_id(&self, user_id: Uuid, id: Uuid) -> Result<Option<GitConfig>> {
+        let config = sqlx::query_as!(
+            GitConfig,
+            "SELECT id, user_id, repository_url, branch_name, auth_type, auth_data, auto_sync, folder_id, sync_interval_secs, created_at, updated_at FROM git_configs WHERE id = $1 AND user_id = $2",
+            id,
+            user_id
+        )
+        .fetch_optional(self.pool.as_ref())
+        .await?;
 
-        if let Some(auth_type) = &request.auth_type {
-            query_parts.push(format!("auth_type = ${}", param_count));
-            params.push(auth_type.clone());
-            param_count += 1;
-        }
+        Ok(config)
+    }
 
-        if request.auth_data.is_some() {
-            query_parts.push(format!("auth_data = ${}", param_count));
-            param_count += 1;
-        }
+    pub async fn get_by_folder(&self, user_id: Uuid, folder_id: Uuid) -> Result<Option<GitConfig>> {
+        let config = sqlx::query_as!(
+            GitConfig,
+            "SELECT id, user_id, repository_url, branch_name, auth_type, auth_data, auto_sync, folder_id, sync_interval_secs, created_at, updated_at FROM git_configs WHERE user_id = $1 AND folder_id = $2",
+            user_id,
+            folder_id
+        )
+        .fetch_optional(self.pool.as_ref())
+        .await?;
 
-        if request.auto_sync.is_some() {
-            query_parts.push(format!("auto_sync = ${}", param_count));
-            param_count += 1;
-        }
+        Ok(config)
+    }
 
-        if query_parts.is_empty() {
-            return Err(Error::BadRequest("No fields to update".to_string()));
-        }
+    /// All configurations of a user, the root repository first
+    pub async fn list_by_user(&self, user_id: Uuid) -> Result<Vec<GitConfig>> {
+        let configs = sqlx::query_as!(
+            GitConfig,
+            "SELECT id, user_id, repository_url, branch_name, auth_type, auth_data, auto_sync, folder_id, sync_interval_secs, created_at, updated_at FROM git_configs WHERE user_id = $1 ORDER BY folder_id IS NOT NULL, created_at",
+            user_id
+        )
+        .fetch_all(self.pool.as_ref())
+        .await?;
 
-        let _query = format!(
-            "UPDATE git_configs SET {} WHERE user_id = ${} RETURNING id, user_id, repository_url, branch_name, auth_type, auth_data, auto_sync, created_at, updated_at",
-            query_parts.join(", "),
-            param_count
-        );
+        Ok(configs)
+    }
 
-        // For simplicity, let's use individual updates for each field
-        // This is not the most efficient but is easier to implement correctly
-        let mut config = self.get_by_user_id(user_id).await?
+    /// Update the configuration of the user's root repository
+    pub async fn update(&self, user_id: Uuid, request: UpdateGitConfigRequest) -> Result<GitConfig> {
+        let config = self.get_by_user_id(user_id).await?
             .ok_or_else(|| Error::NotFound("Git config not found".to_string()))?;
 
-        if let Some(url) = request.repository_url {
-            config = sqlx::query_as!(
-                GitConfig,
-                "UPDATE git_configs SET repository_url = $1 WHERE user_id = $2 RETURNING id, user_id, repository_url, branch_name, auth_type, auth_data, auto_sync, created_at, updated_at",
-                url, user_id
-            )
-            .fetch_one(self.pool.as_ref())
-            .await?;
-        }
+        self.update_by_id(user_id, config.id, request).await
+    }
 
-        if let Some(branch) = request.branch_name {
-            config = sqlx::query_as!(
-                GitConfig,
-                "UPDATE git_configs SET branch_name = $1 WHERE user_id = $2 RETURNING id, user_id, repository_url, branch_name, auth_type, auth_data, auto_sync, created_at, updated_at",
-                branch, user_id
-            )
-            .fetch_one(self.pool.as_ref())
-            .await?;
+    pub async fn update_by_id(&self, user_id: Uuid, id: Uuid, request: UpdateGitConfigRequest) -> Result<GitConfig> {
+        if request.repository_url.is_none()
+            && request.branch_name.is_none()
+            && request.auth_type.is_none()
+            && request.auth_data.is_none()
+            && request.auto_sync.is_none()
+            && request.sync_interval_secs.is_none()
+        {
+            return Err(Error::BadRequest("No fields to update".to_string()));
         }
 
-        if let Some(auth_type) = request.auth_type {
-            config = sqlx::query_as!(
-                GitConfig,
-                "UPDATE git_configs SET auth_type = $1 WHERE user_id = $2 RETURNING id, user_id, repository_url, branch_name, auth_type, auth_data, auto_sync, created_at, updated_at",
-                auth_type, user_id
-            )
-            .fetch_one(self.pool.as_ref())
-            .await?;
-        }
+        let config = sqlx::query_as!(
+            GitConfig,
+            r#"
+            UPDATE git_configs
+            SET repository_url = COALESCE($3, repository_url),
+                branch_name = COALESCE($4, branch_name),
+                auth_type = COALESCE($5, auth_type),
+                auth_data = COALESCE($6, auth_data),
+                auto_sync = COALESCE($7, auto_sync),
+                sync_interval_secs = COALESCE($8, sync_interval_secs)
+            WHERE id = $1 AND user_id = $2
+            RETURNING id, user_id, repository_url, branch_name, auth_type, auth_data, auto_sync, folder_id, sync_interval_secs, created_at, updated_at
+            "#,
+            id,
+            user_id,
+            request.repository_url,
+            request.branch_name,
+            request.auth_type,
+            request.auth_data,
+            request.auto_sync,
+            request.sync_interval_secs
+        )
+        .fetch_optional(self.pool.as_ref())
+        .await?
+        .ok_or_else(|| Error::NotFound("Git config not found".to_string()))?;
 
-        if let Some(auth_data) = request.auth_data {
-            config = sqlx::query_as!(
-                GitConfig,
-                "UPDATE git_configs SET auth_data = $1 WHERE user_id = $2 RETURNING id, user_id, repository_url, branch_name, auth_type, auth_data, auto_sync, created_at, updated_at",
-                auth_data, user_id
-            )
-            .fetch_one(self.pool.as_ref())
-            .await?;
-        }
+        Ok(config)
+    }
 
-        if let Some(auto_sync) = request.auto_sync {
-            config = sqlx::query_as!(
-                GitConfig,
-                "UPDATE git_configs SET auto_sync = $1 WHERE user_id = $2 RETURNING id, user_id, repository_url, branch_name, auth_type, auth_data, auto_sync, created_at, updated_at",
-                auto_sync, user_id
-            )
-            .fetch_one(self.pool.as_ref())
+    /// Delete the configuration of the user's root repository
+    pub async fn delete(&self, user_id: Uuid) -> Result<()> {
+        sqlx::query!("DELETE FROM git_configs WHERE user_id = $1 AND folder_id IS NULL", user_id)
+            .execute(self.pool.as_ref())
             .await?;
-        }
 
-        Ok(config)
+        Ok(())
     }
 
-    pub async fn delete(&self, user_id: Uuid) -> Result<()> {
-        sqlx::query!("DELETE FROM git_configs WHERE user_id = $1", user_id)
+    pub async fn delete_by_id(&self, user_id: Uuid, id: Uuid) -> Result<()> {
+        let result = sqlx::query!("DELETE FROM git_configs WHERE id = $1 AND user_id = $2", id, user_id)
             .execute(self.pool.as_ref())
             .await?;
 
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound("Git config not found".to_string()));
+        }
+
         Ok(())
     }
 
+    /// Titles of a user's folder and its ancestors, from the root down.
+    ///
+    /// Returns None when the document does not exist, is not owned by the user
+    /// or is not a folder.
+    pub async fn get_folder_titles(&self, user_id: Uuid, folder_id: Uuid) -> Result<Option<Vec<String>>> {
+        let folder_type = sqlx::query_scalar!(
+            "SELECT type FROM documents WHERE id = $1 AND owner_id = $2",
+            folder_id,
+            user_id
+        )
+        .fetch_optional(self.pool.as_ref())
+        .await?;
+
+        if folder_type.as_deref() != Some("folder") {
+            return Ok(None);
+        }
+
+        let titles = sqlx::query_scalar!(
+            r#"
+            WITH RECURSIVE ancestors AS (
+                SELECT id, parent_id, title, type, 0 AS depth FROM documents WHERE id = $1
+                UNION ALL
+                SELECT d.id, d.parent_id, d.title, d.type, a.depth + 1
+                FROM documents d
+                JOIN ancestors a ON d.id = a.parent_id
+            )
+            SELECT title AS "title!" FROM ancestors WHERE type = 'folder' ORDER BY depth DESC
+            "#,
+            folder_id
+        )
+        .fetch_all(self.pool.as_ref())
+        .await?;
+
+        Ok(Some(titles))
+    }
+
     pub async fn log_sync_operation(
         &self,
         user_id: Uuid,
+        git_config_id: Option<Uuid>,
         operation: &str,
         status: &str,
         message: Option<&str>,
@@ -170,11 +208,12 @@ impl GitConfigRepository {
         let log = sqlx::query_as!(
             GitSyncLog,
             r#"
-            INSERT INTO git_sync_logs (user_id, operation, status, message, commit_hash)
-            VALUES ($1, $2, $3, $4, $5)
-            RETURNING id, user_id, operation, status, message, commit_hash, created_at
+            INSERT INTO git_sync_logs (user_id, git_config_id, operation, status, message, commit_hash)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, user_id, git_config_id, operation, status, message, commit_hash, created_at
             "#,
             user_id,
+            git_config_id,
             operation,
             status,
             message,
@@ -186,15 +225,99 @@ impl GitConfigRepository {
         Ok(log)
     }
 
-    pub async fn get_sync_logs(&self, user_id: Uuid, limit: i32) -> Result<Vec<GitSyncLog>> {
+    /// Sync logs of one repository; `git_config_id` is None for the root repository
+    pub async fn get_sync_logs(&self, user_id: Uuid, git_config_id: Option<Uuid>, limit: i32) -> Result<Vec<GitSyncLog>> {
         let logs = sqlx::query_as!(
             GitSyncLog,
-            "SELECT id, user_id, operation, status, message, commit_hash, created_at FROM git_sync_logs WHERE user_id = $1 ORDER BY created_at DESC LIMIT $2",
-            user_id, limit as i64
+            r#"
+            SELECT id, user_id, git_config_id, operation, status, message, commit_hash, created_at
+            FROM git_sync_logs
+            WHERE user_id = $1 AND git_config_id IS NOT DISTINCT FROM $2
+            ORDER BY created_at DESC
+            LIMIT $3
+            "#,
+            user_id,
+            git_config_id,
+            limit as i64
         )
         .fetch_all(self.pool.as_ref())
         .await?;
 
         Ok(logs)
     }
-}
\ No newline at end of file
+
+    /// Record the latest batch sync state of a repository; `git_config_id` is None for the root repository
+    pub async fn save_batch_status(&self, user_id: Uuid, status: &GitBatchSyncStatus) -> Result<()> {
+        match status.git_config_id {
+            Some(git_config_id) => {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO git_batch_sync_status (user_id, git_config_id, status, documents, retry_count, commit_hash, message, updated_at)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                    ON CONFLICT (git_config_id) WHERE git_config_id IS NOT NULL DO UPDATE
+                    SET status = EXCLUDED.status,
+                        documents = EXCLUDED.documents,
+                        retry_count = EXCLUDED.retry_count,
+                        commit_hash = EXCLUDED.commit_hash,
+                        message = EXCLUDED.message,
+                        updated_at = EXCLUDED.updated_at
+                    "#,
+                    user_id,
+                    git_config_id,
+                    status.status as GitBatchSyncStage,
+                    &status.documents,
+                    status.retry_count,
+                    status.commit_hash,
+                    status.message,
+                    status.updated_at
+                )
+                .execute(self.pool.as_ref())
+                .await?;
+            }
+            None => {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO git_batch_sync_status (user_id, status, documents, retry_count, commit_hash, message, updated_at)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7)
+                    ON CONFLICT (user_id) WHERE git_config_id IS NULL DO UPDATE
+                    SET status = EXCLUDED.status,
+                        documents = EXCLUDED.documents,
+                        retry_count = EXCLUDED.retry_count,
+                        commit_hash = EXCLUDED.commit_hash,
+                        message = EXCLUDED.message,
+                        updated_at = EXCLUDED.updated_at
+                    "#,
+                    user_id,
+                    status.status as GitBatchSyncStage,
+                    &status.documents,
+                    status.retry_count,
+                    status.commit_hash,
+                    status.message,
+                    status.updated_at
+                )
+                .execute(self.pool.as_ref())
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Latest batch sync state of a repository; `git_config_id` is None for the root repository
+    pub async fn get_batch_status(&self, user_id: Uuid, git_config_id: Option<Uuid>) -> Result<Option<GitBatchSyncStatus>> {
+        let status = sqlx::query_as!(
+            GitBatchSyncStatus,
+            r#"
+            SELECT git_config_id, status AS "status: GitBatchSyncStage", documents, retry_count, commit_hash, message, updated_at
+            FROM git_batch_sync_status
+            WHERE user_id = $1 AND git_config_id IS NOT DISTINCT FROM $2
+            "#,
+            user_id,
+            git_config_id
+        )
+        .fetch_optional(self.pool.as_ref())
+        .await?;
+
+        Ok(status)
+    }
+}
@@ -5,8 +5,16 @@ pub mod scrap;
 pub mod share;
 pub mod git_config;
 pub mod tag;
+pub mod snapshot;
+pub mod comment;
+pub mod suggestion;
+pub mod search;
 
 pub use document::DocumentRepository;
 pub use user::UserRepository;
 pub use share::ShareRepository;
-pub use git_config::GitConfigRepository;
\ No newline at end of file
+pub use git_config::GitConfigRepository;
+pub use snapshot::SnapshotRepository;
+pub use comment::CommentRepository;
+pub use suggestion::SuggestionRepository;
+pub use search::SearchRepository;
\ No newline at end of file
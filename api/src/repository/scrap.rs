@@ -65,7 +65,7 @@ impl ScrapRepository {
         let documents = sqlx::query_as::<_, Document>(
             r#"
             SELECT * FROM documents
-            WHERE owner_id = $1 AND type = 'scrap'
+            WHERE owner_id = $1 AND type = 'scrap' AND deleted_at IS NULL
             ORDER BY updated_at DESC
             "#,
         )
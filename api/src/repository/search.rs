@@ -0,0 +1,138 @@
+use std::sync::Arc;
+use uuid::Uuid;
+use sqlx::{FromRow, PgPool};
+use crate::entities::search::{ContentSearchFilters, ContentSearchHit};
+use crate::error::Result;
+
+#[derive(FromRow)]
+struct SearchRow {
+    #[sqlx(flatten)]
+    hit: ContentSearchHit,
+    total: i64,
+}
+
+pub struct SearchRepository {
+    pool: Arc<PgPool>,
+}
+
+impl SearchRepository {
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self { pool }
+    }
+
+    /// Documents the user can view whose title or content matches a tsquery expression,
+    /// best matches first, with the total number of matches
+    pub async fn search(
+        &self,
+        user_id: Uuid,
+        tsquery: &str,
+        filters: &ContentSearchFilters,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<ContentSearchHit>, i64)> {
+        let rows = sqlx::query_as::<_, SearchRow>(
+            r#"
+            WITH RECURSIVE folder_tree AS (
+                SELECT id FROM documents WHERE id = $5
+                UNION ALL
+                SELECT d.id FROM documents d JOIN folder_tree f ON d.parent_id = f.id
+            ),
+            matches AS (
+                SELECT d.id, d.title, d.type AS document_type, d.file_path,
+                       COALESCE(d.updated_at, d.created_at, NOW()) AS updated_at,
+                       COALESCE(i.content, '') AS content,
+                       q.query,
+                       ts_rank_cd(
+                           setweight(to_tsvector('simple', d.title), 'A')
+                               || setweight(COALESCE(i.content_vector, ''::tsvector), 'B'),
+                           q.query
+                       ) AS rank
+                FROM documents d
+                CROSS JOIN (SELECT $1::tsquery) AS q(query)
+                LEFT JOIN document_search_index i ON i.document_id = d.id
+                WHERE d.type IN ('document', 'scrap')
+                  AND d.deleted_at IS NULL
+                  AND (to_tsvector('simple', d.title) @@ q.query OR i.content_vector @@ q.query)
+                  AND (d.owner_id = $2 OR EXISTS (
+                      SELECT 1 FROM document_permissions p
+                      WHERE p.document_id = d.id AND p.user_id = $2
+                  ))
+                  AND ($3::text IS NULL OR d.type = $3)
+                  AND ($4::text IS NULL OR EXISTS (
+                      SELECT 1 FROM document_tags dt
+                      JOIN tags t ON t.id = dt.tag_id
+                      WHERE dt.document_id = d.id AND t.name = normalize_tag_name($4)
+                  ))
+                  AND ($5::uuid IS NULL OR d.id IN (SELECT id FROM folder_tree))
+                  AND ($6::timestamptz IS NULL OR d.updated_at >= $6)
+                  AND ($7::timestamptz IS NULL OR d.updated_at < $7)
+            )
+            SELECT m.id, m.title, m.document_type, m.file_path, m.updated_at, m.rank, m.total,
+                   ts_headline(
+                       'simple',
+                       replace(replace(replace(m.content, '&', '&amp;'), '<', '&lt;'), '>', '&gt;'),
+                       m.query,
+                       'StartSel=<mark>, StopSel=</mark>, MaxWords=30, MinWords=10, MaxFragments=2, FragmentDelimiter=" … "'
+                   ) AS snippet
+            FROM (
+                SELECT matches.*, COUNT(*) OVER () AS total
+                FROM matches
+                ORDER BY rank DESC, updated_at DESC
+                LIMIT $8 OFFSET $9
+            ) m
+            ORDER BY m.rank DESC, m.updated_at DESC
+            "#,
+        )
+        .bind(tsquery)
+        .bind(user_id)
+        .bind(filters.document_type.as_deref())
+        .bind(filters.tag.as_deref())
+        .bind(filters.folder_id)
+        .bind(filters.updated_after)
+        .bind(filters.updated_before)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(self.pool.as_ref())
+        .await?;
+
+        let total = rows.first().map(|row| row.total).unwrap_or(0);
+        Ok((rows.into_iter().map(|row| row.hit).collect(), total))
+    }
+
+    /// Lexemes of each search term as the `simple` parser splits them, in `phraseto_tsquery`
+    /// form; empty for terms without any
+    pub async fn tokenize(&self, terms: &[String]) -> Result<Vec<String>> {
+        let phrases = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT phraseto_tsquery('simple', t.term)::text
+            FROM unnest($1::text[]) WITH ORDINALITY AS t(term, n)
+            ORDER BY t.n
+            "#,
+        )
+        .bind(terms)
+        .fetch_all(self.pool.as_ref())
+        .await?;
+
+        Ok(phrases)
+    }
+
+    /// Documents with content that were never indexed, such as those saved before indexing existed
+    pub async fn unindexed_documents(&self, limit: i64) -> Result<Vec<Uuid>> {
+        let ids = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            SELECT d.id FROM documents d
+            WHERE d.type IN ('document', 'scrap')
+              AND d.deleted_at IS NULL
+              AND d.crdt_state IS NOT NULL
+              AND NOT EXISTS (SELECT 1 FROM document_search_index i WHERE i.document_id = d.id)
+            ORDER BY d.updated_at DESC NULLS LAST
+            LIMIT $1
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(self.pool.as_ref())
+        .await?;
+
+        Ok(ids)
+    }
+}
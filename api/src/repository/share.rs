@@ -122,4 +122,15 @@ impl ShareRepository {
 
         Ok(())
     }
+
+    /// Remove a user's explicit permission, returning whether one existed
+    pub async fn delete_document_permission(&self, document_id: Uuid, user_id: Uuid) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM document_permissions WHERE document_id = $1 AND user_id = $2")
+            .bind(document_id)
+            .bind(user_id)
+            .execute(self.pool.as_ref())
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
 }
\ No newline at end of file
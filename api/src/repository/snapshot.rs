@@ -0,0 +1,105 @@
+use std::sync::Arc;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use crate::entities::snapshot::DocumentSnapshot;
+use crate::error::Result;
+
+pub struct SnapshotRepository {
+    pool: Arc<PgPool>,
+}
+
+impl SnapshotRepository {
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create(
+        &self,
+        document_id: Uuid,
+        name: Option<&str>,
+        kind: &str,
+        created_by: Option<Uuid>,
+    ) -> Result<DocumentSnapshot> {
+        let snapshot = sqlx::query_as::<_, DocumentSnapshot>(
+            r#"
+            INSERT INTO document_snapshots (document_id, name, kind, created_by)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, document_id, name, kind, created_by, created_at
+            "#,
+        )
+        .bind(document_id)
+        .bind(name)
+        .bind(kind)
+        .bind(created_by)
+        .fetch_one(self.pool.as_ref())
+        .await?;
+
+        Ok(snapshot)
+    }
+
+    pub async fn list_by_document(&self, document_id: Uuid) -> Result<Vec<DocumentSnapshot>> {
+        let snapshots = sqlx::query_as::<_, DocumentSnapshot>(
+            r#"
+            SELECT id, document_id, name, kind, created_by, created_at
+            FROM document_snapshots
+            WHERE document_id = $1
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(document_id)
+        .fetch_all(self.pool.as_ref())
+        .await?;
+
+        Ok(snapshots)
+    }
+
+    pub async fn get_by_id(&self, document_id: Uuid, snapshot_id: Uuid) -> Result<Option<DocumentSnapshot>> {
+        let snapshot = sqlx::query_as::<_, DocumentSnapshot>(
+            r#"
+            SELECT id, document_id, name, kind, created_by, created_at
+            FROM document_snapshots
+            WHERE id = $1 AND document_id = $2
+            "#,
+        )
+        .bind(snapshot_id)
+        .bind(document_id)
+        .fetch_optional(self.pool.as_ref())
+        .await?;
+
+        Ok(snapshot)
+    }
+
+    /// Whether the document has history newer than its latest snapshot
+    pub async fn has_changes_since_last_snapshot(&self, document_id: Uuid) -> Result<bool> {
+        let changed = sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM document_update_history h
+                WHERE h.document_id = $1
+                  AND h.created_at > COALESCE(
+                      (SELECT MAX(created_at) FROM document_snapshots WHERE document_id = $1),
+                      '-infinity'::timestamptz
+                  )
+            )
+            "#,
+        )
+        .bind(document_id)
+        .fetch_one(self.pool.as_ref())
+        .await?;
+
+        Ok(changed)
+    }
+
+    /// Delete automatic snapshots older than the cutoff, keeping named ones
+    pub async fn delete_auto_before(&self, cutoff: DateTime<Utc>) -> Result<u64> {
+        let result = sqlx::query(
+            "DELETE FROM document_snapshots WHERE kind = 'auto' AND created_at < $1",
+        )
+        .bind(cutoff)
+        .execute(self.pool.as_ref())
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
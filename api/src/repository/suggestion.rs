@@ -0,0 +1,111 @@
+use std::sync::Arc;
+use uuid::Uuid;
+use sqlx::PgPool;
+use crate::entities::comment::CommentAuthor;
+use crate::entities::suggestion::{Suggestion, SUGGESTION_STATUS_PENDING};
+use crate::error::Result;
+
+const SUGGESTION_COLUMNS: &str = "id, document_id, anchor_start, anchor_end, original_text, replacement, status, author_id, author_name, decided_by, decided_at, created_at, updated_at";
+
+pub struct SuggestionRepository {
+    pool: Arc<PgPool>,
+}
+
+impl SuggestionRepository {
+    pub fn new(pool: Arc<PgPool>) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create(
+        &self,
+        document_id: Uuid,
+        anchor: (&[u8], &[u8]),
+        original_text: &str,
+        replacement: &str,
+        author: &CommentAuthor,
+    ) -> Result<Suggestion> {
+        let suggestion = sqlx::query_as::<_, Suggestion>(&format!(
+            r#"
+            INSERT INTO document_suggestions (document_id, anchor_start, anchor_end, original_text, replacement, author_id, author_name)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING {}
+            "#,
+            SUGGESTION_COLUMNS
+        ))
+        .bind(document_id)
+        .bind(anchor.0)
+        .bind(anchor.1)
+        .bind(original_text)
+        .bind(replacement)
+        .bind(author.user_id)
+        .bind(&author.name)
+        .fetch_one(self.pool.as_ref())
+        .await?;
+
+        Ok(suggestion)
+    }
+
+    pub async fn get_by_id(&self, document_id: Uuid, suggestion_id: Uuid) -> Result<Option<Suggestion>> {
+        let suggestion = sqlx::query_as::<_, Suggestion>(&format!(
+            "SELECT {} FROM document_suggestions WHERE id = $1 AND document_id = $2",
+            SUGGESTION_COLUMNS
+        ))
+        .bind(suggestion_id)
+        .bind(document_id)
+        .fetch_optional(self.pool.as_ref())
+        .await?;
+
+        Ok(suggestion)
+    }
+
+    /// Suggestions of a document, oldest first, optionally filtered by status
+    pub async fn list_by_document(&self, document_id: Uuid, status: Option<&str>) -> Result<Vec<Suggestion>> {
+        let suggestions = sqlx::query_as::<_, Suggestion>(&format!(
+            r#"
+            SELECT {}
+            FROM document_suggestions
+            WHERE document_id = $1 AND ($2::text IS NULL OR status = $2)
+            ORDER BY created_at
+            "#,
+            SUGGESTION_COLUMNS
+        ))
+        .bind(document_id)
+        .bind(status)
+        .fetch_all(self.pool.as_ref())
+        .await?;
+
+        Ok(suggestions)
+    }
+
+    /// Record the decision on a pending suggestion, None if it was already decided
+    pub async fn decide(&self, suggestion_id: Uuid, status: &str, decided_by: Option<Uuid>) -> Result<Option<Suggestion>> {
+        let suggestion = sqlx::query_as::<_, Suggestion>(&format!(
+            r#"
+            UPDATE document_suggestions
+            SET status = $2, decided_by = $3, decided_at = NOW()
+            WHERE id = $1 AND status = $4
+            RETURNING {}
+            "#,
+            SUGGESTION_COLUMNS
+        ))
+        .bind(suggestion_id)
+        .bind(status)
+        .bind(decided_by)
+        .bind(SUGGESTION_STATUS_PENDING)
+        .fetch_optional(self.pool.as_ref())
+        .await?;
+
+        Ok(suggestion)
+    }
+
+    /// Return a suggestion to pending after its decision could not be carried out
+    pub async fn reset_pending(&self, suggestion_id: Uuid) -> Result<()> {
+        sqlx::query("UPDATE document_suggestions SET status = $2, decided_by = NULL, decided_at = NULL WHERE id = $1")
+            .bind(suggestion_id)
+            .bind(SUGGESTION_STATUS_PENDING)
+            .execute(self.pool.as_ref())
+            .await?;
+
+        Ok(())
+    }
+}
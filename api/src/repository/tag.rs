@@ -368,6 +368,7 @@ impl TagRepository {
             WHERE LOWER(t.name) = LOWER($1)
                 AND (d.owner_id = $2 OR d.visibility = 'public')
                 AND d.type != 'scrap'
+                AND d.deleted_at IS NULL
             ORDER BY d.created_at DESC
             LIMIT $3 OFFSET $4
             "#,
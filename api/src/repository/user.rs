@@ -151,14 +151,15 @@ impl UserRepository {
         Ok(exists)
     }
     
-    pub async fn save_refresh_token(&self, user_id: Uuid, token: &str, expires_at: DateTime<Utc>) -> Result<()> {
+    pub async fn save_refresh_token(&self, user_id: Uuid, token: &str, session_id: Option<Uuid>, expires_at: DateTime<Utc>) -> Result<()> {
         sqlx::query!(
             r#"
-            INSERT INTO refresh_tokens (user_id, token, expires_at)
-            VALUES ($1, $2, $3)
+            INSERT INTO refresh_tokens (user_id, token, session_id, expires_at)
+            VALUES ($1, $2, $3, $4)
             "#,
             user_id,
             token,
+            session_id,
             expires_at
         )
         .execute(self.pool.as_ref())
@@ -167,12 +168,14 @@ impl UserRepository {
         Ok(())
     }
     
-    pub async fn validate_refresh_token(&self, token: &str) -> Result<Uuid> {
+    /// Owner and login session of a valid refresh token
+    pub async fn validate_refresh_token(&self, token: &str) -> Result<(Uuid, Option<Uuid>)> {
         let result = sqlx::query!(
             r#"
-            SELECT user_id
-            FROM refresh_tokens
-            WHERE token = $1 AND expires_at > NOW()
+            SELECT rt.user_id, rt.session_id
+            FROM refresh_tokens rt
+            LEFT JOIN auth_sessions s ON s.id = rt.session_id
+            WHERE rt.token = $1 AND rt.expires_at > NOW() AND s.revoked_at IS NULL
             "#,
             token
         )
@@ -183,16 +186,33 @@ impl UserRepository {
             _ => e.into(),
         })?;
         
-        Ok(result.user_id)
+        Ok((result.user_id, result.session_id))
+    }
+    
+    /// Delete a refresh token of a user, returning its login session
+    pub async fn delete_refresh_token(&self, user_id: Uuid, token: &str) -> Result<Option<Uuid>> {
+        let session_id = sqlx::query_scalar!(
+            r#"
+            DELETE FROM refresh_tokens
+            WHERE token = $1 AND user_id = $2
+            RETURNING session_id
+            "#,
+            token,
+            user_id
+        )
+        .fetch_optional(self.pool.as_ref())
+        .await?;
+        
+        Ok(session_id.flatten())
     }
     
-    pub async fn delete_refresh_token(&self, token: &str) -> Result<()> {
+    pub async fn delete_user_refresh_tokens(&self, user_id: Uuid) -> Result<()> {
         sqlx::query!(
             r#"
             DELETE FROM refresh_tokens
-            WHERE token = $1
+            WHERE user_id = $1
             "#,
-            token
+            user_id
         )
         .execute(self.pool.as_ref())
         .await?;
@@ -200,12 +220,41 @@ impl UserRepository {
         Ok(())
     }
     
-    pub async fn delete_user_refresh_tokens(&self, user_id: Uuid) -> Result<()> {
+    pub async fn create_session(&self, user_id: Uuid) -> Result<Uuid> {
+        let session_id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO auth_sessions (user_id)
+            VALUES ($1)
+            RETURNING id
+            "#,
+            user_id
+        )
+        .fetch_one(self.pool.as_ref())
+        .await?;
+        
+        Ok(session_id)
+    }
+    
+    /// End a login session and drop the refresh tokens issued for it
+    pub async fn revoke_session(&self, user_id: Uuid, session_id: Uuid) -> Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE auth_sessions
+            SET revoked_at = NOW()
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+            "#,
+            session_id,
+            user_id
+        )
+        .execute(self.pool.as_ref())
+        .await?;
+        
         sqlx::query!(
             r#"
             DELETE FROM refresh_tokens
-            WHERE user_id = $1
+            WHERE session_id = $1 AND user_id = $2
             "#,
+            session_id,
             user_id
         )
         .execute(self.pool.as_ref())
@@ -213,4 +262,40 @@ impl UserRepository {
         
         Ok(())
     }
+    
+    pub async fn revoke_user_sessions(&self, user_id: Uuid) -> Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE auth_sessions
+            SET revoked_at = NOW()
+            WHERE user_id = $1 AND revoked_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(self.pool.as_ref())
+        .await?;
+        
+        Ok(())
+    }
+    
+    /// Whether tokens of a login session are still accepted. Tokens issued before
+    /// sessions were tracked carry none and stay valid until they expire.
+    pub async fn is_session_active(&self, session_id: Option<Uuid>) -> Result<bool> {
+        let Some(session_id) = session_id else {
+            return Ok(true);
+        };
+        
+        let active = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM auth_sessions WHERE id = $1 AND revoked_at IS NULL
+            ) as "active!"
+            "#,
+            session_id
+        )
+        .fetch_one(self.pool.as_ref())
+        .await?;
+        
+        Ok(active)
+    }
 }
\ No newline at end of file
@@ -45,12 +45,13 @@ impl AuthService {
         // Create user
         let mut user = self.user_repo.create(email, name, &password_hash, &username).await?;
         
-        // Generate tokens
-        let tokens = self.jwt_service.generate_token_pair(user.id, user.email.clone())?;
+        // Start a login session and issue its tokens
+        let session_id = self.user_repo.create_session(user.id).await?;
+        let tokens = self.jwt_service.generate_token_pair(user.id, user.email.clone(), session_id)?;
         
         // Save refresh token
         let expires_at = Utc::now() + Duration::days(7);
-        self.user_repo.save_refresh_token(user.id, &tokens.refresh_token, expires_at).await?;
+        self.user_repo.save_refresh_token(user.id, &tokens.refresh_token, Some(session_id), expires_at).await?;
         
         // Clear password hash from response
         user.password_hash = String::new();
@@ -67,12 +68,13 @@ impl AuthService {
         verify_password(password, &user.password_hash)
             .map_err(|_| Error::Unauthorized)?;
         
-        // Generate tokens
-        let tokens = self.jwt_service.generate_token_pair(user.id, user.email.clone())?;
+        // Start a login session and issue its tokens
+        let session_id = self.user_repo.create_session(user.id).await?;
+        let tokens = self.jwt_service.generate_token_pair(user.id, user.email.clone(), session_id)?;
         
         // Save refresh token
         let expires_at = Utc::now() + Duration::days(7);
-        self.user_repo.save_refresh_token(user.id, &tokens.refresh_token, expires_at).await?;
+        self.user_repo.save_refresh_token(user.id, &tokens.refresh_token, Some(session_id), expires_at).await?;
         
         // Clear password hash from response
         user.password_hash = String::new();
@@ -82,36 +84,55 @@ impl AuthService {
     
     pub async fn refresh_token(&self, refresh_token: &str) -> Result<TokenPair> {
         // Validate refresh token
-        let user_id = self.user_repo.validate_refresh_token(refresh_token).await?;
+        let (user_id, session_id) = self.user_repo.validate_refresh_token(refresh_token).await?;
         
         // Get user
         let user = self.user_repo.get_by_id(user_id).await?;
         
+        // Refreshed tokens stay in the login session; tokens from before sessions start one
+        let session_id = match session_id {
+            Some(session_id) => session_id,
+            None => self.user_repo.create_session(user.id).await?,
+        };
+        
         // Generate new tokens
-        let tokens = self.jwt_service.generate_token_pair(user.id, user.email)?;
+        let tokens = self.jwt_service.generate_token_pair(user.id, user.email, session_id)?;
         
         // Delete old refresh token
-        self.user_repo.delete_refresh_token(refresh_token).await?;
+        self.user_repo.delete_refresh_token(user.id, refresh_token).await?;
         
         // Save new refresh token
         let expires_at = Utc::now() + Duration::days(7);
-        self.user_repo.save_refresh_token(user.id, &tokens.refresh_token, expires_at).await?;
+        self.user_repo.save_refresh_token(user.id, &tokens.refresh_token, Some(session_id), expires_at).await?;
         
         Ok(tokens)
     }
     
-    pub async fn logout(&self, user_id: Uuid, refresh_token: Option<&str>) -> Result<()> {
+    /// End the login session of the refresh token and of the access token used to log out,
+    /// or every session of the user without a refresh token.
+    ///
+    /// Returns the sessions that were ended, empty when they are not known.
+    pub async fn logout(&self, user_id: Uuid, refresh_token: Option<&str>, session_id: Option<Uuid>) -> Result<Vec<Uuid>> {
+        let mut sessions = Vec::new();
+        
         match refresh_token {
             Some(token) => {
                 // Delete specific refresh token
-                self.user_repo.delete_refresh_token(token).await?
+                let token_session = self.user_repo.delete_refresh_token(user_id, token).await?;
+                for session_id in [token_session, session_id].into_iter().flatten() {
+                    if !sessions.contains(&session_id) {
+                        self.user_repo.revoke_session(user_id, session_id).await?;
+                        sessions.push(session_id);
+                    }
+                }
             }
             None => {
                 // Delete all user's refresh tokens
-                self.user_repo.delete_user_refresh_tokens(user_id).await?
+                self.user_repo.delete_user_refresh_tokens(user_id).await?;
+                self.user_repo.revoke_user_sessions(user_id).await?;
             }
         }
         
-        Ok(())
+        Ok(sessions)
     }
 }
\ No newline at end of file
@@ -0,0 +1,417 @@
+use std::sync::Arc;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+use tokio::sync::{mpsc, Mutex, Notify};
+use uuid::Uuid;
+use yrs::sync::AwarenessUpdate;
+use yrs::updates::decoder::Decode;
+use yrs::updates::encoder::Encode;
+
+use crate::crdt::{AwarenessManager, DocumentManager};
+use crate::error::Result;
+use crate::socketio::{Revocation, SessionRevoker, SocketBroadcaster, broadcaster::UserEvent, connection_tracker::ConnectionTracker};
+
+/// Postgres channel shared by all API replicas
+const CLUSTER_CHANNEL: &str = "refmd_collab";
+/// Payloads above this size go through `cluster_messages`; NOTIFY payloads are capped at 8000 bytes
+const MAX_INLINE_PAYLOAD: usize = 6000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClusterMessageKind {
+    Update,
+    Awareness,
+    /// JSON-encoded `Revocation` of live sessions
+    Revocation,
+    /// JSON-encoded `UserEvent` for a user's sockets
+    UserEvent,
+}
+
+/// Message sent on the collaboration channel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ClusterNotification {
+    node_id: Uuid,
+    document_id: Uuid,
+    kind: ClusterMessageKind,
+    /// Base64 payload, when small enough to travel inline
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<String>,
+    /// Row of `cluster_messages` holding a larger payload
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    message_id: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct OutboundMessage {
+    document_id: Uuid,
+    kind: ClusterMessageKind,
+    data: Vec<u8>,
+}
+
+/// Queues local CRDT and awareness updates for the other API replicas.
+///
+/// Publishing never blocks the caller; a disabled publisher drops everything.
+#[derive(Clone, Default)]
+pub struct ClusterPublisher {
+    sender: Option<mpsc::UnboundedSender<OutboundMessage>>,
+}
+
+impl ClusterPublisher {
+    /// Create a publisher and the queue consumed by `ClusterSyncService`
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<OutboundMessage>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (Self { sender: Some(sender) }, receiver)
+    }
+
+    pub fn publish(&self, document_id: Uuid, kind: ClusterMessageKind, data: &[u8]) {
+        if let Some(sender) = &self.sender {
+            sender.send(OutboundMessage { document_id, kind, data: data.to_vec() }).ok();
+        }
+    }
+}
+
+/// Fans realtime collaboration out between API replicas over Postgres LISTEN/NOTIFY.
+///
+/// Every node publishes the updates and awareness changes its own clients produce.
+/// A node applies remote updates only to documents it holds in memory, so the node
+/// serving a document's clients always has the merged state; documents loaded later
+/// catch up from the update history. Awareness is relayed only for documents with
+/// local clients.
+#[derive(Clone)]
+pub struct ClusterSyncService {
+    node_id: Uuid,
+    pool: Arc<PgPool>,
+    document_manager: Arc<DocumentManager>,
+    awareness_manager: Arc<AwarenessManager>,
+    connection_tracker: Arc<ConnectionTracker>,
+    broadcaster: Arc<SocketBroadcaster>,
+    session_revoker: Arc<SessionRevoker>,
+    outbound: Arc<Mutex<Option<mpsc::UnboundedReceiver<OutboundMessage>>>>,
+    shutdown: Arc<Notify>,
+    is_running: Arc<Mutex<bool>>,
+}
+
+impl ClusterSyncService {
+    pub fn new(
+        pool: Arc<PgPool>,
+        document_manager: Arc<DocumentManager>,
+        awareness_manager: Arc<AwarenessManager>,
+        connection_tracker: Arc<ConnectionTracker>,
+        broadcaster: Arc<SocketBroadcaster>,
+        session_revoker: Arc<SessionRevoker>,
+        outbound: mpsc::UnboundedReceiver<OutboundMessage>,
+    ) -> Self {
+        Self {
+            node_id: Uuid::new_v4(),
+            pool,
+            document_manager,
+            awareness_manager,
+            connection_tracker,
+            broadcaster,
+            session_revoker,
+            outbound: Arc::new(Mutex::new(Some(outbound))),
+            shutdown: Arc::new(Notify::new()),
+            is_running: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    pub fn node_id(&self) -> Uuid {
+        self.node_id
+    }
+
+    pub async fn start(&self) {
+        let mut is_running = self.is_running.lock().await;
+        if *is_running {
+            tracing::warn!("ClusterSyncService is already running");
+            return;
+        }
+
+        let Some(outbound) = self.outbound.lock().await.take() else {
+            tracing::warn!("ClusterSyncService cannot be restarted");
+            return;
+        };
+        *is_running = true;
+        drop(is_running);
+
+        let service = self.clone();
+        tokio::spawn(async move {
+            service.run_publish_loop(outbound).await;
+        });
+
+        let service = self.clone();
+        tokio::spawn(async move {
+            service.run_listen_loop().await;
+        });
+    }
+
+    pub async fn stop(&self) {
+        let mut is_running = self.is_running.lock().await;
+        *is_running = false;
+        self.shutdown.notify_waiters();
+    }
+
+    async fn run_publish_loop(&self, mut outbound: mpsc::UnboundedReceiver<OutboundMessage>) {
+        loop {
+            let message = tokio::select! {
+                _ = self.shutdown.notified() => break,
+                message = outbound.recv() => match message {
+                    Some(message) => message,
+                    None => break,
+                },
+            };
+
+            if let Err(e) = self.publish(message).await {
+                tracing::error!("[ClusterSync] Failed to publish message: {}", e);
+            }
+        }
+
+        tracing::info!("ClusterSyncService publisher stopping");
+    }
+
+    async fn publish(&self, message: OutboundMessage) -> Result<()> {
+        let mut notification = ClusterNotification {
+            node_id: self.node_id,
+            document_id: message.document_id,
+            kind: message.kind,
+            data: None,
+            message_id: None,
+        };
+
+        let encoded = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &message.data);
+        if encoded.len() <= MAX_INLINE_PAYLOAD {
+            notification.data = Some(encoded);
+            sqlx::query("SELECT pg_notify($1, $2)")
+                .bind(CLUSTER_CHANNEL)
+                .bind(serde_json::to_string(&notification)?)
+                .execute(&*self.pool)
+                .await?;
+            return Ok(());
+        }
+
+        // NOTIFY is delivered on commit, so listeners always find the row
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM cluster_messages WHERE created_at < NOW() - INTERVAL '5 minutes'")
+            .execute(&mut *tx)
+            .await?;
+        let message_id: i64 = sqlx::query_scalar("INSERT INTO cluster_messages (payload) VALUES ($1) RETURNING id")
+            .bind(&message.data)
+            .fetch_one(&mut *tx)
+            .await?;
+        notification.message_id = Some(message_id);
+        sqlx::query("SELECT pg_notify($1, $2)")
+            .bind(CLUSTER_CHANNEL)
+            .bind(serde_json::to_string(&notification)?)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn run_listen_loop(&self) {
+        let mut listener = loop {
+            match self.connect_listener().await {
+                Ok(listener) => break listener,
+                Err(e) => {
+                    tracing::error!("[ClusterSync] Failed to listen on '{}': {}", CLUSTER_CHANNEL, e);
+                    tokio::select! {
+                        _ = self.shutdown.notified() => return,
+                        _ = tokio::time::sleep(Duration::from_secs(5)) => {}
+                    }
+                }
+            }
+        };
+
+        tracing::info!("[ClusterSync] Node {} listening on '{}'", self.node_id, CLUSTER_CHANNEL);
+
+        loop {
+            let notification = tokio::select! {
+                _ = self.shutdown.notified() => break,
+                notification = listener.recv() => notification,
+            };
+
+            match notification {
+                Ok(notification) => {
+                    if let Err(e) = self.handle_notification(notification.payload()).await {
+                        tracing::warn!("[ClusterSync] Failed to handle notification: {}", e);
+                    }
+                }
+                Err(e) => {
+                    // The listener reconnects on its own; notifications sent meanwhile are lost
+                    tracing::error!("[ClusterSync] Listener error: {}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+        }
+
+        tracing::info!("ClusterSyncService listener stopping");
+    }
+
+    async fn connect_listener(&self) -> Result<PgListener> {
+        let mut listener = PgListener::connect_with(&self.pool).await?;
+        listener.listen(CLUSTER_CHANNEL).await?;
+        Ok(listener)
+    }
+
+    async fn handle_notification(&self, payload: &str) -> Result<()> {
+        let notification: ClusterNotification = serde_json::from_str(payload)?;
+        if notification.node_id == self.node_id {
+            return Ok(());
+        }
+
+        // Revocations concern sessions rather than documents and always travel inline
+        if notification.kind == ClusterMessageKind::Revocation {
+            if let Some(data) = notification.data {
+                let data = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, data)?;
+                let revocation: Revocation = serde_json::from_slice(&data)?;
+                self.session_revoker.apply(&revocation);
+            }
+            return Ok(());
+        }
+
+        // User events reach the user's sockets wherever they are connected
+        if notification.kind == ClusterMessageKind::UserEvent {
+            if let Some(data) = self.payload(&notification).await? {
+                let event: UserEvent = serde_json::from_slice(&data)?;
+                self.broadcaster.deliver_user_event(&event)?;
+            }
+            return Ok(());
+        }
+
+        let document_id = notification.document_id;
+        let has_clients = !self.connection_tracker.is_document_empty(document_id);
+        let cached = self.document_manager.peek(&document_id);
+        if cached.is_none() && !has_clients {
+            return Ok(());
+        }
+
+        let Some(data) = self.payload(&notification).await? else {
+            return Ok(());
+        };
+
+        match notification.kind {
+            ClusterMessageKind::Update => {
+                if let Some(doc) = cached {
+                    doc.write().apply_update(&data)?;
+                }
+                if has_clients {
+                    self.broadcaster.deliver_document_update(document_id, &data, None)?;
+                }
+            }
+            ClusterMessageKind::Revocation | ClusterMessageKind::UserEvent => {}
+            ClusterMessageKind::Awareness => {
+                if !has_clients {
+                    return Ok(());
+                }
+                // Remote clients are owned by their node, so its removal updates apply here too
+                let update = AwarenessUpdate::decode_v1(&data)?;
+                let awareness = self.awareness_manager.get_or_create(document_id);
+                let owner = format!("node:{}", notification.node_id);
+                if let Some(accepted) = awareness.apply_awareness_update(&owner, &update) {
+                    self.broadcaster.deliver_awareness(document_id, &accepted.encode_v1(), None)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Payload of a notification, inline or from `cluster_messages`
+    async fn payload(&self, notification: &ClusterNotification) -> Result<Option<Vec<u8>>> {
+        let data = match (&notification.data, notification.message_id) {
+            (Some(data), _) => base64::Engine::decode(&base64::engine::general_purpose::STANDARD, data)?,
+            (None, Some(message_id)) => {
+                sqlx::query_scalar::<_, Vec<u8>>("SELECT payload FROM cluster_messages WHERE id = $1")
+                    .bind(message_id)
+                    .fetch_one(&*self.pool)
+                    .await?
+            }
+            (None, None) => return Ok(None),
+        };
+        Ok(Some(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::CrdtDocument;
+    use crate::socketio::SessionRegistry;
+    use crate::test_support::{db_test, TestDb};
+    use crate::websocket::WebsocketPeers;
+
+    /// A node as wired up in `AppState`, with the publisher its local clients write through
+    fn node(pool: Arc<PgPool>) -> (ClusterPublisher, ClusterSyncService) {
+        let (publisher, outbound) = ClusterPublisher::channel();
+        let document_manager = Arc::new(DocumentManager::new());
+        let awareness_manager = Arc::new(AwarenessManager::new());
+        let connection_tracker = Arc::new(ConnectionTracker::new());
+        let websocket_peers = Arc::new(WebsocketPeers::new());
+        let broadcaster = Arc::new(SocketBroadcaster::new(websocket_peers.clone(), publisher.clone()));
+        let session_revoker = Arc::new(SessionRevoker::new(
+            Arc::new(SessionRegistry::new()),
+            websocket_peers,
+            connection_tracker.clone(),
+            awareness_manager.clone(),
+            broadcaster.clone(),
+        ));
+        let service = ClusterSyncService::new(
+            pool,
+            document_manager,
+            awareness_manager,
+            connection_tracker,
+            broadcaster,
+            session_revoker,
+            outbound,
+        );
+        (publisher, service)
+    }
+
+    async fn wait_for_content(service: &ClusterSyncService, document_id: Uuid, expected: &str) -> String {
+        let doc = service.document_manager.peek(&document_id).unwrap();
+        let mut content = String::new();
+        for _ in 0..50 {
+            content = doc.read().get_content().unwrap();
+            if content == expected {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        content
+    }
+
+    db_test! {
+        /// Two replicas against one Postgres: updates published on one node reach the
+        /// in-memory document of the other, both inline and through `cluster_messages`
+        async fn test_two_nodes_relay_updates() {
+            let pool = TestDb::connect().await.pool.clone();
+            let (publisher_a, node_a) = node(pool.clone());
+            let (_, node_b) = node(pool.clone());
+            node_a.start().await;
+            node_b.start().await;
+            tokio::time::sleep(Duration::from_millis(500)).await;
+
+            // Node B serves the document, node A receives an edit for it
+            let document_id = Uuid::new_v4();
+            node_b.document_manager.get_or_create(document_id);
+            let mut edited = CrdtDocument::new(document_id);
+
+            edited.set_content("hello from node A").unwrap();
+            publisher_a.publish(document_id, ClusterMessageKind::Update, &edited.get_state_as_update().unwrap());
+            assert_eq!(wait_for_content(&node_b, document_id, "hello from node A").await, "hello from node A");
+
+            let large = "x".repeat(MAX_INLINE_PAYLOAD * 2);
+            edited.set_content(&large).unwrap();
+            publisher_a.publish(document_id, ClusterMessageKind::Update, &edited.get_state_as_update().unwrap());
+            assert_eq!(wait_for_content(&node_b, document_id, &large).await.len(), large.len());
+
+            // Documents a node does not hold are left to load from history
+            assert!(node_a.document_manager.peek(&document_id).is_none());
+
+            node_a.stop().await;
+            node_b.stop().await;
+        }
+    }
+}
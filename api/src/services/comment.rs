@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::crdt::{CrdtDocument, TextAnchor, TextRange};
+use crate::entities::comment::{
+    Comment, AnchorRequest, CommentAuthor, CommentThread, CommentThreadResponse,
+    THREAD_STATUS_OPEN, THREAD_STATUS_RESOLVED,
+};
+use crate::error::{Error, Result};
+use crate::repository::{CommentRepository, UserRepository};
+use crate::services::crdt::CrdtService;
+use crate::socketio::crdt_sync::document_room;
+use crate::socketio::SocketBroadcaster;
+
+/// Event carrying the full state of a created or changed thread to the document room
+pub const COMMENT_THREAD_EVENT: &str = "comment:thread";
+
+const MAX_COMMENT_LENGTH: usize = 10_000;
+
+/// Comment threads anchored to ranges of document text.
+///
+/// Anchors are Yrs sticky indexes into the document's `content` text, so a thread keeps
+/// pointing at the same text however the document is edited around it.
+pub struct CommentService {
+    comment_repository: Arc<CommentRepository>,
+    user_repository: Arc<UserRepository>,
+    crdt_service: Arc<CrdtService>,
+    broadcaster: Arc<SocketBroadcaster>,
+}
+
+impl CommentService {
+    pub fn new(
+        comment_repository: Arc<CommentRepository>,
+        user_repository: Arc<UserRepository>,
+        crdt_service: Arc<CrdtService>,
+        broadcaster: Arc<SocketBroadcaster>,
+    ) -> Self {
+        Self {
+            comment_repository,
+            user_repository,
+            crdt_service,
+            broadcaster,
+        }
+    }
+
+    /// Author of a new comment: the user's name, or the name a guest gave
+    pub async fn author(&self, user_id: Option<Uuid>, author_name: Option<&str>) -> CommentAuthor {
+        if let Some(user_id) = user_id {
+            if let Ok(user) = self.user_repository.get_by_id(user_id).await {
+                return CommentAuthor { user_id: Some(user_id), name: user.name };
+            }
+        }
+
+        let name = author_name
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| name.chars().take(255).collect())
+            .unwrap_or_else(|| "Guest".to_string());
+        CommentAuthor { user_id, name }
+    }
+
+    /// Threads of a document with their comments and current ranges
+    pub async fn list_threads(&self, document_id: Uuid, include_resolved: bool) -> Result<Vec<CommentThreadResponse>> {
+        let threads = self.comment_repository.list_threads(document_id, include_resolved).await?;
+        self.with_comments(document_id, threads).await
+    }
+
+    pub async fn get_thread(&self, document_id: Uuid, thread_id: Uuid) -> Result<CommentThreadResponse> {
+        let thread = self.find_thread(document_id, thread_id).await?;
+        let mut threads = self.with_comments(document_id, vec![thread]).await?;
+        Ok(threads.remove(0))
+    }
+
+    /// Start a thread on a range of the document
+    pub async fn create_thread(
+        &self,
+        document_id: Uuid,
+        anchor: &AnchorRequest,
+        body: &str,
+        author: &CommentAuthor,
+    ) -> Result<CommentThreadResponse> {
+        let body = validate_body(body)?;
+
+        let doc = self.crdt_service.load_or_create_document(document_id).await?;
+        let (anchor, quote) = {
+            let doc = doc.write();
+            resolve_anchor(&doc, anchor)?
+        };
+        let (start, end) = anchor.encode();
+
+        let (thread, _) = self.comment_repository
+            .create_thread(document_id, &start, &end, &quote, author, body)
+            .await?;
+
+        self.publish(document_id, thread.id).await
+    }
+
+    /// Add a comment to an existing thread
+    pub async fn reply(
+        &self,
+        document_id: Uuid,
+        thread_id: Uuid,
+        body: &str,
+        author: &CommentAuthor,
+    ) -> Result<CommentThreadResponse> {
+        let body = validate_body(body)?;
+        self.find_thread(document_id, thread_id).await?;
+        self.comment_repository.add_comment(thread_id, author, body).await?;
+
+        self.publish(document_id, thread_id).await
+    }
+
+    /// Resolve or reopen a thread
+    pub async fn set_resolved(
+        &self,
+        document_id: Uuid,
+        thread_id: Uuid,
+        resolved: bool,
+        user_id: Option<Uuid>,
+    ) -> Result<CommentThreadResponse> {
+        self.find_thread(document_id, thread_id).await?;
+        let status = if resolved { THREAD_STATUS_RESOLVED } else { THREAD_STATUS_OPEN };
+        self.comment_repository.set_status(thread_id, status, user_id).await?;
+
+        self.publish(document_id, thread_id).await
+    }
+
+    async fn find_thread(&self, document_id: Uuid, thread_id: Uuid) -> Result<CommentThread> {
+        self.comment_repository
+            .get_thread(document_id, thread_id)
+            .await?
+            .ok_or_else(|| Error::NotFound("Comment thread not found".to_string()))
+    }
+
+    /// Send the current state of a thread to everyone in the document
+    async fn publish(&self, document_id: Uuid, thread_id: Uuid) -> Result<CommentThreadResponse> {
+        let thread = self.get_thread(document_id, thread_id).await?;
+        self.broadcaster.emit_to_room(document_room(document_id), COMMENT_THREAD_EVENT, &thread)?;
+        Ok(thread)
+    }
+
+    async fn with_comments(&self, document_id: Uuid, threads: Vec<CommentThread>) -> Result<Vec<CommentThreadResponse>> {
+        let thread_ids: Vec<Uuid> = threads.iter().map(|thread| thread.id).collect();
+        let mut comments: HashMap<Uuid, Vec<Comment>> = HashMap::new();
+        for comment in self.comment_repository.list_comments(&thread_ids).await? {
+            comments.entry(comment.thread_id).or_default().push(comment);
+        }
+
+        let doc = self.crdt_service.load_or_create_document(document_id).await?;
+        let doc = doc.read();
+        let content = doc.get_content()?;
+
+        Ok(threads
+            .into_iter()
+            .map(|thread| {
+                let range = current_range(&doc, &content, &thread);
+                CommentThreadResponse {
+                    anchor_start: base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &thread.anchor_start),
+                    anchor_end: base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &thread.anchor_end),
+                    comments: comments.remove(&thread.id).unwrap_or_default(),
+                    range,
+                    thread,
+                }
+            })
+            .collect())
+    }
+}
+
+fn validate_body(body: &str) -> Result<&str> {
+    let body = body.trim();
+    if body.is_empty() {
+        return Err(Error::BadRequest("Comment cannot be empty".to_string()));
+    }
+    if body.chars().count() > MAX_COMMENT_LENGTH {
+        return Err(Error::BadRequest(format!("Comment cannot be longer than {} characters", MAX_COMMENT_LENGTH)));
+    }
+    Ok(body)
+}
+
+/// Turn a requested anchor into a text anchor and the text it encloses
+fn resolve_anchor(doc: &CrdtDocument, anchor: &AnchorRequest) -> Result<(TextAnchor, String)> {
+    let content = doc.get_content()?;
+    let invalid = || Error::BadRequest("Comment anchor does not cover any text".to_string());
+
+    let anchor = match anchor {
+        AnchorRequest::Relative { start, end } => TextAnchor::from_base64(start, end)?,
+        AnchorRequest::Offsets { start_index, end_index } => {
+            TextAnchor::from_chars(doc, &content, *start_index, *end_index).ok_or_else(invalid)?
+        }
+    };
+
+    let quote = anchor
+        .text_range(doc, &content)
+        .filter(|range| range.start < range.end)
+        .ok_or_else(invalid)?
+        .text;
+    Ok((anchor, quote))
+}
+
+/// Where a thread's anchor currently is, None once the anchored text is gone
+fn current_range(doc: &CrdtDocument, content: &str, thread: &CommentThread) -> Option<TextRange> {
+    TextAnchor::decode(&thread.anchor_start, &thread.anchor_end)?
+        .text_range(doc, content)
+        .filter(|range| range.start < range.end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anchor_requires_text() {
+        let mut doc = CrdtDocument::new_with_content(Uuid::new_v4());
+        doc.set_content("abc").unwrap();
+
+        assert!(resolve_anchor(&doc, &AnchorRequest::Offsets { start_index: 1, end_index: 1 }).is_err());
+        assert!(resolve_anchor(&doc, &AnchorRequest::Offsets { start_index: 1, end_index: 4 }).is_err());
+        assert!(resolve_anchor(&doc, &AnchorRequest::Offsets { start_index: 0, end_index: 3 }).is_ok());
+        assert!(validate_body("  ").is_err());
+    }
+}
@@ -84,15 +84,15 @@ impl CrdtService {
         update: &[u8],
         tx: &mut Transaction<'_, Postgres>,
     ) -> Result<()> {
-        // Apply to in-memory document
-        let doc = self.document_manager.get_or_create(document_id);
+        // Apply to in-memory document (reloading it if it was evicted)
+        let doc = self.load_or_create_document(document_id).await?;
         {
             let mut doc = doc.write();
             doc.apply_update(update)?;
         }
 
         // Save update to history
-        self.document_persistence.save_update(document_id, update, tx).await?;
+        self.document_persistence.save_update(document_id, update, None, tx).await?;
 
         Ok(())
     }
@@ -112,6 +112,7 @@ impl CrdtService {
         &self, 
         document_id: Uuid, 
         content: &str,
+        author: Option<Uuid>,
     ) -> Result<Vec<u8>> {
         tracing::info!("Setting content for document {}: {} chars", document_id, content.len());
         let doc = self.load_or_create_document(document_id).await?;
@@ -127,6 +128,9 @@ impl CrdtService {
             doc.get_update_since(&state_before)?
         };
 
+        // Record the update so history replay includes server-side edits
+        self.document_persistence.save_update_auto(document_id, &update, author).await?;
+
         // Save the document state
         tracing::info!("Saving document state to database");
         self.save_document(document_id).await?;
@@ -135,13 +139,55 @@ impl CrdtService {
         Ok(update)
     }
 
+    /// Edit the document in place, recording and returning the resulting update.
+    ///
+    /// The edit runs under the document's write lock, so it sees no concurrent changes
+    /// between reading positions and applying them.
+    pub async fn edit_document<F>(&self, document_id: Uuid, author: Option<Uuid>, edit: F) -> Result<Vec<u8>>
+    where
+        F: FnOnce(&mut CrdtDocument) -> Result<()>,
+    {
+        let doc = self.load_or_create_document(document_id).await?;
+
+        let update = {
+            let mut doc = doc.write();
+            let state_before = doc.get_state_vector();
+            edit(&mut doc)?;
+            doc.get_update_since(&state_before)?
+        };
+
+        self.document_persistence.save_update_auto(document_id, &update, author).await?;
+        self.save_document(document_id).await?;
+
+        Ok(update)
+    }
+
+    /// Apply an update sent by a client outside a live session and persist it, attributed
+    /// to `author`. Broadcasting it to connected clients is left to the caller.
+    pub async fn apply_client_update(&self, document_id: Uuid, update: &[u8], author: Option<Uuid>) -> Result<()> {
+        self.persist_and_apply_update(document_id, update, author).await?;
+        self.save_document(document_id).await
+    }
+
+    /// Record a client update in the history, attributed to `author`, then apply it to
+    /// the cached document. Updates rejected by persistence, such as those to a trashed
+    /// document, never reach the cached state.
+    pub async fn persist_and_apply_update(&self, document_id: Uuid, update: &[u8], author: Option<Uuid>) -> Result<()> {
+        self.document_persistence.save_update_auto(document_id, update, author).await?;
+
+        let doc = self.load_or_create_document(document_id).await?;
+        doc.write().apply_update(update)?;
+        Ok(())
+    }
+
     /// Update document content (alias for set_document_content without returning update)
     pub async fn update_document_content(
         &self,
         document_id: Uuid,
         content: &str,
+        author: Option<Uuid>,
     ) -> Result<()> {
-        self.set_document_content(document_id, content).await?;
+        self.set_document_content(document_id, content, author).await?;
         Ok(())
     }
 
@@ -181,6 +227,33 @@ impl CrdtService {
         self.awareness_manager.cleanup_all_inactive_users()
     }
 
+    /// Persist a cached document and drop it from the cache.
+    ///
+    /// The document is kept if it is modified or referenced elsewhere while flushing.
+    /// Returns whether it was evicted.
+    pub async fn flush_and_evict(&self, document_id: Uuid) -> Result<bool> {
+        let Some(doc) = self.document_manager.peek(&document_id) else {
+            return Ok(false);
+        };
+
+        let (state, modified_at) = {
+            let doc = doc.read();
+            (doc.get_state_as_update()?, doc.last_modified())
+        };
+        drop(doc);
+
+        let temp_doc = CrdtDocument::from_state(document_id, &state)?;
+        self.document_persistence.save_document(&temp_doc).await?;
+        self.document_persistence.sync_to_documents_table(&temp_doc).await?;
+
+        if self.document_manager.remove_if_unchanged(&document_id, modified_at) {
+            self.awareness_manager.remove(&document_id);
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
     /// Remove document from cache
     pub fn evict_from_cache(&self, document_id: &Uuid) {
         self.document_manager.remove(document_id);
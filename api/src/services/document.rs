@@ -8,8 +8,11 @@ use crate::{
     db::models::Document,
     services::crdt::CrdtService,
     services::git_batch_sync::GitBatchSyncService,
-    services::document_links::DocumentLinksService,
+    services::document_links::{DocumentLinksService, RenameLinksReport},
     services::file::FileService,
+    services::file_watcher::OwnFileWrites,
+    entities::trash::TrashEntry,
+    socketio::{Revocation, SessionRevoker},
     services::tag_parser::TagParser,
     repository::tag::TagRepository,
     config::Config,
@@ -24,6 +27,8 @@ pub struct DocumentService {
     document_links_service: Option<Arc<DocumentLinksService>>,
     file_service: Option<Arc<FileService>>,
     tag_repository: Option<Arc<TagRepository>>,
+    own_writes: Option<Arc<OwnFileWrites>>,
+    session_revoker: Option<Arc<SessionRevoker>>,
 }
 
 impl DocumentService {
@@ -43,6 +48,8 @@ impl DocumentService {
             document_links_service: None,
             file_service: None,
             tag_repository: None,
+            own_writes: None,
+            session_revoker: None,
         }
     }
     
@@ -61,6 +68,16 @@ impl DocumentService {
         self
     }
     
+    pub fn with_own_writes(mut self, own_writes: Arc<OwnFileWrites>) -> Self {
+        self.own_writes = Some(own_writes);
+        self
+    }
+    
+    pub fn with_session_revoker(mut self, session_revoker: Arc<SessionRevoker>) -> Self {
+        self.session_revoker = Some(session_revoker);
+        self
+    }
+    
     pub async fn create_document(&self, owner_id: Uuid, title: &str, content: Option<&str>, doc_type: &str, parent_id: Option<Uuid>) -> Result<Document> {
         if title.trim().is_empty() {
             return Err(Error::BadRequest("Title cannot be empty".to_string()));
@@ -78,6 +95,8 @@ impl DocumentService {
             self.save_to_file(&document).await?;
         }
         
+        self.bind_dangling_links(&document).await;
+        
         Ok(document)
     }
     
@@ -95,7 +114,9 @@ impl DocumentService {
         self.document_repo.list_by_owner(user_id).await
     }
     
-    pub async fn update_document(&self, id: Uuid, user_id: Uuid, title: Option<&str>, content: Option<&str>, parent_id: Option<Uuid>) -> Result<Document> {
+    /// Update a document's title, content or parent. On a rename, links using the old
+    /// title are rewritten and the report of rewritten documents is returned with it.
+    pub async fn update_document(&self, id: Uuid, user_id: Uuid, title: Option<&str>, content: Option<&str>, parent_id: Option<Uuid>) -> Result<(Document, Option<RenameLinksReport>)> {
         // Validate title if provided
         if let Some(t) = title {
             if t.trim().is_empty() {
@@ -135,23 +156,152 @@ impl DocumentService {
             }
         }
         
-        Ok(updated_document)
+        let mut renamed_links = None;
+        if updated_document.title != old_document.title {
+            renamed_links = self.rewrite_links_on_rename(&updated_document, &old_document.title, user_id).await;
+            self.bind_dangling_links(&updated_document).await;
+        }
+        
+        Ok((updated_document, renamed_links))
     }
     
+    /// Rewrite links using a renamed document's old title in the documents linking to it,
+    /// then refresh their files, links and tags
+    pub async fn rewrite_links_on_rename(&self, document: &Document, old_title: &str, user_id: Uuid) -> Option<RenameLinksReport> {
+        let links_service = self.document_links_service.as_ref()?;
+        let report = match links_service.update_links_on_rename(document.id, old_title, &document.title, user_id).await {
+            Ok(report) => report,
+            Err(e) => {
+                tracing::warn!("Failed to rewrite links to renamed document {}: {}", document.id, e);
+                return None;
+            }
+        };
+        
+        for rewrite in &report.updated {
+            match self.document_repo.get_by_id(rewrite.document_id).await {
+                Ok(Some(source)) => {
+                    if let Err(e) = self.save_to_file(&source).await {
+                        tracing::warn!("Failed to save document {} after rewriting links: {}", source.id, e);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Failed to load document {} after rewriting links: {}", rewrite.document_id, e),
+            }
+        }
+        
+        if !report.updated.is_empty() || !report.skipped.is_empty() {
+            tracing::info!(
+                "Rewrote links to renamed document {} in {} document(s), skipped {}",
+                document.id,
+                report.updated.len(),
+                report.skipped.len()
+            );
+        }
+        Some(report)
+    }
+    
+    /// Point links waiting for a document with this document's title at it
+    pub async fn bind_dangling_links(&self, document: &Document) {
+        if let Some(ref links_service) = self.document_links_service {
+            match links_service.bind_dangling_links(document.id, document.owner_id, &document.title).await {
+                Ok(0) => {}
+                Ok(count) => tracing::info!("Bound {} dangling link(s) to document {}", count, document.id),
+                Err(e) => tracing::warn!("Failed to bind dangling links to document {}: {}", document.id, e),
+            }
+        }
+    }
+    
+    /// Move a document, or a folder with everything in it, to the owner's trash.
+    ///
+    /// The rows, tree, links, tags and attachments are kept for a restore; only the
+    /// markdown files are removed from disk, and live editing sessions are ended.
     pub async fn delete_document(&self, id: Uuid, user_id: Uuid) -> Result<()> {
         // Check if user has permission to delete the document
         if !self.document_repo.has_permission(id, user_id, "admin").await? {
             return Err(Error::Forbidden);
         }
         
-        // Get the document to delete its file
-        if let Some(document) = self.document_repo.get_by_id(id).await? {
-            // Delete the file from filesystem
-            self.delete_file(&document).await?;
+        // For now, only allow owner to delete
+        let trashed = self.document_repo.trash(id, user_id, user_id).await?;
+        
+        for document in &trashed {
+            if document.r#type != "folder" {
+                if let Err(e) = self.delete_file(document).await {
+                    tracing::warn!("Failed to remove file of trashed document {}: {}", document.id, e);
+                }
+            }
+            if let Some(ref session_revoker) = self.session_revoker {
+                session_revoker.revoke(Revocation::Document { document_id: document.id });
+            }
         }
         
-        // For now, only allow owner to delete
-        self.document_repo.delete(id, user_id).await
+        tracing::info!("Moved {} item(s) to the trash for document {}", trashed.len(), id);
+        Ok(())
+    }
+    
+    /// Trash entries of a user, with their expiry when trash retention is configured
+    pub async fn list_trash(&self, user_id: Uuid) -> Result<Vec<TrashEntry>> {
+        let retention_days = self.config.trash_retention_days;
+        let mut entries = self.document_repo.list_trash(user_id).await?;
+        if retention_days > 0 {
+            for entry in &mut entries {
+                entry.expires_at = Some(entry.deleted_at + chrono::Duration::days(retention_days));
+            }
+        }
+        Ok(entries)
+    }
+    
+    /// Restore a trash entry to its original folder and write its files back
+    pub async fn restore_document(&self, id: Uuid, user_id: Uuid) -> Result<Document> {
+        let restored = self.document_repo.restore(id, user_id).await?;
+        
+        for document in restored.iter().filter(|document| document.r#type != "folder") {
+            if let Err(e) = self.save_to_file(document).await {
+                tracing::warn!("Failed to write file of restored document {}: {}", document.id, e);
+            }
+        }
+        for document in &restored {
+            self.bind_dangling_links(document).await;
+        }
+        
+        tracing::info!("Restored {} item(s) from the trash for document {}", restored.len(), id);
+        self.document_repo.get_by_id(id).await?
+            .ok_or_else(|| Error::NotFound("Document not found".to_string()))
+    }
+    
+    /// Permanently delete a trash entry, returning the number of documents and folders removed
+    pub async fn purge_document(&self, id: Uuid, user_id: Uuid) -> Result<usize> {
+        let purged = self.document_repo.purge(id, user_id).await?;
+        for document_id in &purged {
+            // Connections cannot normally reach a trashed document, but any left are closed
+            if let Some(ref session_revoker) = self.session_revoker {
+                session_revoker.revoke(Revocation::Document { document_id: *document_id });
+            }
+            self.crdt_service.evict_from_cache(document_id);
+        }
+        Ok(purged.len())
+    }
+    
+    /// Permanently delete everything in a user's trash
+    pub async fn empty_trash(&self, user_id: Uuid) -> Result<usize> {
+        let mut purged = 0;
+        for entry in self.document_repo.list_trash(user_id).await? {
+            purged += self.purge_document(entry.id, user_id).await?;
+        }
+        Ok(purged)
+    }
+    
+    /// Permanently delete trash entries older than the retention period
+    pub async fn purge_expired_trash(&self) -> Result<usize> {
+        let cutoff = chrono::Utc::now() - chrono::Duration::days(self.config.trash_retention_days);
+        let mut purged = 0;
+        for (id, owner_id) in self.document_repo.list_trash_before(cutoff).await? {
+            match self.purge_document(id, owner_id).await {
+                Ok(count) => purged += count,
+                Err(e) => tracing::error!("Failed to purge expired trash entry {}: {}", id, e),
+            }
+        }
+        Ok(purged)
     }
     
     // Generate a file path for a document based on its hierarchy
@@ -192,30 +342,7 @@ impl DocumentService {
     
     // Sanitize filename to be filesystem-safe
     fn sanitize_filename(&self, name: &str) -> String {
-        let mut sanitized = name.trim().to_string();
-        
-        // Replace problematic characters
-        let invalid_chars = ['/', '\\', ':', '*', '?', '"', '<', '>', '|', '\0'];
-        for &ch in &invalid_chars {
-            sanitized = sanitized.replace(ch, "-");
-        }
-        
-        // Replace multiple spaces/dashes with single dash
-        while sanitized.contains("--") {
-            sanitized = sanitized.replace("--", "-");
-        }
-        
-        // Limit length
-        if sanitized.len() > 100 {
-            sanitized.truncate(100);
-        }
-        
-        // Default name if empty
-        if sanitized.is_empty() {
-            sanitized = "untitled".to_string();
-        }
-        
-        sanitized
+        sanitize_filename(name)
     }
     
     // Save document content to file
@@ -225,6 +352,11 @@ impl DocumentService {
             return Ok(());
         }
         
+        // Trashed documents have no file until they are restored
+        if self.document_repo.is_trashed(document.id).await? {
+            return Ok(());
+        }
+        
         tracing::info!("Saving document {} with provided content: {} chars", document.id, content.len());
         
         // Generate file path
@@ -271,6 +403,11 @@ updated_at: {}
             )
         };
         
+        // Let the file watcher know this write is ours
+        if let Some(ref own_writes) = self.own_writes {
+            own_writes.record_write(&file_path, formatted_content.as_bytes());
+        }
+        
         // Write to file with retry
         tracing::info!("Writing to file: {:?}", file_path);
         let mut retries = 3;
@@ -310,7 +447,7 @@ updated_at: {}
         // Queue for batch git sync if enabled
         if self.config.git_auto_sync {
             if let Some(ref batch_sync) = self.git_batch_sync_service {
-                batch_sync.queue_sync(document.owner_id, document.title.clone()).await;
+                batch_sync.queue_sync(document.owner_id, &relative_path, document.title.clone()).await;
             }
         }
         
@@ -342,6 +479,11 @@ updated_at: {}
             return Ok(());
         }
         
+        // Trashed documents have no file until they are restored
+        if self.document_repo.is_trashed(document.id).await? {
+            return Ok(());
+        }
+        
         // Get the content from CRDT
         tracing::info!("Getting content from CRDT for document {}", document.id);
         let content = self.crdt_service.get_document_content(document.id).await?;
@@ -391,6 +533,11 @@ updated_at: {}
             )
         };
         
+        // Let the file watcher know this write is ours
+        if let Some(ref own_writes) = self.own_writes {
+            own_writes.record_write(&file_path, formatted_content.as_bytes());
+        }
+        
         // Write to file with retry
         tracing::info!("Writing to file: {:?}", file_path);
         let mut retries = 3;
@@ -430,7 +577,7 @@ updated_at: {}
         // Queue for batch git sync if enabled
         if self.config.git_auto_sync {
             if let Some(ref batch_sync) = self.git_batch_sync_service {
-                batch_sync.queue_sync(document.owner_id, document.title.clone()).await;
+                batch_sync.queue_sync(document.owner_id, &relative_path, document.title.clone()).await;
             }
         }
         
@@ -460,12 +607,15 @@ updated_at: {}
         if let Some(file_path) = &document.file_path {
             let full_path = self.upload_dir.join(file_path);
             if full_path.exists() {
+                if let Some(ref own_writes) = self.own_writes {
+                    own_writes.record_removal(&full_path);
+                }
                 fs::remove_file(full_path).await?;
                 
                 // Queue deletion for batch git sync if enabled
                 if self.config.git_auto_sync {
                     if let Some(ref batch_sync) = self.git_batch_sync_service {
-                        batch_sync.queue_sync(document.owner_id, format!("Delete: {}", document.title)).await;
+                        batch_sync.queue_sync(document.owner_id, file_path, format!("Delete: {}", document.title)).await;
                     }
                 }
             }
@@ -474,7 +624,7 @@ updated_at: {}
     }
     
     // Move file when document is moved or renamed
-    async fn move_file(&self, document: &Document, old_path: Option<&str>) -> Result<()> {
+    pub async fn move_file(&self, document: &Document, old_path: Option<&str>) -> Result<()> {
         if document.r#type == "folder" {
             // For folders, we need to move all child documents and their attachments
             if let Some(old_file_path) = old_path {
@@ -518,6 +668,11 @@ updated_at: {}
                 }
                 
                 // Move the document file
+                if let Some(ref own_writes) = self.own_writes {
+                    let contents = fs::read(&old_full_path).await?;
+                    own_writes.record_removal(&old_full_path);
+                    own_writes.record_write(&new_file_path, &contents);
+                }
                 fs::rename(&old_full_path, &new_file_path).await?;
                 
                 // Move attachments if FileService is available
@@ -546,7 +701,9 @@ updated_at: {}
                 // Queue move for batch git sync if enabled
                 if self.config.git_auto_sync {
                     if let Some(ref batch_sync) = self.git_batch_sync_service {
-                        batch_sync.queue_sync(document.owner_id, format!("Move/rename: {}", document.title)).await;
+                        // The file may have left the repository of its old folder
+                        batch_sync.queue_sync(document.owner_id, old_file_path, format!("Move/rename: {}", document.title)).await;
+                        batch_sync.queue_sync(document.owner_id, &relative_path, format!("Move/rename: {}", document.title)).await;
                     }
                 }
             }
@@ -557,4 +714,32 @@ updated_at: {}
         
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Sanitize a document or folder title into the name used on disk
+pub fn sanitize_filename(name: &str) -> String {
+    let mut sanitized = name.trim().to_string();
+    
+    // Replace problematic characters
+    let invalid_chars = ['/', '\\', ':', '*', '?', '"', '<', '>', '|', '\0'];
+    for &ch in &invalid_chars {
+        sanitized = sanitized.replace(ch, "-");
+    }
+    
+    // Replace multiple spaces/dashes with single dash
+    while sanitized.contains("--") {
+        sanitized = sanitized.replace("--", "-");
+    }
+    
+    // Limit length
+    if sanitized.len() > 100 {
+        sanitized.truncate(100);
+    }
+    
+    // Default name if empty
+    if sanitized.is_empty() {
+        sanitized = "untitled".to_string();
+    }
+    
+    sanitized
+}
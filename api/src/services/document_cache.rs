@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use serde::Serialize;
+use tokio::sync::Mutex;
+use tokio::time::interval;
+use uuid::Uuid;
+
+use crate::crdt::DocumentManager;
+use crate::services::crdt::CrdtService;
+use crate::socketio::connection_tracker::ConnectionTracker;
+
+#[derive(Debug, Serialize)]
+pub struct CacheStats {
+    pub cached_documents: usize,
+    pub connected_documents: usize,
+    pub estimated_memory_bytes: usize,
+    pub max_documents: usize,
+    pub max_memory_bytes: usize,
+    pub idle_timeout_secs: u64,
+    pub idle_evictions: u64,
+    pub pressure_evictions: u64,
+}
+
+/// Evicts CRDT documents from the in-memory cache.
+///
+/// Documents with no connected sockets are flushed and evicted after the idle timeout.
+/// When the cache exceeds its document count or memory ceiling, unconnected documents
+/// are evicted least recently used first. Documents with connected sockets are never evicted.
+#[derive(Clone)]
+pub struct DocumentCacheService {
+    crdt_service: Arc<CrdtService>,
+    document_manager: Arc<DocumentManager>,
+    connection_tracker: Arc<ConnectionTracker>,
+    idle_timeout: Duration,
+    max_documents: usize,
+    max_memory_bytes: usize,
+    eviction_interval: Duration,
+    idle_evictions: Arc<AtomicU64>,
+    pressure_evictions: Arc<AtomicU64>,
+    is_running: Arc<Mutex<bool>>,
+}
+
+impl DocumentCacheService {
+    pub fn new(
+        crdt_service: Arc<CrdtService>,
+        document_manager: Arc<DocumentManager>,
+        connection_tracker: Arc<ConnectionTracker>,
+        idle_timeout_secs: u64,
+        max_documents: usize,
+        max_memory_mb: usize,
+        eviction_interval_secs: u64,
+    ) -> Self {
+        Self {
+            crdt_service,
+            document_manager,
+            connection_tracker,
+            idle_timeout: Duration::from_secs(idle_timeout_secs),
+            max_documents,
+            max_memory_bytes: max_memory_mb * 1024 * 1024,
+            eviction_interval: Duration::from_secs(eviction_interval_secs.max(1)),
+            idle_evictions: Arc::new(AtomicU64::new(0)),
+            pressure_evictions: Arc::new(AtomicU64::new(0)),
+            is_running: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    pub async fn start(&self) {
+        let mut is_running = self.is_running.lock().await;
+        if *is_running {
+            tracing::warn!("DocumentCacheService is already running");
+            return;
+        }
+        *is_running = true;
+        drop(is_running);
+
+        let service = self.clone();
+        tokio::spawn(async move {
+            service.run_eviction_loop().await;
+        });
+    }
+
+    pub async fn stop(&self) {
+        let mut is_running = self.is_running.lock().await;
+        *is_running = false;
+    }
+
+    async fn run_eviction_loop(&self) {
+        let mut ticker = interval(self.eviction_interval);
+
+        loop {
+            ticker.tick().await;
+
+            let is_running = self.is_running.lock().await;
+            if !*is_running {
+                tracing::info!("DocumentCacheService stopping");
+                break;
+            }
+            drop(is_running);
+
+            self.run_eviction_pass().await;
+            tracing::debug!("Document cache after eviction: {:?}", self.stats());
+        }
+    }
+
+    /// Evict idle documents, then enforce the cache ceilings
+    pub async fn run_eviction_pass(&self) {
+        let candidates: Vec<_> = self.document_manager
+            .documents_by_last_access()
+            .into_iter()
+            .filter(|(id, _)| self.connection_tracker.is_document_empty(*id))
+            .collect();
+
+        // Idle eviction
+        let mut remaining = Vec::new();
+        for (document_id, last_access) in candidates {
+            if last_access.elapsed() < self.idle_timeout {
+                remaining.push(document_id);
+                continue;
+            }
+            if self.evict(document_id).await {
+                self.idle_evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        // Pressure eviction, least recently used first
+        let sizes = self.document_sizes();
+        let mut count = sizes.len();
+        let mut bytes: usize = sizes.values().sum();
+
+        for document_id in remaining {
+            if count <= self.max_documents && bytes <= self.max_memory_bytes {
+                break;
+            }
+            if self.evict(document_id).await {
+                self.pressure_evictions.fetch_add(1, Ordering::Relaxed);
+                count -= 1;
+                bytes -= sizes.get(&document_id).copied().unwrap_or(0).min(bytes);
+            }
+        }
+
+        if count > self.max_documents || bytes > self.max_memory_bytes {
+            tracing::warn!(
+                "Document cache above limits with {} documents (~{} bytes); remaining documents have connected clients",
+                count, bytes
+            );
+        }
+    }
+
+    async fn evict(&self, document_id: Uuid) -> bool {
+        match self.crdt_service.flush_and_evict(document_id).await {
+            Ok(evicted) => {
+                if evicted {
+                    tracing::debug!("Evicted document {} from cache", document_id);
+                }
+                evicted
+            }
+            Err(e) => {
+                tracing::error!("Failed to flush document {} before eviction: {}", document_id, e);
+                false
+            }
+        }
+    }
+
+    fn document_sizes(&self) -> HashMap<Uuid, usize> {
+        self.document_manager
+            .get_all_document_ids()
+            .into_iter()
+            .filter_map(|id| {
+                self.document_manager
+                    .peek(&id)
+                    .map(|doc| (id, doc.read().estimated_size()))
+            })
+            .collect()
+    }
+
+    /// Current cache statistics
+    pub fn stats(&self) -> CacheStats {
+        let sizes = self.document_sizes();
+        let connected_documents = sizes
+            .keys()
+            .filter(|id| !self.connection_tracker.is_document_empty(**id))
+            .count();
+
+        CacheStats {
+            cached_documents: sizes.len(),
+            connected_documents,
+            estimated_memory_bytes: sizes.values().sum(),
+            max_documents: self.max_documents,
+            max_memory_bytes: self.max_memory_bytes,
+            idle_timeout_secs: self.idle_timeout.as_secs(),
+            idle_evictions: self.idle_evictions.load(Ordering::Relaxed),
+            pressure_evictions: self.pressure_evictions.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crdt::{AwarenessManager, DocumentPersistence};
+    use crate::test_support::{db_test, TestDb};
+
+    db_test! {
+        async fn test_pressure_eviction_drops_least_recently_used_first() {
+            let db = TestDb::connect().await;
+            let document_manager = Arc::new(DocumentManager::new());
+            let connection_tracker = Arc::new(ConnectionTracker::new());
+            let crdt_service = Arc::new(CrdtService::new(
+                document_manager.clone(),
+                Arc::new(AwarenessManager::new()),
+                Arc::new(DocumentPersistence::new((*db.pool).clone())),
+            ));
+            let service = DocumentCacheService::new(
+                crdt_service,
+                document_manager.clone(),
+                connection_tracker.clone(),
+                3600,
+                2,
+                1024,
+                60,
+            );
+
+            let owner_id = db.create_user().await;
+            let mut ids = Vec::new();
+            for title in ["Oldest", "Connected", "Older", "Recent"] {
+                let id = db.create_document(title, owner_id).await;
+                document_manager.get_or_create(id);
+                ids.push(id);
+                tokio::time::sleep(Duration::from_millis(2)).await;
+            }
+            let [oldest, connected, older, recent] = ids[..] else { unreachable!() };
+            connection_tracker.join_document("socket", connected);
+
+            // Four cached documents over a ceiling of two: the two least recently used
+            // without connected clients go, the connected one stays despite its age
+            service.run_eviction_pass().await;
+
+            assert!(document_manager.peek(&oldest).is_none());
+            assert!(document_manager.peek(&older).is_none());
+            assert!(document_manager.peek(&connected).is_some());
+            assert!(document_manager.peek(&recent).is_some());
+            assert_eq!(service.stats().pressure_evictions, 2);
+        }
+    }
+}
@@ -2,17 +2,22 @@ use std::sync::Arc;
 use sqlx::PgPool;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
+use serde::Serialize;
 use crate::{
+    entities::share::Permission,
     error::Result,
-    services::link_parser::LinkParser,
+    services::crdt::CrdtService,
+    services::link_parser::{LinkFragment, LinkParser, LinkTarget},
     services::link_resolver::LinkResolver,
+    socketio::SocketBroadcaster,
 };
 
 #[derive(Debug, Clone)]
 pub struct StoredDocumentLink {
     pub id: Uuid,
     pub source_document_id: Uuid,
-    pub target_document_id: Uuid,
+    pub target_document_id: Option<Uuid>,
+    pub target_text: Option<String>,
     pub link_type: String,
     pub link_text: Option<String>,
     pub position_start: Option<i32>,
@@ -29,6 +34,8 @@ pub struct DocumentLinkInfo {
     pub file_path: Option<String>,
     pub link_type: String,
     pub link_text: Option<String>,
+    /// Heading or `^block-id` the link points to
+    pub section: Option<String>,
     pub link_count: i64,
 }
 
@@ -40,6 +47,10 @@ pub struct OutgoingLinkInfo {
     pub file_path: Option<String>,
     pub link_type: String,
     pub link_text: Option<String>,
+    /// Heading or `^block-id` the link points to
+    pub section: Option<String>,
+    /// Whether the section exists in the target, None without a section
+    pub section_exists: Option<bool>,
     pub position_start: Option<i32>,
     pub position_end: Option<i32>,
 }
@@ -47,12 +58,14 @@ pub struct OutgoingLinkInfo {
 pub struct DocumentLinksService {
     pool: Arc<PgPool>,
     pub link_resolver: Arc<LinkResolver>,
+    crdt_service: Arc<CrdtService>,
+    broadcaster: Arc<SocketBroadcaster>,
 }
 
 impl DocumentLinksService {
-    pub fn new(pool: Arc<PgPool>) -> Self {
+    pub fn new(pool: Arc<PgPool>, crdt_service: Arc<CrdtService>, broadcaster: Arc<SocketBroadcaster>) -> Self {
         let link_resolver = Arc::new(LinkResolver::new(pool.clone()));
-        Self { pool, link_resolver }
+        Self { pool, link_resolver, crdt_service, broadcaster }
     }
 
     /// Update links for a document based on its content
@@ -84,38 +97,95 @@ impl DocumentLinksService {
         let targets: Vec<&crate::services::link_parser::LinkTarget> = links.iter().map(|l| &l.target).collect();
         let resolved_docs = self.link_resolver.resolve_targets_batch(&targets, owner_id).await?;
 
-        // Insert resolved links
+        // Check section references against the content of their targets
+        let mut fragments_resolved = Vec::with_capacity(links.len());
         for (link, resolved_doc) in links.iter().zip(resolved_docs.iter()) {
-            if let Some(target_doc) = resolved_doc {
-                // Insert the link
-                sqlx::query!(
-                    r#"
-                    INSERT INTO document_links (
-                        source_document_id, target_document_id, link_type, 
-                        link_text, position_start, position_end
-                    ) VALUES ($1, $2, $3, $4, $5, $6)
-                    ON CONFLICT (source_document_id, target_document_id, position_start) 
-                    DO UPDATE SET 
-                        link_type = EXCLUDED.link_type,
-                        link_text = EXCLUDED.link_text,
-                        position_end = EXCLUDED.position_end,
-                        updated_at = NOW()
-                    "#,
-                    document_id,
-                    target_doc.id,
-                    link.link_type.as_str(),
-                    link.link_text,
-                    link.position_start as i32,
-                    link.position_end as i32
-                )
-                .execute(&mut *tx)
-                .await?;
-            }
+            let resolved = match (&link.fragment, resolved_doc) {
+                (Some(fragment), Some(target_doc)) if target_doc.id == document_id => {
+                    Some(fragment.section_range(content).is_some())
+                }
+                (Some(fragment), Some(target_doc)) => {
+                    Some(self.link_resolver.fragment_exists(target_doc.id, fragment).await?)
+                }
+                _ => None,
+            };
+            fragments_resolved.push(resolved);
+        }
+
+        // Insert links, keeping unresolved ones with the raw target so they can be bound later
+        for ((link, resolved_doc), fragment_resolved) in links.iter().zip(resolved_docs.iter()).zip(fragments_resolved) {
+            let target_text = match &link.target {
+                LinkTarget::Id(id) => id.to_string(),
+                LinkTarget::Title(title) => title.clone(),
+            };
+            
+            sqlx::query(
+                r#"
+                INSERT INTO document_links (
+                    source_document_id, target_document_id, target_text, target_fragment,
+                    fragment_resolved, link_type, link_text, position_start, position_end
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                ON CONFLICT (source_document_id, target_document_id, position_start) 
+                DO UPDATE SET 
+                    target_text = EXCLUDED.target_text,
+                    target_fragment = EXCLUDED.target_fragment,
+                    fragment_resolved = EXCLUDED.fragment_resolved,
+                    link_type = EXCLUDED.link_type,
+                    link_text = EXCLUDED.link_text,
+                    position_end = EXCLUDED.position_end,
+                    updated_at = NOW()
+                "#,
+            )
+            .bind(document_id)
+            .bind(resolved_doc.as_ref().map(|doc| doc.id))
+            .bind(target_text)
+            .bind(link.fragment.as_ref().map(LinkFragment::as_text))
+            .bind(fragment_resolved)
+            .bind(link.link_type.as_str())
+            .bind(&link.link_text)
+            .bind(link.position_start as i32)
+            .bind(link.position_end as i32)
+            .execute(&mut *tx)
+            .await?;
         }
         
         // Commit transaction
         tx.commit().await?;
         
+        // Headings and blocks other documents reference may have changed
+        self.refresh_incoming_fragments(document_id, content).await?;
+        
+        Ok(())
+    }
+
+    /// Recheck the sections that links into a document reference against its content
+    pub async fn refresh_incoming_fragments(&self, document_id: Uuid, content: &str) -> Result<()> {
+        let fragments = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT DISTINCT target_fragment FROM document_links
+            WHERE target_document_id = $1 AND target_fragment IS NOT NULL
+            "#,
+        )
+        .bind(document_id)
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        
+        for fragment in fragments {
+            let exists = LinkFragment::parse(&fragment).is_some_and(|parsed| parsed.section_range(content).is_some());
+            sqlx::query(
+                r#"
+                UPDATE document_links SET fragment_resolved = $3, updated_at = NOW()
+                WHERE target_document_id = $1 AND target_fragment = $2
+                  AND fragment_resolved IS DISTINCT FROM $3
+                "#,
+            )
+            .bind(document_id)
+            .bind(&fragment)
+            .bind(exists)
+            .execute(self.pool.as_ref())
+            .await?;
+        }
+        
         Ok(())
     }
 
@@ -132,12 +202,14 @@ impl DocumentLinksService {
                     d.file_path,
                     dl.link_type,
                     dl.link_text,
+                    dl.target_fragment,
                     COUNT(*)::BIGINT as link_count
                 FROM document_links dl
                 JOIN documents d ON d.id = dl.source_document_id
                 WHERE dl.target_document_id = $1 
                 AND d.owner_id = $2
-                GROUP BY d.id, d.title, d.type, d.file_path, dl.link_type, dl.link_text
+                AND d.deleted_at IS NULL
+                GROUP BY d.id, d.title, d.type, d.file_path, dl.link_type, dl.link_text, dl.target_fragment
                 ORDER BY link_count DESC, d.title
                 "#,
                 document_id,
@@ -161,6 +233,7 @@ impl DocumentLinksService {
                 file_path: row.file_path,
                 link_type: row.link_type,
                 link_text: row.link_text,
+                section: row.target_fragment,
                 link_count: row.link_count.unwrap_or(0),
             })
             .collect())
@@ -179,12 +252,15 @@ impl DocumentLinksService {
                     d.file_path,
                     dl.link_type,
                     dl.link_text,
+                    dl.target_fragment,
+                    dl.fragment_resolved,
                     dl.position_start,
                     dl.position_end
                 FROM document_links dl
                 JOIN documents d ON d.id = dl.target_document_id
                 WHERE dl.source_document_id = $1
                 AND d.owner_id = $2
+                AND d.deleted_at IS NULL
                 ORDER BY dl.position_start
                 "#,
                 document_id,
@@ -206,48 +282,160 @@ impl DocumentLinksService {
                 file_path: row.file_path,
                 link_type: row.link_type,
                 link_text: row.link_text,
+                section: row.target_fragment,
+                section_exists: row.fragment_resolved,
                 position_start: row.position_start,
                 position_end: row.position_end,
             })
             .collect())
     }
 
-    /// Find broken links (links pointing to non-existent documents)
-    pub async fn find_broken_links(&self, _owner_id: Uuid) -> Result<Vec<BrokenLink>> {
-        // This would need a more complex implementation to track unresolved links
-        // For now, return empty as all links are validated on insert
-        Ok(vec![])
+    /// Links in a user's documents whose target does not exist or is in the trash, or
+    /// whose heading or block is missing from the target, optionally limited to
+    /// documents inside a folder at any depth
+    pub async fn find_broken_links(&self, owner_id: Uuid, folder_id: Option<Uuid>) -> Result<Vec<BrokenLink>> {
+        let broken_links = sqlx::query_as::<_, BrokenLink>(
+            r#"
+            WITH RECURSIVE folder_tree AS (
+                SELECT id FROM documents WHERE id = $2
+                UNION ALL
+                SELECT d.id FROM documents d JOIN folder_tree f ON d.parent_id = f.id
+            )
+            SELECT s.id AS source_document_id,
+                   s.title AS source_title,
+                   s.file_path AS source_file_path,
+                   COALESCE(dl.target_text, t.title, '') AS target_text,
+                   dl.target_fragment,
+                   CASE
+                       WHEN dl.target_document_id IS NULL THEN 'missing_document'
+                       WHEN t.deleted_at IS NOT NULL THEN 'trashed_document'
+                       ELSE 'missing_section'
+                   END AS reason,
+                   dl.link_type,
+                   dl.link_text,
+                   dl.position_start AS position
+            FROM document_links dl
+            JOIN documents s ON s.id = dl.source_document_id
+            LEFT JOIN documents t ON t.id = dl.target_document_id
+            WHERE s.owner_id = $1
+              AND s.deleted_at IS NULL
+              AND (dl.target_document_id IS NULL OR t.deleted_at IS NOT NULL OR dl.fragment_resolved = FALSE)
+              AND ($2::uuid IS NULL OR s.id IN (SELECT id FROM folder_tree))
+            ORDER BY s.title, dl.position_start
+            "#,
+        )
+        .bind(owner_id)
+        .bind(folder_id)
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        
+        Ok(broken_links)
     }
 
-    /// Update links when a document is renamed
-    pub async fn update_links_on_rename(&self, document_id: Uuid, old_title: &str, new_title: &str) -> Result<()> {
-        // Find all documents that link to this document by title
-        let affected_docs = self.link_resolver.get_affected_by_rename(document_id).await?;
+    /// Bind links written with a document's title that had no target, or a trashed one,
+    /// to the document. Only links in documents whose owner can view it are bound, as
+    /// when resolving. Returns the number of links bound.
+    pub async fn bind_dangling_links(&self, document_id: Uuid, owner_id: Uuid, title: &str) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            UPDATE document_links dl
+            SET target_document_id = $1, updated_at = NOW()
+            FROM documents s
+            WHERE s.id = dl.source_document_id
+              AND LOWER(dl.target_text) = LOWER($2)
+              AND (dl.target_document_id IS NULL OR dl.target_document_id IN (
+                  SELECT id FROM documents WHERE deleted_at IS NOT NULL
+              ))
+              AND (s.owner_id = $3 OR EXISTS (
+                  SELECT 1 FROM document_permissions p
+                  WHERE p.document_id = $1 AND p.user_id = s.owner_id AND p.permission = ANY($4)
+              ))
+            "#,
+        )
+        .bind(document_id)
+        .bind(title.trim())
+        .bind(owner_id)
+        .bind(Permission::View.granted_by())
+        .execute(self.pool.as_ref())
+        .await?;
         
-        // Update each affected document
-        for source_doc_id in affected_docs {
-            // This would require fetching the document content, updating links, and saving
-            // For now, we'll just update the link_text in the database
-            sqlx::query!(
-                r#"
-                UPDATE document_links
-                SET link_text = CASE 
-                    WHEN link_text = $2 THEN $3
-                    ELSE link_text
-                END,
-                updated_at = NOW()
-                WHERE source_document_id = $1 AND target_document_id = $4
-                "#,
-                source_doc_id,
-                old_title,
-                new_title,
-                document_id
-            )
-            .execute(self.pool.as_ref())
-            .await?;
+        if result.rows_affected() > 0 {
+            let content = self.crdt_service.get_document_content(document_id).await?;
+            self.refresh_incoming_fragments(document_id, &content).await?;
         }
         
-        Ok(())
+        Ok(result.rows_affected())
+    }
+
+    /// Rewrite `[[Old Title]]` and relative `Old Title.md` links to a renamed document in
+    /// the documents linking to it.
+    ///
+    /// Each linking document is edited through its CRDT and the change is broadcast to
+    /// live editors; `|alias` display text is kept. Documents the renaming user cannot
+    /// write are left as they are and reported as skipped. Refreshing the stored links
+    /// and files of the rewritten documents is left to the caller.
+    pub async fn update_links_on_rename(&self, document_id: Uuid, old_title: &str, new_title: &str, user_id: Uuid) -> Result<RenameLinksReport> {
+        let mut report = RenameLinksReport {
+            old_title: old_title.to_string(),
+            new_title: new_title.to_string(),
+            updated: Vec::new(),
+            skipped: Vec::new(),
+        };
+        if old_title.trim().to_lowercase() == new_title.trim().to_lowercase() {
+            return Ok(report);
+        }
+        
+        let sources = sqlx::query_as::<_, (Uuid, String, bool)>(
+            r#"
+            SELECT DISTINCT s.id, s.title,
+                   (s.owner_id = $2 OR EXISTS (
+                       SELECT 1 FROM document_permissions p
+                       WHERE p.document_id = s.id AND p.user_id = $2 AND p.permission = ANY($3)
+                   )) AS writable
+            FROM document_links dl
+            JOIN documents s ON s.id = dl.source_document_id
+            WHERE dl.target_document_id = $1 AND s.deleted_at IS NULL
+            "#,
+        )
+        .bind(document_id)
+        .bind(user_id)
+        .bind(Permission::Edit.granted_by())
+        .fetch_all(self.pool.as_ref())
+        .await?;
+        
+        for (source_id, title, writable) in sources {
+            let content = self.crdt_service.get_document_content(source_id).await?;
+            let (_, links) = LinkParser::rename_title_links(&content, old_title, new_title);
+            if links == 0 {
+                // Linked by id, which survives the rename
+                continue;
+            }
+            
+            if !writable {
+                report.skipped.push(LinkRewrite { document_id: source_id, title, links });
+                continue;
+            }
+            
+            // Rewrite again under the write lock in case the content changed meanwhile
+            let mut links = 0;
+            let update = self.crdt_service.edit_document(source_id, Some(user_id), |doc| {
+                let (content, renamed) = LinkParser::rename_title_links(&doc.get_content()?, old_title, new_title);
+                links = renamed;
+                if renamed > 0 {
+                    doc.set_content(&content)?;
+                }
+                Ok(())
+            }).await?;
+            
+            if links > 0 {
+                if let Err(e) = self.broadcaster.broadcast_document_update(source_id, &update, None) {
+                    tracing::error!("Failed to broadcast link rewrite in document {}: {}", source_id, e);
+                }
+                report.updated.push(LinkRewrite { document_id: source_id, title, links });
+            }
+        }
+        
+        Ok(report)
     }
 
     /// Get link statistics for a document
@@ -277,16 +465,79 @@ impl DocumentLinksService {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, sqlx::FromRow)]
 pub struct BrokenLink {
     pub source_document_id: Uuid,
     pub source_title: String,
-    pub link_text: String,
-    pub position: usize,
+    pub source_file_path: Option<String>,
+    pub target_text: String,
+    pub target_fragment: Option<String>,
+    /// `missing_document`, `trashed_document` or `missing_section`
+    pub reason: String,
+    pub link_type: String,
+    pub link_text: Option<String>,
+    pub position: Option<i32>,
+}
+
+/// Documents whose links were rewritten after a rename
+#[derive(Debug, Clone, Serialize)]
+pub struct RenameLinksReport {
+    pub old_title: String,
+    pub new_title: String,
+    pub updated: Vec<LinkRewrite>,
+    /// Linking documents the renaming user cannot edit, still using the old title
+    pub skipped: Vec<LinkRewrite>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkRewrite {
+    pub document_id: Uuid,
+    pub title: String,
+    /// Number of links to the old title in the document
+    pub links: usize,
 }
 
 #[derive(Debug, Clone)]
 pub struct LinkStats {
     pub backlink_count: usize,
     pub outgoing_link_count: usize,
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::cluster_sync::ClusterPublisher;
+    use crate::test_support::{db_test, TestDb};
+    use crate::websocket::WebsocketPeers;
+
+    db_test! {
+        async fn test_rename_rewrites_links_for_edit_grantees() {
+            let db = TestDb::connect().await;
+            let pool = db.pool.clone();
+            let crdt_service = db.crdt_service();
+            let broadcaster = Arc::new(SocketBroadcaster::new(Arc::new(WebsocketPeers::new()), ClusterPublisher::default()));
+            let service = DocumentLinksService::new(pool.clone(), crdt_service.clone(), broadcaster);
+
+            let owner_id = db.create_user().await;
+            let editor_id = db.create_user().await;
+            let target_id = db.create_document("Old", owner_id).await;
+            let source_id = db.create_document("Notes", owner_id).await;
+            sqlx::query("INSERT INTO document_permissions (document_id, user_id, permission) VALUES ($1, $2, 'edit')")
+                .bind(source_id)
+                .bind(editor_id)
+                .execute(pool.as_ref())
+                .await
+                .unwrap();
+            let content = "See [[Old]] and [the old one](./Old.md#intro)";
+            crdt_service.set_document_content(source_id, content, None).await.unwrap();
+            service.update_document_links(source_id, content).await.unwrap();
+
+            let report = service.update_links_on_rename(target_id, "Old", "New", editor_id).await.unwrap();
+            let content = crdt_service.get_document_content(source_id).await.unwrap();
+
+            assert_eq!(report.updated.iter().map(|rewrite| (rewrite.document_id, rewrite.links)).collect::<Vec<_>>(), vec![(source_id, 2)]);
+            assert!(report.skipped.is_empty());
+            assert_eq!(content, "See [[New]] and [the old one](./New.md#intro)");
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use dashmap::DashMap;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::fs;
+use tokio::sync::{mpsc, Mutex};
+use tokio::time::timeout;
+use uuid::Uuid;
+
+use crate::services::git_import::{is_document_path, GitImportService};
+
+/// How long the server remembers a file it wrote; later events fall back to comparing content
+const OWN_WRITE_TTL: Duration = Duration::from_secs(300);
+
+/// Files the server wrote or removed itself, so the watcher can tell them from external edits.
+///
+/// Only the latest state per path is kept: an event is the server's own if the file on
+/// disk still has the content the server last wrote, or is gone after the server removed it.
+#[derive(Default)]
+pub struct OwnFileWrites {
+    /// Hash of the written content, None once removed
+    entries: DashMap<PathBuf, (Option<u64>, Instant)>,
+}
+
+impl OwnFileWrites {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record content the server is about to write to a file
+    pub fn record_write(&self, path: &Path, contents: &[u8]) {
+        self.record(path, Some(content_hash(contents)));
+    }
+
+    /// Record a file the server is about to remove or move away
+    pub fn record_removal(&self, path: &Path) {
+        self.record(path, None);
+    }
+
+    /// Whether a file is in the state the server last left it in, None meaning it is gone
+    pub fn is_own(&self, path: &Path, contents: Option<&[u8]>) -> bool {
+        self.entries.get(path).is_some_and(|entry| {
+            let (hash, recorded_at) = *entry;
+            recorded_at.elapsed() < OWN_WRITE_TTL && hash == contents.map(content_hash)
+        })
+    }
+
+    fn record(&self, path: &Path, hash: Option<u64>) {
+        self.entries.retain(|_, (_, recorded_at)| recorded_at.elapsed() < OWN_WRITE_TTL);
+        self.entries.insert(path.to_path_buf(), (hash, Instant::now()));
+    }
+}
+
+fn content_hash(contents: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Picks up edits made to the markdown files in the upload directory outside RefMD.
+///
+/// Files changed by an editor, a script or a git checkout are matched to documents by
+/// the `id:` in their frontmatter and imported like pulled files: content changes become
+/// CRDT updates broadcast to connected clients, new files become documents and deleted
+/// files delete their document. Events are collected until the directory has been quiet
+/// for the debounce period, and files still as the server last wrote them are skipped so
+/// its own saves do not loop back.
+#[derive(Clone)]
+pub struct FileWatcherService {
+    upload_dir: PathBuf,
+    own_writes: Arc<OwnFileWrites>,
+    git_import_service: Arc<GitImportService>,
+    debounce: Duration,
+    watcher: Arc<Mutex<Option<RecommendedWatcher>>>,
+}
+
+impl FileWatcherService {
+    pub fn new(
+        upload_dir: PathBuf,
+        own_writes: Arc<OwnFileWrites>,
+        git_import_service: Arc<GitImportService>,
+        debounce_ms: u64,
+    ) -> Self {
+        Self {
+            upload_dir,
+            own_writes,
+            git_import_service,
+            debounce: Duration::from_millis(debounce_ms.max(1)),
+            watcher: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub async fn start(&self) {
+        let mut watcher = self.watcher.lock().await;
+        if watcher.is_some() {
+            tracing::warn!("FileWatcherService is already running");
+            return;
+        }
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let created = notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+            Ok(event) => {
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) {
+                    for path in event.paths {
+                        let _ = sender.send(path);
+                    }
+                }
+            }
+            Err(e) => tracing::warn!("File watcher error: {}", e),
+        });
+
+        let mut created = match created {
+            Ok(created) => created,
+            Err(e) => {
+                tracing::error!("Failed to create file watcher: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = std::fs::create_dir_all(&self.upload_dir) {
+            tracing::error!("Failed to create upload directory {:?}: {}", self.upload_dir, e);
+            return;
+        }
+        if let Err(e) = created.watch(&self.upload_dir, RecursiveMode::Recursive) {
+            tracing::error!("Failed to watch upload directory {:?}: {}", self.upload_dir, e);
+            return;
+        }
+        *watcher = Some(created);
+        drop(watcher);
+
+        let service = self.clone();
+        tokio::spawn(async move {
+            service.run_watch_loop(receiver).await;
+        });
+    }
+
+    /// Dropping the watcher closes its channel, which ends the loop
+    pub async fn stop(&self) {
+        let mut watcher = self.watcher.lock().await;
+        *watcher = None;
+    }
+
+    async fn run_watch_loop(&self, mut receiver: mpsc::UnboundedReceiver<PathBuf>) {
+        while let Some(path) = receiver.recv().await {
+            let mut paths = HashSet::from([path]);
+            let mut closed = false;
+
+            // Wait for the burst of events to settle
+            loop {
+                match timeout(self.debounce, receiver.recv()).await {
+                    Ok(Some(path)) => {
+                        paths.insert(path);
+                    }
+                    Ok(None) => {
+                        closed = true;
+                        break;
+                    }
+                    Err(_) => break,
+                }
+            }
+
+            self.import_paths(paths).await;
+            if closed {
+                break;
+            }
+        }
+
+        tracing::info!("FileWatcherService stopping");
+    }
+
+    /// Import the external changes among a set of changed paths, grouped by owner
+    async fn import_paths(&self, paths: HashSet<PathBuf>) {
+        let mut changes: HashMap<Uuid, Vec<(PathBuf, Option<String>)>> = HashMap::new();
+
+        for path in paths {
+            let Some((owner_id, relative_path)) = document_file(&self.upload_dir, &path) else {
+                continue;
+            };
+
+            let contents = match fs::read(&path).await {
+                Ok(contents) => Some(contents),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => {
+                    tracing::warn!("Failed to read changed file {:?}: {}", path, e);
+                    continue;
+                }
+            };
+            if self.own_writes.is_own(&path, contents.as_deref()) {
+                continue;
+            }
+
+            let text = match contents.map(String::from_utf8).transpose() {
+                Ok(text) => text,
+                Err(_) => {
+                    tracing::warn!("Skipping non UTF-8 file {:?} in file watcher", path);
+                    continue;
+                }
+            };
+            changes.entry(owner_id).or_default().push((relative_path, text));
+        }
+
+        for (owner_id, files) in changes {
+            tracing::info!("Importing {} externally changed files for user {}", files.len(), owner_id);
+            if let Err(e) = self.git_import_service.import_files(owner_id, files).await {
+                tracing::error!("Failed to import changed files for user {}: {}", owner_id, e);
+            }
+        }
+    }
+}
+
+/// Owner and owner-relative path of a document file under the upload directory
+fn document_file(upload_dir: &Path, path: &Path) -> Option<(Uuid, PathBuf)> {
+    let mut components = path.strip_prefix(upload_dir).ok()?.components();
+    let Some(Component::Normal(owner)) = components.next() else {
+        return None;
+    };
+    let owner_id = Uuid::parse_str(owner.to_str()?).ok()?;
+
+    let relative_path = components.as_path();
+    is_document_path(relative_path).then(|| (owner_id, relative_path.to_path_buf()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_own_writes_match_latest_state() {
+        let own_writes = OwnFileWrites::new();
+        let path = Path::new("/uploads/user/Notes.md");
+
+        assert!(!own_writes.is_own(path, Some(b"first")));
+
+        own_writes.record_write(path, b"first");
+        assert!(own_writes.is_own(path, Some(b"first")));
+        assert!(!own_writes.is_own(path, Some(b"edited")));
+        assert!(!own_writes.is_own(path, None));
+
+        own_writes.record_removal(path);
+        assert!(own_writes.is_own(path, None));
+        assert!(!own_writes.is_own(path, Some(b"first")));
+    }
+
+    #[test]
+    fn test_document_file() {
+        let upload_dir = Path::new("/uploads");
+        let owner_id = Uuid::new_v4();
+        let owner_dir = upload_dir.join(owner_id.to_string());
+
+        assert_eq!(
+            document_file(upload_dir, &owner_dir.join("Work/Meeting.md")),
+            Some((owner_id, PathBuf::from("Work/Meeting.md")))
+        );
+        assert_eq!(document_file(upload_dir, &owner_dir.join("Work/attachments/notes.md")), None);
+        assert_eq!(document_file(upload_dir, &owner_dir.join(".git/notes.md")), None);
+        assert_eq!(document_file(upload_dir, Path::new("/uploads/not-a-user/Notes.md")), None);
+        assert_eq!(document_file(upload_dir, Path::new("/elsewhere/Notes.md")), None);
+    }
+}
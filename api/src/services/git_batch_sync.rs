@@ -6,53 +6,134 @@ use tokio::time::interval;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 
-use crate::services::git_sync::GitSyncService;
+use crate::entities::git_config::{GitBatchSyncStage, GitBatchSyncStatus};
+use crate::error::Result;
+use crate::repository::GitConfigRepository;
+use crate::services::git_sync::{GitRepositoryScope, GitSyncService};
+use crate::socketio::SocketBroadcaster;
+
+/// Failed batches are retried this many times before giving up
+const MAX_RETRIES: u32 = 3;
+/// Seconds without changes before a batch is synced, unless the repository sets its own
+const DEFAULT_SYNC_DELAY_SECS: i64 = 30;
+
+/// A user's root repository (None) or one of their folder repositories
+type RepositoryKey = (Uuid, Option<Uuid>);
 
 #[derive(Clone)]
 struct PendingSync {
     user_id: Uuid,
+    git_config_id: Option<Uuid>,
+    sync_delay_secs: i64,
     last_change: DateTime<Utc>,
     document_titles: Vec<String>,
     retry_count: u32,
     last_error: Option<String>,
+    /// Held back by unresolved merge conflicts, checked again after each delay
+    conflicted: bool,
 }
 
+/// Batches document changes into git commits and reports each stage to the user.
+///
+/// Changes are batched per repository, so every mapped folder follows its own
+/// schedule. Stages are stored as each repository's last batch status and emitted as
+/// `git:sync` events to the user's sockets on every replica. A batch blocked by
+/// merge conflicts stays queued and is committed once they are resolved.
 pub struct GitBatchSyncService {
     git_sync_service: Arc<GitSyncService>,
-    pending_syncs: Arc<RwLock<HashMap<Uuid, PendingSync>>>,
+    git_config_repository: Arc<GitConfigRepository>,
+    broadcaster: Arc<SocketBroadcaster>,
+    pending_syncs: Arc<RwLock<HashMap<RepositoryKey, PendingSync>>>,
     sync_interval: Duration,
     is_running: Arc<Mutex<bool>>,
 }
 
 impl GitBatchSyncService {
-    pub fn new(git_sync_service: Arc<GitSyncService>, sync_interval_secs: u64) -> Self {
+    pub fn new(
+        git_sync_service: Arc<GitSyncService>,
+        git_config_repository: Arc<GitConfigRepository>,
+        broadcaster: Arc<SocketBroadcaster>,
+        sync_interval_secs: u64,
+    ) -> Self {
         Self {
             git_sync_service,
+            git_config_repository,
+            broadcaster,
             pending_syncs: Arc::new(RwLock::new(HashMap::new())),
             sync_interval: Duration::from_secs(sync_interval_secs),
             is_running: Arc::new(Mutex::new(false)),
         }
     }
 
-    pub async fn queue_sync(&self, user_id: Uuid, document_title: String) {
+    /// Queue a change to the file at `file_path`, relative to the upload directory
+    pub async fn queue_sync(&self, user_id: Uuid, file_path: &str, document_title: String) {
+        let (git_config_id, sync_delay_secs) = match self.git_sync_service.scope_for_path(user_id, file_path).await {
+            Ok(scope) => {
+                if scope.config.as_ref().is_some_and(|config| !config.auto_sync) {
+                    return;
+                }
+                let delay = scope.config.as_ref()
+                    .and_then(|config| config.sync_interval_secs)
+                    .map(i64::from)
+                    .unwrap_or(DEFAULT_SYNC_DELAY_SECS);
+                (scope.folder_config_id(), delay)
+            }
+            Err(e) => {
+                tracing::warn!("Failed to resolve git repository of {} for user {}: {}", file_path, user_id, e);
+                (None, DEFAULT_SYNC_DELAY_SECS)
+            }
+        };
+
         let mut pending = self.pending_syncs.write().await;
         
-        match pending.get_mut(&user_id) {
+        let queued = match pending.get_mut(&(user_id, git_config_id)) {
             Some(sync) => {
                 sync.last_change = Utc::now();
                 if !sync.document_titles.contains(&document_title) {
                     sync.document_titles.push(document_title);
                 }
+                None
             }
             None => {
-                pending.insert(user_id, PendingSync {
+                let sync = PendingSync {
                     user_id,
+                    git_config_id,
+                    sync_delay_secs,
                     last_change: Utc::now(),
                     document_titles: vec![document_title],
                     retry_count: 0,
                     last_error: None,
-                });
+                    conflicted: false,
+                };
+                pending.insert((user_id, git_config_id), sync.clone());
+                Some(sync)
             }
+        };
+        drop(pending);
+
+        // Only a new batch is reported, further edits just extend it
+        if let Some(sync) = queued {
+            self.report(&sync, GitBatchSyncStage::Queued, None, None).await;
+        }
+    }
+
+    /// Store the repository's batch status and push it to the user's sockets
+    async fn report(&self, sync: &PendingSync, stage: GitBatchSyncStage, commit_hash: Option<String>, message: Option<String>) {
+        let status = GitBatchSyncStatus {
+            git_config_id: sync.git_config_id,
+            status: stage,
+            documents: sync.document_titles.clone(),
+            retry_count: sync.retry_count as i32,
+            commit_hash,
+            message,
+            updated_at: Utc::now(),
+        };
+
+        if let Err(e) = self.git_config_repository.save_batch_status(sync.user_id, &status).await {
+            tracing::error!("Failed to store batch sync status for user {}: {}", sync.user_id, e);
+        }
+        if let Err(e) = self.broadcaster.emit_to_user(sync.user_id, "git:sync", &status) {
+            tracing::error!("Failed to emit batch sync status for user {}: {}", sync.user_id, e);
         }
     }
 
@@ -97,72 +178,188 @@ impl GitBatchSyncService {
         let now = Utc::now();
         let mut pending = self.pending_syncs.write().await;
         
-        // Find users ready for sync (no changes in the last 30 seconds or retry needed)
+        // Find repositories ready for sync (no changes during their delay or retry needed)
         let mut ready_for_sync = Vec::new();
-        for (user_id, sync) in pending.iter() {
+        for (key, sync) in pending.iter() {
             let time_since_last_change = now.signed_duration_since(sync.last_change);
             let should_retry = sync.last_error.is_some() && 
                 time_since_last_change > chrono::Duration::seconds(60 * (sync.retry_count + 1) as i64);
             
-            if time_since_last_change > chrono::Duration::seconds(30) || should_retry {
-                ready_for_sync.push(*user_id);
+            if time_since_last_change > chrono::Duration::seconds(sync.sync_delay_secs) || should_retry {
+                ready_for_sync.push(*key);
             }
         }
 
-        // Process each user's sync
-        for user_id in ready_for_sync {
-            if let Some(mut sync) = pending.remove(&user_id) {
-                let git_sync = self.git_sync_service.clone();
-                let pending_syncs = self.pending_syncs.clone();
-                
+        // Process each repository's sync
+        for key in ready_for_sync {
+            if let Some(sync) = pending.remove(&key) {
+                let service = self.clone();
                 tokio::spawn(async move {
-                    let commit_message = if sync.document_titles.len() == 1 {
-                        format!("Update document: {}", sync.document_titles[0])
-                    } else {
-                        format!("Update {} documents: {}", 
-                            sync.document_titles.len(),
-                            sync.document_titles.join(", ")
-                        )
-                    };
-                    
-                    match git_sync.sync(user_id, Some(commit_message), false).await {
-                        Ok(_) => {
-                            tracing::info!("Batch git sync completed for user {} (retry: {})", user_id, sync.retry_count);
-                            // TODO: Notify frontend via Socket.IO
-                            // This would require adding Socket.IO integration to the batch sync service
-                        }
-                        Err(e) => {
-                            tracing::error!("Batch git sync failed for user {} (retry: {}): {}", user_id, sync.retry_count, e);
-                            
-                            // If we haven't reached max retries, requeue
-                            if sync.retry_count < 3 {
-                                let retry_count = sync.retry_count + 1;
-                                sync.retry_count = retry_count;
-                                sync.last_error = Some(e.to_string());
-                                sync.last_change = Utc::now();
-                                
-                                let mut pending = pending_syncs.write().await;
-                                pending.insert(user_id, sync);
-                                
-                                tracing::info!("Requeued sync for user {} (retry: {})", user_id, retry_count);
-                            } else {
-                                tracing::error!("Max retries reached for user {}, giving up", user_id);
-                            }
-                        }
-                    }
+                    service.run_sync(sync).await;
                 });
             }
         }
     }
+
+    async fn run_sync(&self, mut sync: PendingSync) {
+        let user_id = sync.user_id;
+        let scope = match sync.git_config_id {
+            Some(config_id) => self.git_sync_service.folder_scope(user_id, config_id).await,
+            None => self.git_sync_service.root_scope(user_id).await,
+        };
+        let scope = match scope {
+            Ok(scope) => scope,
+            Err(e) => {
+                // The folder mapping was removed while the batch was pending
+                tracing::warn!("Dropping batch git sync for user {}: {}", user_id, e);
+                return;
+            }
+        };
+        // Committing now would record conflict markers, the user has to resolve them first
+        let conflicts = self.git_sync_service.get_conflicts(&scope).await.ok().filter(|conflicts| conflicts.has_conflicts);
+        match run_stage(&sync, conflicts.is_some()) {
+            Some(GitBatchSyncStage::Conflicted) => {
+                let count = conflicts.as_ref().map_or(0, |conflicts| conflicts.conflicted_files.len());
+                let message = format!("{} files have unresolved merge conflicts", count);
+                tracing::warn!("Batch git sync blocked for user {}: {}", user_id, message);
+                self.report(&sync, GitBatchSyncStage::Conflicted, None, Some(message)).await;
+            }
+            Some(stage) => self.report(&sync, stage, None, None).await,
+            None => {}
+        }
+        if conflicts.is_some() {
+            // The batch waits in the queue until the conflicts are gone
+            sync.conflicted = true;
+            sync.last_change = Utc::now();
+            self.requeue(sync).await;
+            return;
+        }
+        sync.conflicted = false;
+
+        match self.commit_and_push(&scope, &sync).await {
+            Ok(()) => {
+                tracing::info!("Batch git sync completed for user {} (retry: {})", user_id, sync.retry_count);
+            }
+            Err(e) => {
+                tracing::error!("Batch git sync failed for user {} (retry: {}): {}", user_id, sync.retry_count, e);
+                
+                // If we haven't reached max retries, requeue
+                if sync.retry_count < MAX_RETRIES {
+                    let retry_count = sync.retry_count + 1;
+                    sync.retry_count = retry_count;
+                    sync.last_error = Some(e.to_string());
+                    sync.last_change = Utc::now();
+                    self.report(&sync, GitBatchSyncStage::Retrying, None, sync.last_error.clone()).await;
+                    self.requeue(sync).await;
+                    
+                    tracing::info!("Requeued sync for user {} (retry: {})", user_id, retry_count);
+                } else {
+                    tracing::error!("Max retries reached for user {}, giving up", user_id);
+                    self.report(&sync, GitBatchSyncStage::Failed, None, Some(e.to_string())).await;
+                }
+            }
+        }
+    }
+
+    /// Put a batch back in the queue, merged with changes queued while it was running
+    async fn requeue(&self, mut sync: PendingSync) {
+        let mut pending = self.pending_syncs.write().await;
+        if let Some(queued) = pending.remove(&(sync.user_id, sync.git_config_id)) {
+            for title in queued.document_titles {
+                if !sync.document_titles.contains(&title) {
+                    sync.document_titles.push(title);
+                }
+            }
+            sync.last_change = sync.last_change.max(queued.last_change);
+        }
+        pending.insert((sync.user_id, sync.git_config_id), sync);
+    }
+
+    async fn commit_and_push(&self, scope: &GitRepositoryScope, sync: &PendingSync) -> Result<()> {
+        let commit_message = if sync.document_titles.len() == 1 {
+            format!("Update document: {}", sync.document_titles[0])
+        } else {
+            format!("Update {} documents: {}", 
+                sync.document_titles.len(),
+                sync.document_titles.join(", ")
+            )
+        };
+
+        let (commit_hash, _) = self.git_sync_service.commit_pending(scope, Some(commit_message)).await?;
+        if commit_hash.is_some() {
+            self.report(sync, GitBatchSyncStage::Committed, commit_hash.clone(), None).await;
+        }
+        if self.git_sync_service.push_if_configured(scope, commit_hash.as_deref()).await? {
+            self.report(sync, GitBatchSyncStage::Pushed, commit_hash, None).await;
+        }
+
+        Ok(())
+    }
+}
+
+/// Stage to report when a batch comes up for sync, None while its stored `Conflicted`
+/// status still holds
+fn run_stage(sync: &PendingSync, has_conflicts: bool) -> Option<GitBatchSyncStage> {
+    match (has_conflicts, sync.conflicted) {
+        (true, true) => None,
+        (true, false) => Some(GitBatchSyncStage::Conflicted),
+        (false, _) => Some(GitBatchSyncStage::Started),
+    }
 }
 
 impl Clone for GitBatchSyncService {
     fn clone(&self) -> Self {
         Self {
             git_sync_service: self.git_sync_service.clone(),
+            git_config_repository: self.git_config_repository.clone(),
+            broadcaster: self.broadcaster.clone(),
             pending_syncs: self.pending_syncs.clone(),
             sync_interval: self.sync_interval,
             is_running: self.is_running.clone(),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conflicted_batch_stays_conflicted_until_resolved() {
+        let mut sync = PendingSync {
+            user_id: Uuid::new_v4(),
+            git_config_id: None,
+            sync_delay_secs: DEFAULT_SYNC_DELAY_SECS,
+            last_change: Utc::now(),
+            document_titles: vec!["Notes".to_string()],
+            retry_count: 0,
+            last_error: None,
+            conflicted: false,
+        };
+
+        // Each run reports at most one stage, and the last one reported is what the user sees
+        let mut reported = Vec::new();
+        for has_conflicts in [true, true, true, false] {
+            reported.extend(run_stage(&sync, has_conflicts));
+            sync.conflicted = has_conflicts;
+        }
+
+        assert_eq!(reported, vec![GitBatchSyncStage::Conflicted, GitBatchSyncStage::Started]);
+    }
+
+    #[test]
+    fn test_batch_without_conflicts_starts() {
+        let sync = PendingSync {
+            user_id: Uuid::new_v4(),
+            git_config_id: Some(Uuid::new_v4()),
+            sync_delay_secs: DEFAULT_SYNC_DELAY_SECS,
+            last_change: Utc::now(),
+            document_titles: Vec::new(),
+            retry_count: 1,
+            last_error: Some("push rejected".to_string()),
+            conflicted: false,
+        };
+
+        assert_eq!(run_stage(&sync, false), Some(GitBatchSyncStage::Started));
+    }
+}
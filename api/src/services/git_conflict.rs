@@ -73,12 +73,10 @@ impl GitConflictService {
         self.upload_dir.join(user_id.to_string())
     }
 
-    pub async fn detect_conflicts(&self, user_id: Uuid) -> Result<ConflictInfo> {
-        let repo_path = self.get_user_repo_path(user_id);
-        
+    /// Detect conflicts in an opened repository whose working directory is `workdir`
+    pub async fn detect_repository_conflicts(&self, repo: Repository, workdir: &Path) -> Result<ConflictInfo> {
         // Collect conflict information synchronously
         let conflict_data = {
-            let repo = Repository::open(&repo_path)?;
             let statuses = repo.statuses(None)?;
             
             let mut files_to_analyze = Vec::new();
@@ -90,17 +88,19 @@ impl GitConflictService {
                         .to_string();
                     
                     let conflict_type = self.get_conflict_type(&entry);
-                    files_to_analyze.push((file_path, conflict_type));
+                    let versions = self.get_conflict_versions(&repo, &file_path)?;
+                    files_to_analyze.push((file_path, conflict_type, versions));
                 }
             }
             
             files_to_analyze
         };
+        drop(repo);
         
         // Now analyze conflicts asynchronously
         let mut conflicted_files = Vec::new();
-        for (file_path, conflict_type) in conflict_data {
-            let conflicted_file = self.analyze_conflict_file(&repo_path, &file_path, conflict_type).await?;
+        for (file_path, conflict_type, versions) in conflict_data {
+            let conflicted_file = self.analyze_conflict_file(workdir, &file_path, conflict_type, versions).await?;
             conflicted_files.push(conflicted_file);
         }
         
@@ -140,13 +140,13 @@ impl GitConflictService {
         }
     }
 
-    async fn analyze_conflict_file(&self, repo_path: &PathBuf, file_path: &str, conflict_type: ConflictType) -> Result<ConflictedFile> {
-        // Get versions from git in a synchronous block
-        let (our_version, their_version, base_version) = {
-            let repo = Repository::open(repo_path)?;
-            self.get_conflict_versions(&repo, file_path)?
-        };
-        
+    async fn analyze_conflict_file(
+        &self,
+        repo_path: &Path,
+        file_path: &str,
+        conflict_type: ConflictType,
+        (our_version, their_version, base_version): (Option<String>, Option<String>, Option<String>),
+    ) -> Result<ConflictedFile> {
         // Read file content asynchronously
         let full_path = repo_path.join(file_path);
         let content = if full_path.exists() {
@@ -0,0 +1,471 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use git2::{Delta, Oid};
+use serde::Serialize;
+use tokio::fs;
+use uuid::Uuid;
+
+use crate::{
+    db::models::Document,
+    error::Result,
+    repository::DocumentRepository,
+    services::crdt::CrdtService,
+    services::document::{sanitize_filename, DocumentService},
+    services::git_sync::GitRepositoryScope,
+    socketio::SocketBroadcaster,
+};
+
+/// Directory next to the documents holding their uploaded files
+const ATTACHMENTS_DIR: &str = "attachments";
+
+/// Counts of what an import changed in RefMD
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct GitImportResult {
+    pub documents_created: u32,
+    pub documents_updated: u32,
+    pub documents_deleted: u32,
+    pub folders_created: u32,
+}
+
+/// Markdown file as written by `DocumentService::save_to_file_with_content`
+#[derive(Debug)]
+struct MarkdownFile {
+    id: Option<Uuid>,
+    title: Option<String>,
+    doc_type: &'static str,
+    content: String,
+}
+
+/// Directory changed files are imported from, and the folder it maps to
+struct ImportRoot<'a> {
+    user_id: Uuid,
+    folder_id: Option<Uuid>,
+    workdir: &'a Path,
+}
+
+/// A markdown file a pull added, modified or deleted
+struct ChangedFile {
+    /// Path relative to the repository's working directory
+    path: PathBuf,
+    deleted: bool,
+    /// Content after the change, or before it for deleted files
+    file: MarkdownFile,
+}
+
+/// Reconciles the markdown files a pull brought into a repository's working tree
+/// with the documents, folders and CRDT state in the database.
+///
+/// Files are matched to documents by the `id:` in their frontmatter. Changed content is
+/// applied as a CRDT update so connected clients converge, new files become documents
+/// under folders mirroring their directories, and documents whose file was deleted are
+/// removed. Only files changed by the pull are touched, so edits not yet written to disk
+/// are never overwritten by stale files. The file watcher imports files edited on disk
+/// the same way.
+pub struct GitImportService {
+    document_repo: Arc<DocumentRepository>,
+    document_service: Arc<DocumentService>,
+    crdt_service: Arc<CrdtService>,
+    broadcaster: Arc<SocketBroadcaster>,
+    upload_dir: PathBuf,
+}
+
+impl GitImportService {
+    pub fn new(
+        document_repo: Arc<DocumentRepository>,
+        document_service: Arc<DocumentService>,
+        crdt_service: Arc<CrdtService>,
+        broadcaster: Arc<SocketBroadcaster>,
+        upload_dir: PathBuf,
+    ) -> Self {
+        Self {
+            document_repo,
+            document_service,
+            crdt_service,
+            broadcaster,
+            upload_dir,
+        }
+    }
+
+    /// Commit checked out in the repository, None before its first commit
+    pub fn head_commit(&self, scope: &GitRepositoryScope) -> Result<Option<Oid>> {
+        let repo = scope.open()?;
+        let head = repo.head().ok().and_then(|head| head.target());
+        Ok(head)
+    }
+
+    /// Import the markdown files changed between `since` and the checked-out commit.
+    ///
+    /// With no `since` commit every file in the repository is imported, as after a clone.
+    pub async fn import_changes(&self, scope: &GitRepositoryScope, since: Option<Oid>) -> Result<GitImportResult> {
+        let changes = self.changed_files(scope, since)?;
+        let root = ImportRoot {
+            user_id: scope.user_id,
+            folder_id: scope.folder_id(),
+            workdir: scope.workdir(),
+        };
+        self.apply_changes(&root, changes).await
+    }
+
+    /// Import markdown files changed on disk outside RefMD.
+    ///
+    /// Paths are relative to the user's directory in the upload dir; files without
+    /// content were deleted, and are matched to the document last saved at their path.
+    pub async fn import_files(&self, user_id: Uuid, files: Vec<(PathBuf, Option<String>)>) -> Result<GitImportResult> {
+        let changes = files.into_iter()
+            .map(|(path, text)| ChangedFile {
+                path,
+                deleted: text.is_none(),
+                file: parse_markdown_file(text.as_deref().unwrap_or_default()),
+            })
+            .collect();
+
+        let workdir = self.upload_dir.join(user_id.to_string());
+        let root = ImportRoot {
+            user_id,
+            folder_id: None,
+            workdir: &workdir,
+        };
+        self.apply_changes(&root, changes).await
+    }
+
+    async fn apply_changes(&self, root: &ImportRoot<'_>, changes: Vec<ChangedFile>) -> Result<GitImportResult> {
+        let mut result = GitImportResult::default();
+        if changes.is_empty() {
+            return Ok(result);
+        }
+
+        let user_id = root.user_id;
+        let mut documents: HashMap<Uuid, Document> = self.document_repo.list_by_owner(user_id).await?
+            .into_iter()
+            .map(|document| (document.id, document))
+            .collect();
+        let mut folders: HashMap<(Option<Uuid>, String), Uuid> = documents.values()
+            .filter(|document| document.r#type == "folder")
+            .map(|folder| ((folder.parent_id, sanitize_filename(&folder.title)), folder.id))
+            .collect();
+
+        // Imports first, so a file moved within the repository is not taken for a deletion
+        let mut imported = HashSet::new();
+        for change in changes.iter().filter(|change| !change.deleted) {
+            let parent_id = self.ensure_folders(root, &change.path, &mut folders, &mut result).await?;
+            let existing = change.file.id
+                .filter(|id| !imported.contains(id))
+                .and_then(|id| documents.remove(&id))
+                .filter(|document| document.r#type != "folder");
+
+            let document = match existing {
+                Some(document) => {
+                    let (document, changed) = self.update_document(document, &change.file, parent_id).await?;
+                    if changed {
+                        result.documents_updated += 1;
+                    }
+                    document
+                }
+                None => {
+                    let document = self.create_document(user_id, &change.path, &change.file, parent_id).await?;
+                    result.documents_created += 1;
+                    document
+                }
+            };
+            imported.insert(document.id);
+
+            // RefMD keeps the document at the path derived from its title and folders, with
+            // its own frontmatter; a pulled file stored elsewhere is replaced by that copy
+            let source = root.workdir.join(&change.path);
+            let target = self.document_repo.get_by_id(document.id).await?
+                .and_then(|document| document.file_path)
+                .map(|path| self.upload_dir.join(path));
+            if target.is_some_and(|target| target != source) && fs::try_exists(&source).await? {
+                fs::remove_file(&source).await?;
+            }
+        }
+
+        for change in changes.iter().filter(|change| change.deleted) {
+            let deleted_path = root.workdir.join(&change.path);
+            let stored_path = |document: &Document| document.file_path.as_deref().map(|path| self.upload_dir.join(path));
+
+            // Without frontmatter to go by, the document saved at the deleted path
+            let id = change.file.id.or_else(|| {
+                documents.values()
+                    .find(|document| stored_path(document).as_ref() == Some(&deleted_path))
+                    .map(|document| document.id)
+            });
+            let Some(id) = id.filter(|id| !imported.contains(id)) else {
+                continue;
+            };
+            let Some(document) = documents.get(&id) else {
+                continue;
+            };
+
+            // Only documents still kept at the deleted path; they may have moved since
+            if stored_path(document) != Some(deleted_path) {
+                continue;
+            }
+
+            self.document_service.delete_document(id, user_id).await?;
+            self.crdt_service.evict_from_cache(&id);
+            result.documents_deleted += 1;
+        }
+
+        tracing::info!(
+            "Imported file changes for user {}: {} created, {} updated, {} deleted, {} folders created",
+            user_id,
+            result.documents_created,
+            result.documents_updated,
+            result.documents_deleted,
+            result.folders_created,
+        );
+
+        Ok(result)
+    }
+
+    /// Markdown files changed between two commits, read from the repository
+    fn changed_files(&self, scope: &GitRepositoryScope, since: Option<Oid>) -> Result<Vec<ChangedFile>> {
+        let repo = scope.open()?;
+        let Some(head) = repo.head().ok().and_then(|head| head.target()) else {
+            return Ok(Vec::new());
+        };
+        if since == Some(head) {
+            return Ok(Vec::new());
+        }
+
+        let new_tree = repo.find_commit(head)?.tree()?;
+        let old_tree = match since {
+            Some(oid) => Some(repo.find_commit(oid)?.tree()?),
+            None => None,
+        };
+
+        let diff = repo.diff_tree_to_tree(old_tree.as_ref(), Some(&new_tree), None)?;
+
+        let mut changes = Vec::new();
+        for delta in diff.deltas() {
+            let (deleted, file) = match delta.status() {
+                Delta::Added | Delta::Modified | Delta::Renamed | Delta::Copied => (false, delta.new_file()),
+                Delta::Deleted => (true, delta.old_file()),
+                _ => continue,
+            };
+            let Some(path) = file.path() else {
+                continue;
+            };
+            if !is_document_path(path) || scope.is_excluded(path) {
+                continue;
+            }
+
+            let blob = repo.find_blob(file.id())?;
+            let Ok(text) = std::str::from_utf8(blob.content()) else {
+                tracing::warn!("Skipping non UTF-8 file {} in git import", path.display());
+                continue;
+            };
+
+            changes.push(ChangedFile {
+                path: path.to_path_buf(),
+                deleted,
+                file: parse_markdown_file(text),
+            });
+        }
+
+        Ok(changes)
+    }
+
+    /// Find or create the folders mirroring a file's directories, returning its parent
+    async fn ensure_folders(
+        &self,
+        root: &ImportRoot<'_>,
+        path: &Path,
+        folders: &mut HashMap<(Option<Uuid>, String), Uuid>,
+        result: &mut GitImportResult,
+    ) -> Result<Option<Uuid>> {
+        let mut parent_id = root.folder_id;
+        let Some(directory) = path.parent() else {
+            return Ok(parent_id);
+        };
+
+        for component in directory.components() {
+            let Component::Normal(name) = component else {
+                continue;
+            };
+            let name = name.to_string_lossy().to_string();
+            let key = (parent_id, sanitize_filename(&name));
+
+            let folder_id = match folders.get(&key) {
+                Some(folder_id) => *folder_id,
+                None => {
+                    let folder = self.document_service
+                        .create_document(root.user_id, &name, None, "folder", parent_id)
+                        .await?;
+                    result.folders_created += 1;
+                    folders.insert(key, folder.id);
+                    folder.id
+                }
+            };
+            parent_id = Some(folder_id);
+        }
+
+        Ok(parent_id)
+    }
+
+    /// Create a document for a file RefMD does not know yet
+    async fn create_document(
+        &self,
+        user_id: Uuid,
+        path: &Path,
+        file: &MarkdownFile,
+        parent_id: Option<Uuid>,
+    ) -> Result<Document> {
+        let title = file.title.clone()
+            .or_else(|| path.file_stem().map(|stem| stem.to_string_lossy().to_string()))
+            .unwrap_or_else(|| "Untitled".to_string());
+
+        let document = self.document_service
+            .create_document(user_id, &title, Some(&file.content), file.doc_type, parent_id)
+            .await?;
+        self.crdt_service.set_document_content(document.id, &file.content, None).await?;
+        self.document_service.save_to_file_with_content(&document, &file.content).await?;
+
+        Ok(document)
+    }
+
+    /// Apply a file's title, location and content to its document, reporting whether anything changed
+    async fn update_document(&self, document: Document, file: &MarkdownFile, parent_id: Option<Uuid>) -> Result<(Document, bool)> {
+        let old_file_path = document.file_path.clone();
+        let mut document = document;
+        let mut moved = false;
+
+        if let Some(title) = file.title.as_deref().filter(|title| *title != document.title) {
+            let old_title = document.title.clone();
+            document = self.document_repo.update(document.id, document.owner_id, Some(title), None, None).await?;
+            self.document_service.rewrite_links_on_rename(&document, &old_title, document.owner_id).await;
+            self.document_service.bind_dangling_links(&document).await;
+            moved = true;
+        }
+        if document.parent_id != parent_id {
+            document = self.document_repo.update_parent(document.id, document.owner_id, parent_id).await?;
+            moved = true;
+        }
+        if moved {
+            // Takes the old file and its attachments along to the new location
+            self.document_service.move_file(&document, old_file_path.as_deref()).await?;
+        }
+
+        let mut changed = moved;
+
+        let current = self.crdt_service.get_document_content(document.id).await?;
+        if current != file.content {
+            let update = self.crdt_service.set_document_content(document.id, &file.content, None).await?;
+            if let Err(e) = self.broadcaster.broadcast_document_update(document.id, &update, None) {
+                tracing::error!("Failed to broadcast git import of document {}: {}", document.id, e);
+            }
+            changed = true;
+        }
+
+        if changed {
+            self.document_service.save_to_file_with_content(&document, &file.content).await?;
+        }
+
+        Ok((document, changed))
+    }
+}
+
+/// Markdown files outside hidden and attachment directories
+pub(crate) fn is_document_path(path: &Path) -> bool {
+    let is_markdown = path.extension().and_then(|ext| ext.to_str()) == Some("md");
+    let in_skipped_directory = path.parent().is_some_and(|directory| {
+        directory.components().any(|component| match component {
+            Component::Normal(name) => {
+                let name = name.to_string_lossy();
+                name.starts_with('.') || name == ATTACHMENTS_DIR
+            }
+            _ => true,
+        })
+    });
+    is_markdown && !in_skipped_directory
+}
+
+/// Split a markdown file into its RefMD frontmatter fields and content
+fn parse_markdown_file(text: &str) -> MarkdownFile {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut file = MarkdownFile {
+        id: None,
+        title: None,
+        doc_type: "document",
+        content: text.to_string(),
+    };
+
+    let Some(rest) = text.strip_prefix("---\n").or_else(|| text.strip_prefix("---\r\n")) else {
+        return file;
+    };
+    let mut frontmatter_end = None;
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            frontmatter_end = Some((offset, offset + line.len()));
+            break;
+        }
+        offset += line.len();
+    }
+    let Some((end, content_start)) = frontmatter_end else {
+        return file;
+    };
+
+    for line in rest[..end].lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "id" => file.id = Uuid::parse_str(value).ok(),
+            "title" if !value.is_empty() => file.title = Some(value.to_string()),
+            "type" if value == "scrap" => file.doc_type = "scrap",
+            _ => {}
+        }
+    }
+
+    // The frontmatter is followed by a blank line
+    let content = &rest[content_start..];
+    let content = content.strip_prefix("\r\n").or_else(|| content.strip_prefix('\n')).unwrap_or(content);
+    file.content = content.to_string();
+    file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_markdown_file_with_frontmatter() {
+        let text = "---\nid: 3f2504e0-4f89-11d3-9a0c-0305e82c3301\ntitle: Meeting: notes\ncreated_at: 2025-01-01 00:00:00 UTC\nupdated_at: 2025-01-01 00:00:00 UTC\n---\n\n# Agenda\n---\nend";
+        let file = parse_markdown_file(text);
+
+        assert_eq!(file.id, Some(Uuid::parse_str("3f2504e0-4f89-11d3-9a0c-0305e82c3301").unwrap()));
+        assert_eq!(file.title.as_deref(), Some("Meeting: notes"));
+        assert_eq!(file.doc_type, "document");
+        assert_eq!(file.content, "# Agenda\n---\nend");
+    }
+
+    #[test]
+    fn test_parse_markdown_file_scrap() {
+        let file = parse_markdown_file("---\nid: not-a-uuid\ntitle: Ideas\ntype: scrap\n---\n\nbody");
+
+        assert_eq!(file.id, None);
+        assert_eq!(file.doc_type, "scrap");
+        assert_eq!(file.content, "body");
+    }
+
+    #[test]
+    fn test_parse_markdown_file_without_frontmatter() {
+        let file = parse_markdown_file("# Plain\n\ntext");
+
+        assert_eq!(file.id, None);
+        assert_eq!(file.title, None);
+        assert_eq!(file.content, "# Plain\n\ntext");
+    }
+
+    #[test]
+    fn test_is_document_path() {
+        assert!(is_document_path(Path::new("Work/Meeting.md")));
+        assert!(!is_document_path(Path::new("Work/attachments/notes.md")));
+        assert!(!is_document_path(Path::new(".git-folders/notes.md")));
+        assert!(!is_document_path(Path::new("Work/image.png")));
+    }
+}
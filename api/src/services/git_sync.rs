@@ -1,19 +1,99 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::collections::HashMap;
-use git2::{Repository, Signature, RemoteCallbacks, Cred, PushOptions, FetchOptions, MergeOptions};
+use git2::{Repository, RepositoryInitOptions, Signature, RemoteCallbacks, Cred, PushOptions, FetchOptions, MergeOptions, StatusOptions};
 use uuid::Uuid;
 use chrono::{Utc, DateTime};
 use tokio::sync::RwLock;
 
 use crate::{
-    entities::git_config::{GitConfig, GitStatus, GitSyncResponse},
+    entities::git_config::{GitConfig, GitRepositorySyncStatus, GitStatus, GitSyncLogResponse, GitSyncResponse},
     repository::GitConfigRepository,
     utils::encryption::EncryptionService,
+    services::document::sanitize_filename,
     services::git_conflict::{GitConflictService, ConflictInfo},
     error::{Error, Result},
 };
 
+/// Directory inside the user's directory holding the git directories of folder repositories
+const FOLDER_REPOSITORIES_DIR: &str = ".git-folders";
+/// Sync logs of each folder repository included in the root repository's status
+const RECENT_LOG_LIMIT: i32 = 10;
+
+/// A git repository covering part of a user's document tree.
+///
+/// The root repository lives in the user's directory and covers everything that is not
+/// mapped to another repository. A folder repository keeps its git directory under
+/// `.git-folders/<config id>`, outside the folder, so renaming or moving the folder
+/// keeps its history. Each repository excludes the folders mapped to other repositories.
+#[derive(Debug, Clone)]
+pub struct GitRepositoryScope {
+    pub user_id: Uuid,
+    /// Configuration of the repository; the root repository may have none
+    pub config: Option<GitConfig>,
+    workdir: PathBuf,
+    git_dir: Option<PathBuf>,
+    /// Paths relative to `workdir` that belong to other repositories
+    excluded: Vec<String>,
+}
+
+impl GitRepositoryScope {
+    /// Configuration id of a folder repository, None for the root repository
+    pub fn folder_config_id(&self) -> Option<Uuid> {
+        self.config.as_ref().filter(|config| config.folder_id.is_some()).map(|config| config.id)
+    }
+
+    /// Folder a folder repository is mapped to, None for the root repository
+    pub fn folder_id(&self) -> Option<Uuid> {
+        self.config.as_ref().and_then(|config| config.folder_id)
+    }
+
+    /// Directory the repository's files are checked out to
+    pub fn workdir(&self) -> &Path {
+        &self.workdir
+    }
+
+    /// Whether a path relative to the working directory belongs to another repository
+    pub fn is_excluded(&self, relative_path: &Path) -> bool {
+        self.excluded.iter().any(|excluded| relative_path.starts_with(excluded))
+    }
+
+    /// Key identifying the repository among all users' repositories
+    fn key(&self) -> Uuid {
+        self.folder_config_id().unwrap_or(self.user_id)
+    }
+
+    /// Open the repository and keep its excludes in line with the current mappings
+    pub fn open(&self) -> Result<Repository> {
+        let repo = match &self.git_dir {
+            Some(git_dir) => {
+                let repo = Repository::open(git_dir)?;
+                repo.set_workdir(&self.workdir, false)?;
+                repo
+            }
+            None => Repository::open(&self.workdir)?,
+        };
+        self.write_excludes(&repo)?;
+        Ok(repo)
+    }
+
+    fn write_excludes(&self, repo: &Repository) -> Result<()> {
+        let exclude_path = repo.path().join("info").join("exclude");
+        let mut content = String::from("# Managed by RefMD: folders synced to other repositories\n");
+        for path in &self.excluded {
+            content.push_str(&format!("/{}/\n", path));
+        }
+
+        if std::fs::read_to_string(&exclude_path).ok().as_deref() != Some(content.as_str()) {
+            if let Some(parent) = exclude_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&exclude_path, content)?;
+        }
+        Ok(())
+    }
+}
+
 pub struct GitSyncService {
     git_config_repo: Arc<GitConfigRepository>,
     upload_dir: PathBuf,
@@ -36,17 +116,103 @@ impl GitSyncService {
         self.upload_dir.join(user_id.to_string())
     }
 
-    pub async fn init_repository(&self, user_id: Uuid) -> Result<()> {
-        let repo_path = self.get_user_repo_path(user_id);
+    /// All repositories of a user: the root repository first, then one per mapped folder
+    pub async fn repository_scopes(&self, user_id: Uuid) -> Result<Vec<GitRepositoryScope>> {
+        let user_dir = self.get_user_repo_path(user_id);
+
+        let mut root_config = None;
+        let mut folders = Vec::new();
+        for config in self.git_config_repo.list_by_user(user_id).await? {
+            let Some(folder_id) = config.folder_id else {
+                root_config = Some(config);
+                continue;
+            };
+            // Same layout as the document files: one directory per folder title
+            if let Some(titles) = self.git_config_repo.get_folder_titles(user_id, folder_id).await? {
+                let path = titles.iter().map(|title| sanitize_filename(title)).collect::<Vec<_>>().join("/");
+                folders.push((config, path));
+            }
+        }
+
+        let mut root_excluded = vec![FOLDER_REPOSITORIES_DIR.to_string()];
+        root_excluded.extend(folders.iter().map(|(_, path)| path.clone()));
+
+        let mut scopes = vec![GitRepositoryScope {
+            user_id,
+            config: root_config,
+            workdir: user_dir.clone(),
+            git_dir: None,
+            excluded: root_excluded,
+        }];
+
+        for (config, path) in &folders {
+            let prefix = format!("{}/", path);
+            let excluded = folders.iter()
+                .filter_map(|(_, other)| other.strip_prefix(&prefix))
+                .map(|nested| nested.to_string())
+                .collect();
+
+            scopes.push(GitRepositoryScope {
+                user_id,
+                config: Some(config.clone()),
+                workdir: user_dir.join(path),
+                git_dir: Some(user_dir.join(FOLDER_REPOSITORIES_DIR).join(config.id.to_string())),
+                excluded,
+            });
+        }
+
+        Ok(scopes)
+    }
+
+    /// The repository covering the user's whole document tree
+    pub async fn root_scope(&self, user_id: Uuid) -> Result<GitRepositoryScope> {
+        Ok(self.repository_scopes(user_id).await?.remove(0))
+    }
+
+    pub async fn folder_scope(&self, user_id: Uuid, config_id: Uuid) -> Result<GitRepositoryScope> {
+        self.repository_scopes(user_id).await?
+            .into_iter()
+            .find(|scope| scope.folder_config_id() == Some(config_id))
+            .ok_or_else(|| Error::NotFound("Git repository not found".to_string()))
+    }
+
+    /// The repository holding a file, given its path relative to the upload directory
+    pub async fn scope_for_path(&self, user_id: Uuid, relative_path: &str) -> Result<GitRepositoryScope> {
+        let path = self.upload_dir.join(relative_path);
+        let mut scopes = self.repository_scopes(user_id).await?;
+
+        // The deepest mapped folder containing the file wins
+        let folder = scopes.iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, scope)| path.starts_with(&scope.workdir))
+            .max_by_key(|(_, scope)| scope.workdir.components().count())
+            .map(|(index, _)| index);
+
+        Ok(scopes.remove(folder.unwrap_or(0)))
+    }
+
+    pub async fn init_repository(&self, scope: &GitRepositoryScope) -> Result<()> {
+        let user_id = scope.user_id;
         
         // Create directory if it doesn't exist
-        tokio::fs::create_dir_all(&repo_path).await?;
+        tokio::fs::create_dir_all(&scope.workdir).await?;
+
+        // Initialize git repository; folder repositories keep their git directory outside the folder
+        let init_result = match &scope.git_dir {
+            Some(git_dir) => {
+                let mut options = RepositoryInitOptions::new();
+                options.bare(true);
+                Repository::init_opts(git_dir, &options)
+            }
+            None => Repository::init(&scope.workdir),
+        };
 
-        // Initialize git repository
-        match Repository::init(&repo_path) {
+        match init_result {
             Ok(_) => {
                 self.git_config_repo.log_sync_operation(
                     user_id,
+                    scope.folder_config_id(),
                     "init",
                     "success",
                     Some("Repository initialized"),
@@ -54,13 +220,14 @@ impl GitSyncService {
                 ).await?;
                 
                 // Create default .gitignore
-                self.create_default_gitignore(user_id).await?;
+                self.create_default_gitignore(scope).await?;
                 
                 Ok(())
             },
             Err(e) => {
                 self.git_config_repo.log_sync_operation(
                     user_id,
+                    scope.folder_config_id(),
                     "init",
                     "error",
                     Some(&e.to_string()),
@@ -71,14 +238,12 @@ impl GitSyncService {
         }
     }
 
-    pub async fn get_status(&self, user_id: Uuid) -> Result<GitStatus> {
-        let repo_path = self.get_user_repo_path(user_id);
-        let config = self.git_config_repo.get_by_user_id(user_id).await?;
+    pub async fn get_status(&self, scope: &GitRepositoryScope) -> Result<GitStatus> {
+        let user_id = scope.user_id;
+        let config = scope.config.as_ref();
 
         // Check if repository is initialized
-        let repository_initialized = Repository::open(&repo_path).is_ok();
-        
-        if !repository_initialized {
+        let Ok(repo) = scope.open() else {
             return Ok(GitStatus {
                 repository_initialized: false,
                 has_remote: false,
@@ -87,10 +252,10 @@ impl GitSyncService {
                 untracked_files: 0,
                 last_sync: None,
                 sync_enabled: config.map(|c| c.auto_sync).unwrap_or(false),
+                last_batch_sync: self.git_config_repo.get_batch_status(user_id, scope.folder_config_id()).await?,
+                repositories: self.folder_repository_statuses(scope).await?,
             });
-        }
-
-        let repo = Repository::open(&repo_path)?;
+        };
         
         // Check remote
         let has_remote = repo.remotes().map(|r| r.len() > 0).unwrap_or(false);
@@ -103,7 +268,10 @@ impl GitSyncService {
 
         // Get status information (extract values before async call)
         let (uncommitted_changes, untracked_files) = {
-            let statuses = match repo.statuses(None) {
+            // Ignored files, including folders synced to other repositories, are not changes
+            let mut options = StatusOptions::new();
+            options.include_untracked(true).include_ignored(false);
+            let statuses = match repo.statuses(Some(&mut options)) {
                 Ok(statuses) => statuses,
                 Err(e) => {
                     return Err(Error::BadRequest(format!("Failed to get repository status: {}", e)));
@@ -117,9 +285,10 @@ impl GitSyncService {
                 .count() as u32;
             (uncommitted, untracked)
         };
+        drop(repo);
 
         // Get last sync from logs
-        let logs = self.git_config_repo.get_sync_logs(user_id, 1).await?;
+        let logs = self.git_config_repo.get_sync_logs(user_id, scope.folder_config_id(), 1).await?;
         let last_sync = logs.first().map(|log| log.created_at);
 
         Ok(GitStatus {
@@ -130,11 +299,39 @@ impl GitSyncService {
             untracked_files,
             last_sync,
             sync_enabled: config.map(|c| c.auto_sync).unwrap_or(false),
+            last_batch_sync: self.git_config_repo.get_batch_status(user_id, scope.folder_config_id()).await?,
+            repositories: self.folder_repository_statuses(scope).await?,
         })
     }
 
-    pub async fn add_and_commit(&self, user_id: Uuid, message: Option<String>) -> Result<String> {
-        let repo_path = self.get_user_repo_path(user_id);
+    /// Per-repository sync state of the user's folder repositories, reported with the root repository
+    async fn folder_repository_statuses(&self, scope: &GitRepositoryScope) -> Result<Vec<GitRepositorySyncStatus>> {
+        if scope.folder_id().is_some() {
+            return Ok(Vec::new());
+        }
+
+        let mut repositories = Vec::new();
+        for config in self.git_config_repo.list_by_user(scope.user_id).await? {
+            if config.folder_id.is_none() {
+                continue;
+            }
+            let logs = self.git_config_repo.get_sync_logs(scope.user_id, Some(config.id), RECENT_LOG_LIMIT).await?;
+            repositories.push(GitRepositorySyncStatus {
+                id: config.id,
+                folder_id: config.folder_id,
+                repository_url: config.repository_url,
+                auto_sync: config.auto_sync,
+                sync_interval_secs: config.sync_interval_secs,
+                last_sync: logs.first().map(|log| log.created_at),
+                last_batch_sync: self.git_config_repo.get_batch_status(scope.user_id, Some(config.id)).await?,
+                logs: logs.into_iter().map(GitSyncLogResponse::from).collect(),
+            });
+        }
+        Ok(repositories)
+    }
+
+    pub async fn add_and_commit(&self, scope: &GitRepositoryScope, message: Option<String>) -> Result<String> {
+        let user_id = scope.user_id;
         
         let commit_message = message.unwrap_or_else(|| {
             format!("Auto-sync documents - {}", Utc::now().format("%Y-%m-%d %H:%M:%S UTC"))
@@ -142,11 +339,15 @@ impl GitSyncService {
 
         // Perform git operations in a block to ensure git2 objects are dropped before await
         let commit_hash = {
-            let repo = Repository::open(&repo_path)?;
+            let repo = scope.open()?;
 
             // Add all files to index
             let mut index = repo.index()?;
             index.add_all(["."], git2::IndexAddOption::DEFAULT, None)?;
+            // Stop tracking folders that have been mapped to their own repository
+            if !scope.excluded.is_empty() {
+                index.remove_all(&scope.excluded, None)?;
+            }
             index.write()?;
 
             let tree_id = index.write_tree()?;
@@ -187,6 +388,7 @@ impl GitSyncService {
 
         self.git_config_repo.log_sync_operation(
             user_id,
+            scope.folder_config_id(),
             "commit",
             "success",
             Some(&commit_message),
@@ -196,11 +398,14 @@ impl GitSyncService {
         Ok(commit_hash)
     }
 
-    pub async fn push_to_remote(&self, user_id: Uuid) -> Result<()> {
-        // Check if a push is already in progress for this user
+    pub async fn push_to_remote(&self, scope: &GitRepositoryScope) -> Result<()> {
+        let user_id = scope.user_id;
+        let repo_key = scope.key();
+
+        // Check if a push is already in progress for this repository
         {
             let push_map = self.push_in_progress.read().await;
-            if let Some(last_push) = push_map.get(&user_id) {
+            if let Some(last_push) = push_map.get(&repo_key) {
                 let time_since_push = Utc::now().signed_duration_since(*last_push);
                 if time_since_push < chrono::Duration::seconds(10) {
                     tracing::info!("Push already in progress for user {}, skipping", user_id);
@@ -212,17 +417,15 @@ impl GitSyncService {
         // Mark push as in progress
         {
             let mut push_map = self.push_in_progress.write().await;
-            push_map.insert(user_id, Utc::now());
+            push_map.insert(repo_key, Utc::now());
         }
         
-        let config = self.git_config_repo.get_by_user_id(user_id).await?
+        let config = scope.config.clone()
             .ok_or_else(|| Error::BadRequest("Git config not found".to_string()))?;
-
-        let repo_path = self.get_user_repo_path(user_id);
         
         // Perform git operations in a block to ensure git2 objects are dropped before await
         let push_result = {
-            let repo = Repository::open(&repo_path)?;
+            let repo = scope.open()?;
 
             // Set up remote if not exists
             let remote_name = "origin";
@@ -266,6 +469,7 @@ impl GitSyncService {
             Ok(_) => {
                 self.git_config_repo.log_sync_operation(
                     user_id,
+                    scope.folder_config_id(),
                     "push",
                     "success",
                     Some("Successfully pushed to remote"),
@@ -289,6 +493,7 @@ impl GitSyncService {
                 
                 self.git_config_repo.log_sync_operation(
                     user_id,
+                    scope.folder_config_id(),
                     "push",
                     "error",
                     Some(detailed_error),
@@ -301,21 +506,20 @@ impl GitSyncService {
         // Remove from push tracking
         {
             let mut push_map = self.push_in_progress.write().await;
-            push_map.remove(&user_id);
+            push_map.remove(&repo_key);
         }
         
         result
     }
 
-    pub async fn pull_from_remote(&self, user_id: Uuid) -> Result<()> {
-        let config = self.git_config_repo.get_by_user_id(user_id).await?
+    pub async fn pull_from_remote(&self, scope: &GitRepositoryScope) -> Result<()> {
+        let user_id = scope.user_id;
+        let config = scope.config.clone()
             .ok_or_else(|| Error::BadRequest("Git config not found".to_string()))?;
-
-        let repo_path = self.get_user_repo_path(user_id);
         
         // Perform git operations in a block to ensure git2 objects are dropped before await
         let pull_result = {
-            let repo = Repository::open(&repo_path)?;
+            let repo = scope.open()?;
 
             // Set up remote
             let remote_name = "origin";
@@ -340,13 +544,14 @@ impl GitSyncService {
         match pull_result {
             Ok(_) => {
                 // After successful fetch, try to merge
-                let merge_result = self.merge_fetched_branch(user_id, &config.branch_name).await;
+                let merge_result = self.merge_fetched_branch(scope, &config.branch_name).await;
                 
                 match merge_result {
                     Ok(conflict_info) => {
                         if conflict_info.has_conflicts {
                             self.git_config_repo.log_sync_operation(
                                 user_id,
+                                scope.folder_config_id(),
                                 "pull",
                                 "conflict",
                                 Some("Pull completed with conflicts"),
@@ -356,6 +561,7 @@ impl GitSyncService {
                         } else {
                             self.git_config_repo.log_sync_operation(
                                 user_id,
+                                scope.folder_config_id(),
                                 "pull",
                                 "success",
                                 Some("Successfully pulled and merged from remote"),
@@ -367,6 +573,7 @@ impl GitSyncService {
                     Err(e) => {
                         self.git_config_repo.log_sync_operation(
                             user_id,
+                            scope.folder_config_id(),
                             "pull",
                             "error",
                             Some(&format!("Merge failed: {}", e)),
@@ -379,6 +586,7 @@ impl GitSyncService {
             Err(e) => {
                 self.git_config_repo.log_sync_operation(
                     user_id,
+                    scope.folder_config_id(),
                     "pull",
                     "error",
                     Some(&e.to_string()),
@@ -389,12 +597,10 @@ impl GitSyncService {
         }
     }
 
-    async fn merge_fetched_branch(&self, user_id: Uuid, branch_name: &str) -> Result<ConflictInfo> {
-        let repo_path = self.get_user_repo_path(user_id);
-        
+    async fn merge_fetched_branch(&self, scope: &GitRepositoryScope, branch_name: &str) -> Result<ConflictInfo> {
         // Perform git operations in a synchronous block
         let merge_result = {
-            let repo = Repository::open(&repo_path)?;
+            let repo = scope.open()?;
             
             // Get the fetched branch reference
             let fetch_head = format!("refs/remotes/origin/{}", branch_name);
@@ -416,10 +622,13 @@ impl GitSyncService {
             }
             
             if merge_analysis.is_fast_forward() {
-                // Fast-forward merge
-                let refname = format!("refs/heads/{}", branch_name);
-                let mut reference = repo.find_reference(&refname)?;
-                reference.set_target(annotated_commit.id(), "Fast-forward merge")?;
+                // Fast-forward the checked-out branch, which may be named differently from the
+                // remote branch; a freshly initialized repository gets its branch created here
+                let refname = match repo.find_reference("HEAD")?.symbolic_target() {
+                    Some(target) => target.to_string(),
+                    None => format!("refs/heads/{}", branch_name),
+                };
+                repo.reference(&refname, annotated_commit.id(), true, "Fast-forward merge")?;
                 repo.set_head(&refname)?;
                 repo.checkout_head(None)?;
                 
@@ -441,13 +650,12 @@ impl GitSyncService {
         
         // If merge was performed, check for conflicts
         if merge_result {
-            let conflict_service = GitConflictService::new(self.upload_dir.clone());
-            let conflict_info = conflict_service.detect_conflicts(user_id).await?;
+            let conflict_info = self.get_conflicts(scope).await?;
             
             if !conflict_info.has_conflicts {
                 // No conflicts, create merge commit in a synchronous block
                 {
-                    let repo = Repository::open(&repo_path)?;
+                    let repo = scope.open()?;
                     let fetch_head = format!("refs/remotes/origin/{}", branch_name);
                     let annotated_commit = repo.find_annotated_commit(
                         repo.refname_to_id(&fetch_head)?
@@ -487,16 +695,33 @@ impl GitSyncService {
         }
     }
 
-    pub async fn get_conflicts(&self, user_id: Uuid) -> Result<ConflictInfo> {
+    pub async fn get_conflicts(&self, scope: &GitRepositoryScope) -> Result<ConflictInfo> {
         let conflict_service = GitConflictService::new(self.upload_dir.clone());
-        conflict_service.detect_conflicts(user_id).await
+        conflict_service.detect_repository_conflicts(scope.open()?, &scope.workdir).await
+    }
+
+    pub async fn sync(&self, scope: &GitRepositoryScope, message: Option<String>, _force: bool) -> Result<GitSyncResponse> {
+        let (commit_hash, files_changed) = self.commit_pending(scope, message).await?;
+        self.push_if_configured(scope, commit_hash.as_deref()).await?;
+
+        Ok(GitSyncResponse {
+            success: true,
+            message: format!("Sync completed successfully. {} files changed.", files_changed),
+            commit_hash,
+            files_changed,
+        })
     }
 
-    pub async fn sync(&self, user_id: Uuid, message: Option<String>, _force: bool) -> Result<GitSyncResponse> {
+    /// Initialize the repository if needed and commit pending changes.
+    ///
+    /// Returns the new commit, if one was created, and the number of changed files.
+    pub async fn commit_pending(&self, scope: &GitRepositoryScope, message: Option<String>) -> Result<(Option<String>, u32)> {
         // Check if repository is initialized
-        let status = self.get_status(user_id).await?;
+        let mut status = self.get_status(scope).await?;
         if !status.repository_initialized {
-            self.init_repository(user_id).await?;
+            self.init_repository(scope).await?;
+            // Existing documents are untracked in the new repository
+            status = self.get_status(scope).await?;
         }
 
         let mut files_changed = 0;
@@ -505,48 +730,48 @@ impl GitSyncService {
         // Commit changes if any
         if status.uncommitted_changes > 0 || status.untracked_files > 0 {
             files_changed = status.uncommitted_changes + status.untracked_files;
-            commit_hash = Some(self.add_and_commit(user_id, message).await?);
+            commit_hash = Some(self.add_and_commit(scope, message).await?);
         } else {
             // Check if there are any commits at all - if not, create initial commit
-            let repo_path = self.get_user_repo_path(user_id);
-            if let Ok(repo) = Repository::open(&repo_path) {
+            if let Ok(repo) = scope.open() {
                 if repo.head().is_err() {
                     // No commits yet, create initial commit even if no files
-                    commit_hash = Some(self.add_and_commit(user_id, message.or_else(|| Some("Initial commit".to_string()))).await?);
+                    commit_hash = Some(self.add_and_commit(scope, message.or_else(|| Some("Initial commit".to_string()))).await?);
                     files_changed = 0;
                 }
             }
         }
 
-        // Push to remote if configured
-        let config = self.git_config_repo.get_by_user_id(user_id).await?;
-        if let Some(_config) = config {
+        Ok((commit_hash, files_changed))
+    }
+
+    /// Push to the configured remote. Returns false when the repository has no git configuration.
+    pub async fn push_if_configured(&self, scope: &GitRepositoryScope, commit_hash: Option<&str>) -> Result<bool> {
+        let user_id = scope.user_id;
+        if scope.config.is_some() {
             // Always try to push if config exists - push_to_remote will handle remote setup
             self.git_config_repo.log_sync_operation(
                 user_id,
+                scope.folder_config_id(),
                 "push", 
                 "success",
                 Some("Starting push to remote"),
-                commit_hash.as_deref(),
+                commit_hash,
             ).await?;
             
-            self.push_to_remote(user_id).await?;
+            self.push_to_remote(scope).await?;
+            Ok(true)
         } else {
             self.git_config_repo.log_sync_operation(
                 user_id,
+                scope.folder_config_id(),
                 "push",
                 "error", 
                 Some("No Git configuration found"),
                 None,
             ).await?;
+            Ok(false)
         }
-
-        Ok(GitSyncResponse {
-            success: true,
-            message: format!("Sync completed successfully. {} files changed.", files_changed),
-            commit_hash,
-            files_changed,
-        })
     }
 
     fn setup_auth_callbacks<'a>(&self, callbacks: &mut RemoteCallbacks<'a>, config: &'a GitConfig) -> Result<()> {
@@ -588,9 +813,8 @@ impl GitSyncService {
         Ok(())
     }
 
-    pub async fn create_default_gitignore(&self, user_id: Uuid) -> Result<()> {
-        let repo_path = self.get_user_repo_path(user_id);
-        let gitignore_path = repo_path.join(".gitignore");
+    pub async fn create_default_gitignore(&self, scope: &GitRepositoryScope) -> Result<()> {
+        let gitignore_path = scope.workdir.join(".gitignore");
         
         // Check if .gitignore already exists
         if gitignore_path.exists() {
@@ -634,14 +858,13 @@ __pycache__/
         tokio::fs::write(&gitignore_path, gitignore_content).await?;
         
         // Commit the .gitignore file
-        self.add_and_commit(user_id, Some("Add default .gitignore".to_string())).await?;
+        self.add_and_commit(scope, Some("Add default .gitignore".to_string())).await?;
         
         Ok(())
     }
 
-    pub async fn add_to_gitignore(&self, user_id: Uuid, patterns: Vec<String>) -> Result<()> {
-        let repo_path = self.get_user_repo_path(user_id);
-        let gitignore_path = repo_path.join(".gitignore");
+    pub async fn add_to_gitignore(&self, scope: &GitRepositoryScope, patterns: Vec<String>) -> Result<()> {
+        let gitignore_path = scope.workdir.join(".gitignore");
         
         // Read existing content or create new
         let mut content = if gitignore_path.exists() {
@@ -662,25 +885,22 @@ __pycache__/
         tokio::fs::write(&gitignore_path, content).await?;
         
         // Commit the changes
-        self.add_and_commit(user_id, Some("Update .gitignore".to_string())).await?;
+        self.add_and_commit(scope, Some("Update .gitignore".to_string())).await?;
         
         Ok(())
     }
 
-    pub async fn is_path_ignored(&self, user_id: Uuid, path: &str) -> Result<bool> {
-        let repo_path = self.get_user_repo_path(user_id);
-        
+    pub async fn is_path_ignored(&self, scope: &GitRepositoryScope, path: &str) -> Result<bool> {
         let is_ignored = {
-            let repo = Repository::open(&repo_path)?;
+            let repo = scope.open()?;
             repo.is_path_ignored(std::path::Path::new(path))?
         };
         
         Ok(is_ignored)
     }
 
-    pub async fn get_gitignore_patterns(&self, user_id: Uuid) -> Result<Vec<String>> {
-        let repo_path = self.get_user_repo_path(user_id);
-        let gitignore_path = repo_path.join(".gitignore");
+    pub async fn get_gitignore_patterns(&self, scope: &GitRepositoryScope) -> Result<Vec<String>> {
+        let gitignore_path = scope.workdir.join(".gitignore");
         
         if !gitignore_path.exists() {
             return Ok(Vec::new());
@@ -696,10 +916,8 @@ __pycache__/
         Ok(patterns)
     }
 
-    pub async fn get_commit_history(&self, user_id: Uuid, limit: Option<usize>) -> Result<Vec<GitCommit>> {
-        let repo_path = self.get_user_repo_path(user_id);
-        
-        let repo = Repository::open(&repo_path)?;
+    pub async fn get_commit_history(&self, scope: &GitRepositoryScope, limit: Option<usize>) -> Result<Vec<GitCommit>> {
+        let repo = scope.open()?;
         let mut revwalk = repo.revwalk()?;
         revwalk.push_head()?;
         revwalk.set_sorting(git2::Sort::TIME)?;
@@ -763,18 +981,24 @@ __pycache__/
     }
 
     pub async fn get_file_history(&self, user_id: Uuid, file_path: &str, limit: Option<usize>) -> Result<Vec<GitCommit>> {
-        let repo_path = self.get_user_repo_path(user_id);
-        
         // Remove user_id prefix from file_path if present
-        let cleaned_path = if file_path.starts_with(&format!("{}/", user_id)) {
+        let user_path = if file_path.starts_with(&format!("{}/", user_id)) {
             file_path.strip_prefix(&format!("{}/", user_id)).unwrap()
         } else {
             file_path
         };
         
+        // Files in a mapped folder are tracked by that folder's repository
+        let scope = self.scope_for_path(user_id, &format!("{}/{}", user_id, user_path)).await?;
+        let scoped_path = self.get_user_repo_path(user_id).join(user_path);
+        let cleaned_path = scoped_path.strip_prefix(&scope.workdir)
+            .ok()
+            .and_then(|path| path.to_str())
+            .unwrap_or(user_path);
+        
         tracing::info!("get_file_history - original path: {}, cleaned path: {}", file_path, cleaned_path);
         
-        let repo = Repository::open(&repo_path)?;
+        let repo = scope.open()?;
         let mut revwalk = repo.revwalk()?;
         revwalk.push_head()?;
         revwalk.set_sorting(git2::Sort::TIME)?;
@@ -883,15 +1107,16 @@ __pycache__/
         Ok(commits)
     }
 
-    pub async fn deinit_repository(&self, user_id: Uuid) -> Result<()> {
-        let repo_path = self.get_user_repo_path(user_id);
+    pub async fn deinit_repository(&self, scope: &GitRepositoryScope) -> Result<()> {
+        let user_id = scope.user_id;
+        let repo_path = &scope.workdir;
         
         // Check if user directory exists first
-        if !repo_path.exists() {
+        if scope.git_dir.is_none() && !repo_path.exists() {
             return Err(Error::BadRequest("No repository directory found for this user".to_string()));
         }
         
-        let git_dir = repo_path.join(".git");
+        let git_dir = scope.git_dir.clone().unwrap_or_else(|| repo_path.join(".git"));
         
         // Check if .git directory exists
         if !git_dir.exists() {
@@ -913,19 +1138,23 @@ __pycache__/
             }
         }
         
-        // Delete Git configuration from database if it exists
-        // Ignore errors as the config might not exist
-        let _ = self.git_config_repo.delete(user_id).await;
-        
         // Log the operation - ignore errors as this is not critical
         let _ = self.git_config_repo.log_sync_operation(
             user_id,
+            scope.folder_config_id(),
             "deinit",
             "success",
             Some("Repository deinitialized and configuration removed"),
             None,
         ).await;
         
+        // Delete Git configuration from database if it exists
+        // Ignore errors as the config might not exist
+        let _ = match scope.folder_config_id() {
+            Some(config_id) => self.git_config_repo.delete_by_id(user_id, config_id).await,
+            None => self.git_config_repo.delete(user_id).await,
+        };
+        
         Ok(())
     }
 }
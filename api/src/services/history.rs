@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+
+use crate::crdt::{CrdtDocument, DocumentPersistence, TextChunk};
+use crate::entities::snapshot::{AuthorshipSpan, BlameRange, DocumentSnapshot, SNAPSHOT_KIND_AUTO, SNAPSHOT_KIND_MANUAL};
+use crate::error::{Error, Result};
+use crate::repository::SnapshotRepository;
+use crate::services::crdt::CrdtService;
+use crate::socketio::SocketBroadcaster;
+
+/// Result of restoring a document to an earlier point in time
+pub struct RestoreOutcome {
+    pub restored_from: DateTime<Utc>,
+    pub backup_snapshot: DocumentSnapshot,
+    pub content: String,
+}
+
+/// Service for document version history built on the CRDT update log
+pub struct DocumentHistoryService {
+    snapshot_repository: Arc<SnapshotRepository>,
+    document_persistence: Arc<DocumentPersistence>,
+    crdt_service: Arc<CrdtService>,
+    broadcaster: Arc<SocketBroadcaster>,
+}
+
+impl DocumentHistoryService {
+    pub fn new(
+        snapshot_repository: Arc<SnapshotRepository>,
+        document_persistence: Arc<DocumentPersistence>,
+        crdt_service: Arc<CrdtService>,
+        broadcaster: Arc<SocketBroadcaster>,
+    ) -> Self {
+        Self {
+            snapshot_repository,
+            document_persistence,
+            crdt_service,
+            broadcaster,
+        }
+    }
+
+    /// List snapshots of a document, newest first
+    pub async fn list_snapshots(&self, document_id: Uuid) -> Result<Vec<DocumentSnapshot>> {
+        self.snapshot_repository.list_by_document(document_id).await
+    }
+
+    /// Create a named snapshot at the current point in time
+    pub async fn create_snapshot(
+        &self,
+        document_id: Uuid,
+        name: Option<&str>,
+        created_by: Option<Uuid>,
+    ) -> Result<DocumentSnapshot> {
+        let name = name.map(str::trim).filter(|n| !n.is_empty());
+        self.snapshot_repository
+            .create(document_id, name, SNAPSHOT_KIND_MANUAL, created_by)
+            .await
+    }
+
+    /// Create an automatic snapshot if the document changed since the last one
+    pub async fn create_auto_snapshot(
+        &self,
+        document_id: Uuid,
+        label: &str,
+    ) -> Result<Option<DocumentSnapshot>> {
+        if !self.snapshot_repository.has_changes_since_last_snapshot(document_id).await? {
+            return Ok(None);
+        }
+
+        let snapshot = self.snapshot_repository
+            .create(document_id, Some(label), SNAPSHOT_KIND_AUTO, None)
+            .await?;
+        Ok(Some(snapshot))
+    }
+
+    /// Resolve a snapshot id or timestamp into the point in time to replay up to
+    pub async fn resolve_point(
+        &self,
+        document_id: Uuid,
+        snapshot_id: Option<Uuid>,
+        at: Option<DateTime<Utc>>,
+    ) -> Result<DateTime<Utc>> {
+        match (snapshot_id, at) {
+            (Some(snapshot_id), _) => {
+                let snapshot = self.snapshot_repository
+                    .get_by_id(document_id, snapshot_id)
+                    .await?
+                    .ok_or_else(|| Error::NotFound("Snapshot not found".to_string()))?;
+                Ok(snapshot.created_at)
+            }
+            (None, Some(at)) => Ok(at),
+            (None, None) => Err(Error::BadRequest("Either snapshot_id or at is required".to_string())),
+        }
+    }
+
+    /// Render the document markdown as it was at a given time
+    pub async fn get_content_at(&self, document_id: Uuid, at: DateTime<Utc>) -> Result<String> {
+        let updates = self.document_persistence.get_updates_until(document_id, at).await?;
+        let doc = CrdtDocument::from_updates(document_id, &updates)?;
+        doc.get_content()
+    }
+
+    /// Restore the document to its content at a given time.
+    ///
+    /// The old content is applied as a new CRDT update on top of the current state,
+    /// so connected clients converge instead of being reset.
+    pub async fn restore(
+        &self,
+        document_id: Uuid,
+        at: DateTime<Utc>,
+        restored_by: Option<Uuid>,
+    ) -> Result<RestoreOutcome> {
+        let content = self.get_content_at(document_id, at).await?;
+
+        // Keep the current state reachable before overwriting it
+        let backup_snapshot = self.snapshot_repository
+            .create(document_id, Some("Before restore"), SNAPSHOT_KIND_AUTO, restored_by)
+            .await?;
+
+        let update = self.crdt_service.set_document_content(document_id, &content, restored_by).await?;
+
+        if let Err(e) = self.broadcaster.broadcast_document_update(document_id, &update, None) {
+            tracing::error!("Failed to broadcast restore of document {}: {}", document_id, e);
+        }
+
+        Ok(RestoreOutcome {
+            restored_from: at,
+            backup_snapshot,
+            content,
+        })
+    }
+
+    /// Attribute each range of the current content to the author who last wrote it
+    pub async fn blame(&self, document_id: Uuid) -> Result<(String, Vec<BlameRange>)> {
+        let spans = self.document_persistence.get_authorship(document_id).await?;
+        let doc = self.crdt_service.load_or_create_document(document_id).await?;
+        let (content, chunks) = {
+            let doc = doc.read();
+            (doc.get_content()?, doc.text_chunks())
+        };
+
+        Ok((content, attribute_chunks(&chunks, &spans)))
+    }
+}
+
+/// Split text chunks into ranges by the recorded insertion covering each character.
+///
+/// Adjacent ranges of the same client and author are merged, keeping the latest time.
+fn attribute_chunks(chunks: &[TextChunk], spans: &[AuthorshipSpan]) -> Vec<BlameRange> {
+    let mut ranges: Vec<BlameRange> = Vec::new();
+    let mut position = 0;
+    let mut spans_by_client: HashMap<u64, Vec<&AuthorshipSpan>> = HashMap::new();
+    for span in spans {
+        spans_by_client.entry(span.client_id as u64).or_default().push(span);
+    }
+
+    for chunk in chunks {
+        let client_spans = spans_by_client.get(&chunk.client_id).map(Vec::as_slice).unwrap_or_default();
+        let mut clock = chunk.clock as i64;
+        let mut chars = chunk.text.chars().peekable();
+
+        while chars.peek().is_some() {
+            // Spans are oldest first, so the first covering one recorded the insertion
+            let span = client_spans
+                .iter()
+                .find(|span| span.clock_start <= clock && clock < span.clock_end);
+            let limit = match span {
+                Some(span) => span.clock_end,
+                None => client_spans
+                    .iter()
+                    .map(|span| span.clock_start)
+                    .filter(|start| *start > clock)
+                    .min()
+                    .unwrap_or(i64::MAX),
+            };
+
+            let start = position;
+            while let Some(c) = chars.next_if(|_| clock < limit) {
+                clock += c.len_utf16() as i64;
+                position += 1;
+            }
+
+            let range = BlameRange {
+                start,
+                end: position,
+                client_id: chunk.client_id,
+                author_id: span.and_then(|span| span.author_id),
+                author_name: span.and_then(|span| span.author_name.clone()),
+                changed_at: span.map(|span| span.created_at),
+            };
+            match ranges.last_mut() {
+                Some(last) if last.end == range.start
+                    && last.client_id == range.client_id
+                    && last.author_id == range.author_id => {
+                    last.end = range.end;
+                    last.changed_at = last.changed_at.max(range.changed_at);
+                }
+                _ => ranges.push(range),
+            }
+        }
+    }
+
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_attribute_chunks() {
+        let alice = Uuid::new_v4();
+        let at = |h: u32| Utc.with_ymd_and_hms(2025, 7, 9, h, 0, 0).unwrap();
+        let span = |client_id: i64, clocks: std::ops::Range<i64>, author_id: Option<Uuid>, h: u32| AuthorshipSpan {
+            client_id,
+            clock_start: clocks.start,
+            clock_end: clocks.end,
+            author_id,
+            author_name: author_id.map(|_| "alice".to_string()),
+            created_at: at(h),
+        };
+        let spans = vec![span(1, 0..3, Some(alice), 9), span(1, 3..6, Some(alice), 10), span(2, 0..2, None, 11)];
+        let chunk = |client_id: u64, clock: u32, text: &str| TextChunk { client_id, clock, text: text.to_string() };
+
+        // Client 1 typed "héllo!" over two updates, client 2 inserted "ab" in between,
+        // and client 3 has no recorded insertions
+        let chunks = vec![chunk(1, 0, "hél"), chunk(2, 0, "ab"), chunk(1, 3, "lo!"), chunk(3, 0, "?")];
+        let ranges: Vec<_> = attribute_chunks(&chunks, &spans)
+            .into_iter()
+            .map(|range| (range.start, range.end, range.client_id, range.author_id, range.changed_at))
+            .collect();
+
+        assert_eq!(ranges, vec![
+            (0, 3, 1, Some(alice), Some(at(9))),
+            (3, 5, 2, None, Some(at(11))),
+            (5, 8, 1, Some(alice), Some(at(10))),
+            (8, 9, 3, None, None),
+        ]);
+    }
+}
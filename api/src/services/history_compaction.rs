@@ -0,0 +1,120 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::interval;
+use chrono::Utc;
+
+use crate::crdt::DocumentPersistence;
+use crate::error::Result;
+use crate::repository::SnapshotRepository;
+
+/// Background job that merges old CRDT update history into checkpoints.
+///
+/// Updates newer than the retention window are kept as-is. Older updates are merged
+/// into one checkpoint per day, split at named snapshots so those stay restorable.
+/// Automatic snapshots older than the window are dropped.
+#[derive(Clone)]
+pub struct HistoryCompactionService {
+    document_persistence: Arc<DocumentPersistence>,
+    snapshot_repository: Arc<SnapshotRepository>,
+    retention: chrono::Duration,
+    compaction_interval: Duration,
+    is_running: Arc<Mutex<bool>>,
+}
+
+impl HistoryCompactionService {
+    pub fn new(
+        document_persistence: Arc<DocumentPersistence>,
+        snapshot_repository: Arc<SnapshotRepository>,
+        retention_days: i64,
+        compaction_interval_secs: u64,
+    ) -> Self {
+        Self {
+            document_persistence,
+            snapshot_repository,
+            retention: chrono::Duration::days(retention_days),
+            compaction_interval: Duration::from_secs(compaction_interval_secs.max(1)),
+            is_running: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    pub async fn start(&self) {
+        let mut is_running = self.is_running.lock().await;
+        if *is_running {
+            tracing::warn!("HistoryCompactionService is already running");
+            return;
+        }
+        *is_running = true;
+        drop(is_running);
+
+        let service = self.clone();
+        tokio::spawn(async move {
+            service.run_compaction_loop().await;
+        });
+    }
+
+    pub async fn stop(&self) {
+        let mut is_running = self.is_running.lock().await;
+        *is_running = false;
+    }
+
+    async fn run_compaction_loop(&self) {
+        let mut ticker = interval(self.compaction_interval);
+
+        loop {
+            ticker.tick().await;
+
+            let is_running = self.is_running.lock().await;
+            if !*is_running {
+                tracing::info!("HistoryCompactionService stopping");
+                break;
+            }
+            drop(is_running);
+
+            if let Err(e) = self.compact_all().await {
+                tracing::error!("History compaction failed: {}", e);
+            }
+        }
+    }
+
+    /// Run one compaction pass over all documents
+    pub async fn compact_all(&self) -> Result<usize> {
+        let cutoff = Utc::now() - self.retention;
+
+        let pruned = self.snapshot_repository.delete_auto_before(cutoff).await?;
+        if pruned > 0 {
+            tracing::info!("Pruned {} automatic snapshots older than {}", pruned, cutoff);
+        }
+
+        let document_ids = self.document_persistence.get_documents_with_history_before(cutoff).await?;
+        let mut total_removed = 0;
+
+        for document_id in document_ids {
+            let boundaries: Vec<_> = self.snapshot_repository
+                .list_by_document(document_id)
+                .await?
+                .into_iter()
+                .map(|snapshot| snapshot.created_at)
+                .filter(|created_at| *created_at <= cutoff)
+                .collect();
+
+            match self.document_persistence.compact_history(document_id, cutoff, &boundaries).await {
+                Ok(removed) => {
+                    if removed > 0 {
+                        tracing::debug!("Compacted {} history rows for document {}", removed, document_id);
+                    }
+                    total_removed += removed;
+                }
+                Err(e) => {
+                    tracing::error!("Failed to compact history for document {}: {}", document_id, e);
+                }
+            }
+        }
+
+        if total_removed > 0 {
+            tracing::info!("History compaction removed {} rows", total_removed);
+        }
+
+        Ok(total_removed)
+    }
+}
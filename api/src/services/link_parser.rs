@@ -1,12 +1,16 @@
 use regex::Regex;
 use std::collections::HashSet;
+use std::ops::Range;
 use uuid::Uuid;
 use once_cell::sync::Lazy;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct DocumentLink {
     pub target: LinkTarget,
+    /// Heading or block referenced with `#`, if any
+    pub fragment: Option<LinkFragment>,
     pub link_type: LinkType,
+    pub syntax: LinkSyntax,
     pub link_text: Option<String>,
     pub position_start: usize,
     pub position_end: usize,
@@ -18,6 +22,15 @@ pub enum LinkTarget {
     Title(String),
 }
 
+/// Part of a document a link points into
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LinkFragment {
+    /// `[[Doc#Heading]]`
+    Heading(String),
+    /// `[[Doc#^block-id]]`
+    Block(String),
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum LinkType {
     Reference,
@@ -25,6 +38,15 @@ pub enum LinkType {
     Mention,
 }
 
+/// How a link is written in markdown
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkSyntax {
+    /// `[[Doc]]`, `![[Doc]]` or `@[[Doc]]`
+    Wiki,
+    /// `[text](/documents/<uuid>)` or `[text](relative/path/Doc.md)`
+    Markdown,
+}
+
 impl LinkType {
     pub fn as_str(&self) -> &'static str {
         match self {
@@ -48,13 +70,22 @@ static MENTION_REGEX: Lazy<Regex> = Lazy::new(|| {
     Regex::new(r"@\[\[([^\[\]|]+)(?:\|([^\[\]]+))?\]\]").unwrap()
 });
 
+// Inline markdown link with an optional title: [text](url "title")
+static MARKDOWN_LINK_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"\[([^\[\]]*)\]\(\s*<?([^()\s<>]+)>?(?:\s+(?:"[^"]*"|'[^']*'))?\s*\)"#).unwrap()
+});
+
 pub struct LinkParser;
 
 impl LinkParser {
-    /// Parse markdown content and extract all document links
+    /// Parse markdown content and extract all document links.
+    ///
+    /// Links inside code blocks, code spans and HTML comments are ignored.
     pub fn parse_links(content: &str) -> Vec<DocumentLink> {
         let mut links = Vec::new();
         let mut processed_positions = HashSet::new();
+        let ignored = ignored_ranges(content);
+        let is_ignored = |position: usize| ignored.iter().any(|range| range.contains(&position));
 
         // Parse embed links first (they start with !)
         for cap in EMBED_LINK_REGEX.captures_iter(content) {
@@ -62,7 +93,7 @@ impl LinkParser {
             let start = mat.start();
             let end = mat.end();
             
-            if processed_positions.contains(&start) {
+            if processed_positions.contains(&start) || is_ignored(start) {
                 continue;
             }
             processed_positions.insert(start);
@@ -70,11 +101,15 @@ impl LinkParser {
             let target_text = cap.get(1).unwrap().as_str();
             let display_text = cap.get(2).map(|m| m.as_str().to_string());
             
-            let target = Self::parse_target(target_text);
+            let Some((target, fragment)) = Self::parse_target(target_text) else {
+                continue;
+            };
             
             links.push(DocumentLink {
                 target,
+                fragment,
                 link_type: LinkType::Embed,
+                syntax: LinkSyntax::Wiki,
                 link_text: display_text,
                 position_start: start,
                 position_end: end,
@@ -87,7 +122,7 @@ impl LinkParser {
             let start = mat.start();
             let end = mat.end();
             
-            if processed_positions.contains(&start) {
+            if processed_positions.contains(&start) || is_ignored(start) {
                 continue;
             }
             processed_positions.insert(start);
@@ -95,11 +130,15 @@ impl LinkParser {
             let target_text = cap.get(1).unwrap().as_str();
             let display_text = cap.get(2).map(|m| m.as_str().to_string());
             
-            let target = Self::parse_target(target_text);
+            let Some((target, fragment)) = Self::parse_target(target_text) else {
+                continue;
+            };
             
             links.push(DocumentLink {
                 target,
+                fragment,
                 link_type: LinkType::Mention,
+                syntax: LinkSyntax::Wiki,
                 link_text: display_text,
                 position_start: start,
                 position_end: end,
@@ -112,41 +151,114 @@ impl LinkParser {
             let start = mat.start();
             let end = mat.end();
             
-            // Skip if this position was already processed (as embed or mention)
-            if processed_positions.contains(&start) {
+            // Skip the inner part of embeds and mentions, which start one character earlier
+            if processed_positions.contains(&start) || (start > 0 && processed_positions.contains(&(start - 1))) || is_ignored(start) {
                 continue;
             }
             
             let target_text = cap.get(1).unwrap().as_str();
             let display_text = cap.get(2).map(|m| m.as_str().to_string());
             
-            let target = Self::parse_target(target_text);
+            let Some((target, fragment)) = Self::parse_target(target_text) else {
+                continue;
+            };
             
             links.push(DocumentLink {
                 target,
+                fragment,
                 link_type: LinkType::Reference,
+                syntax: LinkSyntax::Wiki,
                 link_text: display_text,
                 position_start: start,
                 position_end: end,
             });
         }
 
+        // Parse standard markdown links to documents, skipping images
+        for cap in MARKDOWN_LINK_REGEX.captures_iter(content) {
+            let mat = cap.get(0).unwrap();
+            let start = mat.start();
+            if is_ignored(start) || content[..start].ends_with('!') {
+                continue;
+            }
+            
+            let Some((target, fragment)) = Self::parse_markdown_target(cap.get(2).unwrap().as_str()) else {
+                continue;
+            };
+            let display_text = cap.get(1).map(|m| m.as_str().trim()).filter(|text| !text.is_empty());
+            
+            links.push(DocumentLink {
+                target,
+                fragment,
+                link_type: LinkType::Reference,
+                syntax: LinkSyntax::Markdown,
+                link_text: display_text.map(str::to_string),
+                position_start: start,
+                position_end: mat.end(),
+            });
+        }
+
         // Sort by position for consistent ordering
         links.sort_by_key(|link| link.position_start);
         
         links
     }
 
-    /// Parse a target string into either a UUID or a title
-    fn parse_target(target: &str) -> LinkTarget {
+    /// Parse a target string into either a UUID or a title, and the fragment after `#`.
+    ///
+    /// Returns None for links within the same document, such as `[[#Heading]]`.
+    fn parse_target(target: &str) -> Option<(LinkTarget, Option<LinkFragment>)> {
+        let (target, fragment) = match target.split_once('#') {
+            Some((target, fragment)) => (target, LinkFragment::parse(fragment)),
+            None => (target, None),
+        };
         let trimmed = target.trim();
+        if trimmed.is_empty() {
+            return None;
+        }
         
         // Try to parse as UUID first
-        if let Ok(uuid) = Uuid::parse_str(trimmed) {
+        let target = if let Ok(uuid) = Uuid::parse_str(trimmed) {
             LinkTarget::Id(uuid)
         } else {
             LinkTarget::Title(trimmed.to_string())
+        };
+        Some((target, fragment))
+    }
+
+    /// Parse the destination of a markdown link into a document target.
+    ///
+    /// `/documents/<uuid>` (or the app's `/document/<uuid>`) links by id; a relative
+    /// path to a `.md` file links by title, which imported documents take from their
+    /// file name. Other destinations, such as external URLs, are not document links.
+    fn parse_markdown_target(url: &str) -> Option<(LinkTarget, Option<LinkFragment>)> {
+        let (path, fragment) = match url.split_once('#') {
+            Some((path, fragment)) => (path, LinkFragment::parse(&percent_decode(fragment))),
+            None => (url, None),
+        };
+        let path = path.split_once('?').map_or(path, |(path, _)| path);
+        
+        let id = path.strip_prefix("/documents/").or_else(|| path.strip_prefix("/document/"));
+        if let Some(id) = id {
+            let id = Uuid::parse_str(id.trim_end_matches('/')).ok()?;
+            return Some((LinkTarget::Id(id), fragment));
+        }
+        
+        // Absolute paths and anything with a scheme (http:, mailto:, ...) point elsewhere
+        if path.starts_with('/') || path.contains(':') {
+            return None;
+        }
+        let path = percent_decode(path);
+        let file_name = path.rsplit('/').next()?;
+        let extension = file_name.len().checked_sub(3)?;
+        if !file_name.is_char_boundary(extension) || !file_name[extension..].eq_ignore_ascii_case(".md") {
+            return None;
+        }
+        let title = file_name[..extension].trim();
+        if title.is_empty() {
+            return None;
         }
+        Some((LinkTarget::Title(title.to_string()), fragment))
     }
 
     /// Extract unique document references from content
@@ -155,7 +267,10 @@ impl LinkParser {
         links.into_iter().map(|link| link.target).collect()
     }
 
-    /// Replace link targets in content (useful for updating links when documents are renamed)
+    /// Replace link targets in content (useful for updating links when documents are renamed).
+    ///
+    /// Markdown links to a `.md` path get the new target as file name, keeping their
+    /// directory; markdown links by id are left alone.
     pub fn update_link_targets<F>(content: &str, mut updater: F) -> String 
     where
         F: FnMut(&LinkTarget) -> Option<String>,
@@ -165,7 +280,23 @@ impl LinkParser {
         
         // Process links in reverse order to maintain positions
         for link in links.iter().rev() {
+            if link.syntax == LinkSyntax::Markdown {
+                if matches!(link.target, LinkTarget::Title(_)) {
+                    if let Some(new_target) = updater(&link.target) {
+                        let written = &content[link.position_start..link.position_end];
+                        if let Some(rewritten) = rename_markdown_path(written, &new_target) {
+                            result.replace_range(link.position_start..link.position_end, &rewritten);
+                        }
+                    }
+                }
+                continue;
+            }
+            
             if let Some(new_target) = updater(&link.target) {
+                let new_target = match &link.fragment {
+                    Some(fragment) => format!("{}#{}", new_target, fragment.as_text()),
+                    None => new_target,
+                };
                 let link_content = match &link.link_type {
                     LinkType::Embed => {
                         if let Some(text) = &link.link_text {
@@ -196,6 +327,355 @@ impl LinkParser {
         
         result
     }
+
+    /// Point title links at a renamed document, keeping any `|alias` display text, and
+    /// relative `.md` path links at its new file name.
+    ///
+    /// Titles match case-insensitively, as when resolving. Returns the new content and
+    /// the number of links rewritten.
+    pub fn rename_title_links(content: &str, old_title: &str, new_title: &str) -> (String, usize) {
+        let old_title = old_title.trim().to_lowercase();
+        let new_title = new_title.trim();
+        let mut renamed = 0;
+        
+        let content = Self::update_link_targets(content, |target| match target {
+            LinkTarget::Title(title) if title.to_lowercase() == old_title => {
+                renamed += 1;
+                Some(new_title.to_string())
+            }
+            _ => None,
+        });
+        
+        (content, renamed)
+    }
+}
+
+impl LinkFragment {
+    /// Parse the text after `#` in a link target
+    pub fn parse(fragment: &str) -> Option<Self> {
+        let fragment = fragment.trim();
+        match fragment.strip_prefix('^') {
+            Some(id) if is_block_id(id) => Some(LinkFragment::Block(id.to_string())),
+            Some(_) => None,
+            None if !fragment.is_empty() => Some(LinkFragment::Heading(fragment.to_string())),
+            None => None,
+        }
+    }
+
+    /// The fragment as written after `#`
+    pub fn as_text(&self) -> String {
+        match self {
+            LinkFragment::Heading(heading) => heading.clone(),
+            LinkFragment::Block(id) => format!("^{}", id),
+        }
+    }
+
+    /// Byte range of the section this fragment points to in markdown content.
+    ///
+    /// A heading matches by its text or by its anchor slug (`#data-model` for
+    /// `## Data Model`). Its section runs from the heading to the next heading of the
+    /// same or a higher level. A block is the paragraph or list item ending with ` ^block-id`, or
+    /// the paragraph before a line holding only the id.
+    pub fn section_range(&self, content: &str) -> Option<Range<usize>> {
+        let lines = markdown_lines(content);
+        match self {
+            LinkFragment::Heading(heading) => {
+                let wanted = normalize_heading(heading);
+                let wanted_slug = heading_slug(heading);
+                let (index, level) = lines.iter().enumerate().find_map(|(index, line)| {
+                    line.heading
+                        .filter(|(_, text)| normalize_heading(text) == wanted || heading_slug(text) == wanted_slug)
+                        .map(|(level, _)| (index, level))
+                })?;
+                let end = lines[index + 1..]
+                    .iter()
+                    .find(|line| line.heading.is_some_and(|(other, _)| other <= level))
+                    .map_or(content.len(), |line| line.range.start);
+                Some(lines[index].range.start..end)
+            }
+            LinkFragment::Block(id) => {
+                let index = lines.iter().position(|line| line.block_id == Some(id.as_str()))?;
+                let text = &content[lines[index].range.clone()];
+                let standalone = text.trim() == format!("^{}", id);
+
+                let last = if standalone { index.checked_sub(1)? } else { index };
+                if lines[last].blank {
+                    return None;
+                }
+                let first = if !standalone && (lines[last].heading.is_some() || is_list_item(&content[lines[last].range.clone()])) {
+                    last
+                } else {
+                    (0..last).rev().find(|&i| lines[i].blank || lines[i].heading.is_some()).map_or(0, |i| i + 1)
+                };
+                Some(lines[first].range.start..lines[last].range.end)
+            }
+        }
+    }
+
+    /// Text of the section this fragment points to, without a block's id marker
+    pub fn section(&self, content: &str) -> Option<String> {
+        let range = self.section_range(content)?;
+        let section = content[range].trim_end();
+        Some(match self {
+            LinkFragment::Heading(_) => section.to_string(),
+            LinkFragment::Block(id) => section
+                .strip_suffix(&format!("^{}", id))
+                .map_or(section, |text| text.trim_end())
+                .to_string(),
+        })
+    }
+}
+
+/// A line of markdown outside fenced code, as far as sections are concerned
+struct MarkdownLine<'a> {
+    /// Byte range of the line without its line break
+    range: Range<usize>,
+    blank: bool,
+    heading: Option<(usize, &'a str)>,
+    block_id: Option<&'a str>,
+}
+
+fn markdown_lines(content: &str) -> Vec<MarkdownLine<'_>> {
+    let mut lines = Vec::new();
+    let mut fence: Option<&str> = None;
+    let mut start = 0;
+
+    for line in content.split_inclusive('\n') {
+        let range = start..start + line.trim_end_matches(['\r', '\n']).len();
+        start += line.len();
+        let text = &content[range.clone()];
+
+        let marker = fence_marker(text);
+        if let Some(open) = fence {
+            if marker == Some(open) {
+                fence = None;
+            }
+            lines.push(MarkdownLine { range, blank: false, heading: None, block_id: None });
+            continue;
+        }
+        if marker.is_some() {
+            fence = marker;
+            lines.push(MarkdownLine { range, blank: false, heading: None, block_id: None });
+            continue;
+        }
+
+        lines.push(MarkdownLine {
+            range,
+            blank: text.trim().is_empty(),
+            heading: parse_heading(text),
+            block_id: parse_block_id(text),
+        });
+    }
+
+    lines
+}
+
+/// Byte ranges of code blocks, code spans and HTML comments, where nothing is a link.
+///
+/// Code spans are only recognised within a single line.
+fn ignored_ranges(content: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut fence: Option<(&str, usize)> = None;
+    let mut comment_start: Option<usize> = None;
+    let mut offset = 0;
+
+    for line in content.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+
+        if let Some((open, fence_start)) = fence {
+            if fence_marker(line) == Some(open) {
+                ranges.push(fence_start..offset);
+                fence = None;
+            }
+            continue;
+        }
+        if comment_start.is_none() {
+            if let Some(marker) = fence_marker(line) {
+                fence = Some((marker, line_start));
+                continue;
+            }
+        }
+
+        let mut i = 0;
+        while i < line.len() {
+            let rest = &line[i..];
+            if let Some(start) = comment_start {
+                match rest.find("-->") {
+                    Some(close) => {
+                        i += close + 3;
+                        ranges.push(start..line_start + i);
+                        comment_start = None;
+                    }
+                    None => break,
+                }
+            } else if rest.starts_with("<!--") {
+                comment_start = Some(line_start + i);
+                i += 4;
+            } else if rest.starts_with('`') {
+                // A code span closes with a run of exactly as many backticks
+                let run = rest.len() - rest.trim_start_matches('`').len();
+                match find_backtick_run(&rest[run..], run) {
+                    Some(close) => {
+                        ranges.push(line_start + i..line_start + i + run + close + run);
+                        i += run + close + run;
+                    }
+                    None => i += run,
+                }
+            } else {
+                i += rest.chars().next().map_or(1, char::len_utf8);
+            }
+        }
+    }
+
+    // Unclosed blocks and comments run to the end of the content
+    if let Some((_, start)) = fence {
+        ranges.push(start..content.len());
+    }
+    if let Some(start) = comment_start {
+        ranges.push(start..content.len());
+    }
+    ranges
+}
+
+/// Offset of the first run of exactly `len` backticks in text
+fn find_backtick_run(text: &str, len: usize) -> Option<usize> {
+    let mut i = 0;
+    while let Some(found) = text[i..].find('`') {
+        let start = i + found;
+        let run = text[start..].len() - text[start..].trim_start_matches('`').len();
+        if run == len {
+            return Some(start);
+        }
+        i = start + run;
+    }
+    None
+}
+
+/// Marker of a line opening or closing a fenced code block
+fn fence_marker(line: &str) -> Option<&'static str> {
+    let trimmed = line.trim_start();
+    ["```", "~~~"].into_iter().find(|marker| trimmed.starts_with(marker))
+}
+
+/// Level and text of an ATX heading line
+fn parse_heading(line: &str) -> Option<(usize, &str)> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+    let line = &line[indent..];
+    let level = line.len() - line.trim_start_matches('#').len();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let rest = &line[level..];
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+
+    // Drop an optional closing sequence of #s
+    let text = rest.trim();
+    let text = match text.trim_end_matches('#') {
+        stripped if stripped.is_empty() || stripped.ends_with([' ', '\t']) => stripped.trim_end(),
+        _ => text,
+    };
+    Some((level, text))
+}
+
+/// Block id marked with ` ^block-id` at the end of a line
+fn parse_block_id(line: &str) -> Option<&str> {
+    let line = line.trim_end();
+    let caret = line.rfind('^')?;
+    let id = &line[caret + 1..];
+    let preceded_by_space = caret == 0 || line[..caret].ends_with([' ', '\t']);
+    (preceded_by_space && is_block_id(id)).then_some(id)
+}
+
+fn is_block_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+fn is_list_item(line: &str) -> bool {
+    let line = line.trim_start();
+    if line.starts_with("- ") || line.starts_with("* ") || line.starts_with("+ ") {
+        return true;
+    }
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    digits > 0 && (line[digits..].starts_with(". ") || line[digits..].starts_with(") "))
+}
+
+fn normalize_heading(heading: &str) -> String {
+    heading.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// Anchor slug of a heading as markdown renderers generate it
+fn heading_slug(heading: &str) -> String {
+    heading
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' | '\t' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Decode `%XX` escapes in a link destination, leaving invalid escapes as written
+/// Point a markdown link `[text](dir/Old.md#fragment)` at the file named after `title`,
+/// keeping its directory, extension, fragment and link title
+fn rename_markdown_path(written: &str, title: &str) -> Option<String> {
+    let destination = MARKDOWN_LINK_REGEX.captures(written)?.get(2)?;
+    let url = destination.as_str();
+    let path_end = url.find(['#', '?']).unwrap_or(url.len());
+    let file_start = url[..path_end].rfind('/').map_or(0, |slash| slash + 1);
+    let extension = path_end.checked_sub(3).filter(|&extension| extension >= file_start)?;
+    
+    let mut rewritten = written.to_string();
+    let file_name = destination.start() + file_start..destination.start() + extension;
+    rewritten.replace_range(file_name, &percent_encode_file_name(title.trim()));
+    Some(rewritten)
+}
+
+/// Escape what a markdown link destination or path cannot hold as is
+fn percent_encode_file_name(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_whitespace() || c.is_control() || "%#?()<>/\\".contains(c) {
+            let mut bytes = [0; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                encoded.push_str(&format!("%{:02X}", byte));
+            }
+        } else {
+            encoded.push(c);
+        }
+    }
+    encoded
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
 }
 
 #[cfg(test)]
@@ -260,4 +740,108 @@ mod tests {
         
         assert_eq!(updated, "Link to [[New Title]] and [[Keep This]].");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_rename_title_links() {
+        let content = "See [[old title]], ![[Old Title|the plan]] and @[[Old Title]], not [[Old Titles]].";
+        let (updated, renamed) = LinkParser::rename_title_links(content, "Old Title", "New Title");
+        
+        assert_eq!(renamed, 3);
+        assert_eq!(updated, "See [[New Title]], ![[New Title|the plan]] and @[[New Title]], not [[Old Titles]].");
+        
+        let (unchanged, renamed) = LinkParser::rename_title_links(content, "Other", "New Title");
+        assert_eq!(renamed, 0);
+        assert_eq!(unchanged, content);
+    }
+
+    #[test]
+    fn test_parse_fragments() {
+        let content = "[[Spec#Data Model]], ![[Spec#^req-12|requirement]], [[Spec#^not valid]] and [[#Local]].";
+        let links = LinkParser::parse_links(content);
+        
+        assert_eq!(links.len(), 3);
+        assert_eq!(links[0].target, LinkTarget::Title("Spec".to_string()));
+        assert_eq!(links[0].fragment, Some(LinkFragment::Heading("Data Model".to_string())));
+        assert_eq!(links[1].link_type, LinkType::Embed);
+        assert_eq!(links[1].fragment, Some(LinkFragment::Block("req-12".to_string())));
+        assert_eq!(links[1].link_text, Some("requirement".to_string()));
+        assert_eq!(links[2].fragment, None);
+        
+        let (renamed, _) = LinkParser::rename_title_links(content, "Spec", "Specification");
+        assert!(renamed.starts_with("[[Specification#Data Model]], ![[Specification#^req-12|requirement]]"));
+    }
+
+    #[test]
+    fn test_heading_sections() {
+        let content = "# Spec\nIntro\n\n## Data Model\nTables\n```\n# not a heading\n```\n### Keys\nIds\n## API ##\nRoutes\n";
+        let section = |heading: &str| LinkFragment::Heading(heading.to_string()).section(content);
+        
+        assert_eq!(
+            section("data  model").as_deref(),
+            Some("## Data Model\nTables\n```\n# not a heading\n```\n### Keys\nIds")
+        );
+        assert_eq!(section("Keys").as_deref(), Some("### Keys\nIds"));
+        assert_eq!(section("API").as_deref(), Some("## API ##\nRoutes"));
+        assert_eq!(section("not a heading"), None);
+    }
+
+    #[test]
+    fn test_ignore_code_and_comments() {
+        let content = "[[Real]] and `[[Inline]]` and ``a ` [[Double]]``\n```md\n[[Fenced]]\n```\n<!-- [[Hidden]]\n![[Hidden Embed]] -->\n~~~\n@[[Tilde]]\n~~~\n`unclosed [[Also Real]]\n";
+        let links = LinkParser::parse_links(content);
+        let targets: Vec<_> = links.iter().map(|link| link.target.clone()).collect();
+        
+        assert_eq!(targets, vec![
+            LinkTarget::Title("Real".to_string()),
+            LinkTarget::Title("Also Real".to_string()),
+        ]);
+        
+        let (renamed, count) = LinkParser::rename_title_links("`[[Old]]` [[Old]]", "Old", "New");
+        assert_eq!(count, 1);
+        assert_eq!(renamed, "`[[Old]]` [[New]]");
+    }
+
+    #[test]
+    fn test_parse_markdown_links() {
+        let uuid = Uuid::new_v4();
+        let content = format!(
+            "See [the spec](/documents/{}#^req-1), [plan](../notes/Release%20Plan.md#data-model \"Plan\"), \
+             [site](https://example.com/a.md), ![diagram](Diagram.md), [](./Empty.MD) and `[code](Code.md)`.",
+            uuid
+        );
+        let links = LinkParser::parse_links(&content);
+        
+        assert_eq!(links.len(), 3);
+        assert_eq!(links[0].target, LinkTarget::Id(uuid));
+        assert_eq!(links[0].fragment, Some(LinkFragment::Block("req-1".to_string())));
+        assert_eq!(links[0].syntax, LinkSyntax::Markdown);
+        assert_eq!(links[0].link_text, Some("the spec".to_string()));
+        assert_eq!(links[1].target, LinkTarget::Title("Release Plan".to_string()));
+        assert_eq!(links[1].fragment, Some(LinkFragment::Heading("data-model".to_string())));
+        assert_eq!(links[2].target, LinkTarget::Title("Empty".to_string()));
+        assert_eq!(links[2].link_text, None);
+        
+        // Path links follow the target's new file name
+        let (renamed, count) = LinkParser::rename_title_links(&content, "Release Plan", "Q3 (Roadmap)");
+        assert_eq!(count, 1);
+        assert_eq!(renamed, content.replace("Release%20Plan.md", "Q3%20%28Roadmap%29.md"));
+        assert_eq!(LinkParser::parse_links(&renamed)[1].target, LinkTarget::Title("Q3 (Roadmap)".to_string()));
+        let (renamed, count) = LinkParser::rename_title_links(&content, "empty", "Full");
+        assert_eq!(count, 1);
+        assert!(renamed.contains("[](./Full.MD)"));
+        
+        let section = LinkFragment::Heading("data-model".to_string()).section("## Data Model\nTables\n");
+        assert_eq!(section.as_deref(), Some("## Data Model\nTables"));
+    }
+
+    #[test]
+    fn test_block_sections() {
+        let content = "First line\nof a paragraph ^para\n\n- one\n- two ^item\n\n| a | b |\n| - | - |\n^table\n";
+        let section = |id: &str| LinkFragment::Block(id.to_string()).section(content);
+        
+        assert_eq!(section("para").as_deref(), Some("First line\nof a paragraph"));
+        assert_eq!(section("item").as_deref(), Some("- two"));
+        assert_eq!(section("table").as_deref(), Some("| a | b |\n| - | - |"));
+        assert_eq!(section("missing"), None);
+    }
+}
@@ -4,7 +4,7 @@ use uuid::Uuid;
 use crate::{
     error::Result,
     db::models::Document,
-    services::link_parser::{LinkTarget, DocumentLink},
+    services::link_parser::{LinkFragment, LinkTarget, DocumentLink},
 };
 
 pub struct LinkResolver {
@@ -28,7 +28,7 @@ impl LinkResolver {
                            crdt_state, version, COALESCE(visibility, 'private') as "visibility!", published_at,
                            created_at as "created_at!", updated_at as "updated_at!", last_edited_by, last_edited_at
                     FROM documents
-                    WHERE id = $1 AND (owner_id = $2 OR id IN (
+                    WHERE id = $1 AND deleted_at IS NULL AND (owner_id = $2 OR id IN (
                         SELECT document_id FROM document_permissions 
                         WHERE user_id = $2 AND permission >= 'view'
                     ))
@@ -50,7 +50,7 @@ impl LinkResolver {
                            crdt_state, version, COALESCE(visibility, 'private') as "visibility!", published_at,
                            created_at as "created_at!", updated_at as "updated_at!", last_edited_by, last_edited_at
                     FROM documents
-                    WHERE LOWER(title) = LOWER($1) AND (owner_id = $2 OR id IN (
+                    WHERE LOWER(title) = LOWER($1) AND deleted_at IS NULL AND (owner_id = $2 OR id IN (
                         SELECT document_id FROM document_permissions 
                         WHERE user_id = $2 AND permission >= 'view'
                     ))
@@ -77,17 +77,17 @@ impl LinkResolver {
         // Separate IDs and titles for batch processing
         let mut ids = Vec::new();
         let mut titles = Vec::new();
-        let mut target_map = std::collections::HashMap::new();
+        let mut target_map: std::collections::HashMap<String, Vec<usize>> = std::collections::HashMap::new();
 
         for (idx, target) in targets.iter().enumerate() {
             match target {
                 LinkTarget::Id(id) => {
                     ids.push(*id);
-                    target_map.insert(format!("id:{}", id), idx);
+                    target_map.entry(format!("id:{}", id)).or_default().push(idx);
                 }
                 LinkTarget::Title(title) => {
                     titles.push(title.clone());
-                    target_map.insert(format!("title:{}", title.to_lowercase()), idx);
+                    target_map.entry(format!("title:{}", title.to_lowercase())).or_default().push(idx);
                 }
             }
         }
@@ -103,7 +103,7 @@ impl LinkResolver {
                        crdt_state, version, COALESCE(visibility, 'private') as "visibility!", published_at,
                        created_at as "created_at!", updated_at as "updated_at!", last_edited_by, last_edited_at
                 FROM documents
-                WHERE id = ANY($1) AND (owner_id = $2 OR id IN (
+                WHERE id = ANY($1) AND deleted_at IS NULL AND (owner_id = $2 OR id IN (
                     SELECT document_id FROM document_permissions 
                     WHERE user_id = $2 AND permission >= 'view'
                 ))
@@ -115,8 +115,8 @@ impl LinkResolver {
             .await?;
 
             for doc in id_docs {
-                if let Some(&idx) = target_map.get(&format!("id:{}", doc.id)) {
-                    results[idx] = Some(doc);
+                for &idx in target_map.get(&format!("id:{}", doc.id)).into_iter().flatten() {
+                    results[idx] = Some(doc.clone());
                 }
             }
         }
@@ -131,7 +131,7 @@ impl LinkResolver {
                        crdt_state, version, COALESCE(visibility, 'private') as "visibility!", published_at,
                        created_at as "created_at!", updated_at as "updated_at!", last_edited_by, last_edited_at
                 FROM documents
-                WHERE LOWER(title) = ANY($1) AND (owner_id = $2 OR id IN (
+                WHERE LOWER(title) = ANY($1) AND deleted_at IS NULL AND (owner_id = $2 OR id IN (
                     SELECT document_id FROM document_permissions 
                     WHERE user_id = $2 AND permission >= 'view'
                 ))
@@ -144,8 +144,8 @@ impl LinkResolver {
             .await?;
 
             for doc in title_docs {
-                if let Some(&idx) = target_map.get(&format!("title:{}", doc.title.to_lowercase())) {
-                    results[idx] = Some(doc);
+                for &idx in target_map.get(&format!("title:{}", doc.title.to_lowercase())).into_iter().flatten() {
+                    results[idx] = Some(doc.clone());
                 }
             }
         }
@@ -229,6 +229,24 @@ impl LinkResolver {
         Ok(document.is_some())
     }
 
+    /// Content of a document as last indexed for search
+    pub async fn indexed_content(&self, document_id: Uuid) -> Result<Option<String>> {
+        let content = sqlx::query_scalar::<_, String>(
+            "SELECT content FROM document_search_index WHERE document_id = $1"
+        )
+        .bind(document_id)
+        .fetch_optional(self.pool.as_ref())
+        .await?;
+        
+        Ok(content)
+    }
+
+    /// Check that a heading or block id exists in a document
+    pub async fn fragment_exists(&self, document_id: Uuid, fragment: &LinkFragment) -> Result<bool> {
+        let content = self.indexed_content(document_id).await?;
+        Ok(content.is_some_and(|content| fragment.section_range(&content).is_some()))
+    }
+
     /// Get all documents that would be affected by renaming a document
     pub async fn get_affected_by_rename(&self, document_id: Uuid) -> Result<Vec<Uuid>> {
         let affected = sqlx::query!(
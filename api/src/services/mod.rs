@@ -9,6 +9,7 @@ pub mod git_sync;
 pub mod git_batch_sync;
 pub mod git_diff;
 pub mod git_conflict;
+pub mod git_import;
 pub mod link_parser;
 pub mod link_resolver;
 pub mod document_links;
@@ -16,6 +17,16 @@ pub mod public_document;
 pub mod url_generator;
 pub mod common;
 pub mod tag_parser;
+pub mod history;
+pub mod comment;
+pub mod suggestion;
+pub mod history_compaction;
+pub mod document_cache;
+pub mod cluster_sync;
+pub mod file_watcher;
+pub mod search;
+pub mod transclusion;
+pub mod trash_expiry;
 
 pub use public_document::PublicDocumentService;
 pub use url_generator::UrlGeneratorService;
@@ -82,6 +82,7 @@ impl PublicDocumentService {
             FROM documents d
             JOIN users u ON u.id = d.owner_id
             WHERE d.visibility = 'public' 
+            AND d.deleted_at IS NULL
             AND d.id = $1 
             AND u.name = $2
             "#,
@@ -117,6 +118,7 @@ impl PublicDocumentService {
             FROM documents d
             JOIN users u ON u.id = d.owner_id
             WHERE d.visibility = 'public' 
+            AND d.deleted_at IS NULL
             AND u.name = $1
             ORDER BY d.published_at DESC
             LIMIT $2 OFFSET $3
@@ -156,6 +158,7 @@ impl PublicDocumentService {
             FROM documents d
             JOIN users u ON u.id = d.owner_id
             WHERE d.visibility = 'public' 
+            AND d.deleted_at IS NULL
             AND d.owner_id = $1
             ORDER BY d.published_at DESC
             "#,
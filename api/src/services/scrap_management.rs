@@ -55,7 +55,7 @@ impl ScrapService {
         let content = ScrapParser::generate_scrap_content(&document.title, &[], &metadata);
 
         // Initialize CRDT with initial content
-        self.crdt_service.update_document_content(document.id, &content).await?;
+        self.crdt_service.update_document_content(document.id, &content, Some(user_id)).await?;
 
         // Save to file
         self.document_service
@@ -277,7 +277,7 @@ impl ScrapService {
         let new_content = ScrapParser::add_post_to_content(&content, post)?;
         
         // Update CRDT - this will handle the synchronization automatically
-        let update = self.crdt_service.set_document_content(document_id, &new_content).await?;
+        let update = self.crdt_service.set_document_content(document_id, &new_content, Some(post.author_id)).await?;
         
         // Get document for file save
         let document = ScrapRepository::get_scrap_by_id(&*self.pool, document_id).await?;
@@ -435,7 +435,7 @@ impl ScrapService {
             let mut retry_count = 0;
             
             while retry_count < max_retries {
-                match self.update_scrap_content_with_post_update(document.id, post_id, &request.content).await {
+                match self.update_scrap_content_with_post_update(document.id, post_id, &request.content, user_id).await {
                     Ok(_) => break,
                     Err(e) => {
                         retry_count += 1;
@@ -452,7 +452,7 @@ impl ScrapService {
         Ok(post_with_tags)
     }
 
-    async fn update_scrap_content_with_post_update(&self, document_id: Uuid, post_id: Uuid, content: &str) -> Result<()> {
+    async fn update_scrap_content_with_post_update(&self, document_id: Uuid, post_id: Uuid, content: &str, user_id: Uuid) -> Result<()> {
         // Get current content from CRDT
         let current_content = self.crdt_service.get_document_content(document_id).await?;
         
@@ -460,7 +460,7 @@ impl ScrapService {
         let new_content = ScrapParser::update_post_in_content(&current_content, post_id, content)?;
         
         // Update CRDT
-        let _update = self.crdt_service.set_document_content(document_id, &new_content).await?;
+        let _update = self.crdt_service.set_document_content(document_id, &new_content, Some(user_id)).await?;
         
         // Get document for file save
         let document = ScrapRepository::get_scrap_by_id(&*self.pool, document_id).await?;
@@ -541,7 +541,7 @@ impl ScrapService {
             let mut retry_count = 0;
             
             while retry_count < max_retries {
-                match self.update_scrap_content_with_post_delete(document.id, post_id).await {
+                match self.update_scrap_content_with_post_delete(document.id, post_id, user_id).await {
                     Ok(_) => break,
                     Err(e) => {
                         retry_count += 1;
@@ -558,7 +558,7 @@ impl ScrapService {
         Ok(())
     }
 
-    async fn update_scrap_content_with_post_delete(&self, document_id: Uuid, post_id: Uuid) -> Result<()> {
+    async fn update_scrap_content_with_post_delete(&self, document_id: Uuid, post_id: Uuid, user_id: Uuid) -> Result<()> {
         // Get current content from CRDT
         let content = self.crdt_service.get_document_content(document_id).await?;
         
@@ -566,7 +566,7 @@ impl ScrapService {
         let new_content = ScrapParser::delete_post_from_content(&content, post_id)?;
         
         // Update CRDT
-        let _update = self.crdt_service.set_document_content(document_id, &new_content).await?;
+        let _update = self.crdt_service.set_document_content(document_id, &new_content, Some(user_id)).await?;
         
         // Get document for file save
         let document = ScrapRepository::get_scrap_by_id(&*self.pool, document_id).await?;
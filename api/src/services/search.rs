@@ -0,0 +1,237 @@
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::crdt::DocumentPersistence;
+use crate::entities::search::{ContentSearchFilters, ContentSearchQuery, ContentSearchResponse};
+use crate::error::{Error, Result};
+use crate::repository::SearchRepository;
+
+const DEFAULT_LIMIT: i64 = 20;
+const MAX_LIMIT: i64 = 100;
+
+/// Documents indexed per batch when filling in the index for older documents
+const BACKFILL_BATCH: i64 = 100;
+
+/// Full-text search over document titles and content.
+///
+/// Content is indexed by `DocumentPersistence::sync_to_documents_table` whenever a CRDT
+/// document is saved; results only include documents the searching user can view.
+pub struct SearchService {
+    search_repository: Arc<SearchRepository>,
+    document_persistence: Arc<DocumentPersistence>,
+}
+
+impl SearchService {
+    pub fn new(search_repository: Arc<SearchRepository>, document_persistence: Arc<DocumentPersistence>) -> Self {
+        Self {
+            search_repository,
+            document_persistence,
+        }
+    }
+
+    pub async fn search(&self, user_id: Uuid, query: &ContentSearchQuery) -> Result<ContentSearchResponse> {
+        let tsquery = build_tsquery(&self.search_repository, &query.q)
+            .await?
+            .ok_or_else(|| Error::BadRequest("Search query has no words to match".to_string()))?;
+
+        if let Some(document_type) = query.document_type.as_deref() {
+            if document_type != "document" && document_type != "scrap" {
+                return Err(Error::BadRequest(format!("Unknown document type: {}", document_type)));
+            }
+        }
+
+        let filters = ContentSearchFilters {
+            tag: query.tag.clone().filter(|tag| !tag.trim().is_empty()),
+            document_type: query.document_type.clone(),
+            folder_id: query.folder_id,
+            updated_after: query.updated_after,
+            updated_before: query.updated_before,
+        };
+        let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+        let offset = query.offset.unwrap_or(0).max(0);
+
+        let (results, total) = self.search_repository.search(user_id, &tsquery, &filters, limit, offset).await?;
+        Ok(ContentSearchResponse { results, total })
+    }
+
+    /// Index the content of documents saved before the search index existed
+    pub async fn index_missing(&self) -> Result<usize> {
+        let mut indexed = 0;
+
+        loop {
+            let ids = self.search_repository.unindexed_documents(BACKFILL_BATCH).await?;
+            if ids.is_empty() {
+                break;
+            }
+
+            for id in ids {
+                // Documents without CRDT history are indexed empty so they are not retried
+                let content = match self.document_persistence.load_document(id).await? {
+                    Some(document) => document.get_content()?,
+                    None => String::new(),
+                };
+                self.document_persistence.index_content(id, &content).await?;
+                indexed += 1;
+            }
+        }
+
+        Ok(indexed)
+    }
+}
+
+/// A search term: a word or a quoted phrase, possibly excluded with a leading `-`
+struct Term<'a> {
+    text: &'a str,
+    phrase: bool,
+    negated: bool,
+}
+
+fn split_terms(query: &str) -> Vec<Term<'_>> {
+    let mut terms = Vec::new();
+    let mut rest = query.trim_start();
+
+    while !rest.is_empty() {
+        let negated = rest.starts_with('-');
+        let body = if negated { &rest[1..] } else { rest };
+
+        let (text, phrase, remaining) = match body.strip_prefix('"') {
+            Some(quoted) => match quoted.find('"') {
+                Some(end) => (&quoted[..end], true, &quoted[end + 1..]),
+                None => (quoted, true, ""),
+            },
+            None => {
+                let end = body.find(char::is_whitespace).unwrap_or(body.len());
+                (&body[..end], false, &body[end..])
+            }
+        };
+
+        terms.push(Term { text, phrase, negated });
+        rest = remaining.trim_start();
+    }
+
+    terms
+}
+
+/// Turn a user query into a tsquery expression.
+///
+/// Words must all match unless joined by `OR`; `"quoted phrases"` match adjacent words,
+/// `word*` matches prefixes and `-word` excludes. Returns None when nothing positive is
+/// left to match. Terms are split into lexemes by Postgres itself, so hosts, file names,
+/// emails and versions stay single lexemes like they are in the index.
+pub async fn build_tsquery(search_repository: &SearchRepository, query: &str) -> Result<Option<String>> {
+    let terms = split_terms(query);
+    let texts: Vec<String> = terms.iter().map(|term| term.text.to_string()).collect();
+    let phrases = search_repository.tokenize(&texts).await?;
+    Ok(compose_tsquery(&terms, &phrases))
+}
+
+/// Join the `phraseto_tsquery` output of each term with the query's operators. The parser
+/// output only holds quoted lexemes, so the expression is always valid.
+fn compose_tsquery(terms: &[Term<'_>], phrases: &[String]) -> Option<String> {
+    let mut expression = String::new();
+    let mut or_next = false;
+    let mut has_positive = false;
+
+    for (term, phrase) in terms.iter().zip(phrases) {
+        if !term.phrase && !term.negated && term.text == "OR" {
+            or_next = !expression.is_empty();
+            continue;
+        }
+
+        if phrase.is_empty() {
+            continue;
+        }
+
+        // The prefix marker applies to the last lexeme of the term
+        let mut clause = phrase.clone();
+        if !term.phrase && term.text.ends_with('*') {
+            clause.push_str(":*");
+        }
+        let clause = if term.negated {
+            format!("!({})", clause)
+        } else {
+            has_positive = true;
+            if clause.contains(" <-> ") { format!("({})", clause) } else { clause }
+        };
+
+        if !expression.is_empty() {
+            expression.push_str(if or_next { " | " } else { " & " });
+        }
+        expression.push_str(&clause);
+        or_next = false;
+    }
+
+    has_positive.then_some(expression)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{db_test, TestDb};
+
+    /// Compose with the lexemes `phraseto_tsquery('simple', ...)` gives for plain words
+    fn compose(query: &str) -> Option<String> {
+        let terms = split_terms(query);
+        let phrases: Vec<String> = terms
+            .iter()
+            .map(|term| {
+                term.text
+                    .split(|c: char| !c.is_alphanumeric())
+                    .filter(|word| !word.is_empty())
+                    .map(|word| format!("'{}'", word.to_lowercase()))
+                    .collect::<Vec<_>>()
+                    .join(" <-> ")
+            })
+            .collect();
+        compose_tsquery(&terms, &phrases)
+    }
+
+    #[test]
+    fn test_compose_tsquery_words_and_phrases() {
+        assert_eq!(compose("Rust  CRDT").as_deref(), Some("'rust' & 'crdt'"));
+        assert_eq!(compose("\"sticky index\" sync").as_deref(), Some("('sticky' <-> 'index') & 'sync'"));
+        assert_eq!(compose("\"unterminated phrase").as_deref(), Some("('unterminated' <-> 'phrase')"));
+    }
+
+    #[test]
+    fn test_compose_tsquery_operators() {
+        assert_eq!(compose("deploy*").as_deref(), Some("'deploy':*"));
+        assert_eq!(compose("notes -draft").as_deref(), Some("'notes' & !('draft')"));
+        assert_eq!(compose("yjs OR automerge").as_deref(), Some("'yjs' | 'automerge'"));
+        assert_eq!(compose("OR yjs").as_deref(), Some("'yjs'"));
+    }
+
+    #[test]
+    fn test_compose_tsquery_skips_terms_without_lexemes() {
+        assert_eq!(compose("-only -excluded"), None);
+        assert_eq!(compose(" !&| "), None);
+    }
+
+    #[test]
+    fn test_compose_tsquery_keeps_parser_tokens() {
+        let terms = split_terms("config.yaml v1.2*");
+        let phrases = vec!["'config.yaml'".to_string(), "'v1.2'".to_string()];
+        assert_eq!(compose_tsquery(&terms, &phrases).as_deref(), Some("'config.yaml' & 'v1.2':*"));
+    }
+
+    db_test! {
+        async fn test_dotted_terms_match_indexed_content() {
+            let pool = TestDb::connect().await.pool.clone();
+            let repository = SearchRepository::new(pool.clone());
+            let content = "Edit config.yaml on api.example.com, mail ops@example.com and pin v1.2.3";
+
+            for query in ["config.yaml", "api.example.com", "ops@example.com", "v1.2*", "\"pin v1.2.3\"", "(config.yaml) & pi:*"] {
+                let tsquery = build_tsquery(&repository, query).await.unwrap().unwrap();
+                let matched: bool = sqlx::query_scalar("SELECT to_tsvector('simple', $1) @@ $2::tsquery")
+                    .bind(content)
+                    .bind(&tsquery)
+                    .fetch_one(pool.as_ref())
+                    .await
+                    .unwrap();
+                assert!(matched, "{} as {} should match", query, tsquery);
+            }
+
+            assert_eq!(build_tsquery(&repository, "-config.yaml !&|").await.unwrap(), None);
+        }
+    }
+}
@@ -2,7 +2,7 @@ use std::sync::Arc;
 use uuid::Uuid;
 use sqlx::PgPool;
 use chrono::Utc;
-use crate::entities::share::{ShareLink, ShareDocumentRequest, ShareResponse, SharedDocument, Permission};
+use crate::entities::share::{ShareLink, ShareDocumentRequest, ShareResponse, SharedDocument, Permission, DocumentPermission};
 use crate::error::{Error, Result};
 use crate::repository::share::ShareRepository;
 use crate::repository::document::DocumentRepository;
@@ -94,7 +94,8 @@ impl ShareService {
         })
     }
 
-    pub async fn delete_share(&self, token: &str, user_id: Uuid) -> Result<()> {
+    /// Delete a share link, returning it so sessions joined through it can be revoked
+    pub async fn delete_share(&self, token: &str, user_id: Uuid) -> Result<ShareLink> {
         // Get share link
         let share_link = self.share_repository.get_share_link_by_token(token).await?
             .ok_or_else(|| Error::NotFound("Share link not found".to_string()))?;
@@ -113,6 +114,58 @@ impl ShareService {
         }
 
         self.share_repository.delete_share_link(token).await?;
+        Ok(share_link)
+    }
+
+    /// Grant or change another user's explicit permission on a document
+    pub async fn set_user_permission(
+        &self,
+        document_id: Uuid,
+        user_id: Uuid,
+        target_user_id: Uuid,
+        permission: Permission,
+    ) -> Result<DocumentPermission> {
+        if permission == Permission::Owner {
+            return Err(Error::BadRequest("Ownership cannot be granted as a permission".to_string()));
+        }
+        self.ensure_can_manage(document_id, user_id, target_user_id).await?;
+
+        let document_permission = DocumentPermission {
+            id: Uuid::new_v4(),
+            document_id,
+            user_id: target_user_id,
+            permission,
+            granted_by: Some(user_id),
+            created_at: Utc::now(),
+        };
+        self.share_repository.create_document_permission(&document_permission).await?;
+
+        Ok(document_permission)
+    }
+
+    /// Remove another user's explicit permission on a document
+    pub async fn remove_user_permission(&self, document_id: Uuid, user_id: Uuid, target_user_id: Uuid) -> Result<()> {
+        self.ensure_can_manage(document_id, user_id, target_user_id).await?;
+
+        if !self.share_repository.delete_document_permission(document_id, target_user_id).await? {
+            return Err(Error::NotFound("Permission not found".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Only owners and admins manage permissions, and the owner's access cannot be changed
+    async fn ensure_can_manage(&self, document_id: Uuid, user_id: Uuid, target_user_id: Uuid) -> Result<()> {
+        let doc = self.document_repository.get_by_id(document_id).await?
+            .ok_or_else(|| Error::NotFound("Document not found".to_string()))?;
+        if doc.owner_id == target_user_id {
+            return Err(Error::BadRequest("The owner's permission cannot be changed".to_string()));
+        }
+        if doc.owner_id != user_id {
+            let permission = self.share_repository.get_user_permission(document_id, user_id).await?;
+            if !permission.map(|p| p.has_permission(Permission::Admin)).unwrap_or(false) {
+                return Err(Error::Forbidden);
+            }
+        }
         Ok(())
     }
 
@@ -0,0 +1,223 @@
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::crdt::{CrdtDocument, TextAnchor};
+use crate::entities::comment::{AnchorRequest, CommentAuthor};
+use crate::entities::suggestion::{
+    Suggestion, SuggestionResponse, SUGGESTION_STATUS_ACCEPTED, SUGGESTION_STATUS_PENDING,
+    SUGGESTION_STATUS_REJECTED,
+};
+use crate::error::{Error, Result};
+use crate::repository::SuggestionRepository;
+use crate::services::crdt::CrdtService;
+use crate::socketio::crdt_sync::document_room;
+use crate::socketio::SocketBroadcaster;
+
+/// Event carrying the full state of a created or decided suggestion to the document room
+pub const SUGGESTION_EVENT: &str = "suggestion:updated";
+
+/// Tracked changes: edits proposed against anchored ranges of the content text that only
+/// change the document once someone with edit permission accepts them.
+pub struct SuggestionService {
+    suggestion_repository: Arc<SuggestionRepository>,
+    crdt_service: Arc<CrdtService>,
+    broadcaster: Arc<SocketBroadcaster>,
+}
+
+impl SuggestionService {
+    pub fn new(
+        suggestion_repository: Arc<SuggestionRepository>,
+        crdt_service: Arc<CrdtService>,
+        broadcaster: Arc<SocketBroadcaster>,
+    ) -> Self {
+        Self {
+            suggestion_repository,
+            crdt_service,
+            broadcaster,
+        }
+    }
+
+    /// Suggestions of a document, optionally only those with the given status
+    pub async fn list_suggestions(&self, document_id: Uuid, status: Option<&str>) -> Result<Vec<SuggestionResponse>> {
+        if let Some(status) = status {
+            if ![SUGGESTION_STATUS_PENDING, SUGGESTION_STATUS_ACCEPTED, SUGGESTION_STATUS_REJECTED].contains(&status) {
+                return Err(Error::BadRequest(format!("Unknown suggestion status: {}", status)));
+            }
+        }
+
+        let suggestions = self.suggestion_repository.list_by_document(document_id, status).await?;
+        self.to_responses(document_id, suggestions).await
+    }
+
+    /// Propose replacing a range of the document
+    pub async fn create_suggestion(
+        &self,
+        document_id: Uuid,
+        anchor: &AnchorRequest,
+        replacement: &str,
+        author: &CommentAuthor,
+    ) -> Result<SuggestionResponse> {
+        let doc = self.crdt_service.load_or_create_document(document_id).await?;
+        let (anchor, original_text) = {
+            let doc = doc.write();
+            resolve_anchor(&doc, anchor)?
+        };
+        if original_text == replacement {
+            return Err(Error::BadRequest("Suggestion does not change the document".to_string()));
+        }
+
+        let (start, end) = anchor.encode();
+        let suggestion = self.suggestion_repository
+            .create(document_id, (&start, &end), &original_text, replacement, author)
+            .await?;
+
+        self.publish(suggestion).await
+    }
+
+    /// Apply a pending suggestion to the document as a regular CRDT update
+    pub async fn accept_suggestion(&self, document_id: Uuid, suggestion_id: Uuid, user_id: Option<Uuid>) -> Result<SuggestionResponse> {
+        let suggestion = self.find_suggestion(document_id, suggestion_id).await?;
+        // Claim the suggestion first so it is applied at most once
+        let accepted = self.suggestion_repository
+            .decide(suggestion_id, SUGGESTION_STATUS_ACCEPTED, user_id)
+            .await?
+            .ok_or_else(|| Error::Conflict("Suggestion has already been decided".to_string()))?;
+
+        // The applied text is attributed to whoever suggested it
+        let update = match self.crdt_service.edit_document(document_id, suggestion.author_id.or(user_id), |doc| apply_suggestion(doc, &suggestion)).await {
+            Ok(update) => update,
+            Err(e) => {
+                self.suggestion_repository.reset_pending(suggestion_id).await?;
+                return Err(e);
+            }
+        };
+        self.broadcaster.broadcast_document_update(document_id, &update, None)?;
+
+        self.publish(accepted).await
+    }
+
+    /// Decline a pending suggestion, leaving the document unchanged
+    pub async fn reject_suggestion(&self, document_id: Uuid, suggestion_id: Uuid, user_id: Option<Uuid>) -> Result<SuggestionResponse> {
+        self.find_suggestion(document_id, suggestion_id).await?;
+        let rejected = self.suggestion_repository
+            .decide(suggestion_id, SUGGESTION_STATUS_REJECTED, user_id)
+            .await?
+            .ok_or_else(|| Error::Conflict("Suggestion has already been decided".to_string()))?;
+
+        self.publish(rejected).await
+    }
+
+    async fn find_suggestion(&self, document_id: Uuid, suggestion_id: Uuid) -> Result<Suggestion> {
+        self.suggestion_repository
+            .get_by_id(document_id, suggestion_id)
+            .await?
+            .ok_or_else(|| Error::NotFound("Suggestion not found".to_string()))
+    }
+
+    /// Send the current state of a suggestion to everyone in the document
+    async fn publish(&self, suggestion: Suggestion) -> Result<SuggestionResponse> {
+        let document_id = suggestion.document_id;
+        let response = self.to_responses(document_id, vec![suggestion]).await?.remove(0);
+        self.broadcaster.emit_to_room(document_room(document_id), SUGGESTION_EVENT, &response)?;
+        Ok(response)
+    }
+
+    async fn to_responses(&self, document_id: Uuid, suggestions: Vec<Suggestion>) -> Result<Vec<SuggestionResponse>> {
+        let doc = self.crdt_service.load_or_create_document(document_id).await?;
+        let doc = doc.read();
+        let content = doc.get_content()?;
+
+        Ok(suggestions
+            .into_iter()
+            .map(|suggestion| {
+                let range = TextAnchor::decode(&suggestion.anchor_start, &suggestion.anchor_end)
+                    .and_then(|anchor| anchor.text_range(&doc, &content));
+                let outdated = suggestion.status == SUGGESTION_STATUS_PENDING
+                    && range.as_ref().map(|range| range.text.as_str()) != Some(suggestion.original_text.as_str());
+                SuggestionResponse {
+                    anchor_start: base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &suggestion.anchor_start),
+                    anchor_end: base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &suggestion.anchor_end),
+                    range,
+                    outdated,
+                    suggestion,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Turn a requested anchor into a text anchor and the text it currently covers
+fn resolve_anchor(doc: &CrdtDocument, anchor: &AnchorRequest) -> Result<(TextAnchor, String)> {
+    let content = doc.get_content()?;
+    let invalid = || Error::BadRequest("Suggestion anchor is outside the document".to_string());
+
+    let anchor = match anchor {
+        AnchorRequest::Relative { start, end } => TextAnchor::from_base64(start, end)?,
+        AnchorRequest::Offsets { start_index, end_index } => {
+            TextAnchor::from_chars(doc, &content, *start_index, *end_index).ok_or_else(invalid)?
+        }
+    };
+
+    let original_text = anchor.text_range(doc, &content).ok_or_else(invalid)?.text;
+    Ok((anchor, original_text))
+}
+
+/// Replace the suggestion's range, provided it still holds the text the suggestion was made on
+fn apply_suggestion(doc: &mut CrdtDocument, suggestion: &Suggestion) -> Result<()> {
+    let outdated = || Error::Conflict("The suggested text has changed since the suggestion was made".to_string());
+
+    let anchor = TextAnchor::decode(&suggestion.anchor_start, &suggestion.anchor_end).ok_or_else(outdated)?;
+    let content = doc.get_content()?;
+    let current = anchor.text_range(doc, &content).ok_or_else(outdated)?;
+    let offsets = anchor.offsets(doc).ok_or_else(outdated)?;
+    if current.text != suggestion.original_text {
+        return Err(outdated());
+    }
+
+    doc.replace_range(offsets.start, offsets.end, &suggestion.replacement)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suggestion(doc: &CrdtDocument, anchor: AnchorRequest, replacement: &str) -> Suggestion {
+        let (anchor, original_text) = resolve_anchor(doc, &anchor).unwrap();
+        let (anchor_start, anchor_end) = anchor.encode();
+        Suggestion {
+            id: Uuid::new_v4(),
+            document_id: doc.id(),
+            anchor_start,
+            anchor_end,
+            original_text,
+            replacement: replacement.to_string(),
+            status: SUGGESTION_STATUS_PENDING.to_string(),
+            author_id: None,
+            author_name: "Guest".to_string(),
+            decided_by: None,
+            decided_at: None,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_apply_suggestions() {
+        let mut doc = CrdtDocument::new_with_content(Uuid::new_v4());
+        doc.set_content("The quick fox").unwrap();
+
+        let insertion = suggestion(&doc, AnchorRequest::Offsets { start_index: 10, end_index: 10 }, "brown ");
+        let replacement = suggestion(&doc, AnchorRequest::Offsets { start_index: 4, end_index: 9 }, "slow");
+        let deletion = suggestion(&doc, AnchorRequest::Offsets { start_index: 0, end_index: 4 }, "");
+
+        apply_suggestion(&mut doc, &insertion).unwrap();
+        assert_eq!(doc.get_content().unwrap(), "The quick brown fox");
+        apply_suggestion(&mut doc, &replacement).unwrap();
+        assert_eq!(doc.get_content().unwrap(), "The slow brown fox");
+        apply_suggestion(&mut doc, &deletion).unwrap();
+        assert_eq!(doc.get_content().unwrap(), "slow brown fox");
+
+        // The replaced text is gone, so the suggestion no longer applies
+        assert!(apply_suggestion(&mut doc, &replacement).is_err());
+    }
+}
@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::entities::share::Permission;
+use crate::error::Result;
+use crate::services::crdt::CrdtService;
+use crate::services::link_parser::{LinkFragment, LinkParser, LinkTarget, LinkType};
+use crate::services::link_resolver::LinkResolver;
+
+/// Depth of nested embeds expanded when the caller does not ask for another
+pub const DEFAULT_EMBED_DEPTH: usize = 5;
+
+/// Deepest nesting of embeds that is ever expanded
+pub const MAX_EMBED_DEPTH: usize = 10;
+
+/// Embeds expanded in one render, at any depth
+const MAX_EMBEDS: usize = 200;
+
+/// Embedded text inserted in one render, in bytes
+const MAX_EMBEDDED_BYTES: usize = 1024 * 1024;
+
+/// What became of one `![[...]]` embed while rendering
+#[derive(Debug, Clone, Serialize)]
+pub struct EmbedInfo {
+    /// Target as written in the embed, including any `#section`
+    pub target: String,
+    /// Embedded document, omitted when the viewer cannot see it
+    pub document_id: Option<Uuid>,
+    pub section: Option<String>,
+    /// Nesting level, 1 for embeds in the rendered document itself
+    pub depth: usize,
+    /// `expanded`, `not_found`, `forbidden`, `unsupported`, `section_not_found`, `cycle`,
+    /// `depth_limit` or `budget_exceeded`
+    pub status: &'static str,
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderedContent {
+    pub content: String,
+    pub embeds: Vec<EmbedInfo>,
+}
+
+type ExpandFuture<'a> = Pin<Box<dyn Future<Output = Result<String>> + Send + 'a>>;
+
+/// State shared by the nested expansions of one render
+struct Expansion {
+    viewer: Option<Uuid>,
+    max_depth: usize,
+    /// Documents and sections being expanded, from the rendered document down
+    path: Vec<(Uuid, Option<LinkFragment>)>,
+    embeds: Vec<EmbedInfo>,
+    /// Embeds expanded so far and the bytes they inserted, bounding the render's size
+    expanded: usize,
+    embedded_bytes: usize,
+    /// Complete expansions of documents and sections, reused when embedded again; the
+    /// embeds inside a reused expansion are only listed the first time
+    memo: HashMap<(Uuid, Option<LinkFragment>), String>,
+}
+
+impl Expansion {
+    /// Whether expanding `bytes` more embedded text stays within the render budget
+    fn within_budget(&self, bytes: usize) -> bool {
+        self.expanded < MAX_EMBEDS && self.embedded_bytes + bytes <= MAX_EMBEDDED_BYTES
+    }
+}
+
+/// Expands `![[Doc]]` and `![[Doc#Section]]` embeds into the content they point to.
+///
+/// Embeds are resolved like links, from the point of view of the embedding document's
+/// owner, and only expanded when the viewer may read the embedded document: anonymous
+/// viewers only see published documents. Embeds that cannot be expanded are left as
+/// written, so clients can still render them as links. A render expands at most
+/// `MAX_EMBEDS` embeds and `MAX_EMBEDDED_BYTES` of embedded text; embeds past that are
+/// replaced by a link noting they were omitted.
+pub struct TransclusionService {
+    pool: Arc<PgPool>,
+    crdt_service: Arc<CrdtService>,
+    link_resolver: Arc<LinkResolver>,
+}
+
+impl TransclusionService {
+    pub fn new(pool: Arc<PgPool>, crdt_service: Arc<CrdtService>, link_resolver: Arc<LinkResolver>) -> Self {
+        Self {
+            pool,
+            crdt_service,
+            link_resolver,
+        }
+    }
+
+    /// Content of a document with its embeds expanded up to `max_depth` levels deep.
+    ///
+    /// Access to the document itself is checked by the caller.
+    pub async fn render(&self, document_id: Uuid, owner_id: Uuid, viewer: Option<Uuid>, max_depth: usize) -> Result<RenderedContent> {
+        let content = self.crdt_service.get_document_content(document_id).await?;
+        self.render_content(document_id, owner_id, content, viewer, max_depth).await
+    }
+
+    /// Expand the embeds in content already loaded for a document
+    pub async fn render_content(
+        &self,
+        document_id: Uuid,
+        owner_id: Uuid,
+        content: String,
+        viewer: Option<Uuid>,
+        max_depth: usize,
+    ) -> Result<RenderedContent> {
+        let mut expansion = Expansion {
+            viewer,
+            max_depth: max_depth.min(MAX_EMBED_DEPTH),
+            path: vec![(document_id, None)],
+            embeds: Vec::new(),
+            expanded: 0,
+            embedded_bytes: 0,
+            memo: HashMap::new(),
+        };
+        let content = self.expand(content, owner_id, 1, &mut expansion).await?;
+
+        Ok(RenderedContent { content, embeds: expansion.embeds })
+    }
+
+    /// Replace the embeds in content of a document owned by `owner_id`, found `depth` levels down
+    fn expand<'a>(&'a self, content: String, owner_id: Uuid, depth: usize, expansion: &'a mut Expansion) -> ExpandFuture<'a> {
+        Box::pin(async move {
+            let links: Vec<_> = LinkParser::parse_links(&content)
+                .into_iter()
+                .filter(|link| link.link_type == LinkType::Embed)
+                .collect();
+            if links.is_empty() {
+                return Ok(content);
+            }
+
+            let targets: Vec<&LinkTarget> = links.iter().map(|link| &link.target).collect();
+            let resolved = self.link_resolver.resolve_targets_batch(&targets, owner_id).await?;
+
+            // Expanded text for each embed, in document order
+            let mut replacements = Vec::with_capacity(links.len());
+            for (link, document) in links.iter().zip(resolved) {
+                let written = &content[link.position_start..link.position_end];
+                let mut info = EmbedInfo {
+                    target: embed_target(written),
+                    document_id: None,
+                    section: link.fragment.as_ref().map(LinkFragment::as_text),
+                    depth,
+                    status: "expanded",
+                };
+
+                // Section or document text to embed once the embed checks out, or its
+                // earlier expansion
+                let mut embedded = None;
+                let mut reused = None;
+                match document {
+                    None => info.status = "not_found",
+                    Some(document) if !self.can_view(document.id, expansion.viewer).await? => info.status = "forbidden",
+                    Some(document) => {
+                        info.document_id = Some(document.id);
+                        let key = (document.id, link.fragment.clone());
+
+                        if document.r#type != "document" {
+                            info.status = "unsupported";
+                        } else if expansion.path.contains(&key) {
+                            info.status = "cycle";
+                        } else if depth > expansion.max_depth {
+                            info.status = "depth_limit";
+                        } else if let Some(expanded) = expansion.memo.get(&key) {
+                            if expansion.within_budget(expanded.len()) {
+                                reused = Some(expanded.clone());
+                            } else {
+                                info.status = "budget_exceeded";
+                            }
+                        } else if !expansion.within_budget(0) {
+                            info.status = "budget_exceeded";
+                        } else {
+                            let text = self.crdt_service.get_document_content(document.id).await?;
+                            let text = match &link.fragment {
+                                Some(fragment) => fragment.section(&text),
+                                None => Some(text),
+                            };
+                            match text {
+                                Some(text) if expansion.within_budget(text.len()) => {
+                                    embedded = Some((key, document.owner_id, text));
+                                }
+                                Some(_) => info.status = "budget_exceeded",
+                                None => info.status = "section_not_found",
+                            }
+                        }
+                    }
+                }
+                let placeholder = (info.status == "budget_exceeded").then(|| omitted_embed(&info.target));
+                expansion.embeds.push(info);
+
+                let expanded = match (embedded, reused) {
+                    (Some((key, embedded_owner, text)), _) => {
+                        expansion.expanded += 1;
+                        expansion.embedded_bytes += text.len();
+                        let nested_from = expansion.embeds.len();
+
+                        expansion.path.push(key.clone());
+                        let expanded = self.expand(text, embedded_owner, depth + 1, expansion).await;
+                        expansion.path.pop();
+                        let expanded = expanded?;
+
+                        // Expansions cut short by a cycle, the depth or the budget depend on
+                        // where they were embedded, so only complete ones are reused
+                        let complete = expansion.embeds[nested_from..]
+                            .iter()
+                            .all(|nested| !matches!(nested.status, "cycle" | "depth_limit" | "budget_exceeded"));
+                        if complete {
+                            expansion.memo.insert(key, expanded.clone());
+                        }
+                        Some(expanded)
+                    }
+                    (None, Some(expanded)) => {
+                        expansion.expanded += 1;
+                        expansion.embedded_bytes += expanded.len();
+                        Some(expanded)
+                    }
+                    (None, None) => placeholder,
+                };
+                replacements.push((link.position_start..link.position_end, expanded));
+            }
+
+            let mut result = content;
+            for (range, expanded) in replacements.into_iter().rev() {
+                if let Some(expanded) = expanded {
+                    result.replace_range(range, expanded.trim_end());
+                }
+            }
+            Ok(result)
+        })
+    }
+
+    /// Whether a viewer may read a document: published ones are readable by anyone,
+    /// others need ownership or a view permission
+    async fn can_view(&self, document_id: Uuid, viewer: Option<Uuid>) -> Result<bool> {
+        let allowed = sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM documents d
+                WHERE d.id = $1 AND d.deleted_at IS NULL
+                  AND (d.visibility IN ('public', 'unlisted')
+                       OR d.owner_id = $2
+                       OR EXISTS (
+                           SELECT 1 FROM document_permissions p
+                           WHERE p.document_id = d.id AND p.user_id = $2 AND p.permission = ANY($3)
+                       ))
+            )
+            "#,
+        )
+        .bind(document_id)
+        .bind(viewer)
+        .bind(Permission::View.granted_by())
+        .fetch_one(self.pool.as_ref())
+        .await?;
+
+        Ok(allowed)
+    }
+}
+
+/// Target part of a written embed such as `![[Doc#Section|alias]]`
+fn embed_target(written: &str) -> String {
+    let inner = written.trim_start_matches("![[").trim_end_matches("]]");
+    inner.split('|').next().unwrap_or(inner).trim().to_string()
+}
+
+/// Stand-in for an embed left out once the render budget is spent, linking to its target
+fn omitted_embed(target: &str) -> String {
+    format!("[[{}]] *(embed omitted: too much embedded content)*", target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{db_test, TestDb};
+
+    #[test]
+    fn test_embed_target() {
+        assert_eq!(embed_target("![[Spec]]"), "Spec");
+        assert_eq!(embed_target("![[ Spec#^req-1 | the requirement ]]"), "Spec#^req-1");
+    }
+
+    async fn create_document(db: &TestDb, crdt_service: &CrdtService, owner_id: Uuid, title: &str, content: &str) -> Uuid {
+        let id = db.create_document(title, owner_id).await;
+        crdt_service.set_document_content(id, content, None).await.unwrap();
+        id
+    }
+
+    fn statuses(rendered: &RenderedContent) -> Vec<(&str, usize)> {
+        rendered.embeds.iter().map(|embed| (embed.status, embed.depth)).collect()
+    }
+
+    db_test! {
+        async fn test_render_stops_at_cycles_depth_access_and_budget() {
+            let db = TestDb::connect().await;
+            let pool = db.pool.clone();
+            let crdt_service = db.crdt_service();
+            let service = TransclusionService::new(pool.clone(), crdt_service.clone(), Arc::new(LinkResolver::new(pool.clone())));
+
+            let owner_id = db.create_user().await;
+            let editor_id = db.create_user().await;
+            let a = create_document(&db, &crdt_service, owner_id, "Cycle A", "A ![[Cycle B]]").await;
+            create_document(&db, &crdt_service, owner_id, "Cycle B", "B ![[Cycle A]]").await;
+            let chain = create_document(&db, &crdt_service, owner_id, "Chain 1", "1 ![[Chain 2]]").await;
+            create_document(&db, &crdt_service, owner_id, "Chain 2", "2 ![[Chain 3]]").await;
+            create_document(&db, &crdt_service, owner_id, "Chain 3", "3").await;
+            let secret = create_document(&db, &crdt_service, owner_id, "Secret", "hidden").await;
+            let page = create_document(&db, &crdt_service, owner_id, "Page", "see ![[Secret]]").await;
+            sqlx::query("INSERT INTO document_permissions (document_id, user_id, permission) VALUES ($1, $2, 'edit')")
+                .bind(secret)
+                .bind(editor_id)
+                .execute(pool.as_ref())
+                .await
+                .unwrap();
+            let wide = create_document(&db, &crdt_service, owner_id, "Wide", &"![[Chain 3]]\n".repeat(MAX_EMBEDS + 1)).await;
+
+            let cycle = service.render(a, owner_id, Some(owner_id), DEFAULT_EMBED_DEPTH).await;
+            let depth = service.render(chain, owner_id, Some(owner_id), 1).await;
+            let anonymous = service.render(page, owner_id, None, DEFAULT_EMBED_DEPTH).await;
+            let grantee = service.render(page, owner_id, Some(editor_id), DEFAULT_EMBED_DEPTH).await;
+            let budget = service.render(wide, owner_id, Some(owner_id), DEFAULT_EMBED_DEPTH).await;
+
+            let cycle = cycle.unwrap();
+            assert_eq!(statuses(&cycle), vec![("expanded", 1), ("cycle", 2)]);
+            assert_eq!(cycle.content, "A B ![[Cycle A]]");
+
+            let depth = depth.unwrap();
+            assert_eq!(statuses(&depth), vec![("expanded", 1), ("depth_limit", 2)]);
+            assert_eq!(depth.content, "1 2 ![[Chain 3]]");
+
+            let anonymous = anonymous.unwrap();
+            assert_eq!(statuses(&anonymous), vec![("forbidden", 1)]);
+            assert_eq!(anonymous.content, "see ![[Secret]]");
+            assert_eq!(grantee.unwrap().content, "see hidden");
+
+            let budget = budget.unwrap();
+            assert!(budget.embeds[..MAX_EMBEDS].iter().all(|embed| embed.status == "expanded"));
+            assert_eq!(budget.embeds[MAX_EMBEDS].status, "budget_exceeded");
+            assert!(budget.content.ends_with(&format!("{}\n", omitted_embed("Chain 3"))));
+        }
+    }
+}
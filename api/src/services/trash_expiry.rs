@@ -0,0 +1,113 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::interval;
+
+use crate::services::document::DocumentService;
+
+/// Background job that permanently deletes trash entries past the retention period
+#[derive(Clone)]
+pub struct TrashExpiryService {
+    document_service: Arc<DocumentService>,
+    purge_interval: Duration,
+    is_running: Arc<Mutex<bool>>,
+}
+
+impl TrashExpiryService {
+    pub fn new(document_service: Arc<DocumentService>, purge_interval_secs: u64) -> Self {
+        Self {
+            document_service,
+            purge_interval: Duration::from_secs(purge_interval_secs.max(1)),
+            is_running: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    pub async fn start(&self) {
+        let mut is_running = self.is_running.lock().await;
+        if *is_running {
+            tracing::warn!("TrashExpiryService is already running");
+            return;
+        }
+        *is_running = true;
+        drop(is_running);
+
+        let service = self.clone();
+        tokio::spawn(async move {
+            service.run_purge_loop().await;
+        });
+    }
+
+    pub async fn stop(&self) {
+        let mut is_running = self.is_running.lock().await;
+        *is_running = false;
+    }
+
+    async fn run_purge_loop(&self) {
+        let mut ticker = interval(self.purge_interval);
+
+        loop {
+            ticker.tick().await;
+
+            let is_running = self.is_running.lock().await;
+            if !*is_running {
+                tracing::info!("TrashExpiryService stopping");
+                break;
+            }
+            drop(is_running);
+
+            match self.document_service.purge_expired_trash().await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!("Purged {} expired item(s) from the trash", purged),
+                Err(e) => tracing::error!("Trash expiry failed: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::repository::DocumentRepository;
+    use crate::test_support::{db_test, TestDb};
+
+    db_test! {
+        async fn test_expired_entries_are_purged() {
+            let db = TestDb::connect().await;
+            let repository = Arc::new(DocumentRepository::new(db.pool.clone()));
+            let config = Config { trash_retention_days: 30, ..Config::from_env().unwrap() };
+            let document_service = Arc::new(DocumentService::new(
+                repository.clone(),
+                std::env::temp_dir(),
+                db.crdt_service(),
+                None,
+                Arc::new(config),
+            ));
+            let service = TrashExpiryService::new(document_service, 3600);
+
+            let owner_id = db.create_user().await;
+            let expired = db.create_document("Expired", owner_id).await;
+            let recent = db.create_document("Recent", owner_id).await;
+            repository.trash(expired, owner_id, owner_id).await.unwrap();
+            repository.trash(recent, owner_id, owner_id).await.unwrap();
+            sqlx::query("UPDATE documents SET deleted_at = NOW() - INTERVAL '31 days' WHERE id = $1")
+                .bind(expired)
+                .execute(db.pool.as_ref())
+                .await
+                .unwrap();
+
+            // The first pass runs right away
+            service.start().await;
+            for _ in 0..50 {
+                if repository.get_by_id(expired).await.unwrap().is_none() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            service.stop().await;
+
+            assert!(repository.get_by_id(expired).await.unwrap().is_none());
+            assert!(repository.get_by_id(recent).await.unwrap().is_some());
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use once_cell::sync::OnceCell;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use socketioxide::{extract::SocketRef, socket::Sid, SocketIo};
+use uuid::Uuid;
+
+use crate::crdt::serialization;
+use crate::error::Result;
+use crate::services::cluster_sync::{ClusterMessageKind, ClusterPublisher};
+use crate::socketio::crdt_sync::{binary_room, document_room, protocol, BinaryEnvelope, YjsMessage};
+use crate::websocket::WebsocketPeers;
+
+/// Room joined by every socket authenticated as the user
+pub fn user_room(user_id: Uuid) -> String {
+    format!("user:{}", user_id)
+}
+
+/// Event for a user's sockets, relayed to the replicas they may be connected to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserEvent {
+    pub user_id: Uuid,
+    pub event: String,
+    pub data: serde_json::Value,
+}
+
+/// Emits server-initiated events to Socket.IO rooms and y-websocket connections.
+///
+/// Services run outside of socket handlers (REST requests, background jobs), so the
+/// `SocketIo` handle is attached once the layer has been built in `main`. CRDT,
+/// awareness and user event broadcasts are also published to the other API replicas.
+pub struct SocketBroadcaster {
+    io: OnceCell<SocketIo>,
+    websocket_peers: Arc<WebsocketPeers>,
+    cluster: ClusterPublisher,
+}
+
+impl SocketBroadcaster {
+    pub fn new(websocket_peers: Arc<WebsocketPeers>, cluster: ClusterPublisher) -> Self {
+        Self {
+            io: OnceCell::new(),
+            websocket_peers,
+            cluster,
+        }
+    }
+
+    /// Publisher for updates that were already delivered to local clients
+    pub fn cluster(&self) -> &ClusterPublisher {
+        &self.cluster
+    }
+
+    /// Attach the Socket.IO instance; subsequent calls are ignored
+    pub fn attach(&self, io: SocketIo) {
+        if self.io.set(io).is_err() {
+            tracing::warn!("SocketBroadcaster already attached");
+        }
+    }
+
+    /// Socket connected to this node, if any
+    pub fn socket(&self, socket_id: &str) -> Option<SocketRef> {
+        let sid = socket_id.parse::<Sid>().ok()?;
+        self.io.get()?.get_socket(sid)
+    }
+
+    /// Emit an event to every socket in a room
+    pub fn emit_to_room<T: Serialize>(&self, room: String, event: &'static str, data: T) -> Result<()> {
+        match self.io.get() {
+            Some(io) => {
+                io.to(room).emit(event, data)?;
+                Ok(())
+            }
+            None => {
+                tracing::debug!("SocketBroadcaster not attached, dropping '{}' event", event);
+                Ok(())
+            }
+        }
+    }
+
+    /// Emit an event to every socket authenticated as the user, on every replica
+    pub fn emit_to_user<T: Serialize>(&self, user_id: Uuid, event: &'static str, data: T) -> Result<()> {
+        let event = UserEvent {
+            user_id,
+            event: event.to_string(),
+            data: serde_json::to_value(data)?,
+        };
+        // User events are not tied to a document
+        self.cluster.publish(Uuid::nil(), ClusterMessageKind::UserEvent, &serde_json::to_vec(&event)?);
+        self.deliver_user_event(&event)
+    }
+
+    /// Deliver a user event to the user's sockets connected to this node only
+    pub fn deliver_user_event(&self, event: &UserEvent) -> Result<()> {
+        match self.io.get() {
+            Some(io) => {
+                io.to(user_room(event.user_id)).emit(event.event.clone(), &event.data)?;
+                Ok(())
+            }
+            None => {
+                tracing::debug!("SocketBroadcaster not attached, dropping '{}' event", event.event);
+                Ok(())
+            }
+        }
+    }
+
+    /// Broadcast a CRDT update to all clients editing the document.
+    ///
+    /// `except_connection` skips the y-websocket connection the update came from.
+    pub fn broadcast_document_update(&self, document_id: Uuid, update: &[u8], except_connection: Option<Uuid>) -> Result<()> {
+        self.cluster.publish(document_id, ClusterMessageKind::Update, update);
+        self.deliver_document_update(document_id, update, except_connection)
+    }
+
+    /// Deliver a CRDT update to the clients connected to this node only
+    pub fn deliver_document_update(&self, document_id: Uuid, update: &[u8], except_connection: Option<Uuid>) -> Result<()> {
+        self.websocket_peers.broadcast(document_id, &protocol::create_update(update), except_connection);
+
+        let Some(io) = self.io.get() else {
+            tracing::debug!("SocketBroadcaster not attached, dropping update for document {}", document_id);
+            return Ok(());
+        };
+
+        io.to(document_room(document_id)).except(binary_room(document_id)).emit(
+            "yjs:sync",
+            YjsMessage::Update {
+                document_id,
+                update: serialization::update_to_base64(update),
+            },
+        )?;
+        io.to(binary_room(document_id))
+            .bin(vec![protocol::create_update(update)])
+            .emit("yjs:binary", BinaryEnvelope { document_id })?;
+        Ok(())
+    }
+
+    /// Broadcast an encoded awareness update to all clients of the document
+    pub fn broadcast_awareness(&self, document_id: Uuid, update: &[u8], except_connection: Option<Uuid>) -> Result<()> {
+        self.cluster.publish(document_id, ClusterMessageKind::Awareness, update);
+        self.deliver_awareness(document_id, update, except_connection)
+    }
+
+    /// Deliver an encoded awareness update to the clients connected to this node only
+    pub fn deliver_awareness(&self, document_id: Uuid, update: &[u8], except_connection: Option<Uuid>) -> Result<()> {
+        self.websocket_peers.broadcast(document_id, &protocol::create_awareness(update), except_connection);
+
+        let Some(io) = self.io.get() else {
+            return Ok(());
+        };
+
+        let encoded = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, update);
+        io.to(document_room(document_id)).except(binary_room(document_id)).emit("yjs:awareness", encoded)?;
+        io.to(binary_room(document_id))
+            .bin(vec![protocol::create_awareness(update)])
+            .emit("yjs:binary", BinaryEnvelope { document_id })?;
+        Ok(())
+    }
+}
@@ -6,11 +6,18 @@ use serde::{Deserialize, Serialize};
 use tracing::{error, info};
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::collections::HashMap;
+use dashmap::DashMap;
 use parking_lot::RwLock;
 use tokio::time::{Duration, Instant};
 
-use crate::crdt::{DocumentManager, AwarenessManager, DocumentPersistence};
+use crate::crdt::{DocumentManager, AwarenessManager, DocumentPersistence, is_empty_update};
+use crate::entities::share::Permission;
 use crate::error::Result;
+use crate::services::cluster_sync::ClusterMessageKind;
+use crate::socketio::session::SYNC_ERROR_EVENT;
+use yrs::sync::{AwarenessUpdate, Message, SyncMessage};
+use yrs::updates::decoder::Decode;
+use yrs::updates::encoder::Encode;
 use crate::state::AppState;
 
 /// Yjs sync message types
@@ -40,6 +47,42 @@ pub enum YjsMessage {
     },
 }
 
+/// Wire format used for Yjs sync, negotiated per connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncProtocol {
+    /// `YjsMessage` JSON events with base64 payloads
+    Json,
+    /// Standard y-protocols messages sent as binary attachments of `yjs:binary` events
+    Binary,
+}
+
+/// JSON part of a `yjs:binary` event; the y-protocols payload travels as a binary attachment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BinaryEnvelope {
+    pub document_id: Uuid,
+}
+
+/// Room containing every socket joined to a document
+pub fn document_room(document_id: Uuid) -> String {
+    format!("doc:{}", document_id)
+}
+
+/// Room containing the sockets of a document that negotiated the binary protocol
+pub fn binary_room(document_id: Uuid) -> String {
+    format!("doc:{}:binary", document_id)
+}
+
+/// Binary rooms a socket must join and leave after negotiating `protocol`, given the
+/// documents it has already joined
+pub fn binary_room_changes(protocol: SyncProtocol, documents: &[Uuid]) -> (Vec<String>, Vec<String>) {
+    let rooms = documents.iter().map(|document_id| binary_room(*document_id)).collect();
+    match protocol {
+        SyncProtocol::Binary => (rooms, Vec::new()),
+        SyncProtocol::Json => (Vec::new(), rooms),
+    }
+}
+
 /// Manages Yjs synchronization over Socket.IO
 pub struct YjsSyncManager {
     document_manager: Arc<DocumentManager>,
@@ -48,6 +91,7 @@ pub struct YjsSyncManager {
     app_state: Arc<AppState>,
     update_counters: Arc<RwLock<HashMap<Uuid, Arc<AtomicU32>>>>,
     last_save_times: Arc<RwLock<HashMap<Uuid, Instant>>>,
+    protocols: Arc<DashMap<String, SyncProtocol>>,
 }
 
 impl YjsSyncManager {
@@ -64,9 +108,57 @@ impl YjsSyncManager {
             app_state,
             update_counters: Arc::new(RwLock::new(HashMap::new())),
             last_save_times: Arc::new(RwLock::new(HashMap::new())),
+            protocols: Arc::new(DashMap::new()),
+        }
+    }
+
+    /// Pick the sync protocol for a socket from the ones the client supports
+    pub fn negotiate_protocol(&self, socket_id: &str, supported: &[SyncProtocol]) -> SyncProtocol {
+        let protocol = if supported.contains(&SyncProtocol::Binary) {
+            SyncProtocol::Binary
+        } else {
+            SyncProtocol::Json
+        };
+        self.protocols.insert(socket_id.to_string(), protocol);
+        protocol
+    }
+
+    /// Protocol negotiated by a socket, JSON unless it asked for something else
+    pub fn protocol_for(&self, socket_id: &str) -> SyncProtocol {
+        self.protocols
+            .get(socket_id)
+            .map(|protocol| *protocol)
+            .unwrap_or(SyncProtocol::Json)
+    }
+
+    /// Drop per-socket state when a socket disconnects
+    pub fn forget_socket(&self, socket_id: &str) {
+        self.protocols.remove(socket_id);
+    }
+
+    /// Check a socket's session before acting on its message, reporting a rejection to it
+    pub fn authorize(&self, socket: &SocketRef, document_id: Uuid, required: Permission) -> bool {
+        match self.app_state.session_registry.authorize(&socket.id.to_string(), document_id, required) {
+            Ok(_) => true,
+            Err(rejection) => {
+                tracing::warn!("[YjsSync] Rejected message from socket {} for document {}: {}", socket.id, document_id, rejection.message);
+                socket.emit(SYNC_ERROR_EVENT, &rejection).ok();
+                false
+            }
         }
     }
 
+    /// Check whether a client update should be applied.
+    ///
+    /// Updates that change nothing are dropped without asking for edit rights, since
+    /// view-only clients send them while syncing.
+    fn authorize_update(&self, socket: &SocketRef, document_id: Uuid, update: &[u8]) -> Result<bool> {
+        if is_empty_update(update)? {
+            return Ok(false);
+        }
+        Ok(self.authorize(socket, document_id, Permission::Edit))
+    }
+
     /// Send initial state to a newly connected client
     pub async fn send_initial_state(
         &self,
@@ -105,6 +197,9 @@ impl YjsSyncManager {
     ) -> Result<()> {
         match message {
             YjsMessage::SyncStep1 { document_id, state_vector } => {
+                if !self.authorize(socket, document_id, Permission::View) {
+                    return Ok(());
+                }
                 self.handle_sync_step1(socket, document_id, &state_vector).await
             }
             YjsMessage::SyncStep2 { document_id, update, state_vector } => {
@@ -114,11 +209,69 @@ impl YjsSyncManager {
                 self.handle_update(socket, document_id, &update).await
             }
             YjsMessage::Awareness { document_id, update } => {
+                if !self.authorize(socket, document_id, Permission::View) {
+                    return Ok(());
+                }
                 self.handle_awareness(socket, document_id, update).await
             }
         }
     }
 
+    /// Handle a binary y-protocols payload for a document
+    pub async fn handle_binary_message(
+        &self,
+        socket: &SocketRef,
+        document_id: Uuid,
+        payload: &[u8],
+    ) -> Result<()> {
+        let mut replies = Vec::new();
+
+        for message in protocol::decode_messages(payload)? {
+            match message {
+                Message::Sync(SyncMessage::SyncStep1(client_sv)) => {
+                    if !self.authorize(socket, document_id, Permission::View) {
+                        continue;
+                    }
+                    let doc = self.app_state.crdt_service.load_or_create_document(document_id).await?;
+                    let (update, server_sv) = {
+                        let doc = doc.read();
+                        (doc.get_update_since(&client_sv.encode_v1())?, doc.get_state_vector())
+                    };
+                    // Reply with what the client is missing, then ask for what we are missing
+                    replies.push(protocol::create_sync_step2(&update));
+                    replies.push(protocol::create_sync_step1(&server_sv)?);
+                    if let Some(awareness) = self.awareness_manager.get_or_create(document_id).full_update() {
+                        replies.push(protocol::create_awareness(&awareness.encode_v1()));
+                    }
+                }
+                Message::Sync(SyncMessage::SyncStep2(update)) | Message::Sync(SyncMessage::Update(update)) => {
+                    if !update.is_empty() && self.authorize_update(socket, document_id, &update)? {
+                        self.apply_and_broadcast_update(socket, document_id, &update).await?;
+                    }
+                }
+                Message::Awareness(update) => {
+                    if self.authorize(socket, document_id, Permission::View) {
+                        self.apply_awareness(socket, document_id, &update)?;
+                    }
+                }
+                Message::AwarenessQuery => {
+                    // Awareness states are relayed between clients, nothing to answer from the server
+                }
+                Message::Auth(_) | Message::Custom(_, _) => {
+                    tracing::debug!("Ignoring unsupported y-protocols message for document {}", document_id);
+                }
+            }
+        }
+
+        if !replies.is_empty() {
+            socket
+                .bin(replies)
+                .emit("yjs:binary", BinaryEnvelope { document_id })?;
+        }
+
+        Ok(())
+    }
+
     /// Handle sync step 1: Client sends their state vector
     async fn handle_sync_step1(
         &self,
@@ -186,7 +339,7 @@ impl YjsSyncManager {
             update_b64
         )?;
 
-        if !update.is_empty() {
+        if !update.is_empty() && self.authorize_update(socket, document_id, &update)? {
             self.apply_and_broadcast_update(socket, document_id, &update).await?;
         }
 
@@ -206,6 +359,10 @@ impl YjsSyncManager {
             update_b64
         )?;
 
+        if !self.authorize_update(socket, document_id, &update)? {
+            return Ok(());
+        }
+
         self.apply_and_broadcast_update(socket, document_id, &update).await
     }
 
@@ -216,34 +373,33 @@ impl YjsSyncManager {
         document_id: Uuid,
         update: &[u8],
     ) -> Result<()> {
-        // Apply update to document
-        let doc = self.document_manager.get_or_create(document_id);
-        {
-            let mut doc = doc.write();
-            doc.apply_update(update)?;
-        }
-
-        // Broadcast to other clients in the room
-        let room_name = format!("doc:{}", document_id);
-        socket.to(room_name).emit("yjs:sync", YjsMessage::Update {
-            document_id,
-            update: base64::Engine::encode(&base64::engine::general_purpose::STANDARD, update),
-        })?;
-
-        // Save update to database for persistence
-        if let Err(e) = self.document_persistence.save_update_auto(document_id, update).await {
+        // Persist and apply, attributed to the socket's user. Rejected updates reach no one
+        let author = self.app_state.session_registry
+            .session(&socket.id.to_string(), document_id)
+            .and_then(|session| session.user_id);
+        if let Err(e) = self.app_state.crdt_service.persist_and_apply_update(document_id, update, author).await {
             error!("Failed to persist update for document {}: {}", document_id, e);
-            
+
             // Notify the client that sent the update about the persistence failure
             socket.emit("sync-error", serde_json::json!({
                 "error": "Failed to persist document changes",
                 "message": format!("Update could not be saved: {}", e),
                 "document_id": document_id
             })).ok();
-            
-            // Continue despite persistence failure to maintain real-time collaboration
+            return Err(e);
         }
 
+        // Broadcast to other clients in the room, in the protocol each negotiated
+        socket.to(document_room(document_id)).except(binary_room(document_id)).emit("yjs:sync", YjsMessage::Update {
+            document_id,
+            update: base64::Engine::encode(&base64::engine::general_purpose::STANDARD, update),
+        })?;
+        socket.to(binary_room(document_id))
+            .bin(vec![protocol::create_update(update)])
+            .emit("yjs:binary", BinaryEnvelope { document_id })?;
+        self.app_state.websocket_peers.broadcast(document_id, &protocol::create_update(update), None);
+        self.app_state.broadcaster.cluster().publish(document_id, ClusterMessageKind::Update, update);
+
         // Check if we should save based on update count or time
         let should_save = {
             let mut counters = self.update_counters.write();
@@ -351,77 +507,152 @@ impl YjsSyncManager {
         Ok(())
     }
     
-    /// Handle awareness update (binary y-protocols format)
+    /// Handle an encoded awareness update (binary y-protocols format).
+    ///
+    /// Updates tagged with a document id go to that document. Untagged updates from older
+    /// clients are routed to the document that already knows their Yjs client ids, or to the
+    /// only document the socket has joined.
     pub async fn handle_awareness_binary(
         &self,
         socket: &SocketRef,
+        document_id: Option<Uuid>,
         data: Vec<u8>,
     ) -> Result<()> {
-        tracing::debug!("[handle_awareness_binary] Received awareness update from socket {}, size: {} bytes", 
-              socket.id, data.len());
-        
-        // The awareness protocol sends updates as binary data
-        // We need to figure out which document this is for
-        // For now, we'll broadcast to all documents the client is connected to
-        
-        // Get documents this socket is connected to from connection tracker
-        // Since we don't have direct access to connection tracker here,
-        // we'll need to parse the awareness data to extract document info
-        
-        // For now, just broadcast the raw awareness data to all rooms the socket is in
-        // This is a simplified approach - in production you'd want to properly
-        // decode the awareness protocol to understand which document it's for
-        
-        // Get all rooms this socket is in
-        let rooms = socket.rooms().unwrap_or_default();
-        
-        let mut broadcasted_to = Vec::new();
-        
-        for room in rooms.iter() {
-            let room_str = room.to_string();
-            if room_str.starts_with("doc:") {
-                // Broadcast awareness update to other clients in the room
-                // Send as base64 encoded string to avoid serialization issues
-                let encoded = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &data);
-                socket.to(room.clone()).emit("yjs:awareness", encoded)?;
-                broadcasted_to.push(room_str);
+        let update = AwarenessUpdate::decode_v1(&data)?;
+        let socket_id = socket.id.to_string();
+
+        let document_id = match document_id {
+            Some(document_id) => Some(document_id),
+            None => {
+                let client_ids: Vec<_> = update.clients.keys().copied().collect();
+                self.awareness_manager
+                    .find_client_document(&socket_id, &client_ids)
+                    .or_else(|| match self.app_state.connection_tracker.get_socket_documents(&socket_id)[..] {
+                        [document_id] => Some(document_id),
+                        _ => None,
+                    })
             }
+        };
+
+        let Some(document_id) = document_id else {
+            tracing::debug!("[handle_awareness_binary] Could not route awareness update from socket {}", socket_id);
+            return Ok(());
+        };
+
+        if !self.app_state.connection_tracker.is_socket_in_document(&socket_id, document_id) {
+            tracing::debug!("[handle_awareness_binary] Socket {} has not joined document {}", socket_id, document_id);
+            return Ok(());
         }
-        
-        if !broadcasted_to.is_empty() {
-            tracing::debug!("[handle_awareness_binary] Broadcasted awareness to rooms: {:?}", broadcasted_to);
+
+        self.apply_awareness(socket, document_id, &update)
+    }
+
+    /// Record an awareness update in the document's state and relay the accepted entries
+    fn apply_awareness(&self, socket: &SocketRef, document_id: Uuid, update: &AwarenessUpdate) -> Result<()> {
+        let awareness = self.awareness_manager.get_or_create(document_id);
+        match awareness.apply_awareness_update(&socket.id.to_string(), update) {
+            Some(accepted) => self.relay_awareness(socket, document_id, &accepted.encode_v1()),
+            None => Ok(()),
         }
-        
+    }
+
+    /// Relay an encoded awareness update to the other clients of a document
+    fn relay_awareness(&self, socket: &SocketRef, document_id: Uuid, update: &[u8]) -> Result<()> {
+        // Send as base64 encoded string to JSON clients to avoid serialization issues
+        let encoded = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, update);
+        socket.to(document_room(document_id)).except(binary_room(document_id)).emit("yjs:awareness", encoded)?;
+        socket.to(binary_room(document_id))
+            .bin(vec![protocol::create_awareness(update)])
+            .emit("yjs:binary", BinaryEnvelope { document_id })?;
+        self.app_state.websocket_peers.broadcast(document_id, &protocol::create_awareness(update), None);
+        self.app_state.broadcaster.cluster().publish(document_id, ClusterMessageKind::Awareness, update);
         Ok(())
     }
 }
 
-/// Helper to create Yjs sync protocol messages
+/// Binary y-protocols message encoding, as used by y-websocket and other Yjs providers
 pub mod protocol {
-    pub const SYNC_STEP_1: u8 = 0;
-    pub const SYNC_STEP_2: u8 = 1;
-    pub const UPDATE: u8 = 2;
-
-    /// Create a sync step 1 message
-    pub fn create_sync_step1(state_vector: &[u8]) -> Vec<u8> {
-        let mut msg = vec![SYNC_STEP_1];
-        msg.extend_from_slice(state_vector);
-        msg
+    use yrs::encoding::read::Cursor;
+    use yrs::sync::{Message, MessageReader, SyncMessage};
+    use yrs::sync::protocol::MSG_AWARENESS;
+    use yrs::updates::decoder::{Decode, DecoderV1};
+    use yrs::encoding::write::Write;
+    use yrs::updates::encoder::{Encode, Encoder, EncoderV1};
+    use yrs::StateVector;
+
+    use crate::error::Result;
+
+    /// Create a sync step 1 message carrying a state vector
+    pub fn create_sync_step1(state_vector: &[u8]) -> Result<Vec<u8>> {
+        let sv = StateVector::decode_v1(state_vector)?;
+        Ok(Message::Sync(SyncMessage::SyncStep1(sv)).encode_v1())
     }
 
-    /// Create a sync step 2 message
-    pub fn create_sync_step2(update: &[u8], state_vector: &[u8]) -> Vec<u8> {
-        let mut msg = vec![SYNC_STEP_2];
-        msg.extend_from_slice(&(update.len() as u32).to_be_bytes());
-        msg.extend_from_slice(update);
-        msg.extend_from_slice(state_vector);
-        msg
+    /// Create a sync step 2 message carrying the update a peer is missing
+    pub fn create_sync_step2(update: &[u8]) -> Vec<u8> {
+        Message::Sync(SyncMessage::SyncStep2(update.to_vec())).encode_v1()
     }
 
     /// Create an update message
     pub fn create_update(update: &[u8]) -> Vec<u8> {
-        let mut msg = vec![UPDATE];
-        msg.extend_from_slice(update);
-        msg
+        Message::Sync(SyncMessage::Update(update.to_vec())).encode_v1()
     }
-}
\ No newline at end of file
+
+    /// Create an awareness message from an encoded awareness update
+    pub fn create_awareness(update: &[u8]) -> Vec<u8> {
+        let mut encoder = EncoderV1::new();
+        encoder.write_var(MSG_AWARENESS);
+        encoder.write_buf(update);
+        encoder.to_vec()
+    }
+
+    /// Decode every message packed into a binary payload
+    pub fn decode_messages(payload: &[u8]) -> Result<Vec<Message>> {
+        let mut decoder = DecoderV1::new(Cursor::new(payload));
+        let messages = MessageReader::new(&mut decoder).collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(messages)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use yrs::{Doc, ReadTxn, Text, Transact};
+
+        #[test]
+        fn test_roundtrip_messages() {
+            let doc = Doc::new();
+            let text = doc.get_or_insert_text("content");
+            text.insert(&mut doc.transact_mut(), 0, "hello");
+            let sv = doc.transact().state_vector().encode_v1();
+            let update = doc.transact().encode_state_as_update_v1(&StateVector::default());
+
+            let mut payload = create_sync_step1(&sv).unwrap();
+            payload.extend(create_update(&update));
+
+            let messages = decode_messages(&payload).unwrap();
+            assert_eq!(messages.len(), 2);
+            assert!(matches!(&messages[0], Message::Sync(SyncMessage::SyncStep1(decoded)) if decoded.encode_v1() == sv));
+            assert!(matches!(&messages[1], Message::Sync(SyncMessage::Update(decoded)) if *decoded == update));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_after_join_moves_binary_rooms() {
+        let documents = [Uuid::new_v4(), Uuid::new_v4()];
+
+        let (join, leave) = binary_room_changes(SyncProtocol::Binary, &documents);
+        assert_eq!(join, vec![binary_room(documents[0]), binary_room(documents[1])]);
+        assert!(leave.is_empty());
+
+        let (join, leave) = binary_room_changes(SyncProtocol::Json, &documents);
+        assert!(join.is_empty());
+        assert_eq!(leave, vec![binary_room(documents[0]), binary_room(documents[1])]);
+
+        assert_eq!(binary_room_changes(SyncProtocol::Binary, &[]), (Vec::new(), Vec::new()));
+    }
+}
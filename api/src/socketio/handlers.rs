@@ -1,15 +1,17 @@
-use socketioxide::{extract::{SocketRef, Data}, SocketIo};
+use socketioxide::{extract::{SocketRef, Data, TryData, Bin}, SocketIo};
 use std::sync::Arc;
 use uuid::Uuid;
 use serde::{Deserialize, Serialize};
 use tracing::{error};
 
 use crate::state::AppState;
-use crate::socketio::crdt_sync::{YjsSyncManager, YjsMessage};
-use crate::socketio::connection_tracker::ConnectionTracker;
+use crate::socketio::crdt_sync::{YjsSyncManager, YjsMessage, SyncProtocol, BinaryEnvelope, binary_room, binary_room_changes, document_room};
+use crate::socketio::{auth::verify_socket_auth, broadcaster::user_room, session::DocumentSession};
 use crate::crdt::{UserPresence, CursorPosition, SelectionRange};
 use crate::entities::share::Permission;
+use crate::entities::comment::{AnchorRequest, CommentAuthor, CommentThreadResponse};
 use crate::middleware::permission::check_any_resource_permission;
+use yrs::updates::encoder::Encode;
 
 #[derive(Debug, Deserialize)]
 struct JoinDocumentRequest {
@@ -36,12 +38,42 @@ struct SelectionUpdateRequest {
     selection: Option<SelectionRange>,
 }
 
+#[derive(Debug, Deserialize)]
+struct CommentCreateRequest {
+    document_id: Uuid,
+    anchor: AnchorRequest,
+    body: String,
+    author_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CommentReplyRequest {
+    document_id: Uuid,
+    thread_id: Uuid,
+    body: String,
+    author_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CommentThreadRequest {
+    document_id: Uuid,
+    thread_id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+struct NegotiateRequest {
+    #[serde(default)]
+    protocols: Vec<SyncProtocol>,
+}
+
 #[derive(Debug, Serialize)]
 struct ErrorResponse {
     error: String,
 }
 
 pub fn setup_handlers(io: SocketIo, state: Arc<AppState>) {
+    state.broadcaster.attach(io.clone());
+
     let sync_manager = Arc::new(YjsSyncManager::new(
         state.document_manager.clone(),
         state.awareness_manager.clone(),
@@ -49,15 +81,48 @@ pub fn setup_handlers(io: SocketIo, state: Arc<AppState>) {
         state.clone(),
     ));
     
-    let connection_tracker = Arc::new(ConnectionTracker::new());
+    let connection_tracker = state.connection_tracker.clone();
 
-    io.ns("/", move |socket: SocketRef| {
+    io.ns("/", move |socket: SocketRef, TryData::<serde_json::Value>(auth)| {
         let state = state.clone();
         let sync_manager = sync_manager.clone();
         let connection_tracker = connection_tracker.clone();
         
         async move {
 
+            // Authenticated sockets receive user-scoped events such as git sync status
+            if let Ok(auth) = auth {
+                if let Ok(claims) = verify_socket_auth(&auth, &state.config.jwt_secret) {
+                    if state.user_repository.is_session_active(claims.sid).await.unwrap_or(false) {
+                        socket.join(user_room(claims.sub)).ok();
+                    }
+                }
+            }
+
+            // Negotiate the sync wire format for this connection
+            {
+                let sync_manager = sync_manager.clone();
+                let connection_tracker = connection_tracker.clone();
+                
+                socket.on("sync:negotiate", move |socket: SocketRef, Data::<NegotiateRequest>(data)| {
+                    let socket_id = socket.id.to_string();
+                    let protocol = sync_manager.negotiate_protocol(&socket_id, &data.protocols);
+                    tracing::info!("[SocketIO] Socket {} negotiated {:?} sync protocol", socket.id, protocol);
+                    
+                    // Documents joined before negotiating switch to the new format as well
+                    let (join, leave) = binary_room_changes(protocol, &connection_tracker.get_socket_documents(&socket_id));
+                    for room in join {
+                        socket.join(room).ok();
+                    }
+                    for room in leave {
+                        socket.leave(room).ok();
+                    }
+                    socket.emit("sync:negotiated", serde_json::json!({
+                        "protocol": protocol
+                    })).ok();
+                });
+            }
+
             // Join document room
             {
                 let state = state.clone();
@@ -66,7 +131,7 @@ pub fn setup_handlers(io: SocketIo, state: Arc<AppState>) {
                 
                 socket.on("join_document", move |socket: SocketRef, Data::<JoinDocumentRequest>(data)| {
                     let state = state.clone();
-                    let _sync_manager = sync_manager.clone();
+                    let sync_manager = sync_manager.clone();
                     let connection_tracker = connection_tracker.clone();
                     
                     async move {
@@ -76,6 +141,7 @@ pub fn setup_handlers(io: SocketIo, state: Arc<AppState>) {
                         // Try to authenticate with JWT token if provided
                         let mut user_id = None;
                         let mut user_email = None;
+                        let mut session_id = None;
                         
                         if let Some(token) = &data.auth_token {
                             // Verify JWT token
@@ -83,6 +149,7 @@ pub fn setup_handlers(io: SocketIo, state: Arc<AppState>) {
                                 Ok(claims) => {
                                     user_id = Some(claims.sub);
                                     user_email = Some(claims.email);
+                                    session_id = claims.sid;
                                     tracing::info!("[SocketIO] JWT authentication successful: user_id={}", claims.sub);
                                 }
                                 Err(e) => {
@@ -92,7 +159,26 @@ pub fn setup_handlers(io: SocketIo, state: Arc<AppState>) {
                         } else {
                             tracing::info!("[SocketIO] No auth token provided");
                         }
-                        
+
+                        // Tokens of a logged out session cannot join again until they expire
+                        match state.user_repository.is_session_active(session_id).await {
+                            Ok(true) => {}
+                            Ok(false) => {
+                                tracing::warn!("[SocketIO] Rejected join from revoked session: user_id={:?}", user_id);
+                                socket.emit("error", ErrorResponse {
+                                    error: "Session has been revoked".to_string()
+                                }).ok();
+                                return;
+                            }
+                            Err(e) => {
+                                tracing::error!("[SocketIO] Session check error: {}", e);
+                                socket.emit("error", ErrorResponse {
+                                    error: format!("Permission denied: {}", e)
+                                }).ok();
+                                return;
+                            }
+                        }
+
                         // Check permissions for any resource type (document or scrap) with optional auth and share token
                         tracing::info!("[SocketIO] Checking permissions: user_id={:?}, share_token={:?}", 
                                      user_id, data.share_token.is_some());
@@ -125,6 +211,15 @@ pub fn setup_handlers(io: SocketIo, state: Arc<AppState>) {
                             return;
                         }
                         
+                        // Every later realtime message for the document is checked against this grant
+                        // and kept alongside what it was granted through so revocations can find it
+                        state.session_registry.grant(&socket.id.to_string(), data.document_id, DocumentSession {
+                            check: check.clone(),
+                            user_id,
+                            session_id,
+                            share_token: if check.is_share_link { data.share_token.clone() } else { None },
+                        });
+                        
                         // If using share link, create a temporary user
                         let (final_user_id, final_user_email) = if check.is_share_link {
                             (
@@ -151,6 +246,9 @@ pub fn setup_handlers(io: SocketIo, state: Arc<AppState>) {
                         
                         // Join the document room
                         socket.join(room_name.clone()).ok();
+                        if sync_manager.protocol_for(&socket.id.to_string()) == SyncProtocol::Binary {
+                            socket.join(binary_room(data.document_id)).ok();
+                        }
                         
                         // Track the connection
                         connection_tracker.join_document(&socket.id.to_string(), data.document_id);
@@ -216,13 +314,18 @@ pub fn setup_handlers(io: SocketIo, state: Arc<AppState>) {
                     async move {
                         let room_name = format!("doc:{}", data.document_id);
                         socket.leave(room_name.clone()).ok();
+                        socket.leave(binary_room(data.document_id)).ok();
                         
                         // Update connection tracking
                         connection_tracker.leave_document(&socket.id.to_string(), data.document_id);
+                        state.session_registry.revoke(&socket.id.to_string(), data.document_id);
 
-                        // Remove user presence
+                        // Remove user presence and tell the others to drop this socket's cursors
                         let awareness = state.awareness_manager.get_or_create(data.document_id);
                         awareness.remove_user(&socket.id.to_string());
+                        if let Some(removal) = awareness.remove_owner(&socket.id.to_string()) {
+                            state.broadcaster.broadcast_awareness(data.document_id, &removal.encode_v1(), None).ok();
+                        }
                         
                         // Check if document can be evicted from cache
                         if connection_tracker.is_document_empty(data.document_id) {
@@ -237,6 +340,10 @@ pub fn setup_handlers(io: SocketIo, state: Arc<AppState>) {
                                     error!("Failed to save document {} to file: {}", data.document_id, e);
                                 }
                             }
+                            // Editing session ended, record an automatic snapshot
+                            if let Err(e) = state.history_service.create_auto_snapshot(data.document_id, "Session end").await {
+                                error!("Failed to create snapshot for document {}: {}", data.document_id, e);
+                            }
                             // Optionally evict from cache to save memory
                             // state.crdt_service.evict_from_cache(&data.document_id);
                         }
@@ -275,14 +382,37 @@ pub fn setup_handlers(io: SocketIo, state: Arc<AppState>) {
                 });
             }
 
+            // Handle binary y-protocols sync messages
+            {
+                let sync_manager = sync_manager.clone();
+                
+                socket.on("yjs:binary", move |socket: SocketRef, Data::<BinaryEnvelope>(envelope), Bin(payloads)| {
+                    let sync_manager = sync_manager.clone();
+                    
+                    async move {
+                        for payload in payloads {
+                            if let Err(e) = sync_manager.handle_binary_message(&socket, envelope.document_id, &payload).await {
+                                error!("Failed to handle binary sync message: {}", e);
+                            }
+                        }
+                    }
+                });
+            }
+
             // Handle cursor updates
             {
                 let state = state.clone();
+                let sync_manager = sync_manager.clone();
                 
                 socket.on("cursor_update", move |socket: SocketRef, Data::<CursorUpdateRequest>(data)| {
                     let state = state.clone();
+                    let sync_manager = sync_manager.clone();
                     
                     async move {
+                        if !sync_manager.authorize(&socket, data.document_id, Permission::View) {
+                            return;
+                        }
+                        
                         let awareness = state.awareness_manager.get_or_create(data.document_id);
                         let cursor = data.cursor.clone();
                         awareness.update_cursor(&socket.id.to_string(), cursor).ok();
@@ -299,11 +429,17 @@ pub fn setup_handlers(io: SocketIo, state: Arc<AppState>) {
             // Handle selection updates
             {
                 let state = state.clone();
+                let sync_manager = sync_manager.clone();
                 
                 socket.on("selection_update", move |socket: SocketRef, Data::<SelectionUpdateRequest>(data)| {
                     let state = state.clone();
+                    let sync_manager = sync_manager.clone();
                     
                     async move {
+                        if !sync_manager.authorize(&socket, data.document_id, Permission::View) {
+                            return;
+                        }
+                        
                         let awareness = state.awareness_manager.get_or_create(data.document_id);
                         let selection = data.selection.clone();
                         awareness.update_selection(&socket.id.to_string(), selection).ok();
@@ -317,51 +453,93 @@ pub fn setup_handlers(io: SocketIo, state: Arc<AppState>) {
                 });
             }
 
-            // Handle scrap post events
+            // Comment threads; the resulting thread is broadcast to the room as `comment:thread`
             {
-                let state_clone = state.clone();
-                socket.on("scrap_post_added", move |socket: SocketRef, Data::<serde_json::Value>(data)| {
-                    let _state = state_clone.clone();
+                let state = state.clone();
+                let sync_manager = sync_manager.clone();
+                
+                socket.on("comment:create", move |socket: SocketRef, Data::<CommentCreateRequest>(data)| {
+                    let state = state.clone();
+                    let sync_manager = sync_manager.clone();
                     
                     async move {
-                        if let Some(document_id) = data.get("document_id").and_then(|v| v.as_str()) {
-                            if let Ok(doc_id) = document_id.parse::<Uuid>() {
-                                let room_name = format!("doc:{}", doc_id);
-                                socket.to(room_name).emit("scrap_post_added", data).ok();
-                            }
+                        if !sync_manager.authorize(&socket, data.document_id, Permission::Comment) {
+                            return;
                         }
+                        
+                        let author = comment_author(&state, &socket, data.document_id, data.author_name.as_deref()).await;
+                        let result = state.comment_service
+                            .create_thread(data.document_id, &data.anchor, &data.body, &author)
+                            .await;
+                        report_comment_error(&socket, result);
                     }
                 });
             }
-            
+
             {
-                let state_clone = state.clone();
-                socket.on("scrap_post_updated", move |socket: SocketRef, Data::<serde_json::Value>(data)| {
-                    let _state = state_clone.clone();
+                let state = state.clone();
+                let sync_manager = sync_manager.clone();
+                
+                socket.on("comment:reply", move |socket: SocketRef, Data::<CommentReplyRequest>(data)| {
+                    let state = state.clone();
+                    let sync_manager = sync_manager.clone();
                     
                     async move {
-                        if let Some(document_id) = data.get("document_id").and_then(|v| v.as_str()) {
-                            if let Ok(doc_id) = document_id.parse::<Uuid>() {
-                                let room_name = format!("doc:{}", doc_id);
-                                socket.to(room_name).emit("scrap_post_updated", data).ok();
-                            }
+                        if !sync_manager.authorize(&socket, data.document_id, Permission::Comment) {
+                            return;
                         }
+                        
+                        let author = comment_author(&state, &socket, data.document_id, data.author_name.as_deref()).await;
+                        let result = state.comment_service
+                            .reply(data.document_id, data.thread_id, &data.body, &author)
+                            .await;
+                        report_comment_error(&socket, result);
                     }
                 });
             }
-            
-            {
-                let state_clone = state.clone();
-                socket.on("scrap_post_deleted", move |socket: SocketRef, Data::<serde_json::Value>(data)| {
-                    let _state = state_clone.clone();
+
+            for (event, resolved) in [("comment:resolve", true), ("comment:reopen", false)] {
+                let state = state.clone();
+                let sync_manager = sync_manager.clone();
+                
+                socket.on(event, move |socket: SocketRef, Data::<CommentThreadRequest>(data)| {
+                    let state = state.clone();
+                    let sync_manager = sync_manager.clone();
                     
                     async move {
-                        if let Some(document_id) = data.get("document_id").and_then(|v| v.as_str()) {
-                            if let Ok(doc_id) = document_id.parse::<Uuid>() {
-                                let room_name = format!("doc:{}", doc_id);
-                                socket.to(room_name).emit("scrap_post_deleted", data).ok();
-                            }
+                        if !sync_manager.authorize(&socket, data.document_id, Permission::Comment) {
+                            return;
+                        }
+                        
+                        let user_id = state.session_registry
+                            .session(&socket.id.to_string(), data.document_id)
+                            .and_then(|session| session.user_id);
+                        let result = state.comment_service
+                            .set_resolved(data.document_id, data.thread_id, resolved, user_id)
+                            .await;
+                        report_comment_error(&socket, result);
+                    }
+                });
+            }
+
+            // Relay scrap post events to the other sockets of the document, from editors only
+            for event in ["scrap_post_added", "scrap_post_updated", "scrap_post_deleted"] {
+                let sync_manager = sync_manager.clone();
+
+                socket.on(event, move |socket: SocketRef, Data::<serde_json::Value>(data)| {
+                    let sync_manager = sync_manager.clone();
+
+                    async move {
+                        let Some(document_id) = data.get("document_id")
+                            .and_then(|v| v.as_str())
+                            .and_then(|id| id.parse::<Uuid>().ok()) else {
+                            return;
+                        };
+                        if !sync_manager.authorize(&socket, document_id, Permission::Edit) {
+                            return;
                         }
+
+                        socket.to(document_room(document_id)).emit(event, data).ok();
                     }
                 });
             }
@@ -382,6 +560,15 @@ pub fn setup_handlers(io: SocketIo, state: Arc<AppState>) {
                             else { "unknown" }
                         );
                         
+                        // Updates tagged with their document arrive as { document_id, update }
+                        let document_id = data.get("document_id")
+                            .and_then(|v| v.as_str())
+                            .and_then(|id| id.parse::<Uuid>().ok());
+                        let data = match data.get("update") {
+                            Some(update) if document_id.is_some() => update.clone(),
+                            _ => data,
+                        };
+
                         // Try to extract binary data from various formats
                         let binary_data = if let Some(array) = data.as_array() {
                             // If it's an array of numbers, convert to Vec<u8>
@@ -412,7 +599,7 @@ pub fn setup_handlers(io: SocketIo, state: Arc<AppState>) {
                         };
                         
                         if !binary_data.is_empty() {
-                            if let Err(e) = sync_manager.handle_awareness_binary(&socket, binary_data).await {
+                            if let Err(e) = sync_manager.handle_awareness_binary(&socket, document_id, binary_data).await {
                                 error!("Failed to handle awareness message: {}", e);
                             }
                         }
@@ -425,12 +612,16 @@ pub fn setup_handlers(io: SocketIo, state: Arc<AppState>) {
             {
                 let state = state.clone();
                 let connection_tracker = connection_tracker.clone();
+                let sync_manager = sync_manager.clone();
                 
                 socket.on_disconnect(move |socket: SocketRef| {
                     let state = state.clone();
                     let connection_tracker = connection_tracker.clone();
+                    let sync_manager = sync_manager.clone();
                     
                     async move {
+                        sync_manager.forget_socket(&socket.id.to_string());
+                        state.session_registry.remove_socket(&socket.id.to_string());
 
                         // Get all documents this socket was connected to
                         let documents = connection_tracker.remove_socket(&socket.id.to_string());
@@ -440,6 +631,9 @@ pub fn setup_handlers(io: SocketIo, state: Arc<AppState>) {
                             // Remove from awareness
                             let awareness = state.awareness_manager.get_or_create(doc_id);
                             awareness.remove_user(&socket.id.to_string());
+                            if let Some(removal) = awareness.remove_owner(&socket.id.to_string()) {
+                                state.broadcaster.broadcast_awareness(doc_id, &removal.encode_v1(), None).ok();
+                            }
                             
                             // Broadcast user left to remaining users
                             let room_name = format!("doc:{}", doc_id);
@@ -474,6 +668,10 @@ pub fn setup_handlers(io: SocketIo, state: Arc<AppState>) {
                             
                             // Check if document can be evicted
                             if connection_tracker.is_document_empty(doc_id) {
+                                // Editing session ended, record an automatic snapshot
+                                if let Err(e) = state.history_service.create_auto_snapshot(doc_id, "Session end").await {
+                                    error!("Failed to create snapshot for document {}: {}", doc_id, e);
+                                }
                                 // Optionally evict from cache to save memory
                                 // state.crdt_service.evict_from_cache(&doc_id);
                             }
@@ -485,6 +683,23 @@ pub fn setup_handlers(io: SocketIo, state: Arc<AppState>) {
     });
 }
 
+/// Author of a comment sent over a socket: the user it joined as, or the name a guest gave
+async fn comment_author(state: &AppState, socket: &SocketRef, document_id: Uuid, author_name: Option<&str>) -> CommentAuthor {
+    let user_id = state.session_registry
+        .session(&socket.id.to_string(), document_id)
+        .and_then(|session| session.user_id);
+    state.comment_service.author(user_id, author_name).await
+}
+
+fn report_comment_error(socket: &SocketRef, result: crate::error::Result<CommentThreadResponse>) {
+    if let Err(e) = result {
+        tracing::warn!("[SocketIO] Comment request from {} failed: {}", socket.id, e);
+        socket.emit("error", ErrorResponse {
+            error: e.to_string()
+        }).ok();
+    }
+}
+
 fn generate_user_color(user_id: &str) -> String {
     // Generate a consistent color based on user ID
     let hash = user_id.bytes().fold(0u32, |acc, b| acc.wrapping_add(b as u32));
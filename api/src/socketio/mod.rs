@@ -2,5 +2,11 @@ pub mod handlers;
 pub mod crdt_sync;
 pub mod connection_tracker;
 pub mod auth;
+pub mod broadcaster;
+pub mod session;
+pub mod revocation;
 
 pub use handlers::setup_handlers;
+pub use broadcaster::SocketBroadcaster;
+pub use session::SessionRegistry;
+pub use revocation::{Revocation, SessionRevoker};
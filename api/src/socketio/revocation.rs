@@ -0,0 +1,395 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+use yrs::updates::encoder::Encode;
+
+use crate::crdt::AwarenessManager;
+use crate::entities::share::Permission;
+use crate::services::cluster_sync::ClusterMessageKind;
+use crate::socketio::broadcaster::{user_room, SocketBroadcaster};
+use crate::socketio::connection_tracker::ConnectionTracker;
+use crate::socketio::crdt_sync::{binary_room, document_room};
+use crate::socketio::session::{DocumentSession, SessionRegistry};
+use crate::websocket::WebsocketPeers;
+
+/// Event telling a socket it lost (part of) its access to a document
+pub const ACCESS_REVOKED_EVENT: &str = "access_revoked";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RevocationReason {
+    /// The share link the socket joined through was deleted
+    ShareLinkDeleted,
+    /// The user's permission on the document was lowered or removed
+    PermissionChanged,
+    /// The user's tokens were revoked by logging out
+    SessionRevoked,
+    /// The document was moved to the trash
+    DocumentTrashed,
+}
+
+/// Access that was taken away, applied to the live sessions it covers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Revocation {
+    /// A share link was deleted
+    ShareLink { document_id: Uuid, token: String },
+    /// A user's explicit permission on a document changed, None when removed
+    UserPermission { document_id: Uuid, user_id: Uuid, permission: Option<Permission> },
+    /// A user logged out; `session_id` limits it to connections of one login session
+    UserSessions { user_id: Uuid, session_id: Option<Uuid> },
+    /// A document was moved to the trash, ending every session on it
+    Document { document_id: Uuid },
+}
+
+/// Payload of an `access_revoked` event
+#[derive(Debug, Clone, Serialize)]
+pub struct AccessRevoked {
+    pub document_id: Uuid,
+    pub reason: RevocationReason,
+    /// Permission the socket keeps, None when it was removed from the document
+    pub permission: Option<Permission>,
+}
+
+/// Downgrades or kicks the sockets and y-websocket connections whose access was revoked.
+///
+/// Sessions are found through the permissions recorded at `join_document` or at the
+/// y-websocket upgrade; connections on other API replicas are reached by relaying the
+/// revocation over the cluster channel.
+pub struct SessionRevoker {
+    session_registry: Arc<SessionRegistry>,
+    websocket_peers: Arc<WebsocketPeers>,
+    connection_tracker: Arc<ConnectionTracker>,
+    awareness_manager: Arc<AwarenessManager>,
+    broadcaster: Arc<SocketBroadcaster>,
+}
+
+impl SessionRevoker {
+    pub fn new(
+        session_registry: Arc<SessionRegistry>,
+        websocket_peers: Arc<WebsocketPeers>,
+        connection_tracker: Arc<ConnectionTracker>,
+        awareness_manager: Arc<AwarenessManager>,
+        broadcaster: Arc<SocketBroadcaster>,
+    ) -> Self {
+        Self {
+            session_registry,
+            websocket_peers,
+            connection_tracker,
+            awareness_manager,
+            broadcaster,
+        }
+    }
+
+    /// Revoke access on every replica
+    pub fn revoke(&self, revocation: Revocation) {
+        match serde_json::to_vec(&revocation) {
+            Ok(data) => self.broadcaster.cluster().publish(revocation.document_id(), ClusterMessageKind::Revocation, &data),
+            Err(e) => tracing::error!("Failed to encode revocation: {}", e),
+        }
+        self.apply(&revocation);
+    }
+
+    /// Apply a revocation to the connections on this node, returning the number of sessions affected
+    pub fn apply(&self, revocation: &Revocation) -> usize {
+        let affected = self.apply_to_sockets(revocation) + self.apply_to_peers(revocation);
+
+        if affected > 0 {
+            tracing::info!("Revoked {} live session(s): {:?}", affected, revocation);
+        }
+        affected
+    }
+
+    fn apply_to_sockets(&self, revocation: &Revocation) -> usize {
+        match revocation {
+            Revocation::ShareLink { .. } => {
+                let sessions = self.session_registry.find_sessions(|session| revocation.covers(session));
+                for (socket_id, document_id) in &sessions {
+                    self.kick(socket_id, *document_id, RevocationReason::ShareLinkDeleted);
+                }
+                sessions.len()
+            }
+            Revocation::UserPermission { document_id, permission, .. } => {
+                let sessions = self.session_registry.find_sessions(|session| revocation.covers(session));
+                let mut affected = 0;
+                for (socket_id, _) in sessions.iter().filter(|(_, id)| id == document_id) {
+                    match permission {
+                        Some(permission) => {
+                            if self.downgrade(socket_id, *document_id, *permission) {
+                                affected += 1;
+                            }
+                        }
+                        None => {
+                            self.kick(socket_id, *document_id, RevocationReason::PermissionChanged);
+                            affected += 1;
+                        }
+                    }
+                }
+                affected
+            }
+            Revocation::UserSessions { user_id, .. } => {
+                let sessions = self.session_registry.find_sessions(|session| revocation.covers(session));
+                for (socket_id, document_id) in &sessions {
+                    self.kick(socket_id, *document_id, RevocationReason::SessionRevoked);
+                    if let Some(socket) = self.broadcaster.socket(socket_id) {
+                        socket.leave(user_room(*user_id)).ok();
+                    }
+                }
+                sessions.len()
+            }
+            Revocation::Document { document_id } => {
+                let sessions = self.session_registry.find_sessions(|_| true);
+                let mut affected = 0;
+                for (socket_id, _) in sessions.iter().filter(|(_, id)| id == document_id) {
+                    self.kick(socket_id, *document_id, RevocationReason::DocumentTrashed);
+                    affected += 1;
+                }
+                affected
+            }
+        }
+    }
+
+    /// Lower the permission of y-websocket connections, or close them. Closed connections
+    /// clean up their awareness and presence as if the client had disconnected.
+    fn apply_to_peers(&self, revocation: &Revocation) -> usize {
+        let peers = self.websocket_peers.find(|session| revocation.covers(session));
+        let mut affected = 0;
+
+        for (peer_document_id, connection_id) in peers {
+            // Share links and logouts reach every document joined through them
+            match revocation {
+                Revocation::UserPermission { document_id, .. } | Revocation::Document { document_id }
+                    if *document_id != peer_document_id => continue,
+                _ => {}
+            }
+            match revocation {
+                Revocation::UserPermission { permission: Some(permission), .. } => {
+                    let previous = self.websocket_peers.set_permission(peer_document_id, connection_id, *permission);
+                    if previous.is_some_and(|previous| !permission.has_permission(previous)) {
+                        affected += 1;
+                    }
+                }
+                _ => {
+                    self.websocket_peers.leave(peer_document_id, connection_id);
+                    affected += 1;
+                }
+            }
+        }
+        affected
+    }
+
+    /// Lower the permission of a session; raised permissions are recorded without notice
+    fn downgrade(&self, socket_id: &str, document_id: Uuid, permission: Permission) -> bool {
+        let Some(previous) = self.session_registry.set_permission(socket_id, document_id, permission) else {
+            return false;
+        };
+        if permission.has_permission(previous) {
+            return false;
+        }
+
+        if let Some(socket) = self.broadcaster.socket(socket_id) {
+            socket.emit(ACCESS_REVOKED_EVENT, AccessRevoked {
+                document_id,
+                reason: RevocationReason::PermissionChanged,
+                permission: Some(permission),
+            }).ok();
+        }
+        true
+    }
+
+    /// Remove a socket from a document as if it had left it
+    fn kick(&self, socket_id: &str, document_id: Uuid, reason: RevocationReason) {
+        self.session_registry.revoke(socket_id, document_id);
+        self.connection_tracker.leave_document(socket_id, document_id);
+
+        let awareness = self.awareness_manager.get_or_create(document_id);
+        awareness.remove_user(socket_id);
+        if let Some(removal) = awareness.remove_owner(socket_id) {
+            self.broadcaster.broadcast_awareness(document_id, &removal.encode_v1(), None).ok();
+        }
+
+        if let Some(socket) = self.broadcaster.socket(socket_id) {
+            socket.leave(document_room(document_id)).ok();
+            socket.leave(binary_room(document_id)).ok();
+            socket.emit(ACCESS_REVOKED_EVENT, AccessRevoked {
+                document_id,
+                reason,
+                permission: None,
+            }).ok();
+        }
+
+        self.broadcaster.emit_to_room(document_room(document_id), "user_left", serde_json::json!({
+            "client_id": socket_id
+        })).ok();
+        self.broadcaster.emit_to_room(document_room(document_id), "user_count_update", serde_json::json!({
+            "count": self.connection_tracker.get_document_sockets(document_id).len()
+        })).ok();
+    }
+}
+
+impl Revocation {
+    /// Whether a session was granted through the access this revocation takes away,
+    /// on whichever document it joined
+    fn covers(&self, session: &DocumentSession) -> bool {
+        match self {
+            Revocation::ShareLink { token, .. } => {
+                session.check.is_share_link && session.share_token.as_deref() == Some(token.as_str())
+            }
+            // Owners keep their access whatever their explicit permission says
+            Revocation::UserPermission { user_id, .. } => {
+                session.user_id == Some(*user_id)
+                    && !session.check.is_share_link
+                    && session.check.permission_level != Permission::Owner
+            }
+            Revocation::UserSessions { user_id, session_id } => {
+                session.user_id == Some(*user_id)
+                    && (session_id.is_none() || session.session_id == *session_id)
+            }
+            Revocation::Document { .. } => true,
+        }
+    }
+
+    /// Document the revocation applies to, nil when it spans all of a user's documents
+    pub fn document_id(&self) -> Uuid {
+        match self {
+            Revocation::ShareLink { document_id, .. }
+            | Revocation::UserPermission { document_id, .. }
+            | Revocation::Document { document_id } => *document_id,
+            Revocation::UserSessions { .. } => Uuid::nil(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::permission::PermissionCheck;
+    use crate::services::cluster_sync::ClusterPublisher;
+    use tokio::sync::mpsc::{self, error::TryRecvError};
+
+    fn revoker() -> SessionRevoker {
+        SessionRevoker::new(
+            Arc::new(SessionRegistry::new()),
+            Arc::new(WebsocketPeers::new()),
+            Arc::new(ConnectionTracker::new()),
+            Arc::new(AwarenessManager::new()),
+            Arc::new(SocketBroadcaster::new(Arc::new(WebsocketPeers::new()), ClusterPublisher::default())),
+        )
+    }
+
+    fn join(revoker: &SessionRevoker, socket_id: &str, document_id: Uuid, session: DocumentSession) {
+        revoker.connection_tracker.join_document(socket_id, document_id);
+        revoker.session_registry.grant(socket_id, document_id, session);
+    }
+
+    const LOGIN_SESSION: Uuid = Uuid::from_u128(100);
+
+    fn session(permission_level: Permission, user_id: Option<Uuid>, share_token: Option<&str>) -> DocumentSession {
+        DocumentSession {
+            check: PermissionCheck {
+                has_access: true,
+                is_share_link: share_token.is_some(),
+                permission_level,
+            },
+            user_id,
+            session_id: Some(LOGIN_SESSION),
+            share_token: share_token.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_share_link_revocation_kicks_its_sessions() {
+        let revoker = revoker();
+        let document_id = Uuid::new_v4();
+        join(&revoker, "guest", document_id, session(Permission::Edit, None, Some("link")));
+        join(&revoker, "other", document_id, session(Permission::View, None, Some("other-link")));
+
+        let affected = revoker.apply(&Revocation::ShareLink { document_id, token: "link".to_string() });
+
+        assert_eq!(affected, 1);
+        assert!(revoker.session_registry.permission("guest", document_id).is_none());
+        assert!(!revoker.connection_tracker.is_socket_in_document("guest", document_id));
+        assert_eq!(revoker.session_registry.permission("other", document_id), Some(Permission::View));
+    }
+
+    #[test]
+    fn test_user_permission_revocation_downgrades_or_kicks() {
+        let revoker = revoker();
+        let document_id = Uuid::new_v4();
+        let user_id = Uuid::new_v4();
+        join(&revoker, "editor", document_id, session(Permission::Edit, Some(user_id), None));
+
+        let lowered = Revocation::UserPermission { document_id, user_id, permission: Some(Permission::View) };
+        assert_eq!(revoker.apply(&lowered), 1);
+        assert_eq!(revoker.session_registry.permission("editor", document_id), Some(Permission::View));
+
+        let removed = Revocation::UserPermission { document_id, user_id, permission: None };
+        assert_eq!(revoker.apply(&removed), 1);
+        assert!(revoker.session_registry.permission("editor", document_id).is_none());
+    }
+
+    #[test]
+    fn test_user_sessions_revocation_matches_login_session() {
+        let revoker = revoker();
+        let user_id = Uuid::new_v4();
+        let document_id = Uuid::new_v4();
+        join(&revoker, "owner", document_id, session(Permission::Owner, Some(user_id), None));
+
+        let other_session = Revocation::UserSessions { user_id, session_id: Some(Uuid::new_v4()) };
+        assert_eq!(revoker.apply(&other_session), 0);
+        let own_session = Revocation::UserSessions { user_id, session_id: Some(LOGIN_SESSION) };
+        assert_eq!(revoker.apply(&own_session), 1);
+        assert!(revoker.connection_tracker.is_document_empty(document_id));
+    }
+
+    #[test]
+    fn test_document_revocation_kicks_everyone_on_it() {
+        let revoker = revoker();
+        let document_id = Uuid::new_v4();
+        let other_document = Uuid::new_v4();
+        join(&revoker, "owner", document_id, session(Permission::Owner, Some(Uuid::new_v4()), None));
+        join(&revoker, "guest", document_id, session(Permission::View, None, Some("link")));
+        join(&revoker, "elsewhere", other_document, session(Permission::Edit, Some(Uuid::new_v4()), None));
+
+        assert_eq!(revoker.apply(&Revocation::Document { document_id }), 2);
+        assert!(revoker.connection_tracker.is_document_empty(document_id));
+        assert_eq!(revoker.session_registry.permission("elsewhere", other_document), Some(Permission::Edit));
+    }
+
+    #[test]
+    fn test_revocations_close_or_downgrade_y_websocket_peers() {
+        let revoker = revoker();
+        let document_id = Uuid::new_v4();
+        let user_id = Uuid::new_v4();
+        let (editor, guest) = (Uuid::new_v4(), Uuid::new_v4());
+        let (editor_tx, mut editor_rx) = mpsc::unbounded_channel();
+        let (guest_tx, mut guest_rx) = mpsc::unbounded_channel();
+        revoker.websocket_peers.join(document_id, editor, editor_tx, session(Permission::Edit, Some(user_id), None));
+        revoker.websocket_peers.join(document_id, guest, guest_tx, session(Permission::Edit, None, Some("link")));
+
+        let lowered = Revocation::UserPermission { document_id, user_id, permission: Some(Permission::View) };
+        assert_eq!(revoker.apply(&lowered), 1);
+        assert_eq!(revoker.websocket_peers.permission(document_id, editor), Some(Permission::View));
+
+        assert_eq!(revoker.apply(&Revocation::ShareLink { document_id, token: "link".to_string() }), 1);
+        assert_eq!(guest_rx.try_recv(), Err(TryRecvError::Disconnected));
+
+        assert_eq!(revoker.apply(&Revocation::UserSessions { user_id, session_id: Some(LOGIN_SESSION) }), 1);
+        assert_eq!(editor_rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn test_document_revocation_closes_y_websocket_peers_on_it() {
+        let revoker = revoker();
+        let document_id = Uuid::new_v4();
+        let other_document = Uuid::new_v4();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (other_tx, mut other_rx) = mpsc::unbounded_channel();
+        revoker.websocket_peers.join(document_id, Uuid::new_v4(), tx, session(Permission::Owner, Some(Uuid::new_v4()), None));
+        revoker.websocket_peers.join(other_document, Uuid::new_v4(), other_tx, session(Permission::Edit, None, Some("link")));
+
+        assert_eq!(revoker.apply(&Revocation::Document { document_id }), 1);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+        assert_eq!(other_rx.try_recv(), Err(TryRecvError::Empty));
+    }
+}
@@ -0,0 +1,182 @@
+use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::entities::share::Permission;
+use crate::middleware::permission::PermissionCheck;
+
+/// Event carrying rejected realtime messages back to the socket that sent them
+pub const SYNC_ERROR_EVENT: &str = "sync-error";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectionCode {
+    /// The socket has not joined the document
+    NotJoined,
+    /// The socket joined with a permission below the one required
+    InsufficientPermission,
+}
+
+/// Structured payload of a `sync-error` event for a rejected message
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionRejection {
+    pub code: RejectionCode,
+    pub document_id: Uuid,
+    pub required: Permission,
+    /// Permission the socket joined with, if it joined
+    pub permission: Option<Permission>,
+    pub message: String,
+}
+
+/// How a socket joined a document and the permission it was granted
+#[derive(Debug, Clone)]
+pub struct DocumentSession {
+    pub check: PermissionCheck,
+    /// Authenticated user, None for guests
+    pub user_id: Option<Uuid>,
+    /// Login session of the access token the socket authenticated with
+    pub session_id: Option<Uuid>,
+    /// Share link the socket joined through
+    pub share_token: Option<String>,
+}
+
+/// Per-socket sessions: the permission each socket was granted on the documents it joined.
+///
+/// Permissions are resolved once in `join_document`; every realtime message afterwards is
+/// checked against the recorded grant instead of going back to the database.
+#[derive(Clone, Default)]
+pub struct SessionRegistry {
+    sessions: Arc<DashMap<String, HashMap<Uuid, DocumentSession>>>,
+}
+
+impl SessionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the permission a socket joined a document with
+    pub fn grant(&self, socket_id: &str, document_id: Uuid, session: DocumentSession) {
+        self.sessions
+            .entry(socket_id.to_string())
+            .or_default()
+            .insert(document_id, session);
+    }
+
+    /// Change the permission of an existing session, returning the previous one
+    pub fn set_permission(&self, socket_id: &str, document_id: Uuid, permission: Permission) -> Option<Permission> {
+        let mut documents = self.sessions.get_mut(socket_id)?;
+        let session = documents.get_mut(&document_id)?;
+        Some(std::mem::replace(&mut session.check.permission_level, permission))
+    }
+
+    /// Session of a socket on a document
+    pub fn session(&self, socket_id: &str, document_id: Uuid) -> Option<DocumentSession> {
+        self.sessions
+            .get(socket_id)
+            .and_then(|documents| documents.get(&document_id).cloned())
+    }
+
+    /// Sessions of every socket matching a predicate, as (socket id, document id) pairs
+    pub fn find_sessions(&self, predicate: impl Fn(&DocumentSession) -> bool) -> Vec<(String, Uuid)> {
+        self.sessions
+            .iter()
+            .flat_map(|entry| {
+                entry.value()
+                    .iter()
+                    .filter(|(_, session)| predicate(session))
+                    .map(|(document_id, _)| (entry.key().clone(), *document_id))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Forget a socket's grant on a document it left
+    pub fn revoke(&self, socket_id: &str, document_id: Uuid) {
+        if let Some(mut documents) = self.sessions.get_mut(socket_id) {
+            documents.remove(&document_id);
+        }
+    }
+
+    /// Drop every grant of a disconnected socket
+    pub fn remove_socket(&self, socket_id: &str) {
+        self.sessions.remove(socket_id);
+    }
+
+    /// Permission a socket was granted on a document, None if it has not joined it
+    pub fn permission(&self, socket_id: &str, document_id: Uuid) -> Option<Permission> {
+        self.sessions
+            .get(socket_id)
+            .and_then(|documents| documents.get(&document_id).map(|session| session.check.permission_level))
+    }
+
+    /// Check that a socket joined a document with at least the required permission
+    pub fn authorize(&self, socket_id: &str, document_id: Uuid, required: Permission) -> Result<Permission, SessionRejection> {
+        match self.permission(socket_id, document_id) {
+            Some(permission) if permission.has_permission(required) => Ok(permission),
+            Some(permission) => Err(SessionRejection {
+                code: RejectionCode::InsufficientPermission,
+                document_id,
+                required,
+                permission: Some(permission),
+                message: format!("{} permission required, joined with {}", required, permission),
+            }),
+            None => Err(SessionRejection {
+                code: RejectionCode::NotJoined,
+                document_id,
+                required,
+                permission: None,
+                message: "Document has not been joined".to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(permission_level: Permission) -> DocumentSession {
+        DocumentSession {
+            check: PermissionCheck {
+                has_access: true,
+                is_share_link: true,
+                permission_level,
+            },
+            user_id: None,
+            session_id: None,
+            share_token: Some("token".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_authorize_by_joined_permission() {
+        let registry = SessionRegistry::new();
+        let document_id = Uuid::new_v4();
+        registry.grant("socket", document_id, check(Permission::View));
+
+        assert_eq!(registry.authorize("socket", document_id, Permission::View).unwrap(), Permission::View);
+        let rejection = registry.authorize("socket", document_id, Permission::Edit).unwrap_err();
+        assert_eq!(rejection.code, RejectionCode::InsufficientPermission);
+        assert_eq!(rejection.permission, Some(Permission::View));
+
+        assert_eq!(registry.set_permission("socket", document_id, Permission::Edit), Some(Permission::View));
+        assert!(registry.authorize("socket", document_id, Permission::Edit).is_ok());
+        assert_eq!(registry.find_sessions(|session| session.share_token.as_deref() == Some("token")), vec![("socket".to_string(), document_id)]);
+    }
+
+    #[test]
+    fn test_authorize_requires_join() {
+        let registry = SessionRegistry::new();
+        let document_id = Uuid::new_v4();
+        registry.grant("socket", document_id, check(Permission::Owner));
+
+        let rejection = registry.authorize("socket", Uuid::new_v4(), Permission::View).unwrap_err();
+        assert_eq!(rejection.code, RejectionCode::NotJoined);
+        assert!(registry.authorize("other", document_id, Permission::View).is_err());
+
+        registry.revoke("socket", document_id);
+        assert!(registry.authorize("socket", document_id, Permission::View).is_err());
+    }
+}
@@ -3,9 +3,11 @@ use std::path::PathBuf;
 use sqlx::PgPool;
 use crate::config::Config;
 use crate::crdt::{DocumentManager, AwarenessManager, DocumentPersistence};
-use crate::services::{crdt::CrdtService, document::DocumentService, file::FileService, share::ShareService, git_sync::GitSyncService, git_batch_sync::GitBatchSyncService, document_links::DocumentLinksService, PublicDocumentService, UrlGeneratorService};
-use crate::repository::{DocumentRepository, ShareRepository, UserRepository, GitConfigRepository, tag::TagRepository};
+use crate::services::{crdt::CrdtService, document::DocumentService, file::FileService, share::ShareService, git_sync::GitSyncService, git_batch_sync::GitBatchSyncService, git_import::GitImportService, document_links::DocumentLinksService, history::DocumentHistoryService, comment::CommentService, suggestion::SuggestionService, history_compaction::HistoryCompactionService, document_cache::DocumentCacheService, cluster_sync::{ClusterPublisher, ClusterSyncService}, file_watcher::{FileWatcherService, OwnFileWrites}, search::SearchService, transclusion::TransclusionService, trash_expiry::TrashExpiryService, PublicDocumentService, UrlGeneratorService};
+use crate::repository::{DocumentRepository, ShareRepository, UserRepository, GitConfigRepository, SnapshotRepository, CommentRepository, SuggestionRepository, SearchRepository, tag::TagRepository};
+use crate::socketio::{SocketBroadcaster, SessionRegistry, SessionRevoker, connection_tracker::ConnectionTracker};
 use crate::utils::jwt::JwtService;
+use crate::websocket::WebsocketPeers;
 
 #[derive(Clone)]
 pub struct AppState {
@@ -16,14 +18,30 @@ pub struct AppState {
     pub awareness_manager: Arc<AwarenessManager>,
     pub document_persistence: Arc<DocumentPersistence>,
     pub crdt_service: Arc<CrdtService>,
+    pub document_cache_service: Arc<DocumentCacheService>,
+    pub connection_tracker: Arc<ConnectionTracker>,
+    pub session_registry: Arc<SessionRegistry>,
+    pub session_revoker: Arc<SessionRevoker>,
     pub document_service: Arc<DocumentService>,
     pub file_service: Arc<FileService>,
     pub share_service: Arc<ShareService>,
     pub git_sync_service: Arc<GitSyncService>,
     pub git_batch_sync_service: Option<Arc<GitBatchSyncService>>,
+    pub git_import_service: Arc<GitImportService>,
     pub document_links_service: Arc<DocumentLinksService>,
     pub public_document_service: Arc<PublicDocumentService>,
     pub url_generator: Arc<UrlGeneratorService>,
+    pub history_service: Arc<DocumentHistoryService>,
+    pub comment_service: Arc<CommentService>,
+    pub suggestion_service: Arc<SuggestionService>,
+    pub search_service: Arc<SearchService>,
+    pub transclusion_service: Arc<TransclusionService>,
+    pub history_compaction_service: Option<Arc<HistoryCompactionService>>,
+    pub trash_expiry_service: Option<Arc<TrashExpiryService>>,
+    pub broadcaster: Arc<SocketBroadcaster>,
+    pub cluster_sync_service: Option<Arc<ClusterSyncService>>,
+    pub file_watcher_service: Option<Arc<FileWatcherService>>,
+    pub websocket_peers: Arc<WebsocketPeers>,
     pub document_repository: Arc<DocumentRepository>,
     pub share_repository: Arc<ShareRepository>,
     pub user_repository: Arc<UserRepository>,
@@ -52,6 +70,20 @@ impl AppState {
             document_persistence.clone(),
         ));
         
+        // Track socket connections per document, shared with the cache eviction job
+        let connection_tracker = Arc::new(ConnectionTracker::new());
+        // Permissions each socket joined its documents with
+        let session_registry = Arc::new(SessionRegistry::new());
+        let document_cache_service = Arc::new(DocumentCacheService::new(
+            crdt_service.clone(),
+            document_manager.clone(),
+            connection_tracker.clone(),
+            config.document_cache_idle_timeout,
+            config.document_cache_max_documents,
+            config.document_cache_max_memory_mb,
+            config.document_cache_eviction_interval,
+        ));
+        
         // Create storage directory from config
         let storage_path = PathBuf::from(&config.upload_dir);
         
@@ -68,10 +100,31 @@ impl AppState {
             &config.jwt_secret,
         ).expect("Failed to create GitSyncService"));
         
+        // Socket.IO handle is attached once the layer is built
+        let websocket_peers = Arc::new(WebsocketPeers::new());
+        let (cluster_publisher, cluster_outbound) = if config.cluster_sync_enabled {
+            let (publisher, outbound) = ClusterPublisher::channel();
+            (publisher, Some(outbound))
+        } else {
+            (ClusterPublisher::default(), None)
+        };
+        let broadcaster = Arc::new(SocketBroadcaster::new(websocket_peers.clone(), cluster_publisher));
+        
+        // Downgrade or kick live sessions when access is revoked
+        let session_revoker = Arc::new(SessionRevoker::new(
+            session_registry.clone(),
+            websocket_peers.clone(),
+            connection_tracker.clone(),
+            awareness_manager.clone(),
+            broadcaster.clone(),
+        ));
+        
         // Create batch sync service if auto sync is enabled
         let git_batch_sync_service = if config.git_sync_enabled && config.git_auto_sync {
             Some(Arc::new(GitBatchSyncService::new(
                 git_sync_service.clone(),
+                git_config_repository.clone(),
+                broadcaster.clone(),
                 config.git_sync_interval,
             )))
         } else {
@@ -79,7 +132,11 @@ impl AppState {
         };
         
         // Create document links service first
-        let document_links_service = Arc::new(DocumentLinksService::new(db_pool.clone()));
+        let document_links_service = Arc::new(DocumentLinksService::new(
+            db_pool.clone(),
+            crdt_service.clone(),
+            broadcaster.clone(),
+        ));
         
         // Create public document service
         let public_document_service = Arc::new(PublicDocumentService::new(db_pool.clone()));
@@ -99,6 +156,9 @@ impl AppState {
         // Create tag repository
         let tag_repository = Arc::new(TagRepository::new((*db_pool).clone()));
         
+        // Files the server writes itself, so the file watcher does not read them back
+        let own_file_writes = Arc::new(OwnFileWrites::new());
+        
         // Create document service with batch sync if enabled
         let document_service = Arc::new(DocumentService::new(
             document_repository.clone(),
@@ -108,7 +168,30 @@ impl AppState {
             Arc::new(config.clone()),
         ).with_links_service(document_links_service.clone())
          .with_file_service(file_service.clone())
-         .with_tag_repository(tag_repository.clone()));
+         .with_tag_repository(tag_repository.clone())
+         .with_own_writes(own_file_writes.clone())
+         .with_session_revoker(session_revoker.clone()));
+        
+        // Create git import service to bring pulled files back into documents
+        let git_import_service = Arc::new(GitImportService::new(
+            document_repository.clone(),
+            document_service.clone(),
+            crdt_service.clone(),
+            broadcaster.clone(),
+            storage_path.clone(),
+        ));
+        
+        // Watch the stored markdown files for edits made outside RefMD
+        let file_watcher_service = if config.file_watcher_enabled {
+            Some(Arc::new(FileWatcherService::new(
+                storage_path.clone(),
+                own_file_writes.clone(),
+                git_import_service.clone(),
+                config.file_watcher_debounce_ms,
+            )))
+        } else {
+            None
+        };
         
         // Create share service with frontend URL from config
         let share_service = Arc::new(ShareService::new(
@@ -120,6 +203,78 @@ impl AppState {
         let share_repository = Arc::new(ShareRepository::new(db_pool.clone()));
         let user_repository = Arc::new(UserRepository::new(db_pool.clone()));
         
+        // Fan realtime updates out to other API replicas when running more than one
+        let cluster_sync_service = cluster_outbound.map(|outbound| {
+            Arc::new(ClusterSyncService::new(
+                db_pool.clone(),
+                document_manager.clone(),
+                awareness_manager.clone(),
+                connection_tracker.clone(),
+                broadcaster.clone(),
+                session_revoker.clone(),
+                outbound,
+            ))
+        });
+        
+        // Create version history service
+        let snapshot_repository = Arc::new(SnapshotRepository::new(db_pool.clone()));
+        let history_service = Arc::new(DocumentHistoryService::new(
+            snapshot_repository.clone(),
+            document_persistence.clone(),
+            crdt_service.clone(),
+            broadcaster.clone(),
+        ));
+        
+        // Create comment service for threads anchored to document text
+        let comment_service = Arc::new(CommentService::new(
+            Arc::new(CommentRepository::new(db_pool.clone())),
+            user_repository.clone(),
+            crdt_service.clone(),
+            broadcaster.clone(),
+        ));
+        
+        // Create suggestion service for proposed edits awaiting review
+        let suggestion_service = Arc::new(SuggestionService::new(
+            Arc::new(SuggestionRepository::new(db_pool.clone())),
+            crdt_service.clone(),
+            broadcaster.clone(),
+        ));
+        
+        // Create full-text search over document content
+        let search_service = Arc::new(SearchService::new(
+            Arc::new(SearchRepository::new(db_pool.clone())),
+            document_persistence.clone(),
+        ));
+        
+        // Expand ![[embeds]] when rendering documents for viewing and export
+        let transclusion_service = Arc::new(TransclusionService::new(
+            db_pool.clone(),
+            crdt_service.clone(),
+            document_links_service.link_resolver.clone(),
+        ));
+        
+        // Create history compaction job unless retention is disabled
+        let history_compaction_service = if config.history_retention_days > 0 {
+            Some(Arc::new(HistoryCompactionService::new(
+                document_persistence.clone(),
+                snapshot_repository.clone(),
+                config.history_retention_days,
+                config.history_compaction_interval,
+            )))
+        } else {
+            None
+        };
+        
+        // Create trash expiry job unless trash is kept until purged by hand
+        let trash_expiry_service = if config.trash_retention_days > 0 {
+            Some(Arc::new(TrashExpiryService::new(
+                document_service.clone(),
+                config.trash_purge_interval,
+            )))
+        } else {
+            None
+        };
+        
         Arc::new(Self {
             config,
             db_pool,
@@ -128,14 +283,30 @@ impl AppState {
             awareness_manager,
             document_persistence,
             crdt_service,
+            document_cache_service,
+            connection_tracker,
+            session_registry,
+            session_revoker,
             document_service,
             file_service,
             share_service,
             git_sync_service,
             git_batch_sync_service,
+            git_import_service,
             document_links_service,
             public_document_service,
             url_generator,
+            history_service,
+            comment_service,
+            suggestion_service,
+            search_service,
+            transclusion_service,
+            history_compaction_service,
+            trash_expiry_service,
+            broadcaster,
+            cluster_sync_service,
+            file_watcher_service,
+            websocket_peers,
             document_repository,
             share_repository,
             user_repository,
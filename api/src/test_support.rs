@@ -0,0 +1,104 @@
+//! Fixtures for tests that run against a migrated Postgres database.
+//!
+//! Such tests are declared with [`db_test!`] and run with
+//! `DATABASE_URL=... cargo test -- --include-ignored`.
+
+use std::sync::Arc;
+use parking_lot::Mutex;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::crdt::{AwarenessManager, DocumentManager, DocumentPersistence};
+use crate::services::crdt::CrdtService;
+
+/// Declare an async test that is skipped unless a database is available
+macro_rules! db_test {
+    ($(#[$meta:meta])* async fn $name:ident() $body:block) => {
+        $(#[$meta])*
+        #[tokio::test]
+        #[ignore = "needs a migrated Postgres database in DATABASE_URL"]
+        async fn $name() $body
+    };
+}
+pub(crate) use db_test;
+
+fn database_url() -> String {
+    std::env::var("DATABASE_URL").expect("DATABASE_URL must be set")
+}
+
+/// A connection to the test database that removes the users it created, and with them
+/// their documents, when dropped. Cleanup also runs when an assertion fails.
+pub struct TestDb {
+    pub pool: Arc<PgPool>,
+    users: Mutex<Vec<Uuid>>,
+}
+
+impl TestDb {
+    pub async fn connect() -> Self {
+        Self {
+            pool: Arc::new(PgPool::connect(&database_url()).await.unwrap()),
+            users: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Create a user with a unique name
+    pub async fn create_user(&self) -> Uuid {
+        let name = format!("test-{}", Uuid::new_v4().simple());
+        let id = sqlx::query_scalar(
+            "INSERT INTO users (email, name, password_hash, username) VALUES ($1, $2, '', $2) RETURNING id"
+        )
+        .bind(format!("{}@example.com", name))
+        .bind(&name[..20])
+        .fetch_one(self.pool.as_ref())
+        .await
+        .unwrap();
+        self.users.lock().push(id);
+        id
+    }
+
+    /// Create an empty document owned by `owner_id`
+    pub async fn create_document(&self, title: &str, owner_id: Uuid) -> Uuid {
+        sqlx::query_scalar("INSERT INTO documents (title, owner_id) VALUES ($1, $2) RETURNING id")
+            .bind(title)
+            .bind(owner_id)
+            .fetch_one(self.pool.as_ref())
+            .await
+            .unwrap()
+    }
+
+    /// A CRDT service with its own document cache over this database
+    pub fn crdt_service(&self) -> Arc<CrdtService> {
+        Arc::new(CrdtService::new(
+            Arc::new(DocumentManager::new()),
+            Arc::new(AwarenessManager::new()),
+            Arc::new(DocumentPersistence::new((*self.pool).clone())),
+        ))
+    }
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        let users = std::mem::take(&mut *self.users.lock());
+        if users.is_empty() {
+            return;
+        }
+
+        // The test's runtime may be the current thread, so clean up on a runtime of our own.
+        // Failures are only reported, panicking here would abort a test that is unwinding
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            let result = runtime.block_on(async {
+                let pool = PgPool::connect(&database_url()).await?;
+                sqlx::query("DELETE FROM users WHERE id = ANY($1)")
+                    .bind(&users)
+                    .execute(&pool)
+                    .await
+            });
+            if let Err(e) = result {
+                eprintln!("Failed to delete test users {:?}: {}", users, e);
+            }
+        })
+        .join()
+        .ok();
+    }
+}
@@ -11,16 +11,20 @@ pub struct Claims {
     pub email: String,
     pub exp: i64,
     pub iat: i64,
+    /// Login session shared by the access and refresh tokens of one login
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<Uuid>,
 }
 
 impl Claims {
-    pub fn new(user_id: Uuid, email: String, expiry_seconds: i64) -> Self {
+    pub fn new(user_id: Uuid, email: String, session_id: Option<Uuid>, expiry_seconds: i64) -> Self {
         let now = Utc::now();
         Self {
             sub: user_id,
             email,
             iat: now.timestamp(),
             exp: (now + Duration::seconds(expiry_seconds)).timestamp(),
+            sid: session_id,
         }
     }
     
@@ -51,9 +55,10 @@ impl JwtService {
         }
     }
     
-    pub fn generate_token_pair(&self, user_id: Uuid, email: String) -> Result<TokenPair> {
-        let access_token = self.generate_token(user_id, email.clone(), self.access_token_expiry)?;
-        let refresh_token = self.generate_token(user_id, email, self.refresh_token_expiry)?;
+    /// Issue the tokens of a login session
+    pub fn generate_token_pair(&self, user_id: Uuid, email: String, session_id: Uuid) -> Result<TokenPair> {
+        let access_token = self.generate_token(user_id, email.clone(), Some(session_id), self.access_token_expiry)?;
+        let refresh_token = self.generate_token(user_id, email, Some(session_id), self.refresh_token_expiry)?;
         
         Ok(TokenPair {
             access_token,
@@ -61,8 +66,8 @@ impl JwtService {
         })
     }
     
-    pub fn generate_token(&self, user_id: Uuid, email: String, expiry_seconds: i64) -> Result<String> {
-        let claims = Claims::new(user_id, email, expiry_seconds);
+    pub fn generate_token(&self, user_id: Uuid, email: String, session_id: Option<Uuid>, expiry_seconds: i64) -> Result<String> {
+        let claims = Claims::new(user_id, email, session_id, expiry_seconds);
         let token = encode(
             &Header::default(),
             &claims,
@@ -84,7 +89,7 @@ impl JwtService {
 // Backwards compatibility functions
 pub fn generate_token(user_id: Uuid, secret: &str, expiry_seconds: i64) -> Result<String> {
     let service = JwtService::new(secret.to_string(), expiry_seconds, expiry_seconds);
-    service.generate_token(user_id, String::new(), expiry_seconds)
+    service.generate_token(user_id, String::new(), None, expiry_seconds)
 }
 
 pub fn verify_token(token: &str, secret: &str) -> Result<Claims> {
@@ -0,0 +1,276 @@
+use axum::{
+    extract::{ws::{CloseFrame, Message as WsMessage, WebSocket, WebSocketUpgrade}, Path, Query, State},
+    middleware::from_fn_with_state,
+    response::Response,
+    routing::get,
+    Extension, Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::time::{Duration, Instant};
+use uuid::Uuid;
+use yrs::sync::{Message, SyncMessage};
+use yrs::updates::encoder::Encode;
+
+use crate::{
+    entities::share::Permission,
+    error::{Error, Result},
+    middleware::{
+        optional_auth::{optional_auth_middleware, OptionalAuthUser},
+        permission::check_any_resource_permission,
+    },
+    socketio::{crdt_sync::protocol, session::DocumentSession},
+    state::AppState,
+};
+
+/// Minimum time between full document saves while a y-websocket client is editing
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Close code sent when a connection's access to the document was revoked
+const ACCESS_REVOKED_CLOSE_CODE: u16 = 4403;
+
+#[derive(Debug, Deserialize)]
+pub struct YWebsocketQuery {
+    /// JWT access token, since browsers cannot set headers on WebSocket requests
+    auth_token: Option<String>,
+    /// Share link token
+    token: Option<String>,
+}
+
+pub fn routes(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/:id", get(y_websocket_handler))
+        .layer(from_fn_with_state(state.clone(), optional_auth_middleware))
+        .with_state(state)
+}
+
+/// Upgrade to a y-websocket connection for a document.
+///
+/// Clients connect to `/api/yjs/{document_id}`, which matches the y-websocket provider
+/// convention of `{serverUrl}/{roomName}` with the document id as room name.
+pub async fn y_websocket_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<AppState>>,
+    Extension(auth_user): Extension<OptionalAuthUser>,
+    Path(document_id): Path<Uuid>,
+    Query(query): Query<YWebsocketQuery>,
+) -> Result<Response> {
+    let (user_id, session_id) = match (auth_user.user_id, &query.auth_token) {
+        (Some(user_id), _) => (Some(user_id), auth_user.session_id),
+        (None, Some(token)) => match crate::utils::jwt::verify_token(token, &state.config.jwt_secret) {
+            Ok(claims) => (Some(claims.sub), claims.sid),
+            Err(_) => (None, None),
+        },
+        (None, None) => (None, None),
+    };
+
+    // Tokens of a logged out session cannot connect again until they expire
+    if !state.user_repository.is_session_active(session_id).await? {
+        return Err(Error::Unauthorized);
+    }
+
+    let check = check_any_resource_permission(
+        &state,
+        document_id,
+        user_id,
+        query.token.clone(),
+        Permission::View
+    ).await?;
+
+    if !check.has_access {
+        return Err(Error::Forbidden);
+    }
+
+    // Kept with the connection so revocations can downgrade or close it
+    let session = DocumentSession {
+        share_token: if check.is_share_link { query.token } else { None },
+        check,
+        user_id,
+        session_id,
+    };
+
+    Ok(ws.on_upgrade(move |socket| handle_connection(state, document_id, session, socket)))
+}
+
+async fn handle_connection(state: Arc<AppState>, document_id: Uuid, session: DocumentSession, mut socket: WebSocket) {
+    let connection_id = Uuid::new_v4();
+    let tracker_id = connection_owner(connection_id);
+    let user_id = session.user_id;
+    let permission = session.check.permission_level;
+    let (tx, mut rx) = mpsc::unbounded_channel::<Vec<u8>>();
+
+    state.websocket_peers.join(document_id, connection_id, tx, session);
+    state.connection_tracker.join_document(&tracker_id, document_id);
+    tracing::info!("[y-websocket] Connection {} opened for document {} (permission={:?})", connection_id, document_id, permission);
+
+    // Start the handshake by asking the client for what the server is missing,
+    // then share the cursors of everyone already editing
+    match initial_sync_step1(&state, document_id).await {
+        Ok(message) => {
+            let mut messages = vec![message];
+            if let Some(awareness) = state.awareness_manager.get_or_create(document_id).full_update() {
+                messages.push(protocol::create_awareness(&awareness.encode_v1()));
+            }
+            for message in messages {
+                if socket.send(WsMessage::Binary(message)).await.is_err() {
+                    close_connection(&state, document_id, connection_id, &tracker_id).await;
+                    return;
+                }
+            }
+        }
+        Err(e) => {
+            tracing::error!("[y-websocket] Failed to load document {}: {}", document_id, e);
+            close_connection(&state, document_id, connection_id, &tracker_id).await;
+            return;
+        }
+    }
+
+    let mut last_save = Instant::now();
+
+    loop {
+        tokio::select! {
+            outgoing = rx.recv() => {
+                // The queue is only dropped when the connection was unregistered by a revocation
+                let Some(message) = outgoing else {
+                    socket.send(WsMessage::Close(Some(CloseFrame {
+                        code: ACCESS_REVOKED_CLOSE_CODE,
+                        reason: "Access revoked".into(),
+                    }))).await.ok();
+                    break;
+                };
+                if socket.send(WsMessage::Binary(message)).await.is_err() {
+                    break;
+                }
+            }
+            incoming = socket.recv() => {
+                let payload = match incoming {
+                    Some(Ok(WsMessage::Binary(payload))) => payload,
+                    Some(Ok(WsMessage::Close(_))) | None => break,
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => {
+                        tracing::debug!("[y-websocket] Connection {} errored: {}", connection_id, e);
+                        break;
+                    }
+                };
+
+                match handle_payload(&state, document_id, connection_id, user_id, &payload).await {
+                    Ok(replies) => {
+                        let mut failed = false;
+                        for reply in replies {
+                            if socket.send(WsMessage::Binary(reply)).await.is_err() {
+                                failed = true;
+                                break;
+                            }
+                        }
+                        if failed {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        tracing::warn!("[y-websocket] Failed to handle message on connection {}: {}", connection_id, e);
+                    }
+                }
+
+                if can_edit(&state, document_id, connection_id) && last_save.elapsed() >= SAVE_INTERVAL {
+                    last_save = Instant::now();
+                    if let Err(e) = state.crdt_service.save_document(document_id).await {
+                        tracing::error!("[y-websocket] Failed to save document {}: {}", document_id, e);
+                    }
+                }
+            }
+        }
+    }
+
+    close_connection(&state, document_id, connection_id, &tracker_id).await;
+}
+
+/// Whether a connection may currently edit; revocations lower or remove its permission
+fn can_edit(state: &AppState, document_id: Uuid, connection_id: Uuid) -> bool {
+    state.websocket_peers
+        .permission(document_id, connection_id)
+        .is_some_and(|permission| permission.has_permission(Permission::Edit))
+}
+
+/// Identifies a y-websocket connection in the connection tracker and awareness state
+fn connection_owner(connection_id: Uuid) -> String {
+    format!("ws:{}", connection_id)
+}
+
+async fn initial_sync_step1(state: &Arc<AppState>, document_id: Uuid) -> Result<Vec<u8>> {
+    let doc = state.crdt_service.load_or_create_document(document_id).await?;
+    let state_vector = doc.read().get_state_vector();
+    protocol::create_sync_step1(&state_vector)
+}
+
+/// Process one binary frame and return the replies for the sender
+async fn handle_payload(
+    state: &Arc<AppState>,
+    document_id: Uuid,
+    connection_id: Uuid,
+    user_id: Option<Uuid>,
+    payload: &[u8],
+) -> Result<Vec<Vec<u8>>> {
+    let mut replies = Vec::new();
+
+    for message in protocol::decode_messages(payload)? {
+        match message {
+            Message::Sync(SyncMessage::SyncStep1(client_sv)) => {
+                let doc = state.crdt_service.load_or_create_document(document_id).await?;
+                let update = doc.read().get_update_since(&client_sv.encode_v1())?;
+                replies.push(protocol::create_sync_step2(&update));
+            }
+            Message::Sync(SyncMessage::SyncStep2(update)) | Message::Sync(SyncMessage::Update(update)) => {
+                if !can_edit(state, document_id, connection_id) {
+                    tracing::debug!("[y-websocket] Dropping update from read-only connection {}", connection_id);
+                    continue;
+                }
+                apply_update(state, document_id, connection_id, user_id, &update).await?;
+            }
+            Message::Awareness(update) => {
+                let awareness = state.awareness_manager.get_or_create(document_id);
+                if let Some(accepted) = awareness.apply_awareness_update(&connection_owner(connection_id), &update) {
+                    state.broadcaster.broadcast_awareness(document_id, &accepted.encode_v1(), Some(connection_id))?;
+                }
+            }
+            Message::AwarenessQuery | Message::Auth(_) | Message::Custom(_, _) => {}
+        }
+    }
+
+    Ok(replies)
+}
+
+async fn apply_update(state: &Arc<AppState>, document_id: Uuid, connection_id: Uuid, user_id: Option<Uuid>, update: &[u8]) -> Result<()> {
+    // Updates rejected by persistence, such as those to a trashed document, reach no one
+    state.crdt_service.persist_and_apply_update(document_id, update, user_id).await?;
+
+    state.broadcaster.broadcast_document_update(document_id, update, Some(connection_id))?;
+
+    Ok(())
+}
+
+async fn close_connection(state: &Arc<AppState>, document_id: Uuid, connection_id: Uuid, tracker_id: &str) {
+    state.websocket_peers.leave(document_id, connection_id);
+    state.connection_tracker.remove_socket(tracker_id);
+    if let Some(removal) = state.awareness_manager.get_or_create(document_id).remove_owner(tracker_id) {
+        state.broadcaster.broadcast_awareness(document_id, &removal.encode_v1(), Some(connection_id)).ok();
+    }
+    tracing::info!("[y-websocket] Connection {} closed for document {}", connection_id, document_id);
+
+    if !state.connection_tracker.is_document_empty(document_id) {
+        return;
+    }
+
+    // Last editor left: persist the document like a Socket.IO session end
+    if let Err(e) = state.crdt_service.save_document(document_id).await {
+        tracing::error!("[y-websocket] Failed to save document {}: {}", document_id, e);
+    }
+    if let Ok(Some(document)) = state.document_repository.get_by_id(document_id).await {
+        if let Err(e) = state.document_service.save_to_file(&document).await {
+            tracing::error!("[y-websocket] Failed to save document {} to file: {}", document_id, e);
+        }
+    }
+    if let Err(e) = state.history_service.create_auto_snapshot(document_id, "Session end").await {
+        tracing::error!("[y-websocket] Failed to create snapshot for document {}: {}", document_id, e);
+    }
+}
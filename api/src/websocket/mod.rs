@@ -0,0 +1,5 @@
+pub mod handlers;
+pub mod peers;
+
+pub use handlers::routes;
+pub use peers::WebsocketPeers;
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use dashmap::DashMap;
+use tokio::sync::mpsc::UnboundedSender;
+use uuid::Uuid;
+
+use crate::entities::share::Permission;
+use crate::socketio::session::DocumentSession;
+
+/// A y-websocket connection: its outgoing message queue and how it was granted access
+struct Peer {
+    sender: UnboundedSender<Vec<u8>>,
+    session: DocumentSession,
+}
+
+/// Outgoing message queues of the y-websocket connections of each document.
+///
+/// Connections are kept with the session they were granted at upgrade, so revocations can
+/// lower their permission or close them like Socket.IO sessions.
+pub struct WebsocketPeers {
+    documents: DashMap<Uuid, HashMap<Uuid, Peer>>,
+}
+
+impl WebsocketPeers {
+    pub fn new() -> Self {
+        Self {
+            documents: DashMap::new(),
+        }
+    }
+
+    /// Register a connection for a document
+    pub fn join(&self, document_id: Uuid, connection_id: Uuid, sender: UnboundedSender<Vec<u8>>, session: DocumentSession) {
+        self.documents
+            .entry(document_id)
+            .or_default()
+            .insert(connection_id, Peer { sender, session });
+    }
+
+    /// Unregister a connection. Dropping its queue ends the connection loop, which closes
+    /// connections unregistered from elsewhere.
+    pub fn leave(&self, document_id: Uuid, connection_id: Uuid) {
+        self.documents.remove_if_mut(&document_id, |_, peers| {
+            peers.remove(&connection_id);
+            peers.is_empty()
+        });
+    }
+
+    /// Current permission of a connection, None once it was closed
+    pub fn permission(&self, document_id: Uuid, connection_id: Uuid) -> Option<Permission> {
+        self.documents
+            .get(&document_id)
+            .and_then(|peers| peers.get(&connection_id).map(|peer| peer.session.check.permission_level))
+    }
+
+    /// Change the permission of a connection, returning the previous one
+    pub fn set_permission(&self, document_id: Uuid, connection_id: Uuid, permission: Permission) -> Option<Permission> {
+        let mut peers = self.documents.get_mut(&document_id)?;
+        let peer = peers.get_mut(&connection_id)?;
+        Some(std::mem::replace(&mut peer.session.check.permission_level, permission))
+    }
+
+    /// Connections whose session matches, as (document_id, connection_id) pairs
+    pub fn find(&self, predicate: impl Fn(&DocumentSession) -> bool) -> Vec<(Uuid, Uuid)> {
+        self.documents
+            .iter()
+            .flat_map(|entry| {
+                let document_id = *entry.key();
+                entry
+                    .value()
+                    .iter()
+                    .filter(|(_, peer)| predicate(&peer.session))
+                    .map(|(connection_id, _)| (document_id, *connection_id))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Send an encoded y-protocols message to every connection of a document
+    pub fn broadcast(&self, document_id: Uuid, message: &[u8], except: Option<Uuid>) {
+        if let Some(peers) = self.documents.get(&document_id) {
+            for (connection_id, peer) in peers.iter() {
+                if Some(*connection_id) != except {
+                    // A closed receiver means the connection is shutting down
+                    peer.sender.send(message.to_vec()).ok();
+                }
+            }
+        }
+    }
+}
+
+impl Default for WebsocketPeers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::permission::PermissionCheck;
+    use tokio::sync::mpsc::{self, error::TryRecvError};
+
+    fn session(permission_level: Permission) -> DocumentSession {
+        DocumentSession {
+            check: PermissionCheck {
+                has_access: true,
+                is_share_link: false,
+                permission_level,
+            },
+            user_id: Some(Uuid::new_v4()),
+            session_id: None,
+            share_token: None,
+        }
+    }
+
+    #[test]
+    fn test_broadcast_skips_sender() {
+        let peers = WebsocketPeers::new();
+        let document_id = Uuid::new_v4();
+        let (conn_a, conn_b) = (Uuid::new_v4(), Uuid::new_v4());
+        let (tx_a, mut rx_a) = mpsc::unbounded_channel();
+        let (tx_b, mut rx_b) = mpsc::unbounded_channel();
+
+        peers.join(document_id, conn_a, tx_a, session(Permission::Edit));
+        peers.join(document_id, conn_b, tx_b, session(Permission::Edit));
+        peers.broadcast(document_id, &[1, 2, 3], Some(conn_a));
+
+        assert!(rx_a.try_recv().is_err());
+        assert_eq!(rx_b.try_recv().unwrap(), vec![1, 2, 3]);
+
+        peers.leave(document_id, conn_a);
+        peers.leave(document_id, conn_b);
+        assert!(peers.documents.is_empty());
+    }
+
+    #[test]
+    fn test_leave_closes_the_connection_queue() {
+        let peers = WebsocketPeers::new();
+        let document_id = Uuid::new_v4();
+        let connection_id = Uuid::new_v4();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        peers.join(document_id, connection_id, tx, session(Permission::Edit));
+        assert_eq!(peers.set_permission(document_id, connection_id, Permission::View), Some(Permission::Edit));
+        assert_eq!(peers.permission(document_id, connection_id), Some(Permission::View));
+
+        peers.leave(document_id, connection_id);
+        assert!(peers.permission(document_id, connection_id).is_none());
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+}
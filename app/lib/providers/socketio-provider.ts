@@ -272,7 +272,7 @@ export class SocketIOProvider extends Observable<string> {
         // Convert to regular array for Socket.IO transmission
         // Socket.IO v4 has issues with Uint8Array, so we convert to regular array
         const dataArray = Array.from(awarenessData);
-        this.socket.emit('yjs:awareness', dataArray);
+        this.socket.emit('yjs:awareness', { document_id: this.documentId, update: dataArray });
       }
     };
     